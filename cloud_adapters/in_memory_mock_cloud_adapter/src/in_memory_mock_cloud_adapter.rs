@@ -2,21 +2,59 @@
 // Licensed under the MIT license.
 // SPDX-License-Identifier: MIT
 
+use std::sync::Mutex;
+
 use async_trait::async_trait;
 use log::{debug, info};
+use tokio::sync::{
+    mpsc::{self, UnboundedReceiver, UnboundedSender},
+    Mutex as AsyncMutex,
+};
 
 use freyja_contracts::cloud_adapter::{
-    CloudAdapter, CloudAdapterError, CloudMessageRequest, CloudMessageResponse,
+    CloudAdapter, CloudAdapterError, CloudCommandRequest, CloudCommandResponse,
+    CloudMessageRequest, CloudMessageResponse,
 };
 
 /// Mocks a cloud adapter in memory
-pub struct InMemoryMockCloudAdapter {}
+pub struct InMemoryMockCloudAdapter {
+    /// Sends commands to this adapter as if they were received from the cloud
+    command_sender: UnboundedSender<CloudCommandRequest>,
+
+    /// Receives the commands sent with `command_sender`
+    command_receiver: AsyncMutex<UnboundedReceiver<CloudCommandRequest>>,
+
+    /// The responses to commands which have been sent to the cloud
+    command_responses: Mutex<Vec<CloudCommandResponse>>,
+}
+
+impl InMemoryMockCloudAdapter {
+    /// Queues a command as if it had been received from the cloud
+    ///
+    /// # Arguments
+    /// - `command`: the command
+    pub fn send_command(&self, command: CloudCommandRequest) {
+        // The adapter owns the receiver, so the channel can't be closed
+        let _ = self.command_sender.send(command);
+    }
+
+    /// Gets the responses to commands which have been sent to the cloud
+    pub fn command_responses(&self) -> Vec<CloudCommandResponse> {
+        self.command_responses.lock().unwrap().clone()
+    }
+}
 
 #[async_trait]
 impl CloudAdapter for InMemoryMockCloudAdapter {
     /// Creates a new instance of a CloudAdapter with default settings
     fn create_new() -> Result<Self, CloudAdapterError> {
-        Ok(Self {})
+        let (command_sender, command_receiver) = mpsc::unbounded_channel();
+
+        Ok(Self {
+            command_sender,
+            command_receiver: AsyncMutex::new(command_receiver),
+            command_responses: Mutex::new(Vec::new()),
+        })
     }
 
     /// Sends the signal to the cloud
@@ -36,6 +74,29 @@ impl CloudAdapter for InMemoryMockCloudAdapter {
 
         Ok(CloudMessageResponse {})
     }
+
+    /// Receives the next command queued with `send_command`
+    async fn receive_command(&self) -> Result<Option<CloudCommandRequest>, CloudAdapterError> {
+        Ok(self.command_receiver.lock().await.recv().await)
+    }
+
+    /// Records the outcome of a command, which can be retrieved with `command_responses`
+    ///
+    /// # Arguments
+    ///
+    /// - `response`: the outcome of the command
+    async fn send_command_response(
+        &self,
+        response: CloudCommandResponse,
+    ) -> Result<(), CloudAdapterError> {
+        let response_json =
+            serde_json::to_string_pretty(&response).map_err(CloudAdapterError::serialize)?;
+
+        info!("Cloud command response:\n{response_json}");
+
+        self.command_responses.lock().unwrap().push(response);
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -44,6 +105,10 @@ mod in_memory_mock_cloud_adapter_tests {
 
     use std::collections::HashMap;
 
+    use freyja_contracts::{
+        cloud_adapter::{CloudCommandAction, CloudCommandOutcome},
+        value::Value,
+    };
     use time::OffsetDateTime;
 
     #[test]
@@ -58,10 +123,38 @@ mod in_memory_mock_cloud_adapter_tests {
 
         let cloud_message = CloudMessageRequest {
             cloud_signal: HashMap::new(),
-            signal_value: String::from("72"),
+            signal_value: Value::Int(72),
             signal_timestamp: OffsetDateTime::now_utc().to_string(),
         };
 
         assert!(cloud_adapter.send_to_cloud(cloud_message).await.is_ok());
     }
+
+    #[tokio::test]
+    async fn receives_queued_commands_and_records_responses() {
+        let cloud_adapter = InMemoryMockCloudAdapter::create_new().unwrap();
+
+        let command = CloudCommandRequest {
+            command_id: String::from("command"),
+            cloud_signal: HashMap::new(),
+            action: CloudCommandAction::SetValue {
+                value: Value::Int(72),
+            },
+        };
+        cloud_adapter.send_command(command.clone());
+        assert_eq!(
+            cloud_adapter.receive_command().await.unwrap(),
+            Some(command)
+        );
+
+        let response = CloudCommandResponse {
+            command_id: String::from("command"),
+            outcome: CloudCommandOutcome::Succeeded { response: None },
+        };
+        assert!(cloud_adapter
+            .send_command_response(response.clone())
+            .await
+            .is_ok());
+        assert_eq!(cloud_adapter.command_responses(), vec![response]);
+    }
 }
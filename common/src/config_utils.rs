@@ -2,7 +2,10 @@
 // Licensed under the MIT license.
 // SPDX-License-Identifier: MIT
 
-use std::{env, path::Path};
+use std::{
+    env,
+    path::{Path, PathBuf},
+};
 
 use config::{ConfigError, File};
 use home::home_dir;
@@ -13,6 +16,29 @@ pub const JSON_EXT: &str = "json";
 const CONFIG_DIR: &str = "config";
 const DOT_FREYJA_DIR: &str = ".freyja";
 const FREYJA_HOME: &str = "FREYJA_HOME";
+const STATE_DIR: &str = "state";
+
+/// Gets the Freyja home directory.
+/// This is `$FREYJA_HOME` if it is set, and defaults to `$HOME/.freyja` otherwise.
+pub fn freyja_home_dir() -> Result<PathBuf, std::io::Error> {
+    match env::var(FREYJA_HOME) {
+        Ok(freyja_home) => Ok(PathBuf::from(freyja_home)),
+        Err(_) => home_dir()
+            .map(|home| home.join(DOT_FREYJA_DIR))
+            .ok_or_else(|| {
+                std::io::Error::new(
+                    std::io::ErrorKind::Other,
+                    "Could not retrieve home directory",
+                )
+            }),
+    }
+}
+
+/// Gets the directory where Freyja components can persist state, which is `$FREYJA_HOME/state`.
+/// The directory is not guaranteed to exist.
+pub fn freyja_state_dir() -> Result<PathBuf, std::io::Error> {
+    freyja_home_dir().map(|freyja_home| freyja_home.join(STATE_DIR))
+}
 
 /// Read config from layered configuration files.
 /// Uses `{config_file_stem}.default.{config_file_ext}` as the base configuration,
@@ -48,27 +74,11 @@ where
         .map_err(&io_error_handler)?
         .join(overrides_filename.clone());
 
-    let freyja_dir_config_path = match env::var(FREYJA_HOME) {
-        Ok(freyja_home) => {
-            // The path below resolves to $FREYJA_HOME/config/{overrides_filename}
-            Path::new(&freyja_home)
-                .join(CONFIG_DIR)
-                .join(overrides_filename)
-        }
-        Err(_) => {
-            // The path below resolves to $HOME/.freyja/config/{overrides_filename}
-            home_dir()
-                .ok_or_else(|| {
-                    io_error_handler(std::io::Error::new(
-                        std::io::ErrorKind::Other,
-                        "Could not retrieve home directory",
-                    ))
-                })?
-                .join(DOT_FREYJA_DIR)
-                .join(CONFIG_DIR)
-                .join(overrides_filename)
-        }
-    };
+    // The path below resolves to $FREYJA_HOME/config/{overrides_filename}
+    let freyja_dir_config_path = freyja_home_dir()
+        .map_err(&io_error_handler)?
+        .join(CONFIG_DIR)
+        .join(overrides_filename);
 
     let config_store = config::Config::builder()
         .add_source(File::from(default_config_file))
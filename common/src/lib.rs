@@ -3,7 +3,10 @@
 // SPDX-License-Identifier: MIT
 
 pub mod config_utils;
+pub mod mapping_service_health_store;
+pub mod resolution_status_store;
 pub mod retry_utils;
+pub mod service_discovery;
 pub mod signal_store;
 
 /// Expands to `env!("OUT_DIR")`.
@@ -0,0 +1,80 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+// SPDX-License-Identifier: MIT
+
+use std::sync::RwLock;
+
+use serde::Serialize;
+
+/// The state of the circuit breaker which guards calls to the mapping service
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CircuitState {
+    /// The mapping service is called normally
+    #[default]
+    Closed,
+
+    /// The mapping service failed repeatedly and is not called until the circuit breaker's open duration has passed
+    Open,
+
+    /// The open duration has passed and the next call will determine whether the circuit closes or opens again
+    HalfOpen,
+}
+
+/// The health of the connection to the mapping service
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct MappingServiceHealth {
+    /// The state of the circuit breaker
+    pub circuit_state: CircuitState,
+
+    /// The number of consecutive failed calls to the mapping service
+    pub consecutive_failures: u32,
+
+    /// The error from the last failed call, if the last call failed
+    pub last_error: Option<String>,
+}
+
+impl MappingServiceHealth {
+    /// Checks whether the last call to the mapping service succeeded
+    pub fn is_healthy(&self) -> bool {
+        self.consecutive_failures == 0
+    }
+}
+
+/// Stores the health of the connection to the mapping service and allows access in a thread-safe manner.
+/// Suitable for use as `Arc<MappingServiceHealthStore>`.
+pub struct MappingServiceHealthStore {
+    /// The current health
+    health: RwLock<MappingServiceHealth>,
+}
+
+impl MappingServiceHealthStore {
+    /// Creates a MappingServiceHealthStore with the default health
+    pub fn new() -> Self {
+        Self {
+            health: RwLock::new(MappingServiceHealth::default()),
+        }
+    }
+
+    /// Gets a copy of the current health.
+    /// Acquires a read lock.
+    pub fn get(&self) -> MappingServiceHealth {
+        self.health.read().unwrap().clone()
+    }
+
+    /// Sets the current health. Returns the previous health.
+    /// Acquires a write lock.
+    ///
+    /// # Arguments
+    /// - `health`: The new health
+    pub fn set(&self, health: MappingServiceHealth) -> MappingServiceHealth {
+        let mut current_health = self.health.write().unwrap();
+        std::mem::replace(&mut *current_health, health)
+    }
+}
+
+impl Default for MappingServiceHealthStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
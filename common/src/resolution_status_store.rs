@@ -0,0 +1,144 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+// SPDX-License-Identifier: MIT
+
+use std::{collections::HashMap, sync::RwLock};
+
+use serde::Serialize;
+
+/// The status of resolving the source entity of a mapped signal
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum ResolutionStatus {
+    /// The signal's entity was resolved and the signal is being emitted
+    Resolved,
+
+    /// The signal's entity has not been resolved yet. Resolution will be retried.
+    Pending {
+        /// The number of failed attempts to resolve the entity
+        attempts: u32,
+
+        /// The error from the last attempt
+        last_error: String,
+    },
+}
+
+/// Stores the resolution status of each mapped signal and allows access in a thread-safe manner.
+/// Suitable for use as `Arc<ResolutionStatusStore>`.
+pub struct ResolutionStatusStore {
+    /// The status of each signal, keyed by signal id
+    statuses: RwLock<HashMap<String, ResolutionStatus>>,
+}
+
+impl ResolutionStatusStore {
+    /// Creates an empty ResolutionStatusStore
+    pub fn new() -> Self {
+        Self {
+            statuses: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Gets the resolution status of a signal. Returns `None` if the signal is not in the mapping.
+    /// Acquires a read lock.
+    ///
+    /// # Arguments
+    /// - `id`: The id of the signal
+    pub fn get(&self, id: &str) -> Option<ResolutionStatus> {
+        let statuses = self.statuses.read().unwrap();
+        statuses.get(id).cloned()
+    }
+
+    /// Gets a copy of the resolution status of every signal in the mapping.
+    /// Acquires a read lock.
+    pub fn get_all(&self) -> HashMap<String, ResolutionStatus> {
+        self.statuses.read().unwrap().clone()
+    }
+
+    /// Gets the ids of the signals whose resolution is pending.
+    /// Acquires a read lock.
+    pub fn get_pending(&self) -> Vec<String> {
+        let statuses = self.statuses.read().unwrap();
+        statuses
+            .iter()
+            .filter(|(_, status)| matches!(status, ResolutionStatus::Pending { .. }))
+            .map(|(id, _)| id.clone())
+            .collect()
+    }
+
+    /// Sets the resolution status of a signal.
+    /// Acquires a write lock.
+    ///
+    /// # Arguments
+    /// - `id`: The id of the signal
+    /// - `status`: The new status of the signal
+    pub fn set(&self, id: String, status: ResolutionStatus) {
+        let mut statuses = self.statuses.write().unwrap();
+        statuses.insert(id, status);
+    }
+
+    /// Removes the resolution status of a signal which is no longer in the mapping.
+    /// Acquires a write lock.
+    ///
+    /// # Arguments
+    /// - `id`: The id of the signal
+    pub fn remove(&self, id: &str) {
+        let mut statuses = self.statuses.write().unwrap();
+        statuses.remove(id);
+    }
+
+    /// Replaces the status of every signal. Signals which are not in the input are removed.
+    /// Acquires a write lock.
+    ///
+    /// # Arguments
+    /// - `statuses`: The status of each signal in the mapping
+    pub fn sync(&self, statuses: HashMap<String, ResolutionStatus>) {
+        *self.statuses.write().unwrap() = statuses;
+    }
+}
+
+impl Default for ResolutionStatusStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod resolution_status_store_tests {
+    use super::*;
+
+    #[test]
+    fn statuses_can_be_set_and_synced() {
+        const ID: &str = "id";
+        const OTHER_ID: &str = "otherid";
+
+        let uut = ResolutionStatusStore::new();
+        uut.sync(
+            [
+                (ID.to_string(), ResolutionStatus::Resolved),
+                (
+                    OTHER_ID.to_string(),
+                    ResolutionStatus::Pending {
+                        attempts: 1,
+                        last_error: "error".to_string(),
+                    },
+                ),
+            ]
+            .into_iter()
+            .collect(),
+        );
+
+        assert_eq!(uut.get(ID), Some(ResolutionStatus::Resolved));
+        assert_eq!(uut.get_pending(), vec![OTHER_ID.to_string()]);
+
+        uut.set(OTHER_ID.to_string(), ResolutionStatus::Resolved);
+        assert!(uut.get_pending().is_empty());
+
+        uut.sync(
+            [(ID.to_string(), ResolutionStatus::Resolved)]
+                .into_iter()
+                .collect(),
+        );
+        assert_eq!(uut.get_all().len(), 1);
+        assert_eq!(uut.get(OTHER_ID), None);
+    }
+}
@@ -0,0 +1,364 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+// SPDX-License-Identifier: MIT
+
+use std::time::Duration;
+
+use log::info;
+use serde::{Deserialize, Serialize};
+use service_discovery_proto::service_registry::v1::{
+    service_registry_client::ServiceRegistryClient, DiscoverRequest,
+};
+use tonic::{Code, Status};
+
+use crate::retry_utils::execute_with_retry;
+
+const DEFAULT_MAX_RETRIES: u32 = 5;
+const DEFAULT_RETRY_INTERVAL_MS: u64 = 1000;
+
+/// The uri of a service.
+/// In config files this is either a fixed uri string or an object of the form
+/// `{ "discover": { "chariott_uri": "...", "namespace": "...", "name": "...", "version": "..." } }`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ServiceUri {
+    /// A fixed uri
+    Static(String),
+
+    /// A uri which is discovered with Chariott's service registry
+    Discover {
+        /// The info needed to discover the service
+        discover: ChariottDiscoveryConfig,
+    },
+}
+
+impl ServiceUri {
+    /// Gets the uri of the service, discovering it with Chariott if necessary
+    pub async fn resolve(&self) -> Result<String, ServiceDiscoveryError> {
+        match self {
+            ServiceUri::Static(uri) => Ok(uri.clone()),
+            ServiceUri::Discover { discover } => discover.discover().await,
+        }
+    }
+}
+
+/// The info needed to discover a service with Chariott
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChariottDiscoveryConfig {
+    /// The uri of Chariott's service registry
+    pub chariott_uri: String,
+
+    /// The namespace of the service
+    pub namespace: String,
+
+    /// The name of the service
+    pub name: String,
+
+    /// The version of the service
+    pub version: String,
+
+    /// Max retries for calls to Chariott
+    #[serde(default = "default_max_retries")]
+    pub max_retries: u32,
+
+    /// Retry interval in milliseconds
+    #[serde(default = "default_retry_interval_ms")]
+    pub retry_interval_ms: u64,
+}
+
+impl ChariottDiscoveryConfig {
+    /// Discovers the uri of the service with Chariott's service registry
+    pub async fn discover(&self) -> Result<String, ServiceDiscoveryError> {
+        discover_service_uri(
+            &self.chariott_uri,
+            &self.namespace,
+            &self.name,
+            &self.version,
+            self.max_retries,
+            Duration::from_millis(self.retry_interval_ms),
+        )
+        .await
+    }
+}
+
+/// Discovers the uri of a service with Chariott's service registry.
+///
+/// # Arguments
+/// - `chariott_uri`: the uri of Chariott's service registry
+/// - `namespace`: the namespace of the service
+/// - `name`: the name of the service
+/// - `version`: the version of the service
+/// - `max_retries`: the maximum number of attempts when calling Chariott
+/// - `retry_interval`: the interval between attempts
+pub async fn discover_service_uri(
+    chariott_uri: &str,
+    namespace: &str,
+    name: &str,
+    version: &str,
+    max_retries: u32,
+    retry_interval: Duration,
+) -> Result<String, ServiceDiscoveryError> {
+    let service = execute_with_retry(
+        max_retries,
+        retry_interval,
+        || async move {
+            let mut client = ServiceRegistryClient::connect(chariott_uri.to_string())
+                .await
+                .map_err(|e| Status::unavailable(e.to_string()))?;
+
+            let request = DiscoverRequest {
+                namespace: namespace.to_string(),
+                name: name.to_string(),
+                version: version.to_string(),
+            };
+
+            match client.discover(request).await {
+                // A service which isn't registered may still be starting up, so this is retried
+                Ok(response) => response
+                    .into_inner()
+                    .service
+                    .ok_or_else(|| Status::not_found("Chariott did not return service metadata")),
+                Err(status) => Err(status),
+            }
+        },
+        Some(format!(
+            "Discovering service {namespace}/{name}/{version} with Chariott"
+        )),
+    )
+    .await
+    .map_err(|status| match status.code() {
+        Code::NotFound => ServiceDiscoveryError::service_not_found(status),
+        _ => ServiceDiscoveryError::communication(status),
+    })?;
+
+    info!(
+        "Discovered service {namespace}/{name}/{version} at {}",
+        service.uri
+    );
+
+    Ok(service.uri)
+}
+
+fn default_max_retries() -> u32 {
+    DEFAULT_MAX_RETRIES
+}
+
+fn default_retry_interval_ms() -> u64 {
+    DEFAULT_RETRY_INTERVAL_MS
+}
+
+proc_macros::error! {
+    ServiceDiscoveryError {
+        ServiceNotFound,
+        Communication
+    }
+}
+
+#[cfg(test)]
+mod service_discovery_tests {
+    use super::*;
+
+    use service_discovery_proto::service_registry::v1::{
+        service_registry_server::{ServiceRegistry, ServiceRegistryServer},
+        DiscoverByNamespaceRequest, DiscoverByNamespaceResponse, DiscoverResponse,
+        ListServicesRequest, ListServicesResponse, RegisterRequest, RegisterResponse,
+        ServiceMetadata, UnregisterRequest, UnregisterResponse,
+    };
+    use tokio::net::TcpListener;
+    use tokio_stream::wrappers::TcpListenerStream;
+    use tonic::{transport::Server, Request, Response};
+
+    const NAMESPACE: &str = "sdv.ibeji";
+    const NAME: &str = "invehicle_digital_twin";
+    const VERSION: &str = "1.0";
+    const SERVICE_URI: &str = "http://0.0.0.0:5010"; // Devskim: ignore DS137138
+
+    /// A stand-in for Chariott's service registry which knows about a single service
+    struct MockChariott {}
+
+    #[tonic::async_trait]
+    impl ServiceRegistry for MockChariott {
+        async fn register(
+            &self,
+            _request: Request<RegisterRequest>,
+        ) -> Result<Response<RegisterResponse>, Status> {
+            Err(Status::unimplemented("register has not been implemented"))
+        }
+
+        async fn unregister(
+            &self,
+            _request: Request<UnregisterRequest>,
+        ) -> Result<Response<UnregisterResponse>, Status> {
+            Err(Status::unimplemented("unregister has not been implemented"))
+        }
+
+        async fn discover(
+            &self,
+            request: Request<DiscoverRequest>,
+        ) -> Result<Response<DiscoverResponse>, Status> {
+            let request = request.into_inner();
+            if request.namespace != NAMESPACE || request.name != NAME || request.version != VERSION
+            {
+                return Err(Status::not_found("No matching service found"));
+            }
+
+            Ok(Response::new(DiscoverResponse {
+                service: Some(ServiceMetadata {
+                    namespace: NAMESPACE.to_string(),
+                    name: NAME.to_string(),
+                    version: VERSION.to_string(),
+                    uri: SERVICE_URI.to_string(),
+                    communication_kind: "grpc+proto".to_string(),
+                    communication_reference: "invehicle_digital_twin.v1".to_string(),
+                }),
+            }))
+        }
+
+        async fn discover_by_namespace(
+            &self,
+            _request: Request<DiscoverByNamespaceRequest>,
+        ) -> Result<Response<DiscoverByNamespaceResponse>, Status> {
+            Err(Status::unimplemented(
+                "discover_by_namespace has not been implemented",
+            ))
+        }
+
+        async fn list_services(
+            &self,
+            _request: Request<ListServicesRequest>,
+        ) -> Result<Response<ListServicesResponse>, Status> {
+            Err(Status::unimplemented(
+                "list_services has not been implemented",
+            ))
+        }
+    }
+
+    /// Starts a mock Chariott on an ephemeral port and returns its uri
+    async fn start_mock_chariott() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        tokio::spawn(
+            Server::builder()
+                .add_service(ServiceRegistryServer::new(MockChariott {}))
+                .serve_with_incoming(TcpListenerStream::new(listener)),
+        );
+
+        format!("http://{addr}") // Devskim: ignore DS137138
+    }
+
+    fn discovery_config(chariott_uri: String, name: &str) -> ChariottDiscoveryConfig {
+        ChariottDiscoveryConfig {
+            chariott_uri,
+            namespace: NAMESPACE.to_string(),
+            name: name.to_string(),
+            version: VERSION.to_string(),
+            max_retries: 2,
+            retry_interval_ms: 10,
+        }
+    }
+
+    #[test]
+    fn service_uri_deserializes_static_uri() {
+        let result: ServiceUri = serde_json::from_str(&format!("\"{SERVICE_URI}\"")).unwrap();
+        assert_eq!(result, ServiceUri::Static(SERVICE_URI.to_string()));
+    }
+
+    #[test]
+    fn service_uri_deserializes_from_config_files() {
+        #[derive(Deserialize)]
+        struct TestConfig {
+            static_uri: ServiceUri,
+            discovered_uri: ServiceUri,
+        }
+
+        let json = format!(
+            r#"{{
+                "static_uri": "{SERVICE_URI}",
+                "discovered_uri": {{ "discover": {{ "chariott_uri": "{SERVICE_URI}", "namespace": "{NAMESPACE}", "name": "{NAME}", "version": "{VERSION}" }} }}
+            }}"#
+        );
+
+        let result: TestConfig = config::Config::builder()
+            .add_source(config::File::from_str(&json, config::FileFormat::Json))
+            .build()
+            .unwrap()
+            .try_deserialize()
+            .unwrap();
+
+        assert_eq!(
+            result.static_uri,
+            ServiceUri::Static(SERVICE_URI.to_string())
+        );
+        assert!(matches!(result.discovered_uri, ServiceUri::Discover { .. }));
+    }
+
+    #[test]
+    fn service_uri_deserializes_discover() {
+        let json = format!(
+            r#"{{ "discover": {{ "chariott_uri": "{SERVICE_URI}", "namespace": "{NAMESPACE}", "name": "{NAME}", "version": "{VERSION}" }} }}"#
+        );
+
+        let result: ServiceUri = serde_json::from_str(&json).unwrap();
+
+        match result {
+            ServiceUri::Discover { discover } => {
+                assert_eq!(discover.chariott_uri, SERVICE_URI);
+                assert_eq!(discover.namespace, NAMESPACE);
+                assert_eq!(discover.name, NAME);
+                assert_eq!(discover.version, VERSION);
+                assert_eq!(discover.max_retries, DEFAULT_MAX_RETRIES);
+                assert_eq!(discover.retry_interval_ms, DEFAULT_RETRY_INTERVAL_MS);
+            }
+            _ => panic!("Expected a Discover value"),
+        }
+    }
+
+    #[tokio::test]
+    async fn resolve_static_uri_returns_uri() {
+        let uut = ServiceUri::Static(SERVICE_URI.to_string());
+        assert_eq!(uut.resolve().await.unwrap(), SERVICE_URI);
+    }
+
+    #[tokio::test]
+    async fn resolve_discover_returns_registered_uri() {
+        let chariott_uri = start_mock_chariott().await;
+        let uut = ServiceUri::Discover {
+            discover: discovery_config(chariott_uri, NAME),
+        };
+
+        assert_eq!(uut.resolve().await.unwrap(), SERVICE_URI);
+    }
+
+    #[tokio::test]
+    async fn resolve_discover_for_unknown_service_fails() {
+        let chariott_uri = start_mock_chariott().await;
+        let uut = ServiceUri::Discover {
+            discover: discovery_config(chariott_uri, "unknown"),
+        };
+
+        let result = uut.resolve().await;
+
+        assert!(result.is_err());
+        assert_eq!(
+            result.err().unwrap().kind(),
+            ServiceDiscoveryErrorKind::ServiceNotFound
+        );
+    }
+
+    #[tokio::test]
+    async fn resolve_discover_without_chariott_fails() {
+        let uut = ServiceUri::Discover {
+            // Nothing listens on port 1, so connecting fails
+            discover: discovery_config("http://127.0.0.1:1".to_string(), NAME), // Devskim: ignore DS137138
+        };
+
+        let result = uut.resolve().await;
+
+        assert!(result.is_err());
+        assert_eq!(
+            result.err().unwrap().kind(),
+            ServiceDiscoveryErrorKind::Communication
+        );
+    }
+}
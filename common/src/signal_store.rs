@@ -2,9 +2,17 @@
 // Licensed under the MIT license.
 // SPDX-License-Identifier: MIT
 
-use std::{collections::HashMap, sync::RwLock};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::RwLock,
+};
 
-use freyja_contracts::signal::{Emission, Signal, SignalPatch};
+use tokio::time::Instant;
+
+use freyja_contracts::{
+    signal::{Emission, Signal, SignalPatch},
+    value::Value,
+};
 
 /// Stores signals and allows access in a thread-safe manner with support for multiple concurrent readers.
 /// Suitable for use as `Arc<SignalStore>`.
@@ -46,11 +54,12 @@ impl SignalStore {
     /// - If the stored signal is not in the input, delete it
     ///
     /// The previous state of the store is discarded.
+    /// Returns the signals which were deleted from the store.
     /// Acquires a write lock.
     ///
     /// # Arguments
     /// - `incoming_signals`: The list of input signals
-    pub fn sync<SyncIterator, IntoSignalPatch>(&self, incoming_signals: SyncIterator)
+    pub fn sync<SyncIterator, IntoSignalPatch>(&self, incoming_signals: SyncIterator) -> Vec<Signal>
     where
         SyncIterator: Iterator<Item = IntoSignalPatch>,
         IntoSignalPatch: Into<SignalPatch>,
@@ -58,45 +67,75 @@ impl SignalStore {
         let mut signals = self.signals.write().unwrap();
 
         // This algorithm avoids trying to iterate over incoming_signals multiple times since iterators are consumed in this process.
-        // If the iterator were cloneable then the implementation could be better, but in general that's not always a feasible constraint.
-        // This function isn't invoked very often (only when we have a new mapping), so less-than-optimal efficiency is less of a concern.
+        // The incoming ids are tracked in a set so that finding the signals to delete is linear in the size of the store.
         let size_hint = incoming_signals.size_hint();
-        let mut incoming_ids = Vec::with_capacity(size_hint.1.unwrap_or(size_hint.0));
+        let mut incoming_ids = HashSet::with_capacity(size_hint.1.unwrap_or(size_hint.0));
         for value in incoming_signals {
-            let SignalPatch {
-                id,
-                source,
-                target,
-                emission_policy,
-            } = value.into();
-
-            // We'll use these ids later to only retain entries in the store which were in the incoming list.
-            // We track it separately from the input iterator since we can't reuse the iterator.
-            incoming_ids.push(id.clone());
-
-            signals
-                .entry(id.clone())
-                // If the incoming signal is already in the data store, update only its target and emission policy
-                .and_modify(|s| {
-                    s.source = source.clone();
-                    s.target = target.clone();
-                    s.emission.policy = emission_policy.clone();
-                })
-                // If the incoming signal is not in the data store, insert a new one
-                .or_insert(Signal {
-                    id,
-                    source,
-                    target,
-                    emission: Emission {
-                        policy: emission_policy,
-                        ..Default::default()
-                    },
-                    ..Default::default()
-                });
+            incoming_ids.insert(Self::apply_patch(&mut signals, value.into()));
         }
 
         // Delete signals in the store but not in the incoming list
-        signals.retain(|id, _| incoming_ids.contains(id));
+        let removed_ids: Vec<String> = signals
+            .keys()
+            .filter(|id| !incoming_ids.contains(*id))
+            .cloned()
+            .collect();
+
+        removed_ids
+            .into_iter()
+            .filter_map(|id| signals.remove(&id))
+            .collect()
+    }
+
+    /// Applies the changes from a mapping delta:
+    /// - For each signal in the input, apply the patch or create a new signal from the patch.
+    /// - Delete the signals with the removed ids.
+    ///
+    /// Signals which are in neither input are not touched.
+    /// Returns the signals which were deleted from the store.
+    /// Acquires a write lock.
+    ///
+    /// # Arguments
+    /// - `changed_signals`: The signals which were added or changed
+    /// - `removed_ids`: The ids of the signals which were removed
+    pub fn apply_delta<DeltaIterator, IntoSignalPatch>(
+        &self,
+        changed_signals: DeltaIterator,
+        removed_ids: &[String],
+    ) -> Vec<Signal>
+    where
+        DeltaIterator: Iterator<Item = IntoSignalPatch>,
+        IntoSignalPatch: Into<SignalPatch>,
+    {
+        let mut signals = self.signals.write().unwrap();
+        for value in changed_signals {
+            Self::apply_patch(&mut signals, value.into());
+        }
+
+        removed_ids
+            .iter()
+            .filter_map(|id| signals.remove(id))
+            .collect()
+    }
+
+    /// For each signal in the input:
+    /// - If the incoming signal is already in the data store, apply the patch.
+    /// - If the incoming signal is not in the data store, create a new signal from the patch.
+    ///
+    /// Unlike `sync`, signals in the data store which are not in the input are kept.
+    /// Acquires a write lock.
+    ///
+    /// # Arguments
+    /// - `incoming_signals`: The list of input signals
+    pub fn upsert<UpsertIterator, IntoSignalPatch>(&self, incoming_signals: UpsertIterator)
+    where
+        UpsertIterator: Iterator<Item = IntoSignalPatch>,
+        IntoSignalPatch: Into<SignalPatch>,
+    {
+        let mut signals = self.signals.write().unwrap();
+        for value in incoming_signals {
+            Self::apply_patch(&mut signals, value.into());
+        }
     }
 
     /// Sets the value of the signal with the given id to the requested value.
@@ -106,7 +145,7 @@ impl SignalStore {
     /// # Arguments
     /// - `id`: The id of the signal to edit
     /// - `value`: The new value to assign to the signal
-    pub fn set_value(&self, id: String, value: String) -> Option<Option<String>> {
+    pub fn set_value(&self, id: String, value: Value) -> Option<Option<Value>> {
         let mut signals = self.signals.write().unwrap();
 
         let mut result = None;
@@ -119,45 +158,81 @@ impl SignalStore {
     }
 
     /// Sets the last emitted value of the signal with the given id to the requested value
-    /// and resets its `next_emssion_ms` based on the emission policy.
+    /// and records the current time as the time of its last emission.
     /// Returns the old value, or `None` if the signal could not be found.
     /// Acquires a write lock.
     ///
     /// # Arguments
     /// - `id`: The id of the signal to edit
     /// - `value`: The new value to assign to the signal's last emitted value
-    pub fn set_last_emitted_value(&self, id: String, value: String) -> Option<Option<String>> {
+    pub fn set_last_emitted_value(&self, id: String, value: Value) -> Option<Option<Value>> {
         let mut signals = self.signals.write().unwrap();
 
         let mut result = None;
         signals.entry(id).and_modify(|s| {
             result = Some(s.emission.last_emitted_value.clone());
             s.emission.last_emitted_value = Some(value);
-            s.emission.next_emission_ms = s.emission.policy.interval_ms;
+            s.emission.last_emission = Some(Instant::now());
         });
 
         result
     }
 
-    /// Adjusts the emission times of all signals in the store by subtracting the provided interval from next_emission_ms.
-    /// If overflow would occur, the value saturates at `u64::MIN` (`0`).
-    /// Returns the updated list of all signals.
+    /// Sets the deadline of the next emission of the signal with the given id.
+    /// Returns the old deadline, or `None` if the signal could not be found.
     /// Acquires a write lock.
     ///
     /// # Arguments
-    /// - `interval_ms`: The value to subtract from each signal's next_emission_ms value
-    pub fn update_emission_times_and_get_all(&self, interval_ms: u64) -> Vec<Signal> {
+    /// - `id`: The id of the signal to edit
+    /// - `next_emission`: The deadline of the signal's next emission
+    pub fn set_next_emission(&self, id: String, next_emission: Instant) -> Option<Option<Instant>> {
         let mut signals = self.signals.write().unwrap();
-        let mut result = Vec::new();
 
-        for (_, signal) in signals.iter_mut() {
-            signal.emission.next_emission_ms =
-                signal.emission.next_emission_ms.saturating_sub(interval_ms);
-            result.push(signal.clone());
-        }
+        let mut result = None;
+        signals.entry(id).and_modify(|s| {
+            result = Some(s.emission.next_emission);
+            s.emission.next_emission = Some(next_emission);
+        });
 
         result
     }
+
+    /// Applies a patch to the signal with the same id, or creates a new signal from the patch if there is no such signal.
+    /// Returns the id of the signal.
+    ///
+    /// # Arguments
+    /// - `signals`: The stored signals
+    /// - `patch`: The patch to apply
+    fn apply_patch(signals: &mut HashMap<String, Signal>, patch: SignalPatch) -> String {
+        let SignalPatch {
+            id,
+            source,
+            target,
+            emission_policy,
+        } = patch;
+
+        signals
+            .entry(id.clone())
+            // If the incoming signal is already in the data store, update only its target and emission policy
+            .and_modify(|s| {
+                s.source = source.clone();
+                s.target = target.clone();
+                s.emission.policy = emission_policy.clone();
+            })
+            // If the incoming signal is not in the data store, insert a new one
+            .or_insert(Signal {
+                id: id.clone(),
+                source,
+                target,
+                emission: Emission {
+                    policy: emission_policy,
+                    ..Default::default()
+                },
+                ..Default::default()
+            });
+
+        id
+    }
 }
 
 impl Default for SignalStore {
@@ -170,12 +245,12 @@ impl Default for SignalStore {
 mod signal_store_tests {
     use super::*;
 
-    use std::collections::HashSet;
+    use std::{collections::HashSet, time::Duration};
 
     use freyja_contracts::{
         conversion::Conversion,
         entity::Entity,
-        signal::{Emission, EmissionPolicy, Target},
+        signal::{Emission, EmissionMode, EmissionPolicy, Target},
     };
 
     const GET_OPERATION: &str = "Get";
@@ -262,7 +337,7 @@ mod signal_store_tests {
 
         let original_signal = Signal {
             id: ID.to_string(),
-            value: Some(ORIGINAL.to_string()),
+            value: Some(ORIGINAL.into()),
             source: Entity {
                 id: ID.to_string(),
                 name: Some(ORIGINAL.to_string()),
@@ -275,15 +350,22 @@ mod signal_store_tests {
                 metadata: [(ORIGINAL.to_string(), ORIGINAL.to_string())]
                     .into_iter()
                     .collect(),
+                reverse_conversion: None,
             },
             emission: Emission {
                 policy: EmissionPolicy {
                     interval_ms: 42,
                     emit_only_if_changed: false,
                     conversion: Conversion::None,
+                    mode: EmissionMode::Interval,
+                    deadband_abs: None,
+                    deadband_pct: None,
+                    max_silence_ms: None,
+                    align_to_wall_clock: false,
                 },
-                next_emission_ms: 42,
-                last_emitted_value: Some(ORIGINAL.to_string()),
+                next_emission: Some(Instant::now() + Duration::from_millis(42)),
+                last_emitted_value: Some(ORIGINAL.into()),
+                last_emission: Some(Instant::now()),
             },
         };
 
@@ -291,7 +373,7 @@ mod signal_store_tests {
         // (except the id)
         let incoming_signal = Signal {
             id: ID.to_string(),
-            value: Some(INCOMING.to_string()),
+            value: Some(INCOMING.into()),
             source: Entity {
                 id: ID.to_string(),
                 name: Some(INCOMING.to_string()),
@@ -304,6 +386,7 @@ mod signal_store_tests {
                 metadata: [(INCOMING.to_string(), INCOMING.to_string())]
                     .into_iter()
                     .collect(),
+                reverse_conversion: None,
             },
             emission: Emission {
                 policy: EmissionPolicy {
@@ -313,9 +396,17 @@ mod signal_store_tests {
                         mul: 1.2,
                         offset: 3.4,
                     },
+                    mode: EmissionMode::OnChange {
+                        min_interval_ms: 100,
+                    },
+                    deadband_abs: Some(0.5),
+                    deadband_pct: Some(1.0),
+                    max_silence_ms: Some(60000),
+                    align_to_wall_clock: true,
                 },
-                next_emission_ms: 123,
-                last_emitted_value: Some(INCOMING.to_string()),
+                next_emission: Some(Instant::now() + Duration::from_millis(123)),
+                last_emitted_value: Some(INCOMING.into()),
+                last_emission: Some(Instant::now()),
             },
         };
 
@@ -325,7 +416,8 @@ mod signal_store_tests {
             signals.insert(ID.to_string(), original_signal.clone());
         }
 
-        uut.sync([incoming_signal.clone()].into_iter());
+        let removed = uut.sync([incoming_signal.clone()].into_iter());
+        assert!(removed.is_empty());
         let updated_signal = uut.get(&ID.to_string()).expect("Test signal should exist");
 
         // The following fields should have changed to match the incoming signal:
@@ -341,17 +433,22 @@ mod signal_store_tests {
 
         // The following fields should NOT have changed to match the incoming signal:
         // - value
-        // - emission.next_emission_ms
+        // - emission.next_emission
         // - emission.last_emitted_value
+        // - emission.last_emission
         assert_eq!(updated_signal.value, original_signal.value);
         assert_eq!(
-            updated_signal.emission.next_emission_ms,
-            original_signal.emission.next_emission_ms
+            updated_signal.emission.next_emission,
+            original_signal.emission.next_emission
         );
         assert_eq!(
             updated_signal.emission.last_emitted_value,
             original_signal.emission.last_emitted_value
         );
+        assert_eq!(
+            updated_signal.emission.last_emission,
+            original_signal.emission.last_emission
+        );
     }
 
     #[test]
@@ -361,7 +458,7 @@ mod signal_store_tests {
 
         let incoming_signal = Signal {
             id: ID.to_string(),
-            value: Some(INCOMING.to_string()),
+            value: Some(INCOMING.into()),
             source: Entity {
                 id: ID.to_string(),
                 name: Some(INCOMING.to_string()),
@@ -374,6 +471,7 @@ mod signal_store_tests {
                 metadata: [(INCOMING.to_string(), INCOMING.to_string())]
                     .into_iter()
                     .collect(),
+                reverse_conversion: None,
             },
             emission: Emission {
                 policy: EmissionPolicy {
@@ -383,15 +481,24 @@ mod signal_store_tests {
                         mul: 1.2,
                         offset: 3.4,
                     },
+                    mode: EmissionMode::OnChange {
+                        min_interval_ms: 100,
+                    },
+                    deadband_abs: Some(0.5),
+                    deadband_pct: Some(1.0),
+                    max_silence_ms: Some(60000),
+                    align_to_wall_clock: true,
                 },
-                next_emission_ms: 123,
-                last_emitted_value: Some(INCOMING.to_string()),
+                next_emission: Some(Instant::now() + Duration::from_millis(123)),
+                last_emitted_value: Some(INCOMING.into()),
+                last_emission: Some(Instant::now()),
             },
         };
 
         let uut = SignalStore::new();
 
-        uut.sync([incoming_signal.clone()].into_iter());
+        let removed = uut.sync([incoming_signal.clone()].into_iter());
+        assert!(removed.is_empty());
         let updated_signal = uut.get(&ID.to_string()).expect("Test signal should exist");
 
         // The following fields should match the incoming signal:
@@ -407,14 +514,16 @@ mod signal_store_tests {
 
         // The following fields should be initialized to default:
         // - value
-        // - emission.next_emission_ms
+        // - emission.next_emission
         // - emission.last_emitted_value
+        // - emission.last_emission
         assert_eq!(updated_signal.value, Default::default());
-        assert_eq!(updated_signal.emission.next_emission_ms, u64::default());
+        assert_eq!(updated_signal.emission.next_emission, Default::default());
         assert_eq!(
             updated_signal.emission.last_emitted_value,
             Default::default()
         );
+        assert_eq!(updated_signal.emission.last_emission, Default::default());
     }
 
     #[test]
@@ -424,7 +533,7 @@ mod signal_store_tests {
 
         let original_signal = Signal {
             id: ID.to_string(),
-            value: Some(ORIGINAL.to_string()),
+            value: Some(ORIGINAL.into()),
             source: Entity {
                 id: ID.to_string(),
                 name: Some(ORIGINAL.to_string()),
@@ -437,15 +546,22 @@ mod signal_store_tests {
                 metadata: [(ORIGINAL.to_string(), ORIGINAL.to_string())]
                     .into_iter()
                     .collect(),
+                reverse_conversion: None,
             },
             emission: Emission {
                 policy: EmissionPolicy {
                     interval_ms: 42,
                     emit_only_if_changed: false,
                     conversion: Conversion::None,
+                    mode: EmissionMode::Interval,
+                    deadband_abs: None,
+                    deadband_pct: None,
+                    max_silence_ms: None,
+                    align_to_wall_clock: false,
                 },
-                next_emission_ms: 42,
-                last_emitted_value: Some(ORIGINAL.to_string()),
+                next_emission: Some(Instant::now() + Duration::from_millis(42)),
+                last_emitted_value: Some(ORIGINAL.into()),
+                last_emission: Some(Instant::now()),
             },
         };
 
@@ -455,9 +571,101 @@ mod signal_store_tests {
             signals.insert(ID.to_string(), original_signal.clone());
         }
 
-        uut.sync(Vec::<SignalPatch>::new().into_iter());
+        let removed = uut.sync(Vec::<SignalPatch>::new().into_iter());
         let maybe_updated_signal = uut.get(&ID.to_string());
         assert!(maybe_updated_signal.is_none());
+        assert_eq!(removed.len(), 1);
+        assert_eq!(removed[0].id, ID);
+    }
+
+    #[test]
+    fn apply_delta_only_touches_affected_signals() {
+        const CHANGED_ID: &str = "changedid";
+        const REMOVED_ID: &str = "removedid";
+        const UNTOUCHED_ID: &str = "untouchedid";
+        const ADDED_ID: &str = "addedid";
+
+        let uut = SignalStore::new();
+        {
+            let mut signals = uut.signals.write().unwrap();
+            for id in [CHANGED_ID, REMOVED_ID, UNTOUCHED_ID] {
+                signals.insert(
+                    id.to_string(),
+                    Signal {
+                        id: id.to_string(),
+                        value: Some("value".into()),
+                        ..Default::default()
+                    },
+                );
+            }
+        }
+
+        let patches = [CHANGED_ID, ADDED_ID].into_iter().map(|id| SignalPatch {
+            id: id.to_string(),
+            emission_policy: EmissionPolicy {
+                interval_ms: 42,
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+
+        let removed = uut.apply_delta(patches, &[REMOVED_ID.to_string()]);
+
+        assert_eq!(removed.len(), 1);
+        assert_eq!(removed[0].id, REMOVED_ID);
+        assert!(uut.get(&REMOVED_ID.to_string()).is_none());
+
+        let changed_signal = uut.get(&CHANGED_ID.to_string()).unwrap();
+        assert_eq!(changed_signal.value, Some("value".into()));
+        assert_eq!(changed_signal.emission.policy.interval_ms, 42);
+
+        let untouched_signal = uut.get(&UNTOUCHED_ID.to_string()).unwrap();
+        assert_eq!(untouched_signal.emission.policy.interval_ms, 0);
+
+        let added_signal = uut.get(&ADDED_ID.to_string()).unwrap();
+        assert_eq!(added_signal.emission.policy.interval_ms, 42);
+    }
+
+    #[test]
+    fn upsert_keeps_signals_not_in_input() {
+        const ID: &str = "id";
+        const OTHER_ID: &str = "otherid";
+
+        let uut = SignalStore::new();
+        {
+            let mut signals = uut.signals.write().unwrap();
+            signals.insert(
+                ID.to_string(),
+                Signal {
+                    id: ID.to_string(),
+                    value: Some("value".into()),
+                    ..Default::default()
+                },
+            );
+        }
+
+        let patches = [ID, OTHER_ID].into_iter().map(|id| SignalPatch {
+            id: id.to_string(),
+            emission_policy: EmissionPolicy {
+                interval_ms: 42,
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+
+        uut.upsert(patches);
+
+        let signal = uut.get(&ID.to_string()).expect("Test signal should exist");
+        assert_eq!(signal.value, Some("value".into()));
+        assert_eq!(signal.emission.policy.interval_ms, 42);
+
+        let other_signal = uut
+            .get(&OTHER_ID.to_string())
+            .expect("Other test signal should exist");
+        assert_eq!(other_signal.emission.policy.interval_ms, 42);
+
+        uut.upsert(Vec::<SignalPatch>::new().into_iter());
+        assert_eq!(uut.get_all().len(), 2);
     }
 
     #[test]
@@ -476,7 +684,7 @@ mod signal_store_tests {
         }
 
         // Test first set returns Some(None) and changes state
-        let value = String::from("value");
+        let value = Value::from("value");
         let result = uut.set_value(ID.to_string(), value.clone());
         assert!(result.is_some());
         assert!(result.unwrap().is_none());
@@ -489,7 +697,7 @@ mod signal_store_tests {
         }
 
         // Test setting non-existent value returns None doesn't change state
-        let result = uut.set_value(String::from("foo"), String::from("foo"));
+        let result = uut.set_value(String::from("foo"), Value::from("foo"));
         assert!(result.is_none());
         {
             let signals = uut.signals.read().unwrap();
@@ -500,7 +708,7 @@ mod signal_store_tests {
         }
 
         // Test second set returns Some(Some("value")) and changes state
-        let result = uut.set_value(ID.to_string(), String::from("new value"));
+        let result = uut.set_value(ID.to_string(), Value::from("new value"));
         assert!(result.is_some());
         assert!(result.as_ref().unwrap().is_some());
         assert_eq!(result.unwrap().unwrap(), value);
@@ -513,24 +721,15 @@ mod signal_store_tests {
         }
     }
 
-    #[test]
-    fn set_last_emitted_value_tests() {
+    #[tokio::test(start_paused = true)]
+    async fn set_last_emitted_value_tests() {
         const ID: &str = "testid";
-        const INTERVAL: u64 = 42;
-        const UPDATED_EMISSION_TIME: u64 = 20;
 
         let uut = SignalStore::new();
         {
             let mut signals = uut.signals.write().unwrap();
             let signal = Signal {
                 id: ID.to_string(),
-                emission: Emission {
-                    policy: EmissionPolicy {
-                        interval_ms: INTERVAL,
-                        ..Default::default()
-                    },
-                    ..Default::default()
-                },
                 ..Default::default()
             };
 
@@ -538,7 +737,8 @@ mod signal_store_tests {
         }
 
         // Test first set returns Some(None) and changes state
-        let value = String::from("value");
+        let value = Value::from("value");
+        let first_emission = Instant::now();
         let result = uut.set_last_emitted_value(ID.to_string(), value.clone());
         assert!(result.is_some());
         assert!(result.unwrap().is_none());
@@ -546,37 +746,24 @@ mod signal_store_tests {
             let signals = uut.signals.read().unwrap();
             let signal = signals.get(&ID.to_string()).unwrap();
             assert_eq!(signal.emission.last_emitted_value, Some(value.clone()));
-            assert_eq!(signal.emission.next_emission_ms, INTERVAL);
+            assert_eq!(signal.emission.last_emission, Some(first_emission));
+            assert_eq!(signal.emission.next_emission, None);
         }
 
-        {
-            // Simulate something changing next_emission_ms, such as the emitter
-            let mut signals = uut.signals.write().unwrap();
-            signals
-                .entry(ID.to_string())
-                .and_modify(|s| s.emission.next_emission_ms = UPDATED_EMISSION_TIME);
-        }
+        tokio::time::advance(Duration::from_millis(42)).await;
 
         // Test setting non-existent value returns None doesn't change state
-        let result = uut.set_last_emitted_value(String::from("foo"), String::from("foo"));
+        let result = uut.set_last_emitted_value(String::from("foo"), Value::from("foo"));
         assert!(result.is_none());
         {
             let signals = uut.signals.read().unwrap();
             let signal = signals.get(&ID.to_string()).unwrap();
             assert_eq!(signal.emission.last_emitted_value, Some(value.clone()));
-            assert_eq!(signal.emission.next_emission_ms, UPDATED_EMISSION_TIME);
-        }
-
-        {
-            // Simulate something changing next_emission_ms, such as the emitter
-            let mut signals = uut.signals.write().unwrap();
-            signals
-                .entry(ID.to_string())
-                .and_modify(|s| s.emission.next_emission_ms = UPDATED_EMISSION_TIME);
+            assert_eq!(signal.emission.last_emission, Some(first_emission));
         }
 
         // Test second set returns Some(Some("value")) and changes state
-        let result = uut.set_last_emitted_value(ID.to_string(), String::from("new value"));
+        let result = uut.set_last_emitted_value(ID.to_string(), Value::from("new value"));
         assert!(result.is_some());
         assert!(result.as_ref().unwrap().is_some());
         assert_eq!(result.unwrap().unwrap(), value);
@@ -584,87 +771,55 @@ mod signal_store_tests {
             let signals = uut.signals.read().unwrap();
             let signal = signals.get(&ID.to_string()).unwrap();
             assert_ne!(signal.emission.last_emitted_value, Some(value.clone()));
-            assert_eq!(signal.emission.next_emission_ms, INTERVAL);
+            assert_eq!(
+                signal.emission.last_emission,
+                Some(first_emission + Duration::from_millis(42))
+            );
         }
     }
 
     #[test]
-    fn update_emission_times_and_get_all_sets_correct_value() {
+    fn set_next_emission_tests() {
         const ID: &str = "testid";
-        const ORIGINAL_VALUE: u64 = 42;
-        const INTERVAL: u64 = 20;
 
         let uut = SignalStore::new();
         {
             let mut signals = uut.signals.write().unwrap();
             let signal = Signal {
                 id: ID.to_string(),
-                emission: Emission {
-                    next_emission_ms: ORIGINAL_VALUE,
-                    ..Default::default()
-                },
                 ..Default::default()
             };
 
             signals.insert(ID.to_string(), signal);
         }
 
-        let mut result = uut.update_emission_times_and_get_all(INTERVAL);
-
-        // Validate the values in the result
-        assert_eq!(result.len(), 1);
-        let signal = result.pop().unwrap();
-        assert_eq!(signal.id, ID.to_string());
-        assert_eq!(signal.emission.next_emission_ms, ORIGINAL_VALUE - INTERVAL);
-
-        // Validate the values in the store itself
+        // Test first set returns Some(None) and changes state
+        let deadline = Instant::now() + Duration::from_millis(42);
+        let result = uut.set_next_emission(ID.to_string(), deadline);
+        assert_eq!(result, Some(None));
         {
             let signals = uut.signals.read().unwrap();
-            assert_eq!(signals.len(), 1);
-            assert!(signals.contains_key(&ID.to_string()));
             let signal = signals.get(&ID.to_string()).unwrap();
-            assert_eq!(signal.id, ID.to_string());
-            assert_eq!(signal.emission.next_emission_ms, ORIGINAL_VALUE - INTERVAL);
+            assert_eq!(signal.emission.next_emission, Some(deadline));
         }
-    }
-
-    #[test]
-    fn update_emission_times_and_get_all_saturates_overflowed_value() {
-        const ID: &str = "testid";
-        const ORIGINAL_VALUE: u64 = 20;
-        const INTERVAL: u64 = u64::MAX;
 
-        let uut = SignalStore::new();
+        // Test setting non-existent deadline returns None doesn't change state
+        let result = uut.set_next_emission(String::from("foo"), Instant::now());
+        assert!(result.is_none());
         {
-            let mut signals = uut.signals.write().unwrap();
-            let signal = Signal {
-                id: ID.to_string(),
-                emission: Emission {
-                    next_emission_ms: ORIGINAL_VALUE,
-                    ..Default::default()
-                },
-                ..Default::default()
-            };
-
-            signals.insert(ID.to_string(), signal);
+            let signals = uut.signals.read().unwrap();
+            let signal = signals.get(&ID.to_string()).unwrap();
+            assert_eq!(signal.emission.next_emission, Some(deadline));
         }
 
-        let mut result = uut.update_emission_times_and_get_all(INTERVAL);
-
-        // Validate the values in the result
-        assert_eq!(result.len(), 1);
-        let signal = result.pop().unwrap();
-        assert_eq!(signal.id, ID.to_string());
-        assert_eq!(signal.emission.next_emission_ms, 0);
-
-        // Validate the values in the store itself
+        // Test second set returns the previous deadline and changes state
+        let new_deadline = deadline + Duration::from_millis(42);
+        let result = uut.set_next_emission(ID.to_string(), new_deadline);
+        assert_eq!(result, Some(Some(deadline)));
         {
             let signals = uut.signals.read().unwrap();
-            assert_eq!(signals.len(), 1);
-            assert!(signals.contains_key(&ID.to_string()));
             let signal = signals.get(&ID.to_string()).unwrap();
-            assert_eq!(signal.id, ID.to_string());
-            assert_eq!(signal.emission.next_emission_ms, 0);
+            assert_eq!(signal.emission.next_emission, Some(new_deadline));
         }
     }
 }
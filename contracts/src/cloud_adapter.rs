@@ -2,13 +2,15 @@
 // Licensed under the MIT license.
 // SPDX-License-Identifier: MIT
 
-use std::collections::HashMap;
+use std::{collections::HashMap, sync::Arc};
 
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 
+use crate::value::Value;
+
 #[async_trait]
-pub trait CloudAdapter {
+pub trait CloudAdapter: Send + Sync {
     /// Creates a new instance of a CloudAdapter with default settings
     fn create_new() -> Result<Self, CloudAdapterError>
     where
@@ -22,6 +24,90 @@ pub trait CloudAdapter {
         &self,
         cloud_message: CloudMessageRequest,
     ) -> Result<CloudMessageResponse, CloudAdapterError>;
+
+    /// Sends a batch of signals to the cloud.
+    /// Returns one result per message, in the same order as the messages in the batch.
+    /// The default implementation calls `send_to_cloud` for each message in turn,
+    /// so adapters for cloud services with a bulk API should override this.
+    ///
+    /// # Arguments
+    /// - `cloud_messages`: the messages to send to the cloud canonical model
+    async fn send_batch_to_cloud(
+        &self,
+        cloud_messages: Vec<CloudMessageRequest>,
+    ) -> Vec<Result<CloudMessageResponse, CloudAdapterError>> {
+        let mut results = Vec::with_capacity(cloud_messages.len());
+        for cloud_message in cloud_messages {
+            results.push(self.send_to_cloud(cloud_message).await);
+        }
+
+        results
+    }
+
+    /// Gets the keys which must be present in the target metadata of each mapping entry
+    /// so that this adapter can send the entry's signal to the cloud.
+    /// Mapping entries which are missing any of these keys are rejected.
+    /// This doesn't depend on the adapter's state, so that mappings can be validated without creating an adapter.
+    /// The default implementation doesn't require any keys.
+    fn required_target_metadata() -> Vec<String> {
+        Vec::new()
+    }
+
+    /// Receives the next command from the cloud.
+    /// Returns `None` once no more commands will be received.
+    /// The default implementation returns `None` immediately, for adapters which don't support cloud-to-device commands.
+    async fn receive_command(&self) -> Result<Option<CloudCommandRequest>, CloudAdapterError> {
+        Ok(None)
+    }
+
+    /// Sends the outcome of a command to the cloud.
+    /// The default implementation discards the response.
+    ///
+    /// # Arguments
+    /// - `response`: the outcome of the command
+    async fn send_command_response(
+        &self,
+        _response: CloudCommandResponse,
+    ) -> Result<(), CloudAdapterError> {
+        Ok(())
+    }
+}
+
+/// Shares a cloud adapter, for example between the emitter and the commander
+#[async_trait]
+impl<T: CloudAdapter> CloudAdapter for Arc<T> {
+    fn create_new() -> Result<Self, CloudAdapterError> {
+        T::create_new().map(Arc::new)
+    }
+
+    async fn send_to_cloud(
+        &self,
+        cloud_message: CloudMessageRequest,
+    ) -> Result<CloudMessageResponse, CloudAdapterError> {
+        self.as_ref().send_to_cloud(cloud_message).await
+    }
+
+    fn required_target_metadata() -> Vec<String> {
+        T::required_target_metadata()
+    }
+
+    async fn send_batch_to_cloud(
+        &self,
+        cloud_messages: Vec<CloudMessageRequest>,
+    ) -> Vec<Result<CloudMessageResponse, CloudAdapterError>> {
+        self.as_ref().send_batch_to_cloud(cloud_messages).await
+    }
+
+    async fn receive_command(&self) -> Result<Option<CloudCommandRequest>, CloudAdapterError> {
+        self.as_ref().receive_command().await
+    }
+
+    async fn send_command_response(
+        &self,
+        response: CloudCommandResponse,
+    ) -> Result<(), CloudAdapterError> {
+        self.as_ref().send_command_response(response).await
+    }
 }
 
 /// Represents a message to send to the cloud canonical model
@@ -31,7 +117,7 @@ pub struct CloudMessageRequest {
     pub cloud_signal: HashMap<String, String>,
 
     // The signal value
-    pub signal_value: String,
+    pub signal_value: Value,
 
     // Timestamp of when the signal was emitted
     pub signal_timestamp: String,
@@ -41,6 +127,53 @@ pub struct CloudMessageRequest {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CloudMessageResponse {}
 
+/// Represents a command from the cloud digital twin
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CloudCommandRequest {
+    /// An id for correlating the command with its response
+    pub command_id: String,
+
+    /// A map containing metadata to identify a cloud canonical model signal.
+    /// This is matched against the target metadata of the signals in the mapping.
+    pub cloud_signal: HashMap<String, String>,
+
+    /// The action to perform
+    pub action: CloudCommandAction,
+}
+
+/// An action requested by the cloud digital twin
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CloudCommandAction {
+    /// Sets the desired state of the signal.
+    /// The value is in the cloud canonical model, and is converted back to the provider's units before it's set.
+    SetValue { value: Value },
+
+    /// Invokes a command on the signal's provider
+    Invoke { payload: Value },
+}
+
+/// Represents the outcome of a command from the cloud digital twin
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CloudCommandResponse {
+    /// The id of the command
+    pub command_id: String,
+
+    /// The outcome of the command
+    pub outcome: CloudCommandOutcome,
+}
+
+/// The outcome of a command from the cloud digital twin
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CloudCommandOutcome {
+    /// The command succeeded. Invocations include the provider's response.
+    Succeeded { response: Option<Value> },
+
+    /// The command failed
+    Failed { reason: String },
+}
+
 proc_macros::error! {
     CloudAdapterError {
         Io,
@@ -51,3 +184,59 @@ proc_macros::error! {
         Unknown
     }
 }
+
+#[cfg(test)]
+mod cloud_adapter_tests {
+    use super::*;
+
+    /// A cloud adapter which rejects messages with a string value
+    struct TestCloudAdapter {}
+
+    #[async_trait]
+    impl CloudAdapter for TestCloudAdapter {
+        fn create_new() -> Result<Self, CloudAdapterError> {
+            Ok(Self {})
+        }
+
+        async fn send_to_cloud(
+            &self,
+            cloud_message: CloudMessageRequest,
+        ) -> Result<CloudMessageResponse, CloudAdapterError> {
+            if let Value::String(_) = cloud_message.signal_value {
+                Err(CloudAdapterErrorKind::Communication.into())
+            } else {
+                Ok(CloudMessageResponse {})
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn send_batch_to_cloud_returns_result_per_message() {
+        let uut = TestCloudAdapter::create_new().unwrap();
+        let cloud_messages = [Value::Int(1), Value::from("foo"), Value::Int(3)]
+            .into_iter()
+            .map(|signal_value| CloudMessageRequest {
+                cloud_signal: HashMap::new(),
+                signal_value,
+                signal_timestamp: "timestamp".to_string(),
+            })
+            .collect();
+
+        let results = uut.send_batch_to_cloud(cloud_messages).await;
+
+        assert_eq!(results.len(), 3);
+        assert!(results[0].is_ok());
+        assert_eq!(
+            results[1].as_ref().err().unwrap().kind(),
+            CloudAdapterErrorKind::Communication
+        );
+        assert!(results[2].is_ok());
+    }
+
+    #[tokio::test]
+    async fn shared_adapter_without_command_support_receives_no_commands() {
+        let uut = Arc::<TestCloudAdapter>::create_new().unwrap();
+
+        assert!(uut.receive_command().await.unwrap().is_none());
+    }
+}
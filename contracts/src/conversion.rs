@@ -2,19 +2,59 @@
 // Licensed under the MIT license.
 // SPDX-License-Identifier: MIT
 
+use std::collections::{HashMap, HashSet};
+
 use serde::{Deserialize, Serialize};
 
-/// A conversion from one value to another
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, PartialOrd)]
+use crate::value::Value;
+
+/// A conversion from one value to another.
+///
+/// Conversions are identified by their properties when deserialized, so a mapping can specify
+/// `null`, `{ "mul": ..., "offset": ... }`, `{ "points": [[x, y], ...] }`, `{ "map": { ... }, "default": ... }`,
+/// `{ "min": ..., "max": ... }`, `{ "decimals": ... }` or `{ "steps": [ ... ] }`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[serde(untagged)]
 pub enum Conversion {
     /// No conversion
     None,
     /// A conversion from x to y in the form y = mul * x + offset
-    Linear { mul: f32, offset: f32 },
+    Linear { mul: f64, offset: f64 },
+    /// A calibration table of `(x, y)` points with linear interpolation between them.
+    /// Inputs outside of the table are clamped to the first or last point.
+    PiecewiseLinear { points: Vec<(f64, f64)> },
+    /// A lookup table from the text representation of the input to an output value.
+    /// Inputs which are not in the table are converted to `default`, or left unchanged if there is no default.
+    Lookup {
+        map: HashMap<String, Value>,
+        #[serde(default)]
+        default: Option<Value>,
+    },
+    /// Limits the input to the range `[min, max]`
+    Clamp { min: f64, max: f64 },
+    /// Rounds the input to the specified number of decimal places
+    Round { decimals: u32 },
+    /// Applies several conversions in order
+    Chain { steps: Vec<Conversion> },
 }
 
 impl Conversion {
+    /// The names of the kinds of conversions which are supported.
+    /// These are reported to the mapping service so that it only uses conversions which this version of Freyja supports.
+    pub const SUPPORTED_KINDS: &'static [&'static str] = &[
+        "none",
+        "linear",
+        "piecewise_linear",
+        "lookup",
+        "clamp",
+        "round",
+        "chain",
+    ];
+
+    /// The maximum number of decimal places which a `Round` conversion can round to.
+    /// An `f64` has at most 15 to 17 significant decimal digits, so rounding to more places has no effect.
+    pub const MAX_ROUND_DECIMALS: u32 = 15;
+
     /// Creates a LinearConversion for Celsius to Fahrenheit
     pub fn c_to_f() -> Self {
         Self::Linear {
@@ -30,13 +70,18 @@ impl Conversion {
 
     /// Inverts a Conversion
     ///
-    /// Note that this may not yield the exact inverse due to floating-point errors
+    /// Note that this may not yield the exact inverse due to floating-point errors.
+    /// Conversions which lose information can only be inverted approximately:
+    /// the inverse of `Clamp` and `Round` is `None`, the inverse of a `PiecewiseLinear` table
+    /// is only exact if the table is monotonic, and the inverse of a `Lookup` table has no default.
     ///
     /// # Example
     /// ```rust
-    /// use freyja_contracts::conversion::Conversion;
+    /// use freyja_contracts::{conversion::Conversion, value::Value};
     /// let c2f = Conversion::c_to_f();
-    /// assert!(42.0 == c2f.inverse().apply(c2f.apply(42.0)));
+    /// let converted = c2f.apply(&Value::Float(42.0)).unwrap();
+    /// let inverted = c2f.inverse().apply(&converted).unwrap().as_f64().unwrap();
+    /// assert!((inverted - 42.0).abs() < 1e-9);
     /// ```
     pub fn inverse(&self) -> Self {
         match self {
@@ -45,28 +90,238 @@ impl Conversion {
                 mul: 1.0 / m,
                 offset: -o / m,
             },
+            Self::PiecewiseLinear { points } => Self::PiecewiseLinear {
+                points: points.iter().map(|&(x, y)| (y, x)).collect(),
+            },
+            Self::Lookup { map, .. } => Self::Lookup {
+                map: map
+                    .iter()
+                    .map(|(input, output)| (output.to_string(), Value::infer(input)))
+                    .collect(),
+                default: None,
+            },
+            Self::Clamp { .. } | Self::Round { .. } => Self::None,
+            Self::Chain { steps } => Self::Chain {
+                steps: steps.iter().rev().map(Self::inverse).collect(),
+            },
         }
     }
 
-    /// Converts the input
+    /// Checks whether `inverse` undoes the conversion exactly, apart from floating-point errors.
+    /// This is false for `Clamp` and `Round`, for `PiecewiseLinear` tables which are not strictly monotonic,
+    /// for `Lookup` tables which map several inputs to the same output,
+    /// and for chains with any step which can't be inverted exactly.
     ///
-    /// # Arguments
+    /// # Example
+    /// ```rust
+    /// use freyja_contracts::conversion::Conversion;
+    /// assert!(Conversion::c_to_f().has_exact_inverse());
+    /// assert!(!Conversion::Round { decimals: 1 }.has_exact_inverse());
+    /// ```
+    pub fn has_exact_inverse(&self) -> bool {
+        match self {
+            Self::None | Self::Linear { .. } => true,
+            Self::PiecewiseLinear { points } => {
+                let mut sorted = points.to_vec();
+                sorted.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+                // The differences between consecutive points, which must all have the same sign
+                let deltas: Vec<(f64, f64)> = sorted
+                    .windows(2)
+                    .map(|segment| (segment[1].0 - segment[0].0, segment[1].1 - segment[0].1))
+                    .collect();
+
+                !deltas.is_empty()
+                    && deltas.iter().all(|(dx, _)| *dx > 0.0)
+                    && (deltas.iter().all(|(_, dy)| *dy > 0.0)
+                        || deltas.iter().all(|(_, dy)| *dy < 0.0))
+            }
+            Self::Lookup { map, .. } => {
+                let outputs: HashSet<String> = map.values().map(Value::to_string).collect();
+                outputs.len() == map.len()
+            }
+            Self::Clamp { .. } | Self::Round { .. } => false,
+            Self::Chain { steps } => steps.iter().all(Self::has_exact_inverse),
+        }
+    }
+
+    /// Checks that the conversion is well-formed so that it can be applied and inverted.
+    /// Linear conversions require a finite, non-zero `mul` and a finite `offset`,
+    /// piecewise linear conversions require at least one point with finite coordinates,
+    /// clamp conversions require `min <= max`, and round conversions require at most `MAX_ROUND_DECIMALS` decimals.
+    /// Chains are valid if all of their steps are valid.
     ///
-    /// - `input`: the value to convert
+    /// # Example
+    /// ```rust
+    /// use freyja_contracts::conversion::Conversion;
+    /// assert!(Conversion::c_to_f().validate().is_ok());
+    /// assert!(Conversion::Linear { mul: 0.0, offset: 1.0 }.validate().is_err());
+    /// ```
+    pub fn validate(&self) -> Result<(), ConversionError> {
+        match self {
+            Self::None | Self::Lookup { .. } => Ok(()),
+            Self::Round { decimals } => {
+                if *decimals <= Self::MAX_ROUND_DECIMALS {
+                    Ok(())
+                } else {
+                    Err(ConversionError::invalid_conversion(format!(
+                        "A round conversion can round to at most {} decimals, but has {decimals} decimals",
+                        Self::MAX_ROUND_DECIMALS
+                    )))
+                }
+            }
+            Self::Linear { mul, offset } => {
+                if *mul == 0.0 || !mul.is_finite() || !offset.is_finite() {
+                    Err(ConversionError::invalid_conversion(format!(
+                        "A linear conversion requires a finite, non-zero mul and a finite offset, but has mul {mul} and offset {offset}"
+                    )))
+                } else {
+                    Ok(())
+                }
+            }
+            Self::PiecewiseLinear { points } => {
+                if points.is_empty() {
+                    Err(ConversionError::invalid_conversion(
+                        "A piecewise linear conversion requires at least one point",
+                    ))
+                } else if points.iter().any(|(x, y)| !x.is_finite() || !y.is_finite()) {
+                    Err(ConversionError::invalid_conversion(
+                        "The points of a piecewise linear conversion must be finite",
+                    ))
+                } else {
+                    Ok(())
+                }
+            }
+            Self::Clamp { min, max } => {
+                // This comparison is false if either bound is NaN
+                if min <= max {
+                    Ok(())
+                } else {
+                    Err(ConversionError::invalid_conversion(format!(
+                        "A clamp conversion requires min <= max, but has min {min} and max {max}"
+                    )))
+                }
+            }
+            Self::Chain { steps } => steps.iter().try_for_each(Self::validate),
+        }
+    }
+
+    /// Checks that the tables in the conversion can be inverted, so that its inverse can convert values back.
+    /// Piecewise linear tables must be strictly monotonic, and lookup tables must not map several inputs to the same output.
+    /// `Clamp` and `Round` are expected to lose information, so they aren't checked.
     ///
     /// # Example
     /// ```rust
     /// use freyja_contracts::conversion::Conversion;
+    /// assert!(Conversion::PiecewiseLinear { points: vec![(0.0, 0.0), (1.0, 10.0)] }.validate_inverse().is_ok());
+    /// assert!(Conversion::PiecewiseLinear { points: vec![(0.0, 0.0), (1.0, 0.0)] }.validate_inverse().is_err());
+    /// ```
+    pub fn validate_inverse(&self) -> Result<(), ConversionError> {
+        match self {
+            Self::PiecewiseLinear { .. } if !self.has_exact_inverse() => {
+                Err(ConversionError::invalid_conversion(
+                    "The points of a piecewise linear conversion must be strictly monotonic so that it can be inverted",
+                ))
+            }
+            Self::Lookup { .. } if !self.has_exact_inverse() => {
+                Err(ConversionError::invalid_conversion(
+                    "A lookup conversion must map each input to a different output so that it can be inverted",
+                ))
+            }
+            Self::Chain { steps } => steps.iter().try_for_each(Self::validate_inverse),
+            _ => Ok(()),
+        }
+    }
+
+    /// Converts the input.
+    /// All conversions other than `None` and `Lookup` require a numeric input and produce a `Float` output.
+    ///
+    /// # Arguments
+    /// - `input`: the value to convert
+    ///
+    /// # Example
+    /// ```rust
+    /// use freyja_contracts::{conversion::Conversion, value::Value};
     /// let c2f = Conversion::c_to_f();
-    /// assert!(32.0 == c2f.apply(0.0));
-    /// assert!(212.0 == c2f.apply(100.0));
+    /// assert_eq!(c2f.apply(&Value::Int(0)).unwrap(), Value::Float(32.0));
+    /// assert_eq!(c2f.apply(&Value::Float(100.0)).unwrap(), Value::Float(212.0));
     /// ```
-    pub fn apply(&self, input: f32) -> f32 {
+    pub fn apply(&self, input: &Value) -> Result<Value, ConversionError> {
         match self {
-            Self::None => input,
-            Self::Linear { mul: m, offset: o } => input * m + o,
+            Self::None => Ok(input.clone()),
+            Self::Linear { mul: m, offset: o } => {
+                Ok(Value::Float(Self::numeric_input(input)? * m + o))
+            }
+            Self::PiecewiseLinear { points } => {
+                Self::interpolate(points, Self::numeric_input(input)?).map(Value::Float)
+            }
+            Self::Lookup { map, default } => Ok(map
+                .get(&input.to_string())
+                .or(default.as_ref())
+                .unwrap_or(input)
+                .clone()),
+            Self::Clamp { min, max } => Ok(Value::Float(
+                Self::numeric_input(input)?.max(*min).min(*max),
+            )),
+            Self::Round { decimals } => {
+                // This prevents the number of decimals from overflowing the exponent below
+                self.validate()?;
+                let factor = 10_f64.powi(*decimals as i32);
+                Ok(Value::Float(
+                    (Self::numeric_input(input)? * factor).round() / factor,
+                ))
+            }
+            Self::Chain { steps } => steps
+                .iter()
+                .try_fold(input.clone(), |value, step| step.apply(&value)),
         }
     }
+
+    /// Gets the numeric value of the input to a numeric conversion
+    ///
+    /// # Arguments
+    /// - `input`: the value to convert
+    fn numeric_input(input: &Value) -> Result<f64, ConversionError> {
+        input.as_f64().ok_or_else(|| {
+            ConversionError::invalid_input(format!("{input} is not a numeric value"))
+        })
+    }
+
+    /// Linearly interpolates between the points of a calibration table
+    ///
+    /// # Arguments
+    /// - `points`: the `(x, y)` points of the table
+    /// - `x`: the value to convert
+    fn interpolate(points: &[(f64, f64)], x: f64) -> Result<f64, ConversionError> {
+        let mut sorted = points.to_vec();
+        sorted.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        let (first, last) = match (sorted.first(), sorted.last()) {
+            (Some(first), Some(last)) => (*first, *last),
+            _ => {
+                return Err(ConversionError::invalid_conversion(
+                    "A piecewise linear conversion requires at least one point",
+                ))
+            }
+        };
+
+        if x <= first.0 {
+            return Ok(first.1);
+        }
+
+        if x >= last.0 {
+            return Ok(last.1);
+        }
+
+        let (x0, y0, x1, y1) = sorted
+            .windows(2)
+            .find(|segment| x <= segment[1].0)
+            .map(|segment| (segment[0].0, segment[0].1, segment[1].0, segment[1].1))
+            // x is strictly between the first and last points, so it falls in one of the segments
+            .unwrap();
+
+        Ok(y0 + (x - x0) * (y1 - y0) / (x1 - x0))
+    }
 }
 
 impl Default for Conversion {
@@ -75,13 +330,22 @@ impl Default for Conversion {
     }
 }
 
+proc_macros::error! {
+    ConversionError {
+        InvalidInput,
+        InvalidConversion
+    }
+}
+
 #[cfg(test)]
 mod conversion_tests {
     use super::*;
 
-    /// Valdiates that abs(lhs - rhs) < epsilon, or that lhs and rhs are both f32::NAN or infinite with the same sign
-    fn f32_close_enough(lhs: f32, rhs: f32, epsilon: f32) -> bool {
-        f32::abs(lhs - rhs) < epsilon
+    use serde_json::json;
+
+    /// Valdiates that abs(lhs - rhs) < epsilon, or that lhs and rhs are both f64::NAN or infinite with the same sign
+    fn f64_close_enough(lhs: f64, rhs: f64, epsilon: f64) -> bool {
+        f64::abs(lhs - rhs) < epsilon
             || lhs.is_nan() && rhs.is_nan()
             || lhs.is_infinite()
                 && rhs.is_infinite()
@@ -89,6 +353,15 @@ mod conversion_tests {
                 && rhs.is_sign_positive()
     }
 
+    /// Applies a conversion to a number and returns the numeric result
+    fn apply_f64(conversion: &Conversion, input: f64) -> f64 {
+        conversion
+            .apply(&Value::Float(input))
+            .unwrap()
+            .as_f64()
+            .unwrap()
+    }
+
     #[test]
     fn can_invert_none() {
         let c = Conversion::None;
@@ -114,19 +387,15 @@ mod conversion_tests {
     fn can_apply_none() {
         let c = Conversion::None;
 
-        // Try multiple values to make sure it works across various f32 and not just one input
+        // None doesn't require numeric values
         let vals = vec![
-            0.0,
-            -0.0,
-            -1.23,
-            42.0,
-            77.7,
-            f32::NAN,
-            f32::INFINITY,
-            std::f32::consts::PI,
+            Value::Float(-1.23),
+            Value::Int(42),
+            Value::Bool(true),
+            Value::String("foo".to_string()),
         ];
         for v in vals.into_iter() {
-            assert!(f32_close_enough(v, c.apply(v), 0.001));
+            assert_eq!(c.apply(&v).unwrap(), v);
         }
     }
 
@@ -135,20 +404,20 @@ mod conversion_tests {
         let (mul, offset) = (0.125, 1.0);
         let c = Conversion::Linear { mul, offset };
 
-        // Try multiple values to make sure it works across various f32 and not just one input
+        // Try multiple values to make sure it works across various f64 and not just one input
         let vals = vec![
             0.0,
             -0.0,
             -1.23,
             42.0,
             77.7,
-            f32::NAN,
-            f32::INFINITY,
-            std::f32::consts::PI,
+            f64::NAN,
+            f64::INFINITY,
+            std::f64::consts::PI,
         ];
         for v in vals.into_iter() {
             let expected = v * mul + offset;
-            assert!(f32_close_enough(expected, c.apply(v), 0.001));
+            assert!(f64_close_enough(expected, apply_f64(&c, v), 0.001));
         }
     }
 
@@ -160,19 +429,19 @@ mod conversion_tests {
         };
         let i = c.inverse();
 
-        // Try multiple values to make sure it works across various f32 and not just one input
+        // Try multiple values to make sure it works across various f64 and not just one input
         let vals = vec![
             0.0,
             -0.0,
             -1.23,
             42.0,
             77.7,
-            f32::NAN,
-            f32::INFINITY,
-            std::f32::consts::PI,
+            f64::NAN,
+            f64::INFINITY,
+            std::f64::consts::PI,
         ];
         for v in vals.into_iter() {
-            assert!(f32_close_enough(v, i.apply(c.apply(v)), 0.001));
+            assert!(f64_close_enough(v, apply_f64(&i, apply_f64(&c, v)), 0.001));
         }
     }
 
@@ -190,8 +459,389 @@ mod conversion_tests {
         ];
 
         for (f, c) in vals.into_iter() {
-            assert!(f32_close_enough(f, c2f.apply(c), 0.001));
-            assert!(f32_close_enough(f2c.apply(f), c, 0.001));
+            assert!(f64_close_enough(f, apply_f64(&c2f, c), 0.001));
+            assert!(f64_close_enough(apply_f64(&f2c, f), c, 0.001));
+        }
+    }
+
+    #[test]
+    fn numeric_conversions_reject_non_numeric_values() {
+        let c = Conversion::c_to_f();
+        let result = c.apply(&Value::String("foo".to_string()));
+
+        assert!(result.is_err());
+        assert_eq!(
+            result.err().unwrap().kind(),
+            ConversionErrorKind::InvalidInput
+        );
+    }
+
+    #[test]
+    fn can_apply_piecewise_linear() {
+        // The points are intentionally out of order
+        let c = Conversion::PiecewiseLinear {
+            points: vec![(10.0, 100.0), (0.0, 0.0), (5.0, 20.0)],
+        };
+
+        let vals = vec![
+            (-5.0, 0.0),
+            (0.0, 0.0),
+            (2.5, 10.0),
+            (5.0, 20.0),
+            (7.5, 60.0),
+            (10.0, 100.0),
+            (20.0, 100.0),
+        ];
+
+        for (input, expected) in vals.into_iter() {
+            assert!(f64_close_enough(expected, apply_f64(&c, input), 0.001));
+        }
+    }
+
+    #[test]
+    fn piecewise_linear_without_points_fails() {
+        let c = Conversion::PiecewiseLinear { points: vec![] };
+        let result = c.apply(&Value::Float(1.0));
+
+        assert!(result.is_err());
+        assert_eq!(
+            result.err().unwrap().kind(),
+            ConversionErrorKind::InvalidConversion
+        );
+    }
+
+    #[test]
+    fn can_apply_lookup() {
+        let c = Conversion::Lookup {
+            map: [
+                ("true".to_string(), Value::Int(1)),
+                ("false".to_string(), Value::Int(0)),
+                ("3".to_string(), Value::from("Drive")),
+            ]
+            .into_iter()
+            .collect(),
+            default: None,
+        };
+
+        assert_eq!(c.apply(&Value::Bool(true)).unwrap(), Value::Int(1));
+        assert_eq!(c.apply(&Value::from("false")).unwrap(), Value::Int(0));
+        assert_eq!(c.apply(&Value::Int(3)).unwrap(), Value::from("Drive"));
+
+        // Values which aren't in the table are unchanged without a default
+        assert_eq!(c.apply(&Value::Int(4)).unwrap(), Value::Int(4));
+
+        let c = match c {
+            Conversion::Lookup { map, .. } => Conversion::Lookup {
+                map,
+                default: Some(Value::from("Unknown")),
+            },
+            _ => unreachable!(),
+        };
+        assert_eq!(c.apply(&Value::Int(4)).unwrap(), Value::from("Unknown"));
+    }
+
+    #[test]
+    fn can_invert_lookup() {
+        let c = Conversion::Lookup {
+            map: [("3".to_string(), Value::from("Drive"))]
+                .into_iter()
+                .collect(),
+            default: Some(Value::from("Unknown")),
+        };
+
+        let i = c.inverse();
+
+        assert_eq!(i.apply(&Value::from("Drive")).unwrap(), Value::Int(3));
+        assert_eq!(i.apply(&Value::from("Park")).unwrap(), Value::from("Park"));
+    }
+
+    #[test]
+    fn can_apply_clamp() {
+        let c = Conversion::Clamp {
+            min: 0.0,
+            max: 100.0,
+        };
+
+        assert_eq!(apply_f64(&c, -1.0), 0.0);
+        assert_eq!(apply_f64(&c, 42.5), 42.5);
+        assert_eq!(apply_f64(&c, 101.0), 100.0);
+        assert_eq!(c.inverse(), Conversion::None);
+    }
+
+    #[test]
+    fn can_apply_round() {
+        let c = Conversion::Round { decimals: 2 };
+
+        assert_eq!(apply_f64(&c, 77.70001), 77.7);
+        assert_eq!(apply_f64(&c, -1.236), -1.24);
+        assert_eq!(
+            Conversion::Round { decimals: 0 }
+                .apply(&Value::Float(2.5))
+                .unwrap(),
+            Value::Float(3.0)
+        );
+    }
+
+    #[test]
+    fn can_apply_and_invert_chain() {
+        let c = Conversion::Chain {
+            steps: vec![
+                Conversion::f_to_c(),
+                Conversion::Clamp {
+                    min: -40.0,
+                    max: 85.0,
+                },
+                Conversion::Round { decimals: 1 },
+            ],
+        };
+
+        assert_eq!(apply_f64(&c, 212.0), 85.0);
+        assert_eq!(apply_f64(&c, 70.0), 21.1);
+
+        let i = c.inverse();
+        assert!(f64_close_enough(apply_f64(&i, 21.1), 69.98, 0.001));
+    }
+
+    #[test]
+    fn has_exact_inverse_rejects_lossy_conversions() {
+        let exact = [
+            Conversion::None,
+            Conversion::c_to_f(),
+            Conversion::PiecewiseLinear {
+                points: vec![(10.0, 100.0), (0.0, 0.0)],
+            },
+            Conversion::PiecewiseLinear {
+                points: vec![(0.0, 100.0), (10.0, 0.0)],
+            },
+            Conversion::Lookup {
+                map: [("1".to_string(), Value::from("On"))].into_iter().collect(),
+                default: None,
+            },
+            Conversion::Chain {
+                steps: vec![Conversion::f_to_c(), Conversion::c_to_f()],
+            },
+        ];
+        let lossy = [
+            Conversion::PiecewiseLinear {
+                points: vec![(0.0, 0.0)],
+            },
+            Conversion::PiecewiseLinear {
+                points: vec![(0.0, 0.0), (5.0, 10.0), (10.0, 0.0)],
+            },
+            Conversion::Lookup {
+                map: [
+                    ("1".to_string(), Value::from("On")),
+                    ("2".to_string(), Value::from("On")),
+                ]
+                .into_iter()
+                .collect(),
+                default: None,
+            },
+            Conversion::Clamp { min: 0.0, max: 1.0 },
+            Conversion::Round { decimals: 1 },
+            Conversion::Chain {
+                steps: vec![Conversion::f_to_c(), Conversion::Round { decimals: 1 }],
+            },
+        ];
+
+        for c in exact.iter() {
+            assert!(c.has_exact_inverse(), "{c:?}");
         }
+
+        for c in lossy.iter() {
+            assert!(!c.has_exact_inverse(), "{c:?}");
+        }
+    }
+
+    #[test]
+    fn deserializes_all_kinds() {
+        let json = json!([
+            null,
+            { "mul": 0.5556, "offset": -17.7778 },
+            { "points": [[0, 0], [10, 100]] },
+            { "map": { "true": 1 } },
+            { "map": { "3": "Drive" }, "default": "Unknown" },
+            { "min": 0, "max": 100 },
+            { "decimals": 2 },
+            { "steps": [{ "mul": 2, "offset": 0 }, { "decimals": 0 }] }
+        ]);
+
+        let result: Vec<Conversion> = serde_json::from_value(json).unwrap();
+
+        assert_eq!(
+            result,
+            vec![
+                Conversion::None,
+                Conversion::Linear {
+                    mul: 0.5556,
+                    offset: -17.7778
+                },
+                Conversion::PiecewiseLinear {
+                    points: vec![(0.0, 0.0), (10.0, 100.0)]
+                },
+                Conversion::Lookup {
+                    map: [("true".to_string(), Value::Int(1))].into_iter().collect(),
+                    default: None
+                },
+                Conversion::Lookup {
+                    map: [("3".to_string(), Value::from("Drive"))]
+                        .into_iter()
+                        .collect(),
+                    default: Some(Value::from("Unknown"))
+                },
+                Conversion::Clamp {
+                    min: 0.0,
+                    max: 100.0
+                },
+                Conversion::Round { decimals: 2 },
+                Conversion::Chain {
+                    steps: vec![
+                        Conversion::Linear {
+                            mul: 2.0,
+                            offset: 0.0
+                        },
+                        Conversion::Round { decimals: 0 }
+                    ]
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn validate_rejects_malformed_conversions() {
+        let valid = [
+            Conversion::None,
+            Conversion::c_to_f(),
+            Conversion::PiecewiseLinear {
+                points: vec![(0.0, 1.0)],
+            },
+            Conversion::Clamp { min: 1.0, max: 1.0 },
+            Conversion::Round {
+                decimals: Conversion::MAX_ROUND_DECIMALS,
+            },
+            Conversion::Chain {
+                steps: vec![Conversion::f_to_c(), Conversion::Round { decimals: 1 }],
+            },
+        ];
+
+        for conversion in valid {
+            assert!(conversion.validate().is_ok(), "{conversion:?} is valid");
+        }
+
+        let invalid = [
+            Conversion::Linear {
+                mul: 0.0,
+                offset: 1.0,
+            },
+            Conversion::Linear {
+                mul: 1.0,
+                offset: f64::NAN,
+            },
+            Conversion::PiecewiseLinear { points: vec![] },
+            Conversion::PiecewiseLinear {
+                points: vec![(0.0, f64::INFINITY)],
+            },
+            Conversion::Clamp { min: 2.0, max: 1.0 },
+            Conversion::Round {
+                decimals: Conversion::MAX_ROUND_DECIMALS + 1,
+            },
+            Conversion::Round { decimals: u32::MAX },
+            Conversion::Chain {
+                steps: vec![
+                    Conversion::c_to_f(),
+                    Conversion::Linear {
+                        mul: 0.0,
+                        offset: 0.0,
+                    },
+                ],
+            },
+        ];
+
+        for conversion in invalid {
+            assert_eq!(
+                conversion.validate().unwrap_err().kind(),
+                ConversionErrorKind::InvalidConversion,
+                "{conversion:?} is invalid"
+            );
+        }
+    }
+
+    #[test]
+    fn round_rejects_too_many_decimals() {
+        let c = Conversion::Round { decimals: u32::MAX };
+
+        assert_eq!(
+            c.apply(&Value::Float(1.5)).unwrap_err().kind(),
+            ConversionErrorKind::InvalidConversion
+        );
+    }
+
+    #[test]
+    fn validate_inverse_rejects_tables_which_cannot_be_inverted() {
+        let invertible = [
+            Conversion::Clamp { min: 0.0, max: 1.0 },
+            Conversion::PiecewiseLinear {
+                points: vec![(0.0, 10.0), (10.0, 0.0)],
+            },
+            Conversion::Lookup {
+                map: [("1".to_string(), Value::from("On"))].into_iter().collect(),
+                default: Some(Value::from("Off")),
+            },
+        ];
+
+        for conversion in invertible {
+            assert!(
+                conversion.validate_inverse().is_ok(),
+                "{conversion:?} can be inverted"
+            );
+        }
+
+        let not_invertible = [
+            Conversion::PiecewiseLinear {
+                points: vec![(0.0, 0.0), (5.0, 10.0), (10.0, 0.0)],
+            },
+            Conversion::Lookup {
+                map: [
+                    ("1".to_string(), Value::from("On")),
+                    ("2".to_string(), Value::from("On")),
+                ]
+                .into_iter()
+                .collect(),
+                default: None,
+            },
+            Conversion::Chain {
+                steps: vec![
+                    Conversion::f_to_c(),
+                    Conversion::PiecewiseLinear {
+                        points: vec![(0.0, 1.0), (1.0, 1.0)],
+                    },
+                ],
+            },
+        ];
+
+        for conversion in not_invertible {
+            assert_eq!(
+                conversion.validate_inverse().unwrap_err().kind(),
+                ConversionErrorKind::InvalidConversion,
+                "{conversion:?} can't be inverted"
+            );
+        }
+    }
+
+    #[test]
+    fn serialization_round_trips() {
+        let c = Conversion::Chain {
+            steps: vec![
+                Conversion::PiecewiseLinear {
+                    points: vec![(0.0, 1.0), (2.0, 3.0)],
+                },
+                Conversion::Clamp { min: 1.0, max: 2.0 },
+            ],
+        };
+
+        let json = serde_json::to_string(&c).unwrap();
+        let result: Conversion = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(result, c);
     }
 }
@@ -9,7 +9,7 @@ use crate::entity::Entity;
 
 /// Provides digital twin data
 #[async_trait]
-pub trait DigitalTwinAdapter {
+pub trait DigitalTwinAdapter: Send + Sync {
     /// Creates a new instance of a DigitalTwinAdapter with default settings
     fn create_new() -> Result<Self, DigitalTwinAdapterError>
     where
@@ -23,6 +23,20 @@ pub trait DigitalTwinAdapter {
         &self,
         request: GetDigitalTwinProviderRequest,
     ) -> Result<GetDigitalTwinProviderResponse, DigitalTwinAdapterError>;
+
+    /// Lists the entities which are available in the vehicle.
+    /// This is optional, and the default implementation returns an `Unsupported` error.
+    ///
+    /// # Arguments
+    /// - `request`: the request for listing entities
+    async fn list_entities(
+        &self,
+        _request: ListEntitiesRequest,
+    ) -> Result<ListEntitiesResponse, DigitalTwinAdapterError> {
+        Err(DigitalTwinAdapterError::unsupported(
+            "listing entities is not supported by this adapter",
+        ))
+    }
 }
 
 /// A request for digital twin providers
@@ -39,6 +53,17 @@ pub struct GetDigitalTwinProviderResponse {
     pub entity: Entity,
 }
 
+/// A request for listing the available entities
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ListEntitiesRequest {}
+
+/// The response for listing the available entities
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ListEntitiesResponse {
+    /// The available entities
+    pub entities: Vec<Entity>,
+}
+
 proc_macros::error! {
     DigitalTwinAdapterError {
         EntityNotFound,
@@ -47,6 +72,7 @@ proc_macros::error! {
         Deserialize,
         Communication,
         ParseError,
+        Unsupported,
         Unknown
     }
 }
@@ -6,10 +6,10 @@ use std::collections::HashMap;
 
 use serde::{Deserialize, Serialize};
 
-use crate::conversion::Conversion;
+use crate::{conversion::Conversion, signal::EmissionMode};
 
 /// Represents a mapping from the device digital twin to the cloud
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct DigitalTwinMapEntry {
     /// The name of the source signal provider
     pub source: String,
@@ -20,11 +20,38 @@ pub struct DigitalTwinMapEntry {
     /// The interval at which the signal data should be sent
     pub interval_ms: u64,
 
+    /// Specifies whether to send the signal data on multiples of the interval in wall-clock time
+    #[serde(default)]
+    pub align_to_wall_clock: bool,
+
     /// A conversion to apply to the signal data
+    #[serde(default)]
     pub conversion: Conversion,
 
+    /// A conversion to apply to values from the cloud before they are set on the source.
+    /// If this is not set, the inverse of `conversion` is used.
+    #[serde(default)]
+    pub reverse_conversion: Option<Conversion>,
+
     /// Specifies whether to emit the signal when there's a change
     pub emit_on_change: bool,
+
+    /// Determines when the signal is emitted
+    #[serde(default)]
+    pub mode: EmissionMode,
+
+    /// The absolute amount by which a numeric value must change to be emitted when `emit_on_change` is set
+    #[serde(default)]
+    pub deadband_abs: Option<f64>,
+
+    /// The percentage of the last emitted value by which a numeric value must change to be emitted
+    /// when `emit_on_change` is set
+    #[serde(default)]
+    pub deadband_pct: Option<f64>,
+
+    /// The maximum time in milliseconds to go without emitting the signal, even if it has not changed
+    #[serde(default)]
+    pub max_silence_ms: Option<u64>,
 }
 
 impl Default for DigitalTwinMapEntry {
@@ -33,8 +60,14 @@ impl Default for DigitalTwinMapEntry {
             source: String::new(),
             target: HashMap::new(),
             interval_ms: 0,
+            align_to_wall_clock: false,
             conversion: Conversion::None,
+            reverse_conversion: None,
             emit_on_change: false,
+            mode: EmissionMode::Interval,
+            deadband_abs: None,
+            deadband_pct: None,
+            max_silence_ms: None,
         }
     }
 }
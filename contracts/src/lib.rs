@@ -11,3 +11,4 @@ pub mod mapping_client;
 pub mod provider_proxy;
 pub mod provider_proxy_selector;
 pub mod signal;
+pub mod value;
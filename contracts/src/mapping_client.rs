@@ -2,7 +2,10 @@
 // Licensed under the MIT license.
 // SPDX-License-Identifier: MIT
 
-use std::collections::{HashMap, HashSet};
+use std::{
+    collections::{HashMap, HashSet},
+    time::Duration,
+};
 
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
@@ -11,7 +14,7 @@ use crate::digital_twin_map_entry::DigitalTwinMapEntry;
 
 /// Client interface for communicating with a mapping service
 #[async_trait]
-pub trait MappingClient {
+pub trait MappingClient: Send + Sync {
     /// Creates a new instance of a MappingClient with default settings
     fn create_new() -> Result<Self, MappingClientError>
     where
@@ -28,6 +31,32 @@ pub trait MappingClient {
         request: CheckForWorkRequest,
     ) -> Result<CheckForWorkResponse, MappingClientError>;
 
+    /// Waits until the mapping service has work for the caller or the timeout elapses, whichever comes first.
+    /// This allows mapping services to push changes to the client as soon as they happen, for example with long polling or server-sent events.
+    /// If the timeout elapses without any work, the response indicates that there is no work.
+    ///
+    /// The default implementation falls back to polling: it checks for work and, if there is none, sleeps for the timeout.
+    ///
+    /// # Arguments
+    ///
+    /// - `request`: the request to send
+    async fn wait_for_work(
+        &self,
+        request: WaitForWorkRequest,
+    ) -> Result<CheckForWorkResponse, MappingClientError> {
+        let response = self
+            .check_for_work(CheckForWorkRequest {
+                version: request.version,
+                vehicle: request.vehicle,
+            })
+            .await?;
+        if !response.has_work {
+            tokio::time::sleep(Duration::from_millis(request.timeout_ms)).await;
+        }
+
+        Ok(response)
+    }
+
     /// Sends the provider inventory to the mapping service
     ///
     /// # Arguments
@@ -49,11 +78,66 @@ pub trait MappingClient {
         &self,
         request: GetMappingRequest,
     ) -> Result<GetMappingResponse, MappingClientError>;
+
+    /// Reports the mapping entries which were rejected by the cartographer's validation to the mapping service.
+    ///
+    /// The default implementation discards the report.
+    ///
+    /// # Arguments
+    ///
+    /// - `request`: the request to send
+    async fn report_rejected_entries(
+        &self,
+        _request: ReportRejectedEntriesRequest,
+    ) -> Result<ReportRejectedEntriesResponse, MappingClientError> {
+        Ok(ReportRejectedEntriesResponse {})
+    }
+
+    /// Gets an update to the mapping from the mapping service.
+    /// Mapping services which support versioning can return only the entries which changed since the version in the request.
+    ///
+    /// The default implementation always returns the full mapping from `get_mapping`.
+    ///
+    /// # Arguments
+    ///
+    /// - `request`: the request to send
+    async fn get_mapping_update(
+        &self,
+        request: GetMappingRequest,
+    ) -> Result<MappingUpdate, MappingClientError> {
+        self.get_mapping(request).await.map(MappingUpdate::Full)
+    }
+}
+
+/// Information about the vehicle which is sent with requests to the mapping service
+/// so that the mapping service can tailor the mapping to the vehicle
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct VehicleInfo {
+    /// The identity of the vehicle, such as its VIN or a device id, or `None` if no identity is configured
+    pub vehicle_id: Option<String>,
+
+    /// The version of Freyja
+    pub freyja_version: String,
+
+    /// The protocols which Freyja can use to communicate with providers
+    pub supported_protocols: Vec<String>,
+
+    /// The kinds of conversions which Freyja supports
+    pub supported_conversions: Vec<String>,
 }
 
 /// A request for the check for work api
-#[derive(Debug, Serialize, Deserialize)]
-pub struct CheckForWorkRequest {}
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct CheckForWorkRequest {
+    /// The version of the mapping that the caller has, or `None` if the caller has no mapping or the mapping isn't versioned.
+    /// Mapping services which support versioning can use this to determine whether there is work for the caller.
+    #[serde(default)]
+    pub version: Option<u64>,
+
+    /// Information about the vehicle making the request
+    #[serde(default)]
+    pub vehicle: VehicleInfo,
+}
 
 /// A response for the check for work api
 #[derive(Debug, Serialize, Deserialize)]
@@ -62,6 +146,21 @@ pub struct CheckForWorkResponse {
     pub has_work: bool,
 }
 
+/// A request for the wait for work api
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WaitForWorkRequest {
+    /// The maximum time to wait for work, in milliseconds
+    pub timeout_ms: u64,
+
+    /// The version of the mapping that the caller has, or `None` if the caller has no mapping or the mapping isn't versioned
+    #[serde(default)]
+    pub version: Option<u64>,
+
+    /// Information about the vehicle making the request
+    #[serde(default)]
+    pub vehicle: VehicleInfo,
+}
+
 /// A request for sending inventory
 #[derive(Debug, Serialize, Deserialize)]
 pub struct SendInventoryRequest {
@@ -73,14 +172,110 @@ pub struct SendInventoryRequest {
 pub struct SendInventoryResponse {}
 
 /// A request for a mapping
-#[derive(Debug, Serialize, Deserialize)]
-pub struct GetMappingRequest {}
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct GetMappingRequest {
+    /// The version of the mapping that the caller has, or `None` if the caller has no mapping or the mapping isn't versioned.
+    /// Mapping services which support versioning can use this to return only the changes since this version.
+    #[serde(default)]
+    pub version: Option<u64>,
+
+    /// Information about the vehicle making the request
+    #[serde(default)]
+    pub vehicle: VehicleInfo,
+}
 
 /// A response with a mapping
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct GetMappingResponse {
     /// The map
     pub map: HashMap<String, DigitalTwinMapEntry>,
+
+    /// The version of the mapping, or `None` if the mapping service doesn't support versioning
+    #[serde(default)]
+    pub version: Option<u64>,
+}
+
+/// A mapping entry which was rejected by validation
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RejectedMappingEntry {
+    /// The id of the rejected entry
+    pub id: String,
+
+    /// The reasons that the entry was rejected
+    pub reasons: Vec<String>,
+}
+
+/// A request for reporting rejected mapping entries
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReportRejectedEntriesRequest {
+    /// The version of the mapping which contains the rejected entries, or `None` if the mapping isn't versioned
+    #[serde(default)]
+    pub version: Option<u64>,
+
+    /// Information about the vehicle making the request
+    #[serde(default)]
+    pub vehicle: VehicleInfo,
+
+    /// The rejected entries
+    pub rejected_entries: Vec<RejectedMappingEntry>,
+}
+
+/// A response to reporting rejected mapping entries
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReportRejectedEntriesResponse {}
+
+/// An update to a mapping
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum MappingUpdate {
+    /// The full mapping, which replaces the caller's mapping
+    Full(GetMappingResponse),
+
+    /// The changes to the caller's mapping
+    Delta(MappingDelta),
+}
+
+/// The changes between two versions of a mapping
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct MappingDelta {
+    /// The version of the mapping that the changes apply to
+    pub base_version: u64,
+
+    /// The version of the mapping after the changes are applied
+    pub version: u64,
+
+    /// The entries which were added to the mapping, keyed by signal id
+    #[serde(default)]
+    pub added: HashMap<String, DigitalTwinMapEntry>,
+
+    /// The entries which were changed, keyed by signal id
+    #[serde(default)]
+    pub changed: HashMap<String, DigitalTwinMapEntry>,
+
+    /// The ids of the signals which were removed from the mapping
+    #[serde(default)]
+    pub removed: Vec<String>,
+}
+
+impl MappingDelta {
+    /// Applies the changes to a mapping
+    ///
+    /// # Arguments
+    /// - `mapping`: the mapping to update. This should be the mapping with the delta's base version
+    pub fn apply_to(&self, mapping: &mut GetMappingResponse) {
+        for id in self.removed.iter() {
+            mapping.map.remove(id);
+        }
+
+        mapping.map.extend(
+            self.added
+                .iter()
+                .chain(self.changed.iter())
+                .map(|(id, entry)| (id.clone(), entry.clone())),
+        );
+
+        mapping.version = Some(self.version);
+    }
 }
 
 proc_macros::error! {
@@ -92,3 +287,97 @@ proc_macros::error! {
         Unknown
     }
 }
+
+#[cfg(test)]
+mod mapping_client_tests {
+    use super::*;
+
+    use std::sync::Mutex;
+
+    use tokio::time::Instant;
+
+    /// A mapping client which only supports polling
+    struct PollingMappingClient {
+        work: Mutex<Vec<bool>>,
+    }
+
+    #[async_trait]
+    impl MappingClient for PollingMappingClient {
+        fn create_new() -> Result<Self, MappingClientError> {
+            Ok(Self {
+                work: Mutex::new(Vec::new()),
+            })
+        }
+
+        async fn check_for_work(
+            &self,
+            _request: CheckForWorkRequest,
+        ) -> Result<CheckForWorkResponse, MappingClientError> {
+            Ok(CheckForWorkResponse {
+                has_work: self.work.lock().unwrap().pop().unwrap_or_default(),
+            })
+        }
+
+        async fn get_mapping(
+            &self,
+            _request: GetMappingRequest,
+        ) -> Result<GetMappingResponse, MappingClientError> {
+            Ok(GetMappingResponse::default())
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn wait_for_work_falls_back_to_polling() {
+        let uut = PollingMappingClient {
+            work: Mutex::new(vec![false, true]),
+        };
+        let request = || WaitForWorkRequest {
+            timeout_ms: 1000,
+            version: None,
+            vehicle: VehicleInfo::default(),
+        };
+
+        let start = Instant::now();
+        assert!(uut.wait_for_work(request()).await.unwrap().has_work);
+        assert_eq!(start.elapsed(), Duration::ZERO);
+
+        assert!(!uut.wait_for_work(request()).await.unwrap().has_work);
+        assert_eq!(start.elapsed(), Duration::from_millis(1000));
+    }
+
+    #[test]
+    fn delta_is_applied_to_mapping() {
+        let entry = |interval_ms| DigitalTwinMapEntry {
+            interval_ms,
+            ..Default::default()
+        };
+
+        let mut mapping = GetMappingResponse {
+            map: [
+                ("changed", entry(1)),
+                ("removed", entry(1)),
+                ("kept", entry(1)),
+            ]
+            .into_iter()
+            .map(|(id, entry)| (id.to_string(), entry))
+            .collect(),
+            version: Some(1),
+        };
+
+        let delta = MappingDelta {
+            base_version: 1,
+            version: 2,
+            added: [("added".to_string(), entry(2))].into_iter().collect(),
+            changed: [("changed".to_string(), entry(2))].into_iter().collect(),
+            removed: vec!["removed".to_string()],
+        };
+
+        delta.apply_to(&mut mapping);
+
+        assert_eq!(mapping.version, Some(2));
+        assert_eq!(mapping.map.len(), 3);
+        assert_eq!(mapping.map["added"].interval_ms, 2);
+        assert_eq!(mapping.map["changed"].interval_ms, 2);
+        assert_eq!(mapping.map["kept"].interval_ms, 1);
+    }
+}
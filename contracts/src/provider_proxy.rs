@@ -2,18 +2,21 @@
 // Licensed under the MIT license.
 // SPDX-License-Identifier: MIT
 
-use std::{fmt::Debug, sync::Arc};
+use std::{fmt::Debug, future::Future, pin::Pin};
 
 use async_trait::async_trait;
-use crossbeam::queue::SegQueue;
+use tokio::sync::mpsc::UnboundedSender;
+
+use crate::value::Value;
 
 /// Represents a signal value
+#[derive(Clone, Debug)]
 pub struct SignalValue {
     /// The entity's id
     pub entity_id: String,
 
     /// The entity's value
-    pub value: String,
+    pub value: Value,
 }
 
 #[async_trait]
@@ -22,10 +25,10 @@ pub trait ProviderProxy: Debug {
     ///
     /// # Arguments
     /// - `provider_uri`: the provider uri for accessing an entity's information
-    /// - `signal_values_queue`: shared queue for all provider proxies to push new signal values of entities
+    /// - `signal_values_sender`: shared channel for all provider proxies to send new signal values of entities
     fn create_new(
         provider_uri: &str,
-        signal_values_queue: Arc<SegQueue<SignalValue>>,
+        signal_values_sender: UnboundedSender<SignalValue>,
     ) -> Result<Box<dyn ProviderProxy + Send + Sync>, ProviderProxyError>
     where
         Self: Sized;
@@ -51,6 +54,37 @@ pub trait ProviderProxy: Debug {
         operation: &str,
     ) -> Result<(), ProviderProxyError>;
 
+    /// Unregisters an entity id from the local cache inside a provider proxy.
+    /// If the entity was subscribed to, the expectation is unsubscribe will happen in this function.
+    ///
+    /// # Arguments
+    /// - `entity_id`: the entity id to remove
+    async fn unregister_entity(&self, entity_id: &str) -> Result<(), ProviderProxyError>;
+
+    /// Sets the value of an entity on its provider.
+    /// The default implementation returns an error, for provider proxies which don't support setting values.
+    ///
+    /// # Arguments
+    /// - `entity_id`: the entity id to set the value of
+    /// - `value`: the value to set, in the provider's units
+    async fn set_value(&self, entity_id: &str, _value: Value) -> Result<(), ProviderProxyError> {
+        Err(ProviderProxyError::operation_not_supported(format!(
+            "Setting the value of {entity_id} is not supported by this provider proxy"
+        )))
+    }
+
+    /// Invokes a command on an entity's provider and returns the provider's response.
+    /// The default implementation returns an error, for provider proxies which don't support invoking commands.
+    ///
+    /// # Arguments
+    /// - `entity_id`: the entity id to invoke
+    /// - `payload`: the payload of the command
+    async fn invoke(&self, entity_id: &str, _payload: Value) -> Result<Value, ProviderProxyError> {
+        Err(ProviderProxyError::operation_not_supported(format!(
+            "Invoking {entity_id} is not supported by this provider proxy"
+        )))
+    }
+
     /// Checks if this operation is supported
     ///
     /// # Arguments
@@ -60,6 +94,45 @@ pub trait ProviderProxy: Debug {
         Self: Sized + Send + Sync;
 }
 
+/// A future which runs a consumer endpoint
+pub type ConsumerEndpointFuture =
+    Pin<Box<dyn Future<Output = Result<(), ProviderProxyError>> + Send>>;
+
+/// Creates provider proxies for a protocol.
+/// Factories are registered with the provider proxy selector to add support for a protocol.
+pub trait ProviderProxyFactory: Send + Sync {
+    /// Checks if the provider proxies created by this factory support an operation
+    ///
+    /// # Arguments
+    /// - `operation`: the operation to check
+    fn is_operation_supported(&self, operation: &str) -> bool;
+
+    /// Creates a provider proxy
+    ///
+    /// # Arguments
+    /// - `provider_uri`: the provider uri for accessing an entity's information
+    /// - `signal_values_sender`: shared channel for all provider proxies to send new signal values of entities
+    fn create_proxy(
+        &self,
+        provider_uri: &str,
+        signal_values_sender: UnboundedSender<SignalValue>,
+    ) -> Result<Box<dyn ProviderProxy + Send + Sync>, ProviderProxyError>;
+
+    /// Creates the consumer endpoint which receives the values that providers publish,
+    /// if the provider proxies created by this factory need one.
+    /// A single endpoint is shared by all of the provider proxies that this factory creates.
+    /// Returns `None` by default.
+    ///
+    /// # Arguments
+    /// - `signal_values_sender`: shared channel for all provider proxies to send new signal values of entities
+    fn create_consumer_endpoint(
+        &self,
+        _signal_values_sender: UnboundedSender<SignalValue>,
+    ) -> Option<ConsumerEndpointFuture> {
+        None
+    }
+}
+
 proc_macros::error! {
     ProviderProxyError {
         Io,
@@ -68,6 +141,7 @@ proc_macros::error! {
         Deserialize,
         Communication,
         EntityNotFound,
+        OperationNotSupported,
         Unknown
     }
 }
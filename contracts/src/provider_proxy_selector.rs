@@ -2,9 +2,19 @@
 // Licensed under the MIT license.
 // SPDX-License-Identifier: MIT
 
+use std::{future::Future, pin::Pin};
+
 use async_trait::async_trait;
 
-use crate::entity::Entity;
+use crate::{entity::Entity, value::Value};
+
+/// A future which resolves once the provider has set an entity's value
+pub type SetValueFuture =
+    Pin<Box<dyn Future<Output = Result<(), ProviderProxySelectorError>> + Send>>;
+
+/// A future which resolves to the provider's response to an invocation
+pub type InvokeResponseFuture =
+    Pin<Box<dyn Future<Output = Result<Value, ProviderProxySelectorError>> + Send>>;
 
 /// Manages a collection of proxies and provides access to them.
 /// Conceptually similar to a gateway for the proxies.
@@ -20,6 +30,13 @@ pub trait ProviderProxySelector {
         entity: &Entity,
     ) -> Result<(), ProviderProxySelectorError>;
 
+    /// Removes an entity from the proxy that handles it.
+    /// The proxy is shut down if it no longer handles any entities.
+    ///
+    /// # Arguments
+    /// - `entity_id`: the entity to remove
+    async fn remove_entity(&mut self, entity_id: &str) -> Result<(), ProviderProxySelectorError>;
+
     /// Requests that the value of an entity be published as soon as possible
     ///
     /// # Arguments
@@ -28,6 +45,32 @@ pub trait ProviderProxySelector {
         &mut self,
         entity_id: &str,
     ) -> Result<(), ProviderProxySelectorError>;
+
+    /// Sets the value of an entity using the proxy that handles it.
+    /// The returned future resolves once the provider has set the value. It doesn't borrow the selector,
+    /// so callers can release the selector while waiting for the provider.
+    ///
+    /// # Arguments
+    /// - `entity_id`: the entity to set the value of
+    /// - `value`: the value to set, in the provider's units
+    async fn set_entity_value(
+        &mut self,
+        entity_id: &str,
+        value: Value,
+    ) -> Result<SetValueFuture, ProviderProxySelectorError>;
+
+    /// Invokes a command on an entity using the proxy that handles it.
+    /// The returned future resolves to the provider's response. It doesn't borrow the selector,
+    /// so callers can release the selector while waiting for a response.
+    ///
+    /// # Arguments
+    /// - `entity_id`: the entity to invoke
+    /// - `payload`: the payload of the command
+    async fn invoke_entity(
+        &mut self,
+        entity_id: &str,
+        payload: Value,
+    ) -> Result<InvokeResponseFuture, ProviderProxySelectorError>;
 }
 
 proc_macros::error! {
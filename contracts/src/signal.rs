@@ -2,9 +2,12 @@
 // Licensed under the MIT license.
 // SPDX-License-Identifier: MIT
 
-use std::collections::HashMap;
+use std::{collections::HashMap, time::Duration};
 
-use crate::{conversion::Conversion, entity::Entity};
+use serde::{Deserialize, Serialize};
+use tokio::time::Instant;
+
+use crate::{conversion::Conversion, entity::Entity, value::Value};
 
 /// Conveys information about a signal, its current state, and how the data should be emitted
 #[derive(Clone, Debug, Default, PartialEq)]
@@ -12,7 +15,7 @@ pub struct Signal {
     /// The signal id. In most cases, this should be the same as the source id
     pub id: String,
     /// The signal's current value, if it's been set
-    pub value: Option<String>,
+    pub value: Option<Value>,
     /// The signal's source entity information
     pub source: Entity,
     /// The signal's target mapping information
@@ -21,6 +24,22 @@ pub struct Signal {
     pub emission: Emission,
 }
 
+impl Signal {
+    /// Gets the conversion to apply to values from the cloud before they are set on the signal's source.
+    /// This is the target's reverse conversion if there is one, and otherwise the inverse of the emission conversion.
+    /// Returns `None` if there is no reverse conversion and the emission conversion has no exact inverse,
+    /// since values from the cloud can't be converted back to the source's units in that case.
+    pub fn reverse_conversion(&self) -> Option<Conversion> {
+        match &self.target.reverse_conversion {
+            Some(conversion) => Some(conversion.clone()),
+            None if self.emission.policy.conversion.has_exact_inverse() => {
+                Some(self.emission.policy.conversion.inverse())
+            }
+            None => None,
+        }
+    }
+}
+
 /// A partial signal representation used in the signal store's sync API
 #[derive(Clone, Debug, Default, PartialEq)]
 pub struct SignalPatch {
@@ -39,6 +58,9 @@ pub struct SignalPatch {
 pub struct Target {
     /// Metadata that will be passed to the cloud adapter to perform the mapping
     pub metadata: HashMap<String, String>,
+    /// A conversion to apply to values from the cloud before they are set on the source.
+    /// If this is `None`, the inverse of the emission policy's conversion is used.
+    pub reverse_conversion: Option<Conversion>,
 }
 
 /// Metadata about a signal's emission
@@ -46,11 +68,35 @@ pub struct Target {
 pub struct Emission {
     /// The emission policy
     pub policy: EmissionPolicy,
-    /// The time until the signal's next emission
-    /// Note that the default for this value is 0, which the emitter will interpret as ready to emit ASAP
-    pub next_emission_ms: u64,
+    /// The deadline of the signal's next scheduled emission
+    /// Note that the default for this value is `None`, which the emitter will interpret as ready to emit ASAP
+    pub next_emission: Option<Instant>,
     /// The last emitted value
-    pub last_emitted_value: Option<String>,
+    pub last_emitted_value: Option<Value>,
+    /// The time at which the signal was last emitted.
+    /// This is used together with the policy's `max_silence_ms` to determine when a heartbeat emission is due.
+    pub last_emission: Option<Instant>,
+}
+
+impl Emission {
+    /// Determines whether the signal's scheduled emission is due
+    ///
+    /// # Arguments
+    /// - `now`: the current time
+    pub fn is_due(&self, now: Instant) -> bool {
+        self.next_emission.map_or(true, |deadline| deadline <= now)
+    }
+
+    /// Gets the time in milliseconds since the signal was last emitted, or `u64::MAX` if it has never been emitted
+    ///
+    /// # Arguments
+    /// - `now`: the current time
+    pub fn time_since_emission_ms(&self, now: Instant) -> u64 {
+        self.last_emission.map_or(u64::MAX, |last_emission| {
+            u64::try_from(now.saturating_duration_since(last_emission).as_millis())
+                .unwrap_or(u64::MAX)
+        })
+    }
 }
 
 /// A signal's emission policy
@@ -58,10 +104,121 @@ pub struct Emission {
 pub struct EmissionPolicy {
     /// The interal at which the signal should be emitted
     pub interval_ms: u64,
+    /// Indicates whether emissions should happen on multiples of the interval in wall-clock time,
+    /// for example every 10s on the :00/:10 boundary
+    pub align_to_wall_clock: bool,
     /// Indicates whether the signal data should only be emitted if the value has changed
     pub emit_only_if_changed: bool,
     /// A conversion to apply to the signal before emission
     pub conversion: Conversion,
+    /// Determines when the signal is emitted
+    pub mode: EmissionMode,
+    /// The absolute amount by which a numeric value must change to be considered changed.
+    /// Only used if `emit_only_if_changed` is set.
+    pub deadband_abs: Option<f64>,
+    /// The percentage of the last emitted value by which a numeric value must change to be considered changed.
+    /// Only used if `emit_only_if_changed` is set.
+    pub deadband_pct: Option<f64>,
+    /// The maximum time to go without emitting the signal.
+    /// If this is set, the signal is emitted once this time has passed even if its value has not changed.
+    pub max_silence_ms: Option<u64>,
+}
+
+/// Determines when a signal is emitted
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EmissionMode {
+    /// The signal is emitted each time its interval elapses
+    #[default]
+    Interval,
+    /// The signal is emitted as soon as a changed value arrives, but no sooner than `min_interval_ms` after its last emission.
+    /// The signal is still checked each time its interval elapses, but is only emitted then if it has changed.
+    OnChange { min_interval_ms: u64 },
+}
+
+impl EmissionPolicy {
+    /// Determines whether the signal should only be emitted when its value changes,
+    /// either because `emit_only_if_changed` is set or because the signal is emitted on change.
+    pub fn is_change_driven(&self) -> bool {
+        self.emit_only_if_changed || matches!(self.mode, EmissionMode::OnChange { .. })
+    }
+
+    /// Determines whether a value has changed compared to the last emitted value.
+    /// Numeric values are compared by their numeric value, so `Int(1)` and `Float(1.0)` are equal,
+    /// and only count as changed if the difference exceeds the larger of the two deadbands.
+    /// Other values count as changed if they are not equal.
+    ///
+    /// # Arguments
+    /// - `last_emitted_value`: the last emitted value
+    /// - `value`: the current value
+    pub fn is_changed(&self, last_emitted_value: &Value, value: &Value) -> bool {
+        match (last_emitted_value.as_f64(), value.as_f64()) {
+            (Some(last), Some(current)) => {
+                let abs_tolerance = self.deadband_abs.unwrap_or(0.0);
+                let pct_tolerance = self.deadband_pct.unwrap_or(0.0) / 100.0 * last.abs();
+
+                (current - last).abs() > abs_tolerance.max(pct_tolerance)
+            }
+            _ => last_emitted_value != value,
+        }
+    }
+
+    /// Gets the deadline of the emission which follows a scheduled emission.
+    /// Deadlines advance by whole intervals from the previous deadline rather than from the current time,
+    /// so the time spent emitting data doesn't cause the emissions to drift. Intervals which were missed entirely are skipped.
+    /// If `align_to_wall_clock` is set, the deadline is instead the next multiple of the interval in wall-clock time.
+    ///
+    /// # Arguments
+    /// - `previous`: the deadline of the previous emission, or `None` if the signal hasn't been scheduled yet
+    /// - `now`: the current time
+    /// - `since_epoch`: the current wall-clock time as a duration since the Unix epoch
+    pub fn next_emission(
+        &self,
+        previous: Option<Instant>,
+        now: Instant,
+        since_epoch: Duration,
+    ) -> Instant {
+        if self.interval_ms == 0 {
+            return now;
+        }
+
+        if self.align_to_wall_clock {
+            let offset_ms = since_epoch.as_millis() % u128::from(self.interval_ms);
+            // The offset is less than the interval, so it fits in a u64
+            return add_millis(now, self.interval_ms - offset_ms as u64);
+        }
+
+        match previous {
+            Some(previous) if previous <= now => {
+                let elapsed_ms = u64::try_from((now - previous).as_millis()).unwrap_or(u64::MAX);
+                let intervals = elapsed_ms / self.interval_ms + 1;
+                add_millis(previous, self.interval_ms.saturating_mul(intervals))
+            }
+            Some(previous) => add_millis(previous, self.interval_ms),
+            None => add_millis(now, self.interval_ms),
+        }
+    }
+
+    /// Determines whether a heartbeat emission is due.
+    ///
+    /// # Arguments
+    /// - `time_since_emission_ms`: the time since the signal was last emitted
+    pub fn is_heartbeat_due(&self, time_since_emission_ms: u64) -> bool {
+        self.max_silence_ms
+            .is_some_and(|max_silence_ms| time_since_emission_ms >= max_silence_ms)
+    }
+}
+
+/// Adds a number of milliseconds to an instant, saturating at a time roughly 30 years in the future.
+/// This mirrors how tokio handles sleeps which are too long to represent.
+///
+/// # Arguments
+/// - `instant`: the instant to add to
+/// - `ms`: the number of milliseconds to add
+fn add_millis(instant: Instant, ms: u64) -> Instant {
+    instant
+        .checked_add(Duration::from_millis(ms))
+        .unwrap_or_else(|| instant + Duration::from_secs(86400 * 365 * 30))
 }
 
 impl From<Signal> for SignalPatch {
@@ -74,3 +231,256 @@ impl From<Signal> for SignalPatch {
         }
     }
 }
+
+#[cfg(test)]
+mod signal_tests {
+    use super::*;
+
+    #[test]
+    fn reverse_conversion_defaults_to_inverse_of_emission_conversion() {
+        let mut signal = Signal {
+            emission: Emission {
+                policy: EmissionPolicy {
+                    conversion: Conversion::c_to_f(),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        assert_eq!(
+            signal.reverse_conversion(),
+            Some(Conversion::c_to_f().inverse())
+        );
+
+        signal.target.reverse_conversion = Some(Conversion::Round { decimals: 1 });
+        assert_eq!(
+            signal.reverse_conversion(),
+            Some(Conversion::Round { decimals: 1 })
+        );
+    }
+
+    #[test]
+    fn reverse_conversion_is_none_without_exact_inverse() {
+        let mut signal = Signal {
+            emission: Emission {
+                policy: EmissionPolicy {
+                    conversion: Conversion::Clamp {
+                        min: 0.0,
+                        max: 100.0,
+                    },
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        assert_eq!(signal.reverse_conversion(), None);
+
+        signal.target.reverse_conversion = Some(Conversion::None);
+        assert_eq!(signal.reverse_conversion(), Some(Conversion::None));
+    }
+
+    #[test]
+    fn is_changed_compares_numbers_by_value() {
+        let policy = EmissionPolicy::default();
+
+        assert!(!policy.is_changed(&Value::Int(1), &Value::Float(1.0)));
+        assert!(policy.is_changed(&Value::Int(1), &Value::Float(1.5)));
+        assert!(policy.is_changed(&Value::Float(77.70001), &Value::Float(77.70002)));
+    }
+
+    #[test]
+    fn is_changed_honors_absolute_deadband() {
+        let policy = EmissionPolicy {
+            deadband_abs: Some(0.5),
+            ..Default::default()
+        };
+
+        assert!(!policy.is_changed(&Value::Float(77.70001), &Value::Float(77.70002)));
+        assert!(!policy.is_changed(&Value::Float(10.0), &Value::Float(9.5)));
+        assert!(policy.is_changed(&Value::Float(10.0), &Value::Float(10.6)));
+        assert!(policy.is_changed(&Value::Int(10), &Value::Int(9)));
+    }
+
+    #[test]
+    fn is_changed_honors_percentage_deadband() {
+        let policy = EmissionPolicy {
+            deadband_pct: Some(10.0),
+            ..Default::default()
+        };
+
+        assert!(!policy.is_changed(&Value::Float(200.0), &Value::Float(215.0)));
+        assert!(policy.is_changed(&Value::Float(200.0), &Value::Float(225.0)));
+        assert!(policy.is_changed(&Value::Float(-200.0), &Value::Float(-175.0)));
+    }
+
+    #[test]
+    fn is_changed_uses_larger_deadband() {
+        let policy = EmissionPolicy {
+            deadband_abs: Some(5.0),
+            deadband_pct: Some(1.0),
+            ..Default::default()
+        };
+
+        // 1% of 100 is 1, so the absolute deadband applies
+        assert!(!policy.is_changed(&Value::Float(100.0), &Value::Float(104.0)));
+        // 1% of 1000 is 10, so the percentage deadband applies
+        assert!(!policy.is_changed(&Value::Float(1000.0), &Value::Float(1008.0)));
+        assert!(policy.is_changed(&Value::Float(1000.0), &Value::Float(1011.0)));
+    }
+
+    #[test]
+    fn is_changed_compares_non_numeric_values_for_equality() {
+        let policy = EmissionPolicy {
+            deadband_abs: Some(5.0),
+            ..Default::default()
+        };
+
+        assert!(!policy.is_changed(&Value::from("on"), &Value::from("on")));
+        assert!(policy.is_changed(&Value::from("on"), &Value::from("off")));
+        assert!(policy.is_changed(&Value::from("1"), &Value::Int(1)));
+        assert!(policy.is_changed(&Value::Bool(true), &Value::Bool(false)));
+    }
+
+    #[test]
+    fn is_change_driven_includes_on_change_mode() {
+        assert!(!EmissionPolicy::default().is_change_driven());
+
+        let policy = EmissionPolicy {
+            emit_only_if_changed: true,
+            ..Default::default()
+        };
+        assert!(policy.is_change_driven());
+
+        let policy = EmissionPolicy {
+            mode: EmissionMode::OnChange { min_interval_ms: 0 },
+            ..Default::default()
+        };
+        assert!(policy.is_change_driven());
+    }
+
+    #[test]
+    fn next_emission_advances_from_previous_deadline() {
+        let policy = EmissionPolicy {
+            interval_ms: 1000,
+            ..Default::default()
+        };
+        let now = Instant::now();
+        let since_epoch = Duration::from_millis(123456);
+
+        assert_eq!(
+            policy.next_emission(None, now, since_epoch),
+            now + Duration::from_millis(1000)
+        );
+
+        // An emission which is processed late doesn't delay the following emission
+        let previous = now - Duration::from_millis(300);
+        assert_eq!(
+            policy.next_emission(Some(previous), now, since_epoch),
+            previous + Duration::from_millis(1000)
+        );
+
+        // Deadlines in the future are also advanced by exactly one interval
+        let previous = now + Duration::from_millis(300);
+        assert_eq!(
+            policy.next_emission(Some(previous), now, since_epoch),
+            previous + Duration::from_millis(1000)
+        );
+    }
+
+    #[test]
+    fn next_emission_skips_missed_intervals() {
+        let policy = EmissionPolicy {
+            interval_ms: 1000,
+            ..Default::default()
+        };
+        let now = Instant::now();
+        let since_epoch = Duration::from_millis(123456);
+
+        let previous = now - Duration::from_millis(3500);
+        assert_eq!(
+            policy.next_emission(Some(previous), now, since_epoch),
+            previous + Duration::from_millis(4000)
+        );
+
+        let previous = now - Duration::from_millis(3000);
+        assert_eq!(
+            policy.next_emission(Some(previous), now, since_epoch),
+            previous + Duration::from_millis(4000)
+        );
+    }
+
+    #[test]
+    fn next_emission_aligns_to_wall_clock() {
+        let policy = EmissionPolicy {
+            interval_ms: 10000,
+            align_to_wall_clock: true,
+            ..Default::default()
+        };
+        let now = Instant::now();
+
+        assert_eq!(
+            policy.next_emission(None, now, Duration::from_millis(1_700_000_003_250)),
+            now + Duration::from_millis(6750)
+        );
+        assert_eq!(
+            policy.next_emission(
+                Some(now - Duration::from_millis(5)),
+                now,
+                Duration::from_millis(1_700_000_010_000)
+            ),
+            now + Duration::from_millis(10000)
+        );
+    }
+
+    #[test]
+    fn next_emission_with_zero_interval_is_immediate() {
+        let now = Instant::now();
+        assert_eq!(
+            EmissionPolicy::default().next_emission(Some(now), now, Duration::ZERO),
+            now
+        );
+    }
+
+    #[test]
+    fn next_emission_saturates_long_intervals() {
+        let policy = EmissionPolicy {
+            interval_ms: u64::MAX,
+            ..Default::default()
+        };
+        let now = Instant::now();
+
+        assert!(policy.next_emission(None, now, Duration::ZERO) > now);
+    }
+
+    #[test]
+    fn emission_times_are_relative_to_now() {
+        let now = Instant::now();
+        let emission = Emission {
+            next_emission: Some(now),
+            last_emission: Some(now - Duration::from_millis(250)),
+            ..Default::default()
+        };
+
+        assert!(emission.is_due(now));
+        assert!(!emission.is_due(now - Duration::from_millis(1)));
+        assert!(Emission::default().is_due(now));
+        assert_eq!(emission.time_since_emission_ms(now), 250);
+        assert_eq!(Emission::default().time_since_emission_ms(now), u64::MAX);
+    }
+
+    #[test]
+    fn is_heartbeat_due_honors_max_silence() {
+        let policy = EmissionPolicy {
+            max_silence_ms: Some(1000),
+            ..Default::default()
+        };
+
+        assert!(!policy.is_heartbeat_due(999));
+        assert!(policy.is_heartbeat_due(1000));
+        assert!(!EmissionPolicy::default().is_heartbeat_due(u64::MAX));
+    }
+}
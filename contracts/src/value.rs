@@ -0,0 +1,265 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+// SPDX-License-Identifier: MIT
+
+use std::fmt::{self, Display};
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use serde::{ser::SerializeMap, Deserialize, Deserializer, Serialize, Serializer};
+
+/// The key of the object that `Bytes` values are serialized as
+const BYTES_KEY: &str = "bytes";
+
+/// A typed signal value.
+///
+/// Values serialize to their natural JSON representation, so `Bool`, `Int`, `Float` and `Json` values
+/// are sent as JSON booleans, numbers, objects and arrays rather than quoted strings.
+/// `Bytes` values serialize as `{"bytes": "<base64>"}` objects so that they deserialize as `Bytes` values again.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    /// A boolean value
+    Bool(bool),
+
+    /// An integer value
+    Int(i64),
+
+    /// A floating-point value
+    Float(f64),
+
+    /// A string value
+    String(String),
+
+    /// A binary value
+    Bytes(Vec<u8>),
+
+    /// A structured value
+    Json(serde_json::Value),
+}
+
+impl Value {
+    /// Infers a typed value from its text representation.
+    /// Booleans, integers, finite floating-point numbers, JSON objects and JSON arrays are recognized,
+    /// and anything else is treated as a string.
+    ///
+    /// # Arguments
+    /// - `text`: the text to parse
+    ///
+    /// # Example
+    /// ```rust
+    /// use freyja_contracts::value::Value;
+    /// assert_eq!(Value::infer("true"), Value::Bool(true));
+    /// assert_eq!(Value::infer("42"), Value::Int(42));
+    /// assert_eq!(Value::infer("42.5"), Value::Float(42.5));
+    /// assert_eq!(Value::infer("foo"), Value::String("foo".to_string()));
+    /// ```
+    pub fn infer(text: &str) -> Self {
+        let trimmed = text.trim();
+
+        if let Ok(b) = trimmed.parse::<bool>() {
+            return Self::Bool(b);
+        }
+
+        if let Ok(i) = trimmed.parse::<i64>() {
+            return Self::Int(i);
+        }
+
+        // Strings such as "NaN" and "inf" also parse as floats, but are much more likely to be text
+        if let Ok(f) = trimmed.parse::<f64>() {
+            if f.is_finite() {
+                return Self::Float(f);
+            }
+        }
+
+        if trimmed.starts_with('{') || trimmed.starts_with('[') {
+            if let Ok(json) = serde_json::from_str(trimmed) {
+                return Self::Json(json);
+            }
+        }
+
+        Self::String(text.to_string())
+    }
+
+    /// Gets the value as a number, if it's numeric
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Self::Int(i) => Some(*i as f64),
+            Self::Float(f) => Some(*f),
+            _ => None,
+        }
+    }
+}
+
+impl Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Bool(b) => write!(f, "{b}"),
+            Self::Int(i) => write!(f, "{i}"),
+            Self::Float(x) => write!(f, "{x}"),
+            Self::String(s) => write!(f, "{s}"),
+            Self::Bytes(bytes) => write!(f, "{}", BASE64.encode(bytes)),
+            Self::Json(json) => write!(f, "{json}"),
+        }
+    }
+}
+
+impl From<bool> for Value {
+    fn from(value: bool) -> Self {
+        Self::Bool(value)
+    }
+}
+
+impl From<i64> for Value {
+    fn from(value: i64) -> Self {
+        Self::Int(value)
+    }
+}
+
+impl From<f64> for Value {
+    fn from(value: f64) -> Self {
+        Self::Float(value)
+    }
+}
+
+impl From<String> for Value {
+    fn from(value: String) -> Self {
+        Self::String(value)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(value: &str) -> Self {
+        Self::String(value.to_string())
+    }
+}
+
+impl From<Vec<u8>> for Value {
+    fn from(value: Vec<u8>) -> Self {
+        Self::Bytes(value)
+    }
+}
+
+impl From<serde_json::Value> for Value {
+    /// Converts a JSON value, using the scalar variants for JSON scalars
+    fn from(value: serde_json::Value) -> Self {
+        match value {
+            serde_json::Value::Bool(b) => Self::Bool(b),
+            serde_json::Value::Number(n) => match (n.as_i64(), n.as_f64()) {
+                (Some(i), _) => Self::Int(i),
+                (None, Some(f)) => Self::Float(f),
+                // Numbers which don't fit in an i64 or f64 are kept as they are
+                (None, None) => Self::Json(serde_json::Value::Number(n)),
+            },
+            serde_json::Value::String(s) => Self::String(s),
+            other => Self::Json(other),
+        }
+    }
+}
+
+impl Serialize for Value {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Self::Bool(b) => serializer.serialize_bool(*b),
+            Self::Int(i) => serializer.serialize_i64(*i),
+            Self::Float(f) => serializer.serialize_f64(*f),
+            Self::String(s) => serializer.serialize_str(s),
+            Self::Bytes(bytes) => {
+                let mut map = serializer.serialize_map(Some(1))?;
+                map.serialize_entry(BYTES_KEY, &BASE64.encode(bytes))?;
+                map.end()
+            }
+            Self::Json(json) => json.serialize(serializer),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Value {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let json = serde_json::Value::deserialize(deserializer)?;
+
+        // Objects with a single base64 string under the bytes key are serialized `Bytes` values
+        if let Some(bytes) = json
+            .as_object()
+            .filter(|object| object.len() == 1)
+            .and_then(|object| object.get(BYTES_KEY))
+            .and_then(|encoded| encoded.as_str())
+            .and_then(|encoded| BASE64.decode(encoded).ok())
+        {
+            return Ok(Self::Bytes(bytes));
+        }
+
+        Ok(Self::from(json))
+    }
+}
+
+#[cfg(test)]
+mod value_tests {
+    use super::*;
+
+    use serde_json::json;
+
+    #[test]
+    fn infer_recognizes_types() {
+        assert_eq!(Value::infer("false"), Value::Bool(false));
+        assert_eq!(Value::infer("-7"), Value::Int(-7));
+        assert_eq!(Value::infer(" 1.5 "), Value::Float(1.5));
+        assert_eq!(
+            Value::infer(r#"{"lat": 47.6, "lon": -122.3}"#),
+            Value::Json(json!({"lat": 47.6, "lon": -122.3}))
+        );
+        assert_eq!(Value::infer("[1, 2]"), Value::Json(json!([1, 2])));
+    }
+
+    #[test]
+    fn infer_falls_back_to_string() {
+        for text in ["foo", "NaN", "inf", "{not json", ""] {
+            assert_eq!(Value::infer(text), Value::String(text.to_string()));
+        }
+    }
+
+    #[test]
+    fn serializes_with_json_types() {
+        let values = vec![
+            Value::Bool(true),
+            Value::Int(42),
+            Value::Float(2.5),
+            Value::String("42".to_string()),
+            Value::Bytes(vec![0xde, 0xad, 0xbe, 0xef]),
+            Value::Json(json!({"a": [1, 2]})),
+        ];
+
+        let result = serde_json::to_value(values).unwrap();
+
+        assert_eq!(
+            result,
+            json!([true, 42, 2.5, "42", {"bytes": "3q2+7w=="}, {"a": [1, 2]}])
+        );
+    }
+
+    #[test]
+    fn round_trips_through_json() {
+        let values = vec![
+            Value::Bool(true),
+            Value::Int(i64::MIN),
+            Value::Float(-0.125),
+            Value::String("true".to_string()),
+            Value::String("3q2+7w==".to_string()),
+            Value::Bytes(vec![0xde, 0xad, 0xbe, 0xef]),
+            Value::Bytes(Vec::new()),
+            Value::Json(json!({"a": null})),
+            Value::Json(json!({"bytes": "not base64!"})),
+        ];
+
+        let json = serde_json::to_string(&values).unwrap();
+        let result: Vec<Value> = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(result, values);
+    }
+
+    #[test]
+    fn as_f64_returns_numbers_only() {
+        assert_eq!(Value::Int(3).as_f64(), Some(3.0));
+        assert_eq!(Value::Float(0.5).as_f64(), Some(0.5));
+        assert_eq!(Value::String("3".to_string()).as_f64(), None);
+        assert_eq!(Value::Bool(true).as_f64(), None);
+    }
+}
@@ -0,0 +1,19 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+// SPDX-License-Identifier: MIT
+
+use freyja_common::service_discovery::ServiceUri;
+use serde::{Deserialize, Serialize};
+
+/// Config for the Ibeji digital twin adapter
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Config {
+    /// The uri of Ibeji's In-Vehicle Digital Twin service
+    pub invehicle_digital_twin_service_uri: ServiceUri,
+
+    /// Max retries for calls to the In-Vehicle Digital Twin service
+    pub max_retries: u32,
+
+    /// Retry interval in milliseconds
+    pub retry_interval_ms: u64,
+}
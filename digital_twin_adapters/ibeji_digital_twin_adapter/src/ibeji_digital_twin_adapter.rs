@@ -0,0 +1,434 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+// SPDX-License-Identifier: MIT
+
+use std::time::Duration;
+
+use async_trait::async_trait;
+use core_protobuf_data_access::invehicle_digital_twin::v1::{
+    invehicle_digital_twin_client::InvehicleDigitalTwinClient, EntityAccessInfo, FindByIdRequest,
+};
+use log::debug;
+use tokio::sync::OnceCell;
+use tonic::{transport::Channel, Code};
+
+use crate::config::Config;
+use freyja_common::{config_utils, out_dir, retry_utils::execute_with_retry};
+use freyja_contracts::{
+    digital_twin_adapter::{
+        DigitalTwinAdapter, DigitalTwinAdapterError, GetDigitalTwinProviderRequest,
+        GetDigitalTwinProviderResponse,
+    },
+    entity::Entity,
+};
+
+const CONFIG_FILE_STEM: &str = "ibeji_digital_twin_adapter_config";
+const GET_OPERATION: &str = "Get";
+const SUBSCRIBE_OPERATION: &str = "Subscribe";
+
+/// The operations that Freyja can use, in order of preference.
+/// Subscribe is preferred since it doesn't require Freyja to poll the provider.
+const SUPPORTED_OPERATIONS: &[&str] = &[SUBSCRIBE_OPERATION, GET_OPERATION];
+
+/// Contacts Ibeji's In-Vehicle Digital Twin service to get entity access info
+pub struct IbejiDigitalTwinAdapter {
+    /// The adapter config
+    config: Config,
+
+    /// Client for the In-Vehicle Digital Twin service, which is created on first use
+    client: OnceCell<InvehicleDigitalTwinClient<Channel>>,
+}
+
+impl IbejiDigitalTwinAdapter {
+    /// Creates a new IbejiDigitalTwinAdapter with the specified config
+    ///
+    /// # Arguments
+    /// - `config`: the config to use
+    pub fn from_config(config: Config) -> Result<Self, DigitalTwinAdapterError> {
+        Ok(Self {
+            config,
+            client: OnceCell::new(),
+        })
+    }
+
+    /// Gets the client for the In-Vehicle Digital Twin service.
+    /// On first use this resolves the service uri, discovering it with Chariott if necessary.
+    /// The connection itself is established lazily by the channel.
+    async fn client(
+        &self,
+    ) -> Result<&InvehicleDigitalTwinClient<Channel>, DigitalTwinAdapterError> {
+        self.client
+            .get_or_try_init(|| async {
+                let uri = self
+                    .config
+                    .invehicle_digital_twin_service_uri
+                    .resolve()
+                    .await
+                    .map_err(DigitalTwinAdapterError::communication)?;
+
+                let channel = Channel::from_shared(uri)
+                    .map_err(DigitalTwinAdapterError::parse_error)?
+                    .connect_lazy();
+
+                Ok(InvehicleDigitalTwinClient::new(channel))
+            })
+            .await
+    }
+
+    /// Converts the access info returned by Ibeji into an entity.
+    /// Ibeji can return multiple endpoints for an entity, but an entity only has one,
+    /// so this selects the first endpoint which supports the most preferred operation.
+    ///
+    /// # Arguments
+    /// - `access_info`: the entity access info returned by Ibeji
+    fn entity_from_access_info(
+        access_info: EntityAccessInfo,
+    ) -> Result<Entity, DigitalTwinAdapterError> {
+        let (endpoint, operation) = SUPPORTED_OPERATIONS
+            .iter()
+            .find_map(|operation| {
+                access_info
+                    .endpoint_info_list
+                    .iter()
+                    .find(|endpoint| endpoint.operations.iter().any(|op| op == operation))
+                    .map(|endpoint| (endpoint, operation))
+            })
+            .ok_or_else(|| {
+                DigitalTwinAdapterError::unknown(format!(
+                    "Entity {} does not have an endpoint with a supported operation",
+                    access_info.id
+                ))
+            })?;
+
+        // Ibeji uses empty strings for missing values
+        let non_empty = |s: &String| Some(s.clone()).filter(|s| !s.is_empty());
+
+        Ok(Entity {
+            id: access_info.id.clone(),
+            name: non_empty(&access_info.name),
+            uri: endpoint.uri.clone(),
+            description: non_empty(&access_info.description),
+            operation: operation.to_string(),
+            protocol: endpoint.protocol.clone(),
+        })
+    }
+}
+
+#[async_trait]
+impl DigitalTwinAdapter for IbejiDigitalTwinAdapter {
+    /// Creates a new instance of an IbejiDigitalTwinAdapter
+    fn create_new() -> Result<Self, DigitalTwinAdapterError> {
+        let config = config_utils::read_from_files(
+            CONFIG_FILE_STEM,
+            config_utils::JSON_EXT,
+            out_dir!(),
+            DigitalTwinAdapterError::io,
+            DigitalTwinAdapterError::deserialize,
+        )?;
+
+        Self::from_config(config)
+    }
+
+    /// Gets the info of an entity by calling Ibeji's FindById API
+    ///
+    /// # Arguments
+    /// - `request`: the request containing the id of the entity to look up
+    async fn find_by_id(
+        &self,
+        request: GetDigitalTwinProviderRequest,
+    ) -> Result<GetDigitalTwinProviderResponse, DigitalTwinAdapterError> {
+        let entity_id = request.entity_id;
+        let client = self.client().await?;
+
+        let response = execute_with_retry(
+            self.config.max_retries,
+            Duration::from_millis(self.config.retry_interval_ms),
+            || {
+                let mut client = client.clone();
+                let request = FindByIdRequest {
+                    id: entity_id.clone(),
+                };
+
+                async move {
+                    match client.find_by_id(request).await {
+                        // A missing entity won't appear by retrying, so don't treat this as a failure
+                        Err(status) if status.code() == Code::NotFound => Ok(Err(status)),
+                        result => result.map(Ok),
+                    }
+                }
+            },
+            Some(format!("Calling FindById for entity {entity_id}")),
+        )
+        .await
+        .map_err(DigitalTwinAdapterError::communication)?
+        .map_err(DigitalTwinAdapterError::entity_not_found)?
+        .into_inner();
+
+        debug!("Received FindById response for entity {entity_id}: {response:?}");
+
+        let access_info = response.entity_access_info.ok_or_else(|| {
+            DigitalTwinAdapterError::entity_not_found(format!(
+                "No access info returned for entity {entity_id}"
+            ))
+        })?;
+
+        Ok(GetDigitalTwinProviderResponse {
+            entity: Self::entity_from_access_info(access_info)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod ibeji_digital_twin_adapter_tests {
+    use super::*;
+
+    use std::{collections::HashMap, sync::Mutex};
+
+    use core_protobuf_data_access::invehicle_digital_twin::v1::{
+        invehicle_digital_twin_server::InvehicleDigitalTwin, EndpointInfo, FindByIdResponse,
+        RegisterRequest, RegisterResponse,
+    };
+    use tonic::{Request, Response, Status};
+
+    use freyja_common::service_discovery::ServiceUri;
+    use freyja_contracts::digital_twin_adapter::DigitalTwinAdapterErrorKind;
+
+    /// A stand-in for Ibeji's In-Vehicle Digital Twin service which keeps registered entities in memory
+    #[derive(Default)]
+    pub struct MockInvehicleDigitalTwin {
+        entities: Mutex<HashMap<String, EntityAccessInfo>>,
+    }
+
+    #[tonic::async_trait]
+    impl InvehicleDigitalTwin for MockInvehicleDigitalTwin {
+        async fn find_by_id(
+            &self,
+            request: Request<FindByIdRequest>,
+        ) -> Result<Response<FindByIdResponse>, Status> {
+            let id = request.into_inner().id;
+            self.entities
+                .lock()
+                .unwrap()
+                .get(&id)
+                .cloned()
+                .map(|entity_access_info| {
+                    Response::new(FindByIdResponse {
+                        entity_access_info: Some(entity_access_info),
+                    })
+                })
+                .ok_or_else(|| Status::not_found(format!("Unable to find the entity {id}")))
+        }
+
+        async fn register(
+            &self,
+            request: Request<RegisterRequest>,
+        ) -> Result<Response<RegisterResponse>, Status> {
+            let mut entities = self.entities.lock().unwrap();
+            for entity_access_info in request.into_inner().entity_access_info_list {
+                entities.insert(entity_access_info.id.clone(), entity_access_info);
+            }
+
+            Ok(Response::new(RegisterResponse {}))
+        }
+    }
+
+    fn endpoint(protocol: &str, operations: &[&str], uri: &str) -> EndpointInfo {
+        EndpointInfo {
+            protocol: protocol.to_string(),
+            operations: operations.iter().map(|op| op.to_string()).collect(),
+            uri: uri.to_string(),
+            context: String::new(),
+        }
+    }
+
+    fn access_info(id: &str, endpoint_info_list: Vec<EndpointInfo>) -> EntityAccessInfo {
+        EntityAccessInfo {
+            name: "name".to_string(),
+            id: id.to_string(),
+            description: String::new(),
+            endpoint_info_list,
+        }
+    }
+
+    #[test]
+    fn entity_from_access_info_prefers_subscribe() {
+        let access_info = access_info(
+            "id",
+            vec![
+                endpoint("grpc", &[GET_OPERATION], "http://get"),
+                endpoint("grpc", &[SUBSCRIBE_OPERATION], "http://subscribe"),
+            ],
+        );
+
+        let result = IbejiDigitalTwinAdapter::entity_from_access_info(access_info);
+
+        assert!(result.is_ok());
+        let entity = result.unwrap();
+        assert_eq!(entity.id, "id");
+        assert_eq!(entity.name, Some("name".to_string()));
+        assert_eq!(entity.description, None);
+        assert_eq!(entity.uri, "http://subscribe");
+        assert_eq!(entity.operation, SUBSCRIBE_OPERATION);
+        assert_eq!(entity.protocol, "grpc");
+    }
+
+    #[test]
+    fn entity_from_access_info_falls_back_to_get() {
+        let access_info = access_info(
+            "id",
+            vec![
+                endpoint("grpc", &["Invoke"], "http://invoke"),
+                endpoint("http", &["Set", GET_OPERATION], "http://get"),
+            ],
+        );
+
+        let result = IbejiDigitalTwinAdapter::entity_from_access_info(access_info);
+
+        assert!(result.is_ok());
+        let entity = result.unwrap();
+        assert_eq!(entity.uri, "http://get");
+        assert_eq!(entity.operation, GET_OPERATION);
+        assert_eq!(entity.protocol, "http");
+    }
+
+    #[test]
+    fn entity_from_access_info_without_supported_operation_fails() {
+        let access_info = access_info("id", vec![endpoint("grpc", &["Invoke"], "http://invoke")]);
+
+        let result = IbejiDigitalTwinAdapter::entity_from_access_info(access_info);
+
+        assert!(result.is_err());
+        assert_eq!(
+            result.err().unwrap().kind(),
+            DigitalTwinAdapterErrorKind::Unknown
+        );
+    }
+
+    #[tokio::test]
+    async fn find_by_id_with_invalid_uri_fails() {
+        let uut = IbejiDigitalTwinAdapter::from_config(Config {
+            invehicle_digital_twin_service_uri: ServiceUri::Static("not a uri".to_string()),
+            max_retries: 1,
+            retry_interval_ms: 0,
+        })
+        .unwrap();
+
+        let result = uut
+            .find_by_id(GetDigitalTwinProviderRequest {
+                entity_id: "entity".to_string(),
+            })
+            .await;
+
+        assert!(result.is_err());
+        assert_eq!(
+            result.err().unwrap().kind(),
+            DigitalTwinAdapterErrorKind::ParseError
+        );
+    }
+
+    /// The tests below uses Unix sockets to create a channel between a gRPC client and a gRPC server.
+    /// Unix sockets are more ideal than using TCP/IP sockets since Rust tests will run in parallel
+    /// so you would need to set an arbitrary port per test for TCP/IP sockets.
+    #[cfg(unix)]
+    mod unix_tests {
+        use super::*;
+
+        use std::sync::Arc;
+
+        use core_protobuf_data_access::invehicle_digital_twin::v1::invehicle_digital_twin_server::InvehicleDigitalTwinServer;
+        use tempfile::TempPath;
+        use tokio::net::{UnixListener, UnixStream};
+        use tokio_stream::wrappers::UnixListenerStream;
+        use tonic::transport::{Endpoint, Server, Uri};
+        use tower::service_fn;
+
+        async fn create_test_adapter(bind_path: Arc<TempPath>) -> IbejiDigitalTwinAdapter {
+            let channel = Endpoint::try_from("http://URI_IGNORED") // Devskim: ignore DS137138
+                .unwrap()
+                .connect_with_connector(service_fn(move |_: Uri| {
+                    let bind_path = bind_path.clone();
+                    async move { UnixStream::connect(bind_path.as_ref()).await }
+                }))
+                .await
+                .unwrap();
+
+            IbejiDigitalTwinAdapter {
+                config: Config {
+                    invehicle_digital_twin_service_uri: ServiceUri::Static(
+                        "http://URI_IGNORED".to_string(), // Devskim: ignore DS137138
+                    ),
+                    max_retries: 1,
+                    retry_interval_ms: 0,
+                },
+                client: OnceCell::new_with(Some(InvehicleDigitalTwinClient::new(channel))),
+            }
+        }
+
+        async fn run_test_grpc_server(uds_stream: UnixListenerStream) {
+            let mock_invehicle_digital_twin = MockInvehicleDigitalTwin::default();
+            mock_invehicle_digital_twin
+                .register(Request::new(RegisterRequest {
+                    entity_access_info_list: vec![access_info(
+                        "entity",
+                        vec![endpoint("grpc", &[GET_OPERATION], "http://provider")],
+                    )],
+                }))
+                .await
+                .unwrap();
+
+            Server::builder()
+                .add_service(InvehicleDigitalTwinServer::new(mock_invehicle_digital_twin))
+                .serve_with_incoming(uds_stream)
+                .await
+                .unwrap();
+        }
+
+        #[tokio::test]
+        async fn find_by_id_test() {
+            // Create the Unix Socket
+            let bind_path = Arc::new(tempfile::NamedTempFile::new().unwrap().into_temp_path());
+            let uds = match UnixListener::bind(bind_path.as_ref()) {
+                Ok(unix_listener) => unix_listener,
+                Err(_) => {
+                    std::fs::remove_file(bind_path.as_ref()).unwrap();
+                    UnixListener::bind(bind_path.as_ref()).unwrap()
+                }
+            };
+            let uds_stream = UnixListenerStream::new(uds);
+
+            let request_future = async {
+                let ibeji_digital_twin_adapter = create_test_adapter(bind_path.clone()).await;
+
+                let result = ibeji_digital_twin_adapter
+                    .find_by_id(GetDigitalTwinProviderRequest {
+                        entity_id: "entity".to_string(),
+                    })
+                    .await;
+                assert!(result.is_ok());
+                let entity = result.unwrap().entity;
+                assert_eq!(entity.id, "entity");
+                assert_eq!(entity.uri, "http://provider");
+                assert_eq!(entity.operation, GET_OPERATION);
+                assert_eq!(entity.protocol, "grpc");
+
+                let result = ibeji_digital_twin_adapter
+                    .find_by_id(GetDigitalTwinProviderRequest {
+                        entity_id: "unknown_entity".to_string(),
+                    })
+                    .await;
+                assert!(result.is_err());
+                assert_eq!(
+                    result.err().unwrap().kind(),
+                    DigitalTwinAdapterErrorKind::EntityNotFound
+                );
+            };
+
+            tokio::select! {
+                _ = run_test_grpc_server(uds_stream) => (),
+                _ = request_future => ()
+            }
+
+            std::fs::remove_file(bind_path.as_ref()).unwrap();
+        }
+    }
+}
@@ -8,7 +8,8 @@ use crate::config::Config;
 use freyja_common::{config_utils, out_dir};
 use freyja_contracts::digital_twin_adapter::{
     DigitalTwinAdapter, DigitalTwinAdapterError, DigitalTwinAdapterErrorKind,
-    GetDigitalTwinProviderRequest, GetDigitalTwinProviderResponse,
+    GetDigitalTwinProviderRequest, GetDigitalTwinProviderResponse, ListEntitiesRequest,
+    ListEntitiesResponse,
 };
 
 const CONFIG_FILE_STEM: &str = "in_memory_digital_twin_config";
@@ -62,6 +63,24 @@ impl DigitalTwinAdapter for InMemoryMockDigitalTwinAdapter {
             })
             .ok_or(DigitalTwinAdapterErrorKind::EntityNotFound.into())
     }
+
+    /// Lists all of the entities in the config
+    ///
+    /// # Arguments
+    /// - `request`: the request to send
+    async fn list_entities(
+        &self,
+        _request: ListEntitiesRequest,
+    ) -> Result<ListEntitiesResponse, DigitalTwinAdapterError> {
+        Ok(ListEntitiesResponse {
+            entities: self
+                .config
+                .values
+                .iter()
+                .map(|entity_config| entity_config.entity.clone())
+                .collect(),
+        })
+    }
 }
 
 #[cfg(test)]
@@ -107,4 +126,32 @@ mod in_memory_mock_digital_twin_adapter_tests {
         assert_eq!(response.entity.id, ENTITY_ID);
         assert_eq!(response.entity.operation, OPERATION);
     }
+
+    #[tokio::test]
+    async fn list_entities_returns_all_entities() {
+        let config = Config {
+            values: ["entity1", "entity2"]
+                .into_iter()
+                .map(|id| EntityConfig {
+                    entity: Entity {
+                        id: id.to_string(),
+                        name: None,
+                        uri: String::from("http://0.0.0.0:1111"), // Devskim: ignore DS137138
+                        description: None,
+                        operation: OPERATION.to_string(),
+                        protocol: String::from("in-memory"),
+                    },
+                })
+                .collect(),
+        };
+
+        let in_memory_digital_twin_adapter = InMemoryMockDigitalTwinAdapter { config };
+        let response = in_memory_digital_twin_adapter
+            .list_entities(ListEntitiesRequest {})
+            .await
+            .unwrap();
+
+        let ids: Vec<String> = response.entities.into_iter().map(|e| e.id).collect();
+        assert_eq!(ids, vec!["entity1", "entity2"]);
+    }
 }
@@ -2,11 +2,12 @@
 // Licensed under the MIT license.
 // SPDX-License-Identifier: MIT
 
+use freyja_common::service_discovery::ServiceUri;
 use serde::{Deserialize, Serialize};
 
 /// Config for the mock digital twin adapter
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Config {
     /// the base uri for the digital twin service
-    pub digital_twin_service_uri: String,
+    pub digital_twin_service_uri: ServiceUri,
 }
@@ -5,13 +5,14 @@
 use async_trait::async_trait;
 use freyja_common::{config_utils, out_dir};
 use reqwest::Client;
+use tokio::sync::OnceCell;
 
 use crate::config::Config;
 use freyja_contracts::digital_twin_adapter::{
     DigitalTwinAdapter, DigitalTwinAdapterError, GetDigitalTwinProviderRequest,
-    GetDigitalTwinProviderResponse,
+    GetDigitalTwinProviderResponse, ListEntitiesRequest, ListEntitiesResponse,
 };
-use mock_digital_twin::ENTITY_QUERY_PATH;
+use mock_digital_twin::{ENTITY_LIST_PATH, ENTITY_QUERY_PATH};
 
 const CONFIG_FILE_STEM: &str = "mock_digital_twin_adapter_config";
 
@@ -21,6 +22,9 @@ pub struct MockDigitalTwinAdapter {
     /// The adapter config
     config: Config,
 
+    /// The resolved base uri for the digital twin service
+    digital_twin_service_uri: OnceCell<String>,
+
     /// Async Reqwest HTTP Client
     client: Client,
 }
@@ -33,10 +37,20 @@ impl MockDigitalTwinAdapter {
     pub fn from_config(config: Config) -> Result<Self, DigitalTwinAdapterError> {
         Ok(Self {
             config,
+            digital_twin_service_uri: OnceCell::new(),
             client: Client::new(),
         })
     }
 
+    /// Gets the base uri for the digital twin service, discovering it on first use if necessary
+    async fn digital_twin_service_uri(&self) -> Result<&str, DigitalTwinAdapterError> {
+        self.digital_twin_service_uri
+            .get_or_try_init(|| self.config.digital_twin_service_uri.resolve())
+            .await
+            .map(String::as_str)
+            .map_err(DigitalTwinAdapterError::communication)
+    }
+
     /// Helper to map HTTP error codes to our own error type
     ///
     /// # Arguments
@@ -76,7 +90,8 @@ impl DigitalTwinAdapter for MockDigitalTwinAdapter {
     ) -> Result<GetDigitalTwinProviderResponse, DigitalTwinAdapterError> {
         let target = format!(
             "{}{ENTITY_QUERY_PATH}{}",
-            self.config.digital_twin_service_uri, request.entity_id
+            self.digital_twin_service_uri().await?,
+            request.entity_id
         );
 
         self.client
@@ -90,4 +105,29 @@ impl DigitalTwinAdapter for MockDigitalTwinAdapter {
             .await
             .map_err(DigitalTwinAdapterError::deserialize)
     }
+
+    /// Lists the entities which are currently enabled in the mock digital twin via an HTTP request.
+    ///
+    /// # Arguments
+    /// - `request`: the request to send to the mock digital twin server
+    async fn list_entities(
+        &self,
+        _request: ListEntitiesRequest,
+    ) -> Result<ListEntitiesResponse, DigitalTwinAdapterError> {
+        let target = format!(
+            "{}{ENTITY_LIST_PATH}",
+            self.digital_twin_service_uri().await?
+        );
+
+        self.client
+            .get(&target)
+            .send()
+            .await
+            .map_err(DigitalTwinAdapterError::communication)?
+            .error_for_status()
+            .map_err(Self::map_status_err)?
+            .json::<ListEntitiesResponse>()
+            .await
+            .map_err(DigitalTwinAdapterError::deserialize)
+    }
 }
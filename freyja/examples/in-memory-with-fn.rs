@@ -10,10 +10,12 @@ use in_memory_mock_mapping_client::in_memory_mock_mapping_client::InMemoryMockMa
 async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     // This example shows how you can use the freyja_main function manually rather than using the freyja_main! macro.
     // This is useful when you need to do some additional work such as complex adapter setup or dependency resolution before invoking freyja_main.
+    // Factories for provider proxies with custom protocols can also be passed to freyja_main,
+    // for example vec![("my-protocol".to_string(), Box::new(MyProviderProxyFactory) as _)].
     freyja::freyja_main::<
         InMemoryMockDigitalTwinAdapter,
         InMemoryMockCloudAdapter,
         InMemoryMockMappingClient,
-    >()
+    >(Vec::new())
     .await
 }
@@ -2,23 +2,92 @@
 // Licensed under the MIT license.
 // SPDX-License-Identifier: MIT
 
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 use std::time::Duration;
 
-use freyja_common::signal_store::SignalStore;
+use freyja_common::{
+    mapping_service_health_store::MappingServiceHealthStore,
+    resolution_status_store::{ResolutionStatus, ResolutionStatusStore},
+    signal_store::SignalStore,
+};
 use log::{info, warn};
 
+use crate::{
+    circuit_breaker::CircuitBreaker,
+    config::CartographerConfig,
+    mapping_cache::{CachedMapping, MappingCache},
+    mapping_validator::MappingValidator,
+};
 use freyja_contracts::{
-    conversion::Conversion,
     digital_twin_adapter::{
         DigitalTwinAdapter, DigitalTwinAdapterError, DigitalTwinAdapterErrorKind,
-        GetDigitalTwinProviderRequest,
+        GetDigitalTwinProviderRequest, ListEntitiesRequest,
+    },
+    digital_twin_map_entry::DigitalTwinMapEntry,
+    mapping_client::{
+        CheckForWorkRequest, CheckForWorkResponse, GetMappingRequest, GetMappingResponse,
+        MappingClient, MappingClientError, MappingDelta, MappingUpdate, RejectedMappingEntry,
+        ReportRejectedEntriesRequest, SendInventoryRequest, VehicleInfo, WaitForWorkRequest,
     },
-    mapping_client::{CheckForWorkRequest, GetMappingRequest, MappingClient},
     provider_proxy_selector::ProviderProxySelector,
-    signal::{EmissionPolicy, SignalPatch, Target},
+    signal::{EmissionPolicy, Signal, SignalPatch, Target},
 };
-use tokio::sync::Mutex;
+use tokio::{sync::Mutex, time::Instant};
+
+/// The maximum time to wait before retrying to resolve the entity of a signal
+const RESOLUTION_RETRY_MAX_BACKOFF: Duration = Duration::from_secs(300);
+
+/// A signal whose entity has not been resolved yet
+struct PendingSignal {
+    /// The signal patch. Its source is populated once the entity is resolved
+    patch: SignalPatch,
+
+    /// The number of failed attempts to resolve the entity
+    attempts: u32,
+
+    /// The error from the last attempt
+    last_error: String,
+
+    /// The time at which resolution should be retried
+    next_attempt: Instant,
+}
+
+impl PendingSignal {
+    /// Gets the resolution status of the signal
+    fn status(&self) -> ResolutionStatus {
+        ResolutionStatus::Pending {
+            attempts: self.attempts,
+            last_error: self.last_error.clone(),
+        }
+    }
+}
+
+/// The mapping state which is maintained by the cartographer's run loop
+#[derive(Default)]
+struct MappingState {
+    /// The last applied mapping
+    mapping: Option<GetMappingResponse>,
+
+    /// The signals whose entities have not been resolved yet, keyed by signal id
+    pending_signals: HashMap<String, PendingSignal>,
+
+    /// The ids of the entries of the last applied mapping which were rejected by validation
+    rejected_ids: HashSet<String>,
+
+    /// The ids of the entities which were last sent to the mapping service as the inventory
+    inventory: Option<HashSet<String>>,
+
+    /// Whether the digital twin adapter doesn't support listing entities, in which case no inventory is sent
+    inventory_unsupported: bool,
+}
+
+impl MappingState {
+    /// Gets the version of the last applied mapping, or `None` if there is no mapping or it isn't versioned
+    fn mapping_version(&self) -> Option<u64> {
+        self.mapping.as_ref().and_then(|mapping| mapping.version)
+    }
+}
 
 /// Manages mappings from the mapping service
 pub struct Cartographer<TMappingClient, TDigitalTwinAdapter, TProviderProxySelector> {
@@ -34,8 +103,23 @@ pub struct Cartographer<TMappingClient, TDigitalTwinAdapter, TProviderProxySelec
     /// The provider proxy selector
     provider_proxy_selector: Arc<Mutex<TProviderProxySelector>>,
 
-    /// The mapping service polling interval
-    poll_interval: Duration,
+    /// The cartographer config
+    config: CartographerConfig,
+
+    /// The cache for the last applied mapping, if enabled
+    mapping_cache: Option<MappingCache>,
+
+    /// Information about the vehicle which is sent with requests to the mapping service
+    vehicle: VehicleInfo,
+
+    /// The validator for mappings
+    validator: MappingValidator,
+
+    /// The shared resolution status of each signal in the mapping
+    resolution_statuses: Arc<ResolutionStatusStore>,
+
+    /// The shared health of the connection to the mapping service
+    mapping_service_health: Arc<MappingServiceHealthStore>,
 }
 
 impl<
@@ -51,122 +135,672 @@ impl<
     /// - `mapping_client`: the client for the mapping service
     /// - `digital_twin_client`: the client for the digital twin service
     /// - `provider_proxy_selector`: the provider proxy selector
-    /// - `poll_interval`: the interval at which the cartographer should poll for changes
+    /// - `config`: the cartographer config
+    /// - `mapping_cache`: the cache for the last applied mapping, or `None` to disable caching
+    /// - `vehicle`: information about the vehicle which is sent with requests to the mapping service
+    /// - `validator`: the validator for mappings
+    /// - `resolution_statuses`: the shared store for the resolution status of each signal
+    /// - `mapping_service_health`: the shared store for the health of the connection to the mapping service
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         signals: Arc<SignalStore>,
         mapping_client: TMappingClient,
         digital_twin_client: TDigitalTwinAdapter,
         provider_proxy_selector: Arc<Mutex<TProviderProxySelector>>,
-        poll_interval: Duration,
+        config: CartographerConfig,
+        mapping_cache: Option<MappingCache>,
+        vehicle: VehicleInfo,
+        validator: MappingValidator,
+        resolution_statuses: Arc<ResolutionStatusStore>,
+        mapping_service_health: Arc<MappingServiceHealthStore>,
     ) -> Self {
         Self {
             signals,
             mapping_client,
             digital_twin_client,
             provider_proxy_selector,
-            poll_interval,
+            config,
+            mapping_cache,
+            vehicle,
+            validator,
+            resolution_statuses,
+            mapping_service_health,
         }
     }
 
-    /// Run the cartographer. This will do the following in a loop:
+    /// Gets the interval at which the mapping service is polled
+    fn poll_interval(&self) -> Duration {
+        Duration::from_millis(self.config.poll_interval_ms)
+    }
+
+    /// Run the cartographer. This will first restore the cached mapping, if there is one, and then do the following in a loop:
     ///
-    /// 1. Check to see if the mapping service has more work. If not, skip to the last step
-    /// 1. ~~Send the new inventory to the mapping service~~
+    /// 1. Wait for the mapping service to have more work, or until the poll interval elapses. If there is no work, skip to the retry step.
+    /// A cached mapping is always reconciled with the mapping service's mapping without waiting, even if the service has no work.
+    /// This step is skipped while backing off after failed calls to the mapping service or while the circuit breaker is open
+    /// 1. Send the inventory of entities to the mapping service if it changed since it was last sent
     /// 1. Get the new mapping from the mapping service
+    /// 1. Validate the mapping and report any rejected entries to the mapping service
     /// 1. Query the digital twin service for entity information
     /// 1. Create or update provider proxies for the new entities
-    /// 1. Update the signal store with the new data
-    /// 1. Sleep until the next iteration
+    /// 1. Update the signal store with the signals whose entities were resolved
+    /// 1. Remove the entities of signals which are no longer in the mapping from their provider proxies
+    /// 1. Update the mapping cache
+    /// 1. Retry resolving the entities of signals which are pending resolution and are due for a retry
+    /// 1. If the mapping service wasn't called, sleep until the next iteration
     pub async fn run(&self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let mut state = MappingState::default();
+        let mut reconcile_cached_mapping = self.restore_cached_mapping(&mut state).await;
+        let mut circuit_breaker =
+            CircuitBreaker::new(&self.config, self.mapping_service_health.clone());
+
         loop {
-            let mapping_client_result = self
-                .mapping_client
-                .check_for_work(CheckForWorkRequest {})
-                .await;
-
-            if mapping_client_result.is_err() {
-                let error = mapping_client_result.err().unwrap();
-                log::error!(
-                    "Failed to check for mapping work; will try again later. Error: {error}"
-                );
-                continue;
-            }
+            let mut called_mapping_service = false;
 
-            if mapping_client_result.unwrap().has_work {
-                info!("Cartographer detected mapping work");
+            if circuit_breaker.try_acquire() {
+                let work_result = self
+                    .check_for_work(&mut state, reconcile_cached_mapping)
+                    .await;
 
-                let patches_result = self.get_mapping_as_signal_patches().await;
-                if patches_result.is_err() {
-                    let error = patches_result.err().unwrap();
-                    log::error!("Falied to get mapping from mapping client: {error}");
-                    continue;
-                }
+                match work_result {
+                    Ok(response) if response.has_work || reconcile_cached_mapping => {
+                        info!("Cartographer detected mapping work");
 
-                let mut patches = patches_result.unwrap();
-                let mut failed_signals = Vec::new();
-
-                for patch in patches.iter_mut() {
-                    // Many of the API calls in populate_entity are probably unnecessary, but this code gets executed
-                    // infrequently enough that the sub-optimal performance is not a major concern.
-                    // A bulk find_by_id API in the digital twin service would make this a non-issue
-                    let populate_result = self.populate_source(patch).await;
-
-                    if populate_result.is_err() {
-                        match populate_result
-                            .err()
-                            .unwrap()
-                            .downcast::<DigitalTwinAdapterError>()
-                        {
-                            Ok(e) if e.kind() == DigitalTwinAdapterErrorKind::EntityNotFound => {
-                                warn!("Entity not found for signal {}", patch.id);
-                            }
-                            Ok(e) => {
-                                log::error!("Error fetching entity for signal {}: {e:?}", patch.id);
+                        match self.apply_mapping(&mut state).await {
+                            Ok(_) => {
+                                circuit_breaker.record_success();
+                                called_mapping_service = true;
+                                reconcile_cached_mapping = false;
                             }
                             Err(e) => {
-                                log::error!("Error fetching entity for signal {}: {e:?}", patch.id);
+                                log::error!("Falied to get mapping from mapping client: {e}");
+                                circuit_breaker.record_failure(format!("{e:?}"));
                             }
                         }
-
-                        failed_signals.push(patch.id.clone());
+                    }
+                    Ok(_) => {
+                        circuit_breaker.record_success();
+                        called_mapping_service = true;
+                    }
+                    Err(e) => {
+                        log::error!(
+                            "Failed to check for mapping work; will try again later. Error: {e}"
+                        );
+                        circuit_breaker.record_failure(format!("{e:?}"));
                     }
                 }
-
-                self.signals.sync(
-                    patches
-                        .into_iter()
-                        .filter(|s| !failed_signals.contains(&s.id)),
-                );
             }
 
-            tokio::time::sleep(self.poll_interval).await;
+            self.retry_pending_signals(&mut state).await;
+
+            // Waiting for work already paced the loop. Otherwise, wake up for the next poll,
+            // or earlier if the mapping service can be called again sooner
+            if !called_mapping_service {
+                let next_poll = Instant::now() + self.poll_interval();
+                tokio::time::sleep_until(circuit_breaker.next_attempt().min(next_poll)).await;
+            }
         }
     }
 
-    /// Gets the mapping from the mapping client and returns a corresponding list of signal patches.
-    async fn get_mapping_as_signal_patches(
+    /// Sends the inventory to the mapping service if needed and then checks for work.
+    /// A cached mapping is reconciled right away.
+    /// Otherwise, this waits for the mapping service to push work, or until it's time to poll again.
+    ///
+    /// Arguments
+    /// - `state`: The mapping state
+    /// - `reconcile_cached_mapping`: Whether a cached mapping still needs to be reconciled with the mapping service
+    async fn check_for_work(
         &self,
-    ) -> Result<Vec<SignalPatch>, Box<dyn std::error::Error + Send + Sync>> {
-        Ok(self
+        state: &mut MappingState,
+        reconcile_cached_mapping: bool,
+    ) -> Result<CheckForWorkResponse, MappingClientError> {
+        self.send_inventory(state).await?;
+
+        let version = state.mapping_version();
+        if reconcile_cached_mapping {
+            self.mapping_client
+                .check_for_work(CheckForWorkRequest {
+                    version,
+                    vehicle: self.vehicle.clone(),
+                })
+                .await
+        } else {
+            self.mapping_client
+                .wait_for_work(WaitForWorkRequest {
+                    timeout_ms: self.config.poll_interval_ms,
+                    version,
+                    vehicle: self.vehicle.clone(),
+                })
+                .await
+        }
+    }
+
+    /// Sends the ids of the entities listed by the digital twin adapter to the mapping service
+    /// if they changed since the inventory was last sent.
+    /// Failures to list the entities are logged and don't prevent checking for work.
+    ///
+    /// Arguments
+    /// - `state`: The mapping state
+    async fn send_inventory(&self, state: &mut MappingState) -> Result<(), MappingClientError> {
+        if state.inventory_unsupported {
+            return Ok(());
+        }
+
+        let inventory: HashSet<String> = match self
+            .digital_twin_client
+            .list_entities(ListEntitiesRequest {})
+            .await
+        {
+            Ok(response) => response.entities.into_iter().map(|e| e.id).collect(),
+            Err(e) if e.kind() == DigitalTwinAdapterErrorKind::Unsupported => {
+                info!("The digital twin adapter doesn't support listing entities, so no inventory will be sent to the mapping service");
+                state.inventory_unsupported = true;
+                return Ok(());
+            }
+            Err(e) => {
+                warn!("Failed to list entities; the inventory will be sent later. Error: {e:?}");
+                return Ok(());
+            }
+        };
+
+        if state.inventory.as_ref() == Some(&inventory) {
+            return Ok(());
+        }
+
+        info!(
+            "Sending inventory with {} entities to the mapping service",
+            inventory.len()
+        );
+
+        self.mapping_client
+            .send_inventory(SendInventoryRequest {
+                inventory: inventory.clone(),
+            })
+            .await?;
+
+        state.inventory = Some(inventory);
+        Ok(())
+    }
+
+    /// Gets an update to the mapping from the mapping client and applies it.
+    /// If the mapping service sends the changes since the current version of the mapping, only the affected signals are updated.
+    /// If the changes don't apply to the current version, the full mapping is requested instead.
+    ///
+    /// Arguments
+    /// - `state`: The mapping state
+    async fn apply_mapping(&self, state: &mut MappingState) -> Result<(), MappingClientError> {
+        let version = state.mapping_version();
+        match self
             .mapping_client
-            .get_mapping(GetMappingRequest {})
+            .get_mapping_update(GetMappingRequest {
+                version,
+                vehicle: self.vehicle.clone(),
+            })
             .await?
-            .map
+        {
+            MappingUpdate::Full(mapping) => self.apply_full_mapping(state, mapping).await,
+            MappingUpdate::Delta(delta) if version == Some(delta.base_version) => {
+                self.apply_mapping_delta(state, delta).await
+            }
+            MappingUpdate::Delta(delta) => {
+                warn!(
+                    "Received mapping changes for version {} but the current mapping version is {version:?}. Getting the full mapping instead",
+                    delta.base_version
+                );
+
+                let mapping = self
+                    .mapping_client
+                    .get_mapping(GetMappingRequest {
+                        version: None,
+                        vehicle: self.vehicle.clone(),
+                    })
+                    .await?;
+                self.apply_full_mapping(state, mapping).await;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Applies a full mapping, replacing the current mapping.
+    /// Entries which are rejected by validation are not applied.
+    /// Signals whose entities can't be resolved are added to the pending signals to be retried later,
+    /// and the rest of the mapping is applied without them.
+    ///
+    /// Arguments
+    /// - `state`: The mapping state
+    /// - `mapping`: The new mapping
+    async fn apply_full_mapping(&self, state: &mut MappingState, mapping: GetMappingResponse) {
+        // The new mapping replaces any signals which were pending from the previous mapping
+        state.pending_signals.clear();
+
+        let rejected_entries = self.validate_mapping(&mapping);
+        let rejected_ids: HashSet<String> = rejected_entries
+            .iter()
+            .map(|rejected_entry| rejected_entry.id.clone())
+            .collect();
+
+        let mut resolved_patches = Vec::new();
+        for mut patch in Self::signal_patches_from_mapping(&mapping)
             .into_iter()
-            .map(|(id, entry)| SignalPatch {
-                id,
-                // this gets populated later, set to default for now
-                source: Default::default(),
-                target: Target {
-                    metadata: entry.target,
-                },
-                emission_policy: EmissionPolicy {
-                    interval_ms: entry.interval_ms,
-                    emit_only_if_changed: entry.emit_on_change,
-                    conversion: Conversion::default(),
-                },
+            .filter(|patch| !rejected_ids.contains(&patch.id))
+        {
+            // Many of the API calls in populate_entity are probably unnecessary, but this code gets executed
+            // infrequently enough that the sub-optimal performance is not a major concern.
+            // A bulk find_by_id API in the digital twin service would make this a non-issue
+            match self.populate_source(&mut patch).await {
+                Ok(_) => resolved_patches.push(patch),
+                Err(e) => self.defer_resolution(&mut state.pending_signals, patch, 1, e),
+            }
+        }
+
+        info!(
+            "Applying mapping with {} resolved signal(s), {} signal(s) pending resolution, and {} rejected entries",
+            resolved_patches.len(),
+            state.pending_signals.len(),
+            rejected_ids.len()
+        );
+
+        let statuses = resolved_patches
+            .iter()
+            .map(|patch| (patch.id.clone(), ResolutionStatus::Resolved))
+            .chain(
+                state
+                    .pending_signals
+                    .iter()
+                    .map(|(id, pending_signal)| (id.clone(), pending_signal.status())),
+            )
+            .collect();
+        self.resolution_statuses.sync(statuses);
+
+        let removed_signals = self.signals.sync(resolved_patches.into_iter());
+        self.remove_sources(removed_signals).await;
+
+        self.report_rejected_entries(mapping.version, rejected_entries)
+            .await;
+
+        state.rejected_ids = rejected_ids;
+        state.mapping = Some(mapping);
+        self.update_mapping_cache(state);
+    }
+
+    /// Applies the changes from a mapping delta. Only the signals which were added, changed, or removed are updated.
+    /// The resulting mapping is validated, so signals which are rejected are removed
+    /// and signals which were rejected before but are valid now are added.
+    /// Added or changed signals whose entities can't be resolved are added to the pending signals to be retried later.
+    ///
+    /// Arguments
+    /// - `state`: The mapping state
+    /// - `delta`: The changes to apply. These must apply to the current version of the mapping
+    async fn apply_mapping_delta(&self, state: &mut MappingState, delta: MappingDelta) {
+        let mut mapping = state.mapping.clone().unwrap_or_default();
+        delta.apply_to(&mut mapping);
+
+        let rejected_entries = self.validate_mapping(&mapping);
+        let rejected_ids: HashSet<String> = rejected_entries
+            .iter()
+            .map(|rejected_entry| rejected_entry.id.clone())
+            .collect();
+
+        // Entries which were rejected before but are valid now are applied along with the added and changed entries
+        let updated_ids: HashSet<&String> = delta
+            .added
+            .keys()
+            .chain(delta.changed.keys())
+            .chain(
+                state
+                    .rejected_ids
+                    .iter()
+                    .filter(|id| mapping.map.contains_key(*id)),
+            )
+            .filter(|id| !rejected_ids.contains(*id))
+            .collect();
+
+        // Pending signals which changed, were removed, or were rejected are replaced by the delta
+        for id in delta
+            .added
+            .keys()
+            .chain(delta.changed.keys())
+            .chain(delta.removed.iter())
+            .chain(rejected_ids.iter())
+        {
+            state.pending_signals.remove(id);
+        }
+
+        let mut resolved_patches = Vec::new();
+        let mut unresolved_ids = Vec::new();
+        for id in updated_ids {
+            let mut patch = Self::signal_patch(id, &mapping.map[id]);
+            match self.populate_source(&mut patch).await {
+                Ok(_) => {
+                    self.resolution_statuses
+                        .set(id.clone(), ResolutionStatus::Resolved);
+                    resolved_patches.push(patch);
+                }
+                Err(e) => {
+                    unresolved_ids.push(id.clone());
+                    self.defer_resolution(&mut state.pending_signals, patch, 1, e);
+                }
+            }
+        }
+
+        info!(
+            "Applying mapping changes from version {} to {} with {} added, {} changed, and {} removed signal(s). {} signal(s) are pending resolution and {} entries are rejected",
+            delta.base_version,
+            delta.version,
+            delta.added.len(),
+            delta.changed.len(),
+            delta.removed.len(),
+            unresolved_ids.len(),
+            rejected_ids.len()
+        );
+
+        for id in delta.removed.iter().chain(rejected_ids.iter()) {
+            self.resolution_statuses.remove(id);
+        }
+
+        // Like with a full mapping, changed signals whose entities can't be resolved are removed until they are resolved
+        let removed_ids: Vec<String> = delta
+            .removed
+            .iter()
+            .chain(rejected_ids.iter())
+            .cloned()
+            .chain(unresolved_ids)
+            .collect();
+        let removed_signals = self
+            .signals
+            .apply_delta(resolved_patches.into_iter(), &removed_ids);
+        self.remove_sources(removed_signals).await;
+
+        self.report_rejected_entries(mapping.version, rejected_entries)
+            .await;
+
+        state.rejected_ids = rejected_ids;
+        state.mapping = Some(mapping);
+        self.update_mapping_cache(state);
+    }
+
+    /// Validates a mapping and logs the entries which are rejected.
+    /// Returns the rejected entries.
+    ///
+    /// Arguments
+    /// - `mapping`: The mapping to validate
+    fn validate_mapping(&self, mapping: &GetMappingResponse) -> Vec<RejectedMappingEntry> {
+        let rejected_entries = self.validator.validate(&mapping.map);
+        for rejected_entry in rejected_entries.iter() {
+            warn!(
+                "Rejected mapping entry {}: {}",
+                rejected_entry.id,
+                rejected_entry.reasons.join("; ")
+            );
+        }
+
+        rejected_entries
+    }
+
+    /// Reports rejected mapping entries to the mapping service, if there are any.
+    /// Failures are logged, since they don't affect the mapping which is applied.
+    ///
+    /// Arguments
+    /// - `version`: The version of the mapping which contains the rejected entries
+    /// - `rejected_entries`: The rejected entries
+    async fn report_rejected_entries(
+        &self,
+        version: Option<u64>,
+        rejected_entries: Vec<RejectedMappingEntry>,
+    ) {
+        if rejected_entries.is_empty() {
+            return;
+        }
+
+        if let Err(e) = self
+            .mapping_client
+            .report_rejected_entries(ReportRejectedEntriesRequest {
+                version,
+                vehicle: self.vehicle.clone(),
+                rejected_entries,
             })
-            .collect())
+            .await
+        {
+            warn!("Failed to report rejected mapping entries to the mapping service: {e:?}");
+        }
+    }
+
+    /// Retries resolving the entities of pending signals whose next attempt is due.
+    /// Signals which are resolved are added to the signal store, and the others are retried later with a longer backoff.
+    ///
+    /// Arguments
+    /// - `state`: The mapping state
+    async fn retry_pending_signals(&self, state: &mut MappingState) {
+        let now = Instant::now();
+        let due_ids: Vec<String> = state
+            .pending_signals
+            .iter()
+            .filter(|(_, pending_signal)| pending_signal.next_attempt <= now)
+            .map(|(id, _)| id.clone())
+            .collect();
+
+        if due_ids.is_empty() {
+            return;
+        }
+
+        let mut resolved_patches = Vec::new();
+        for id in due_ids {
+            let PendingSignal {
+                mut patch,
+                attempts,
+                ..
+            } = state.pending_signals.remove(&id).unwrap();
+
+            match self.populate_source(&mut patch).await {
+                Ok(_) => {
+                    info!("Resolved entity for signal {id} after {attempts} failed attempt(s)");
+                    self.resolution_statuses.set(id, ResolutionStatus::Resolved);
+                    resolved_patches.push(patch);
+                }
+                Err(e) => self.defer_resolution(&mut state.pending_signals, patch, attempts + 1, e),
+            }
+        }
+
+        if !resolved_patches.is_empty() {
+            self.signals.upsert(resolved_patches.into_iter());
+            self.update_mapping_cache(state);
+        }
+    }
+
+    /// Logs a failed attempt to resolve a signal's entity and schedules the next attempt with exponential backoff.
+    ///
+    /// Arguments
+    /// - `pending_signals`: The signals which are pending resolution
+    /// - `patch`: The signal patch whose entity could not be resolved
+    /// - `attempts`: The number of failed attempts, including this one. Must be at least 1
+    /// - `error`: The error from this attempt
+    fn defer_resolution(
+        &self,
+        pending_signals: &mut HashMap<String, PendingSignal>,
+        patch: SignalPatch,
+        attempts: u32,
+        error: Box<dyn std::error::Error + Send + Sync>,
+    ) {
+        let last_error = match error.downcast::<DigitalTwinAdapterError>() {
+            Ok(e) if e.kind() == DigitalTwinAdapterErrorKind::EntityNotFound => {
+                warn!("Entity not found for signal {}", patch.id);
+                format!("{e:?}")
+            }
+            Ok(e) => {
+                log::error!("Error fetching entity for signal {}: {e:?}", patch.id);
+                format!("{e:?}")
+            }
+            Err(e) => {
+                log::error!("Error fetching entity for signal {}: {e:?}", patch.id);
+                format!("{e:?}")
+            }
+        };
+
+        let backoff = self
+            .poll_interval()
+            .saturating_mul(2u32.saturating_pow(attempts - 1))
+            .min(RESOLUTION_RETRY_MAX_BACKOFF);
+
+        info!(
+            "Will retry resolving the entity for signal {} in {backoff:?} ({attempts} failed attempt(s))",
+            patch.id
+        );
+
+        let pending_signal = PendingSignal {
+            patch,
+            attempts,
+            last_error,
+            next_attempt: Instant::now() + backoff,
+        };
+
+        self.resolution_statuses
+            .set(pending_signal.patch.id.clone(), pending_signal.status());
+        pending_signals.insert(pending_signal.patch.id.clone(), pending_signal);
+    }
+
+    /// Stores the last applied mapping and the sources of the signals in the signal store in the mapping cache, if it's enabled.
+    ///
+    /// Arguments
+    /// - `state`: The mapping state
+    fn update_mapping_cache(&self, state: &MappingState) {
+        if let (Some(mapping_cache), Some(mapping)) = (&self.mapping_cache, &state.mapping) {
+            let cached_mapping = CachedMapping {
+                mapping: mapping.clone(),
+                entities: self
+                    .signals
+                    .get_all()
+                    .into_iter()
+                    .map(|signal| (signal.id, signal.source))
+                    .collect(),
+            };
+
+            if let Err(e) = mapping_cache.store(&cached_mapping) {
+                warn!("Unable to update the mapping cache: {e:?}");
+            }
+        }
+    }
+
+    /// Restores the cached mapping, if there is one. Signals are added to the signal store with the cached sources,
+    /// and provider proxies are created for those sources, so that data can be emitted before the mapping service is reachable.
+    /// Signals without a cached source are added to the pending signals.
+    /// Returns whether a cached mapping was restored.
+    ///
+    /// Arguments
+    /// - `state`: The mapping state
+    async fn restore_cached_mapping(&self, state: &mut MappingState) -> bool {
+        let cached_mapping = match self.mapping_cache.as_ref().map(|cache| cache.load()) {
+            Some(Ok(Some(cached_mapping))) => cached_mapping,
+            Some(Err(e)) => {
+                warn!("Unable to load the cached mapping: {e:?}");
+                return false;
+            }
+            _ => return false,
+        };
+
+        // The mapping was validated when it was applied, but the validation config may have changed since then
+        let rejected_ids: HashSet<String> = self
+            .validate_mapping(&cached_mapping.mapping)
+            .into_iter()
+            .map(|rejected_entry| rejected_entry.id)
+            .collect();
+
+        let mut restored_patches = Vec::new();
+        {
+            let mut provider_proxy_selector = self.provider_proxy_selector.lock().await;
+            for mut patch in Self::signal_patches_from_mapping(&cached_mapping.mapping)
+                .into_iter()
+                .filter(|patch| !rejected_ids.contains(&patch.id))
+            {
+                let entity = match cached_mapping.entities.get(&patch.id) {
+                    Some(entity) => entity.clone(),
+                    None => {
+                        // The entity wasn't resolved when the mapping was cached, so retry right away
+                        let pending_signal = PendingSignal {
+                            patch,
+                            attempts: 0,
+                            last_error: "The entity was not resolved when the mapping was cached"
+                                .to_string(),
+                            next_attempt: Instant::now(),
+                        };
+
+                        self.resolution_statuses
+                            .set(pending_signal.patch.id.clone(), pending_signal.status());
+                        state
+                            .pending_signals
+                            .insert(pending_signal.patch.id.clone(), pending_signal);
+                        continue;
+                    }
+                };
+
+                match provider_proxy_selector
+                    .create_or_update_proxy(&entity)
+                    .await
+                {
+                    Ok(_) => {
+                        patch.source = entity;
+                        self.resolution_statuses
+                            .set(patch.id.clone(), ResolutionStatus::Resolved);
+                        restored_patches.push(patch);
+                    }
+                    Err(e) => {
+                        self.defer_resolution(&mut state.pending_signals, patch, 1, Box::new(e))
+                    }
+                }
+            }
+        }
+
+        info!(
+            "Restored {} signal(s) from the cached mapping",
+            restored_patches.len()
+        );
+
+        self.signals.sync(restored_patches.into_iter());
+        state.rejected_ids = rejected_ids;
+        state.mapping = Some(cached_mapping.mapping);
+
+        true
+    }
+
+    /// Returns a list of signal patches corresponding to the provided mapping.
+    /// The sources of the patches are not populated.
+    ///
+    /// Arguments
+    /// - `mapping`: The mapping from the mapping service
+    fn signal_patches_from_mapping(mapping: &GetMappingResponse) -> Vec<SignalPatch> {
+        mapping
+            .map
+            .iter()
+            .map(|(id, entry)| Self::signal_patch(id, entry))
+            .collect()
+    }
+
+    /// Returns the signal patch corresponding to a mapping entry.
+    /// The source of the patch is not populated.
+    ///
+    /// Arguments
+    /// - `id`: The signal id
+    /// - `entry`: The mapping entry for the signal
+    fn signal_patch(id: &str, entry: &DigitalTwinMapEntry) -> SignalPatch {
+        SignalPatch {
+            id: id.to_string(),
+            // this gets populated later, set to default for now
+            source: Default::default(),
+            target: Target {
+                metadata: entry.target.clone(),
+                reverse_conversion: entry.reverse_conversion.clone(),
+            },
+            emission_policy: EmissionPolicy {
+                interval_ms: entry.interval_ms,
+                align_to_wall_clock: entry.align_to_wall_clock,
+                emit_only_if_changed: entry.emit_on_change,
+                conversion: entry.conversion.clone(),
+                mode: entry.mode.clone(),
+                deadband_abs: entry.deadband_abs,
+                deadband_pct: entry.deadband_pct,
+                max_silence_ms: entry.max_silence_ms,
+            },
+        }
     }
 
     /// Populates the source of the provided signal with data retrieved from the digital twin service.
@@ -196,6 +830,25 @@ impl<
 
         Ok(())
     }
+
+    /// Removes the sources of the provided signals from their provider proxies.
+    /// This unsubscribes from the providers and shuts down proxies which are no longer needed.
+    ///
+    /// Arguments
+    /// - `removed_signals`: The signals which were removed from the mapping
+    async fn remove_sources(&self, removed_signals: Vec<Signal>) {
+        let mut provider_proxy_selector = self.provider_proxy_selector.lock().await;
+        for signal in removed_signals {
+            info!("Signal {} was removed from the mapping", signal.id);
+
+            if let Err(e) = provider_proxy_selector
+                .remove_entity(&signal.source.id)
+                .await
+            {
+                warn!("Unable to remove entity for signal {}: {e:?}", signal.id);
+            }
+        }
+    }
 }
 
 #[cfg(test)]
@@ -206,16 +859,29 @@ mod cartographer_tests {
 
     use async_trait::async_trait;
     use mockall::{predicate::eq, *};
+    use tempfile::TempDir;
+
+    use crate::config::MappingValidationConfig;
+
+    use freyja_common::mapping_service_health_store::CircuitState;
+    use tokio::sync::Notify;
 
     use freyja_contracts::{
-        digital_twin_adapter::{DigitalTwinAdapterError, GetDigitalTwinProviderResponse},
+        conversion::Conversion,
+        digital_twin_adapter::{
+            DigitalTwinAdapterError, GetDigitalTwinProviderResponse, ListEntitiesResponse,
+        },
         digital_twin_map_entry::DigitalTwinMapEntry,
         entity::Entity,
         mapping_client::{
-            CheckForWorkResponse, GetMappingResponse, MappingClientError, SendInventoryRequest,
-            SendInventoryResponse,
+            CheckForWorkResponse, GetMappingResponse, MappingClientError,
+            ReportRejectedEntriesResponse, SendInventoryRequest, SendInventoryResponse,
+        },
+        provider_proxy_selector::{
+            InvokeResponseFuture, ProviderProxySelectorError, SetValueFuture,
         },
-        provider_proxy_selector::ProviderProxySelectorError,
+        signal::EmissionMode,
+        value::Value,
     };
 
     mock! {
@@ -234,6 +900,27 @@ mod cartographer_tests {
         }
     }
 
+    mock! {
+        pub InventoryDigitalTwinAdapterImpl {}
+
+        #[async_trait]
+        impl DigitalTwinAdapter for InventoryDigitalTwinAdapterImpl {
+            fn create_new() -> Result<Self, DigitalTwinAdapterError>
+            where
+                Self: Sized;
+
+            async fn find_by_id(
+                &self,
+                request: GetDigitalTwinProviderRequest,
+            ) -> Result<GetDigitalTwinProviderResponse, DigitalTwinAdapterError>;
+
+            async fn list_entities(
+                &self,
+                request: ListEntitiesRequest,
+            ) -> Result<ListEntitiesResponse, DigitalTwinAdapterError>;
+        }
+    }
+
     mock! {
         pub MappingClientImpl {}
 
@@ -257,6 +944,11 @@ mod cartographer_tests {
                 &self,
                 request: GetMappingRequest,
             ) -> Result<GetMappingResponse, MappingClientError>;
+
+            async fn report_rejected_entries(
+                &self,
+                request: ReportRejectedEntriesRequest,
+            ) -> Result<ReportRejectedEntriesResponse, MappingClientError>;
         }
     }
 
@@ -266,63 +958,83 @@ mod cartographer_tests {
         #[async_trait]
         impl ProviderProxySelector for ProviderProxySelector {
             async fn create_or_update_proxy(&mut self, entity: &Entity) -> Result<(), ProviderProxySelectorError>;
+            async fn remove_entity(&mut self, entity_id: &str) -> Result<(), ProviderProxySelectorError>;
             async fn request_entity_value(&mut self, entity_id: &str) -> Result<(), ProviderProxySelectorError>;
+            async fn set_entity_value(&mut self, entity_id: &str, value: Value) -> Result<SetValueFuture, ProviderProxySelectorError>;
+            async fn invoke_entity(&mut self, entity_id: &str, payload: Value) -> Result<InvokeResponseFuture, ProviderProxySelectorError>;
         }
     }
 
-    #[tokio::test]
-    async fn get_mapping_as_signals_returns_correct_value() {
+    #[test]
+    fn signal_patches_from_mapping_returns_correct_value() {
         const ID: &str = "testid";
         let test_map_entry = DigitalTwinMapEntry {
             source: ID.to_string(),
             target: HashMap::new(),
             interval_ms: 42,
-            conversion: Default::default(),
+            align_to_wall_clock: true,
+            conversion: Conversion::c_to_f(),
+            reverse_conversion: Some(Conversion::f_to_c()),
             emit_on_change: true,
+            mode: EmissionMode::OnChange {
+                min_interval_ms: 100,
+            },
+            deadband_abs: Some(0.5),
+            deadband_pct: Some(1.0),
+            max_silence_ms: Some(60000),
         };
 
-        let test_map_entry_clone = test_map_entry.clone();
-
-        let mut mock_mapping_client = MockMappingClientImpl::new();
-        mock_mapping_client
-            .expect_get_mapping()
-            .returning(move |_| {
-                Ok(GetMappingResponse {
-                    map: [(ID.to_string(), test_map_entry_clone.clone())]
-                        .into_iter()
-                        .collect(),
-                })
-            });
-
-        let uut = Cartographer {
-            signals: Arc::new(SignalStore::new()),
-            mapping_client: mock_mapping_client,
-            digital_twin_client: MockDigitalTwinAdapterImpl::new(),
-            provider_proxy_selector: Arc::new(Mutex::new(MockProviderProxySelector::new())),
-            poll_interval: Duration::from_secs(1),
+        let mapping = GetMappingResponse {
+            map: [(ID.to_string(), test_map_entry.clone())]
+                .into_iter()
+                .collect(),
+            version: None,
         };
 
-        let result = uut.get_mapping_as_signal_patches().await;
+        let mut signals = Cartographer::<
+            MockMappingClientImpl,
+            MockDigitalTwinAdapterImpl,
+            MockProviderProxySelector,
+        >::signal_patches_from_mapping(&mapping);
 
-        assert!(result.is_ok());
-        let mut signals = result.unwrap();
         assert_eq!(signals.len(), 1);
         let signal = signals.pop().unwrap();
         assert_eq!(signal.id, ID.to_string());
         assert_eq!(signal.target.metadata, test_map_entry.target);
+        assert_eq!(
+            signal.target.reverse_conversion,
+            test_map_entry.reverse_conversion
+        );
         assert_eq!(
             signal.emission_policy.interval_ms,
             test_map_entry.interval_ms
         );
+        assert_eq!(
+            signal.emission_policy.align_to_wall_clock,
+            test_map_entry.align_to_wall_clock
+        );
         assert_eq!(
             signal.emission_policy.emit_only_if_changed,
             test_map_entry.emit_on_change
         );
         assert_eq!(signal.emission_policy.conversion, test_map_entry.conversion);
-    }
-
-    #[tokio::test]
-    async fn populate_source_tests() {
+        assert_eq!(signal.emission_policy.mode, test_map_entry.mode);
+        assert_eq!(
+            signal.emission_policy.deadband_abs,
+            test_map_entry.deadband_abs
+        );
+        assert_eq!(
+            signal.emission_policy.deadband_pct,
+            test_map_entry.deadband_pct
+        );
+        assert_eq!(
+            signal.emission_policy.max_silence_ms,
+            test_map_entry.max_silence_ms
+        );
+    }
+
+    #[tokio::test]
+    async fn populate_source_tests() {
         const ID: &str = "testid";
         let test_entity = Entity {
             id: ID.to_string(),
@@ -360,7 +1072,12 @@ mod cartographer_tests {
             mapping_client: MockMappingClientImpl::new(),
             digital_twin_client: mock_dt_adapter,
             provider_proxy_selector,
-            poll_interval: Duration::from_secs(1),
+            config: test_config(1000),
+            mapping_cache: None,
+            vehicle: VehicleInfo::default(),
+            validator: test_validator(),
+            resolution_statuses: Arc::new(ResolutionStatusStore::new()),
+            mapping_service_health: Arc::new(MappingServiceHealthStore::new()),
         };
 
         let result = uut.populate_source(test_signal_patch).await;
@@ -370,4 +1087,836 @@ mod cartographer_tests {
         assert!(result.is_ok());
         assert_eq!(test_signal_patch.source, test_entity);
     }
+
+    #[tokio::test]
+    async fn remove_sources_removes_entities_from_provider_proxies() {
+        const ID: &str = "testid";
+        const OTHER_ID: &str = "otherid";
+
+        let mut mock_provider_proxy_selector = MockProviderProxySelector::new();
+        mock_provider_proxy_selector
+            .expect_remove_entity()
+            .with(eq(ID))
+            .once()
+            .returning(|_| Ok(()));
+        mock_provider_proxy_selector
+            .expect_remove_entity()
+            .with(eq(OTHER_ID))
+            .once()
+            .returning(|_| {
+                Err(ProviderProxySelectorError::entity_not_found(
+                    "entity not found",
+                ))
+            });
+
+        let uut = Cartographer {
+            signals: Arc::new(SignalStore::new()),
+            mapping_client: MockMappingClientImpl::new(),
+            digital_twin_client: MockDigitalTwinAdapterImpl::new(),
+            provider_proxy_selector: Arc::new(Mutex::new(mock_provider_proxy_selector)),
+            config: test_config(1000),
+            mapping_cache: None,
+            vehicle: VehicleInfo::default(),
+            validator: test_validator(),
+            resolution_statuses: Arc::new(ResolutionStatusStore::new()),
+            mapping_service_health: Arc::new(MappingServiceHealthStore::new()),
+        };
+
+        let removed_signals = [ID, OTHER_ID]
+            .into_iter()
+            .map(|id| Signal {
+                id: id.to_string(),
+                source: Entity {
+                    id: id.to_string(),
+                    ..Default::default()
+                },
+                ..Default::default()
+            })
+            .collect();
+
+        uut.remove_sources(removed_signals).await;
+
+        uut.provider_proxy_selector.lock().await.checkpoint();
+    }
+
+    #[tokio::test]
+    async fn send_inventory_only_sends_changed_inventory() {
+        const ID: &str = "testid";
+        const OTHER_ID: &str = "otherid";
+
+        let mut sequence = Sequence::new();
+        let mut mock_dt_adapter = MockInventoryDigitalTwinAdapterImpl::new();
+        for ids in [vec![ID, OTHER_ID], vec![OTHER_ID, ID], vec![ID]] {
+            mock_dt_adapter
+                .expect_list_entities()
+                .once()
+                .in_sequence(&mut sequence)
+                .returning(move |_| {
+                    Ok(ListEntitiesResponse {
+                        entities: ids.iter().map(|id| test_entity(id)).collect(),
+                    })
+                });
+        }
+
+        let mut mock_mapping_client = MockMappingClientImpl::new();
+        for ids in [vec![ID, OTHER_ID], vec![ID]] {
+            let inventory: HashSet<String> = ids.into_iter().map(String::from).collect();
+            mock_mapping_client
+                .expect_send_inventory()
+                .withf(move |request| request.inventory == inventory)
+                .once()
+                .returning(|_| Ok(SendInventoryResponse {}));
+        }
+
+        let uut = Cartographer {
+            signals: Arc::new(SignalStore::new()),
+            mapping_client: mock_mapping_client,
+            digital_twin_client: mock_dt_adapter,
+            provider_proxy_selector: Arc::new(Mutex::new(MockProviderProxySelector::new())),
+            config: test_config(1000),
+            mapping_cache: None,
+            vehicle: VehicleInfo::default(),
+            validator: test_validator(),
+            resolution_statuses: Arc::new(ResolutionStatusStore::new()),
+            mapping_service_health: Arc::new(MappingServiceHealthStore::new()),
+        };
+
+        let mut state = MappingState::default();
+        for _ in 0..3 {
+            uut.send_inventory(&mut state).await.unwrap();
+        }
+
+        assert_eq!(
+            state.inventory,
+            Some([ID.to_string()].into_iter().collect())
+        );
+    }
+
+    #[tokio::test]
+    async fn send_inventory_does_nothing_when_listing_entities_is_unsupported() {
+        let uut = Cartographer {
+            signals: Arc::new(SignalStore::new()),
+            mapping_client: MockMappingClientImpl::new(),
+            digital_twin_client: MockDigitalTwinAdapterImpl::new(),
+            provider_proxy_selector: Arc::new(Mutex::new(MockProviderProxySelector::new())),
+            config: test_config(1000),
+            mapping_cache: None,
+            vehicle: VehicleInfo::default(),
+            validator: test_validator(),
+            resolution_statuses: Arc::new(ResolutionStatusStore::new()),
+            mapping_service_health: Arc::new(MappingServiceHealthStore::new()),
+        };
+
+        let mut state = MappingState::default();
+        uut.send_inventory(&mut state).await.unwrap();
+
+        assert!(state.inventory_unsupported);
+        assert_eq!(state.inventory, None);
+    }
+
+    fn test_config(poll_interval_ms: u64) -> CartographerConfig {
+        CartographerConfig {
+            poll_interval_ms,
+            max_backoff_ms: poll_interval_ms * 4,
+            backoff_jitter: 0.0,
+            circuit_breaker_failure_threshold: 3,
+            circuit_breaker_open_duration_ms: poll_interval_ms * 10,
+        }
+    }
+
+    fn test_validator() -> MappingValidator {
+        MappingValidator::new(
+            &MappingValidationConfig {
+                min_interval_ms: 0,
+                max_interval_ms: u64::MAX,
+            },
+            Vec::new(),
+        )
+    }
+
+    fn test_entity(id: &str) -> Entity {
+        Entity {
+            id: id.to_string(),
+            uri: "uri".to_string(),
+            operation: "Subscribe".to_string(),
+            protocol: "in-memory".to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn test_mapping(ids: &[&str]) -> GetMappingResponse {
+        GetMappingResponse {
+            map: ids
+                .iter()
+                .map(|id| {
+                    (
+                        id.to_string(),
+                        DigitalTwinMapEntry {
+                            source: id.to_string(),
+                            target: [("id".to_string(), id.to_string())].into_iter().collect(),
+                            interval_ms: 1000,
+                            ..Default::default()
+                        },
+                    )
+                })
+                .collect(),
+            version: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn apply_mapping_rejects_invalid_entries() {
+        const ID: &str = "testid";
+        const INVALID_ID: &str = "invalidid";
+
+        let mut mapping = test_mapping(&[ID, INVALID_ID]);
+        mapping.map.get_mut(INVALID_ID).unwrap().conversion = Conversion::Linear {
+            mul: 0.0,
+            offset: 0.0,
+        };
+
+        let mut mock_mapping_client = MockMappingClientImpl::new();
+        mock_mapping_client
+            .expect_get_mapping()
+            .once()
+            .returning(move |_| Ok(mapping.clone()));
+        mock_mapping_client
+            .expect_report_rejected_entries()
+            .withf(|request| {
+                request.rejected_entries.len() == 1 && request.rejected_entries[0].id == INVALID_ID
+            })
+            .once()
+            .returning(|_| Ok(ReportRejectedEntriesResponse {}));
+
+        // Only the valid entry is resolved
+        let mut mock_dt_adapter = MockDigitalTwinAdapterImpl::new();
+        mock_dt_adapter
+            .expect_find_by_id()
+            .withf(|request| request.entity_id == ID)
+            .once()
+            .returning(|request| {
+                Ok(GetDigitalTwinProviderResponse {
+                    entity: test_entity(&request.entity_id),
+                })
+            });
+
+        let mut mock_provider_proxy_selector = MockProviderProxySelector::new();
+        mock_provider_proxy_selector
+            .expect_create_or_update_proxy()
+            .once()
+            .returning(|_| Ok(()));
+
+        let uut = Cartographer {
+            signals: Arc::new(SignalStore::new()),
+            mapping_client: mock_mapping_client,
+            digital_twin_client: mock_dt_adapter,
+            provider_proxy_selector: Arc::new(Mutex::new(mock_provider_proxy_selector)),
+            config: test_config(1000),
+            mapping_cache: None,
+            vehicle: VehicleInfo::default(),
+            validator: test_validator(),
+            resolution_statuses: Arc::new(ResolutionStatusStore::new()),
+            mapping_service_health: Arc::new(MappingServiceHealthStore::new()),
+        };
+
+        let mut state = MappingState::default();
+        uut.apply_mapping(&mut state).await.unwrap();
+
+        let signals = uut.signals.get_all();
+        assert_eq!(signals.len(), 1);
+        assert_eq!(signals[0].id, ID);
+        assert_eq!(uut.resolution_statuses.get(INVALID_ID), None);
+        assert!(state.rejected_ids.contains(INVALID_ID));
+    }
+
+    #[tokio::test]
+    async fn restore_cached_mapping_adds_cached_signals() {
+        const ID: &str = "testid";
+        const UNRESOLVED_ID: &str = "unresolvedid";
+
+        let dir = TempDir::new().unwrap();
+        let mapping_cache = MappingCache::new(dir.path());
+        mapping_cache
+            .store(&CachedMapping {
+                mapping: test_mapping(&[ID, UNRESOLVED_ID]),
+                entities: [(ID.to_string(), test_entity(ID))].into_iter().collect(),
+            })
+            .unwrap();
+
+        let mut mock_provider_proxy_selector = MockProviderProxySelector::new();
+        mock_provider_proxy_selector
+            .expect_create_or_update_proxy()
+            .with(eq(test_entity(ID)))
+            .once()
+            .returning(|_| Ok(()));
+
+        let uut = Cartographer {
+            signals: Arc::new(SignalStore::new()),
+            mapping_client: MockMappingClientImpl::new(),
+            digital_twin_client: MockDigitalTwinAdapterImpl::new(),
+            provider_proxy_selector: Arc::new(Mutex::new(mock_provider_proxy_selector)),
+            config: test_config(1000),
+            mapping_cache: Some(mapping_cache),
+            vehicle: VehicleInfo::default(),
+            validator: test_validator(),
+            resolution_statuses: Arc::new(ResolutionStatusStore::new()),
+            mapping_service_health: Arc::new(MappingServiceHealthStore::new()),
+        };
+
+        let mut state = MappingState::default();
+        assert!(uut.restore_cached_mapping(&mut state).await);
+
+        uut.provider_proxy_selector.lock().await.checkpoint();
+
+        let signals = uut.signals.get_all();
+        assert_eq!(signals.len(), 1);
+        assert_eq!(signals[0].id, ID);
+        assert_eq!(signals[0].source, test_entity(ID));
+
+        // Signals without a cached entity are retried
+        assert!(state.pending_signals.contains_key(UNRESOLVED_ID));
+        assert_eq!(
+            uut.resolution_statuses.get(ID),
+            Some(ResolutionStatus::Resolved)
+        );
+        assert_eq!(uut.resolution_statuses.get_pending(), vec![UNRESOLVED_ID]);
+    }
+
+    #[tokio::test]
+    async fn restore_cached_mapping_does_nothing_without_cached_mapping() {
+        let dir = TempDir::new().unwrap();
+
+        let uut = Cartographer {
+            signals: Arc::new(SignalStore::new()),
+            mapping_client: MockMappingClientImpl::new(),
+            digital_twin_client: MockDigitalTwinAdapterImpl::new(),
+            provider_proxy_selector: Arc::new(Mutex::new(MockProviderProxySelector::new())),
+            config: test_config(1000),
+            mapping_cache: Some(MappingCache::new(dir.path())),
+            vehicle: VehicleInfo::default(),
+            validator: test_validator(),
+            resolution_statuses: Arc::new(ResolutionStatusStore::new()),
+            mapping_service_health: Arc::new(MappingServiceHealthStore::new()),
+        };
+
+        assert!(
+            !uut.restore_cached_mapping(&mut MappingState::default())
+                .await
+        );
+        assert!(uut.signals.get_all().is_empty());
+    }
+
+    #[tokio::test]
+    async fn run_reconciles_cached_mapping_with_mapping_service() {
+        const CACHED_ID: &str = "cachedid";
+        const NEW_ID: &str = "newid";
+
+        let dir = TempDir::new().unwrap();
+        MappingCache::new(dir.path())
+            .store(&CachedMapping {
+                mapping: test_mapping(&[CACHED_ID]),
+                entities: [(CACHED_ID.to_string(), test_entity(CACHED_ID))]
+                    .into_iter()
+                    .collect(),
+            })
+            .unwrap();
+
+        let vehicle = VehicleInfo {
+            vehicle_id: Some("vin".to_string()),
+            freyja_version: "1.0.0".to_string(),
+            supported_protocols: vec!["in-memory".to_string()],
+            supported_conversions: vec!["linear".to_string()],
+        };
+
+        // The mapping service reports no work, but the cached mapping is still reconciled.
+        // The vehicle info is sent with each request
+        let mut mock_mapping_client = MockMappingClientImpl::new();
+        let expected_vehicle = vehicle.clone();
+        mock_mapping_client
+            .expect_check_for_work()
+            .withf(move |request| request.vehicle == expected_vehicle)
+            .returning(|_| Ok(CheckForWorkResponse { has_work: false }));
+        let expected_vehicle = vehicle.clone();
+        mock_mapping_client
+            .expect_get_mapping()
+            .withf(move |request| request.vehicle == expected_vehicle)
+            .once()
+            .returning(|_| Ok(test_mapping(&[NEW_ID])));
+
+        let mut mock_dt_adapter = MockDigitalTwinAdapterImpl::new();
+        mock_dt_adapter.expect_find_by_id().returning(|request| {
+            Ok(GetDigitalTwinProviderResponse {
+                entity: test_entity(&request.entity_id),
+            })
+        });
+
+        let mut mock_provider_proxy_selector = MockProviderProxySelector::new();
+        mock_provider_proxy_selector
+            .expect_create_or_update_proxy()
+            .times(2)
+            .returning(|_| Ok(()));
+        mock_provider_proxy_selector
+            .expect_remove_entity()
+            .with(eq(CACHED_ID))
+            .once()
+            .returning(|_| Ok(()));
+
+        let uut = Cartographer {
+            signals: Arc::new(SignalStore::new()),
+            mapping_client: mock_mapping_client,
+            digital_twin_client: mock_dt_adapter,
+            provider_proxy_selector: Arc::new(Mutex::new(mock_provider_proxy_selector)),
+            config: test_config(10),
+            mapping_cache: Some(MappingCache::new(dir.path())),
+            vehicle,
+            validator: test_validator(),
+            resolution_statuses: Arc::new(ResolutionStatusStore::new()),
+            mapping_service_health: Arc::new(MappingServiceHealthStore::new()),
+        };
+
+        let _ = tokio::time::timeout(Duration::from_millis(100), uut.run()).await;
+
+        uut.provider_proxy_selector.lock().await.checkpoint();
+
+        let signals = uut.signals.get_all();
+        assert_eq!(signals.len(), 1);
+        assert_eq!(signals[0].id, NEW_ID);
+
+        let cached_mapping = MappingCache::new(dir.path()).load().unwrap().unwrap();
+        assert!(cached_mapping.mapping.map.contains_key(NEW_ID));
+        assert!(!cached_mapping.mapping.map.contains_key(CACHED_ID));
+        assert_eq!(cached_mapping.entities[NEW_ID], test_entity(NEW_ID));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn unresolved_signals_are_retried_until_resolved() {
+        const ID: &str = "testid";
+        const LATE_ID: &str = "lateid";
+
+        let mut mock_mapping_client = MockMappingClientImpl::new();
+        mock_mapping_client
+            .expect_get_mapping()
+            .once()
+            .returning(|_| Ok(test_mapping(&[ID, LATE_ID])));
+
+        // The provider for LATE_ID only comes online after a few attempts
+        let mut late_attempts = 0;
+        let mut mock_dt_adapter = MockDigitalTwinAdapterImpl::new();
+        mock_dt_adapter
+            .expect_find_by_id()
+            .returning(move |request| {
+                if request.entity_id == LATE_ID {
+                    late_attempts += 1;
+                    if late_attempts < 3 {
+                        return Err(DigitalTwinAdapterError::entity_not_found("not found"));
+                    }
+                }
+
+                Ok(GetDigitalTwinProviderResponse {
+                    entity: test_entity(&request.entity_id),
+                })
+            });
+
+        let mut mock_provider_proxy_selector = MockProviderProxySelector::new();
+        mock_provider_proxy_selector
+            .expect_create_or_update_proxy()
+            .times(2)
+            .returning(|_| Ok(()));
+
+        let uut = Cartographer {
+            signals: Arc::new(SignalStore::new()),
+            mapping_client: mock_mapping_client,
+            digital_twin_client: mock_dt_adapter,
+            provider_proxy_selector: Arc::new(Mutex::new(mock_provider_proxy_selector)),
+            config: test_config(10),
+            mapping_cache: None,
+            vehicle: VehicleInfo::default(),
+            validator: test_validator(),
+            resolution_statuses: Arc::new(ResolutionStatusStore::new()),
+            mapping_service_health: Arc::new(MappingServiceHealthStore::new()),
+        };
+
+        let mut state = MappingState::default();
+
+        // The partially resolved mapping is applied
+        uut.apply_mapping(&mut state).await.unwrap();
+        assert_eq!(uut.signals.get_all().len(), 1);
+        assert!(uut.signals.get(&ID.to_string()).is_some());
+        assert_eq!(
+            uut.resolution_statuses.get(LATE_ID),
+            Some(ResolutionStatus::Pending {
+                attempts: 1,
+                last_error: format!(
+                    "{:?}",
+                    DigitalTwinAdapterError::entity_not_found("not found")
+                ),
+            })
+        );
+
+        // Retries wait for the backoff to expire
+        uut.retry_pending_signals(&mut state).await;
+        assert_eq!(state.pending_signals[LATE_ID].attempts, 1);
+
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        uut.retry_pending_signals(&mut state).await;
+        assert_eq!(state.pending_signals[LATE_ID].attempts, 2);
+
+        // The backoff doubles after each failed attempt
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        uut.retry_pending_signals(&mut state).await;
+        assert_eq!(state.pending_signals[LATE_ID].attempts, 2);
+
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        uut.retry_pending_signals(&mut state).await;
+        assert!(state.pending_signals.is_empty());
+
+        uut.provider_proxy_selector.lock().await.checkpoint();
+
+        assert_eq!(uut.signals.get_all().len(), 2);
+        assert_eq!(
+            uut.signals.get(&LATE_ID.to_string()).unwrap().source,
+            test_entity(LATE_ID)
+        );
+        assert_eq!(
+            uut.resolution_statuses.get(LATE_ID),
+            Some(ResolutionStatus::Resolved)
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn run_backs_off_and_opens_circuit_when_mapping_service_is_unreachable() {
+        let mut mock_mapping_client = MockMappingClientImpl::new();
+        mock_mapping_client
+            .expect_check_for_work()
+            .times(3)
+            .returning(|_| Err(MappingClientError::communication("unreachable")));
+
+        let mapping_service_health = Arc::new(MappingServiceHealthStore::new());
+
+        let uut = Cartographer {
+            signals: Arc::new(SignalStore::new()),
+            mapping_client: mock_mapping_client,
+            digital_twin_client: MockDigitalTwinAdapterImpl::new(),
+            provider_proxy_selector: Arc::new(Mutex::new(MockProviderProxySelector::new())),
+            config: test_config(10),
+            mapping_cache: None,
+            vehicle: VehicleInfo::default(),
+            validator: test_validator(),
+            resolution_statuses: Arc::new(ResolutionStatusStore::new()),
+            mapping_service_health: mapping_service_health.clone(),
+        };
+
+        // The calls are made after 0ms, 10ms and 30ms. The circuit then opens for 100ms
+        let _ = tokio::time::timeout(Duration::from_millis(100), uut.run()).await;
+
+        let health = mapping_service_health.get();
+        assert!(!health.is_healthy());
+        assert_eq!(health.consecutive_failures, 3);
+        assert_eq!(health.circuit_state, CircuitState::Open);
+    }
+
+    /// A mapping client which pushes work to the cartographer when notified
+    struct PushMappingClient {
+        work_available: Arc<Notify>,
+        mapping: GetMappingResponse,
+    }
+
+    #[async_trait]
+    impl MappingClient for PushMappingClient {
+        fn create_new() -> Result<Self, MappingClientError> {
+            Err(MappingClientError::unknown("not supported"))
+        }
+
+        async fn check_for_work(
+            &self,
+            _request: CheckForWorkRequest,
+        ) -> Result<CheckForWorkResponse, MappingClientError> {
+            Ok(CheckForWorkResponse { has_work: false })
+        }
+
+        async fn wait_for_work(
+            &self,
+            request: WaitForWorkRequest,
+        ) -> Result<CheckForWorkResponse, MappingClientError> {
+            let notified = tokio::time::timeout(
+                Duration::from_millis(request.timeout_ms),
+                self.work_available.notified(),
+            )
+            .await;
+
+            Ok(CheckForWorkResponse {
+                has_work: notified.is_ok(),
+            })
+        }
+
+        async fn get_mapping(
+            &self,
+            _request: GetMappingRequest,
+        ) -> Result<GetMappingResponse, MappingClientError> {
+            Ok(self.mapping.clone())
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn run_applies_pushed_mapping_without_waiting_for_poll_interval() {
+        const ID: &str = "testid";
+
+        let mut mock_dt_adapter = MockDigitalTwinAdapterImpl::new();
+        mock_dt_adapter.expect_find_by_id().returning(|request| {
+            Ok(GetDigitalTwinProviderResponse {
+                entity: test_entity(&request.entity_id),
+            })
+        });
+
+        let mut mock_provider_proxy_selector = MockProviderProxySelector::new();
+        mock_provider_proxy_selector
+            .expect_create_or_update_proxy()
+            .once()
+            .returning(|_| Ok(()));
+
+        let work_available = Arc::new(Notify::new());
+
+        let uut = Cartographer {
+            signals: Arc::new(SignalStore::new()),
+            mapping_client: PushMappingClient {
+                work_available: work_available.clone(),
+                mapping: test_mapping(&[ID]),
+            },
+            digital_twin_client: mock_dt_adapter,
+            provider_proxy_selector: Arc::new(Mutex::new(mock_provider_proxy_selector)),
+            config: test_config(60000),
+            mapping_cache: None,
+            vehicle: VehicleInfo::default(),
+            validator: test_validator(),
+            resolution_statuses: Arc::new(ResolutionStatusStore::new()),
+            mapping_service_health: Arc::new(MappingServiceHealthStore::new()),
+        };
+
+        let push_work = async {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            work_available.notify_one();
+        };
+
+        let _ = tokio::time::timeout(Duration::from_millis(100), async {
+            tokio::join!(uut.run(), push_work)
+        })
+        .await;
+
+        uut.provider_proxy_selector.lock().await.checkpoint();
+
+        assert!(uut.signals.get(&ID.to_string()).is_some());
+    }
+
+    /// A mapping client which sends a fixed mapping update
+    struct DeltaMappingClient {
+        mapping: GetMappingResponse,
+        update: MappingUpdate,
+    }
+
+    #[async_trait]
+    impl MappingClient for DeltaMappingClient {
+        fn create_new() -> Result<Self, MappingClientError> {
+            Err(MappingClientError::unknown("not supported"))
+        }
+
+        async fn check_for_work(
+            &self,
+            _request: CheckForWorkRequest,
+        ) -> Result<CheckForWorkResponse, MappingClientError> {
+            Ok(CheckForWorkResponse { has_work: true })
+        }
+
+        async fn get_mapping(
+            &self,
+            _request: GetMappingRequest,
+        ) -> Result<GetMappingResponse, MappingClientError> {
+            Ok(self.mapping.clone())
+        }
+
+        async fn get_mapping_update(
+            &self,
+            _request: GetMappingRequest,
+        ) -> Result<MappingUpdate, MappingClientError> {
+            Ok(self.update.clone())
+        }
+    }
+
+    /// Creates a cartographer whose signal store and mapping state contain a mapping with the specified version
+    fn delta_test_cartographer(
+        mapping_client: DeltaMappingClient,
+        mock_dt_adapter: MockDigitalTwinAdapterImpl,
+        mock_provider_proxy_selector: MockProviderProxySelector,
+        ids: &[&str],
+        version: u64,
+    ) -> (
+        Cartographer<DeltaMappingClient, MockDigitalTwinAdapterImpl, MockProviderProxySelector>,
+        MappingState,
+    ) {
+        let uut = Cartographer {
+            signals: Arc::new(SignalStore::new()),
+            mapping_client,
+            digital_twin_client: mock_dt_adapter,
+            provider_proxy_selector: Arc::new(Mutex::new(mock_provider_proxy_selector)),
+            config: test_config(1000),
+            mapping_cache: None,
+            vehicle: VehicleInfo::default(),
+            validator: test_validator(),
+            resolution_statuses: Arc::new(ResolutionStatusStore::new()),
+            mapping_service_health: Arc::new(MappingServiceHealthStore::new()),
+        };
+
+        let mapping = GetMappingResponse {
+            version: Some(version),
+            ..test_mapping(ids)
+        };
+
+        uut.signals.sync(
+            Cartographer::<DeltaMappingClient, MockDigitalTwinAdapterImpl, MockProviderProxySelector>::signal_patches_from_mapping(&mapping)
+                .into_iter()
+                .map(|mut patch| {
+                    patch.source = test_entity(&patch.id);
+                    patch
+                }),
+        );
+
+        let state = MappingState {
+            mapping: Some(mapping),
+            ..Default::default()
+        };
+
+        (uut, state)
+    }
+
+    #[tokio::test]
+    async fn apply_mapping_applies_delta_to_affected_signals_only() {
+        const KEPT_ID: &str = "keptid";
+        const CHANGED_ID: &str = "changedid";
+        const REMOVED_ID: &str = "removedid";
+        const ADDED_ID: &str = "addedid";
+
+        let delta = MappingDelta {
+            base_version: 1,
+            version: 2,
+            added: test_mapping(&[ADDED_ID]).map,
+            changed: [(
+                CHANGED_ID.to_string(),
+                DigitalTwinMapEntry {
+                    source: CHANGED_ID.to_string(),
+                    interval_ms: 42,
+                    ..Default::default()
+                },
+            )]
+            .into_iter()
+            .collect(),
+            removed: vec![REMOVED_ID.to_string()],
+        };
+
+        // Only the added and changed signals are resolved
+        let mut mock_dt_adapter = MockDigitalTwinAdapterImpl::new();
+        mock_dt_adapter
+            .expect_find_by_id()
+            .times(2)
+            .returning(|request| {
+                assert_ne!(request.entity_id, KEPT_ID);
+                Ok(GetDigitalTwinProviderResponse {
+                    entity: test_entity(&request.entity_id),
+                })
+            });
+
+        let mut mock_provider_proxy_selector = MockProviderProxySelector::new();
+        mock_provider_proxy_selector
+            .expect_create_or_update_proxy()
+            .times(2)
+            .returning(|_| Ok(()));
+        mock_provider_proxy_selector
+            .expect_remove_entity()
+            .with(eq(REMOVED_ID))
+            .once()
+            .returning(|_| Ok(()));
+
+        let (uut, mut state) = delta_test_cartographer(
+            DeltaMappingClient {
+                mapping: GetMappingResponse::default(),
+                update: MappingUpdate::Delta(delta),
+            },
+            mock_dt_adapter,
+            mock_provider_proxy_selector,
+            &[KEPT_ID, CHANGED_ID, REMOVED_ID],
+            1,
+        );
+
+        uut.apply_mapping(&mut state).await.unwrap();
+
+        uut.provider_proxy_selector.lock().await.checkpoint();
+
+        let mut ids: Vec<String> = uut.signals.get_all().into_iter().map(|s| s.id).collect();
+        ids.sort();
+        assert_eq!(ids, vec![ADDED_ID, CHANGED_ID, KEPT_ID]);
+        assert_eq!(
+            uut.signals
+                .get(&CHANGED_ID.to_string())
+                .unwrap()
+                .emission
+                .policy
+                .interval_ms,
+            42
+        );
+        assert_eq!(uut.resolution_statuses.get(REMOVED_ID), None);
+
+        let mapping = state.mapping.unwrap();
+        assert_eq!(mapping.version, Some(2));
+        assert_eq!(mapping.map.len(), 3);
+        assert_eq!(mapping.map[CHANGED_ID].interval_ms, 42);
+    }
+
+    #[tokio::test]
+    async fn apply_mapping_gets_full_mapping_when_delta_does_not_apply() {
+        const ID: &str = "testid";
+        const NEW_ID: &str = "newid";
+
+        let mut mock_dt_adapter = MockDigitalTwinAdapterImpl::new();
+        mock_dt_adapter.expect_find_by_id().returning(|request| {
+            Ok(GetDigitalTwinProviderResponse {
+                entity: test_entity(&request.entity_id),
+            })
+        });
+
+        let mut mock_provider_proxy_selector = MockProviderProxySelector::new();
+        mock_provider_proxy_selector
+            .expect_create_or_update_proxy()
+            .once()
+            .returning(|_| Ok(()));
+        mock_provider_proxy_selector
+            .expect_remove_entity()
+            .with(eq(ID))
+            .once()
+            .returning(|_| Ok(()));
+
+        let (uut, mut state) = delta_test_cartographer(
+            DeltaMappingClient {
+                mapping: GetMappingResponse {
+                    version: Some(5),
+                    ..test_mapping(&[NEW_ID])
+                },
+                update: MappingUpdate::Delta(MappingDelta {
+                    base_version: 4,
+                    version: 5,
+                    ..Default::default()
+                }),
+            },
+            mock_dt_adapter,
+            mock_provider_proxy_selector,
+            &[ID],
+            1,
+        );
+
+        uut.apply_mapping(&mut state).await.unwrap();
+
+        uut.provider_proxy_selector.lock().await.checkpoint();
+
+        let signals = uut.signals.get_all();
+        assert_eq!(signals.len(), 1);
+        assert_eq!(signals[0].id, NEW_ID);
+        assert_eq!(state.mapping_version(), Some(5));
+    }
 }
@@ -0,0 +1,217 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+// SPDX-License-Identifier: MIT
+
+use std::{sync::Arc, time::Duration};
+
+use log::{info, warn};
+use rand::Rng;
+use tokio::time::Instant;
+
+use crate::config::CartographerConfig;
+use freyja_common::mapping_service_health_store::{
+    CircuitState, MappingServiceHealth, MappingServiceHealthStore,
+};
+
+/// Determines when the cartographer may call the mapping service.
+/// Failed calls are retried with exponential backoff and jitter, and after too many consecutive failures
+/// the circuit opens and the mapping service isn't called again until the open duration has passed.
+/// The resulting health is published to a shared `MappingServiceHealthStore`.
+pub struct CircuitBreaker {
+    /// The polling interval, which is also the initial backoff
+    poll_interval: Duration,
+
+    /// The maximum backoff
+    max_backoff: Duration,
+
+    /// The amount of jitter to apply to backoffs, as a fraction of the backoff
+    backoff_jitter: f64,
+
+    /// The number of consecutive failures after which the circuit opens
+    failure_threshold: u32,
+
+    /// The time that the circuit stays open
+    open_duration: Duration,
+
+    /// The shared mapping service health
+    health: Arc<MappingServiceHealthStore>,
+
+    /// The earliest time at which the mapping service may be called again
+    next_attempt: Instant,
+}
+
+impl CircuitBreaker {
+    /// Creates a new circuit breaker which allows the mapping service to be called immediately
+    ///
+    /// # Arguments
+    /// - `config`: the cartographer config
+    /// - `health`: the shared mapping service health
+    pub fn new(config: &CartographerConfig, health: Arc<MappingServiceHealthStore>) -> Self {
+        Self {
+            poll_interval: Duration::from_millis(config.poll_interval_ms),
+            max_backoff: Duration::from_millis(config.max_backoff_ms),
+            backoff_jitter: config.backoff_jitter,
+            failure_threshold: config.circuit_breaker_failure_threshold,
+            open_duration: Duration::from_millis(config.circuit_breaker_open_duration_ms),
+            health,
+            next_attempt: Instant::now(),
+        }
+    }
+
+    /// Gets the earliest time at which the mapping service may be called again
+    pub fn next_attempt(&self) -> Instant {
+        self.next_attempt
+    }
+
+    /// Checks whether the mapping service may be called now.
+    /// If the circuit is open and its open duration has passed, the circuit becomes half-open.
+    pub fn try_acquire(&mut self) -> bool {
+        if Instant::now() < self.next_attempt {
+            return false;
+        }
+
+        let mut health = self.health.get();
+        if health.circuit_state == CircuitState::Open {
+            info!("Mapping service circuit breaker is half-open; checking whether the mapping service has recovered");
+            health.circuit_state = CircuitState::HalfOpen;
+            self.health.set(health);
+        }
+
+        true
+    }
+
+    /// Records a successful call to the mapping service. This closes the circuit and resets the backoff,
+    /// so the mapping service may be called again immediately.
+    pub fn record_success(&mut self) {
+        let previous_health = self.health.set(MappingServiceHealth::default());
+        if !previous_health.is_healthy() {
+            info!(
+                "Mapping service recovered after {} consecutive failure(s)",
+                previous_health.consecutive_failures
+            );
+        }
+
+        self.next_attempt = Instant::now();
+    }
+
+    /// Records a failed call to the mapping service and schedules the next attempt.
+    /// The circuit opens if the failure threshold is reached or if the circuit was half-open.
+    ///
+    /// # Arguments
+    /// - `error`: a description of the error
+    pub fn record_failure(&mut self, error: String) {
+        let mut health = self.health.get();
+        health.consecutive_failures = health.consecutive_failures.saturating_add(1);
+        health.last_error = Some(error);
+
+        let delay = if health.consecutive_failures >= self.failure_threshold {
+            if health.circuit_state != CircuitState::Open {
+                warn!(
+                    "Opening mapping service circuit breaker after {} consecutive failure(s). The mapping service will not be called for {:?}",
+                    health.consecutive_failures, self.open_duration
+                );
+            }
+
+            health.circuit_state = CircuitState::Open;
+            self.open_duration
+        } else {
+            self.poll_interval
+                .saturating_mul(2u32.saturating_pow(health.consecutive_failures - 1))
+                .min(self.max_backoff)
+        };
+
+        self.next_attempt = Instant::now() + self.apply_jitter(delay);
+        self.health.set(health);
+    }
+
+    /// Randomly varies a delay by up to the configured jitter in either direction
+    ///
+    /// # Arguments
+    /// - `delay`: the delay to vary
+    fn apply_jitter(&self, delay: Duration) -> Duration {
+        if self.backoff_jitter <= 0.0 {
+            return delay;
+        }
+
+        let factor = 1.0 + rand::thread_rng().gen_range(-self.backoff_jitter..=self.backoff_jitter);
+        delay.mul_f64(factor.max(0.0))
+    }
+}
+
+#[cfg(test)]
+mod circuit_breaker_tests {
+    use super::*;
+
+    fn test_config(backoff_jitter: f64) -> CartographerConfig {
+        CartographerConfig {
+            poll_interval_ms: 100,
+            max_backoff_ms: 300,
+            backoff_jitter,
+            circuit_breaker_failure_threshold: 4,
+            circuit_breaker_open_duration_ms: 1000,
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn failures_back_off_exponentially_and_open_the_circuit() {
+        let health = Arc::new(MappingServiceHealthStore::new());
+        let mut uut = CircuitBreaker::new(&test_config(0.0), health.clone());
+
+        assert!(uut.try_acquire());
+
+        let start = Instant::now();
+        let mut delays = Vec::new();
+        for _ in 0..4 {
+            uut.record_failure("error".to_string());
+            delays.push(uut.next_attempt() - start);
+        }
+
+        assert_eq!(
+            delays,
+            [100, 200, 300, 1000].map(Duration::from_millis).to_vec()
+        );
+        assert!(!uut.try_acquire());
+
+        let current_health = health.get();
+        assert_eq!(current_health.circuit_state, CircuitState::Open);
+        assert_eq!(current_health.consecutive_failures, 4);
+        assert_eq!(current_health.last_error, Some("error".to_string()));
+
+        // Once the circuit has been open long enough, a single call is allowed
+        tokio::time::sleep(Duration::from_millis(1000)).await;
+        assert!(uut.try_acquire());
+        assert_eq!(health.get().circuit_state, CircuitState::HalfOpen);
+
+        // A failed call while half-open opens the circuit again
+        uut.record_failure("error".to_string());
+        assert_eq!(health.get().circuit_state, CircuitState::Open);
+        assert_eq!(
+            uut.next_attempt() - Instant::now(),
+            Duration::from_millis(1000)
+        );
+
+        tokio::time::sleep(Duration::from_millis(1000)).await;
+        assert!(uut.try_acquire());
+        uut.record_success();
+
+        assert_eq!(health.get(), MappingServiceHealth::default());
+        assert!(uut.try_acquire());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn jitter_stays_within_configured_range() {
+        let mut uut = CircuitBreaker::new(
+            &test_config(0.5),
+            Arc::new(MappingServiceHealthStore::new()),
+        );
+
+        for _ in 0..20 {
+            uut.record_failure("error".to_string());
+            let delay = uut.next_attempt() - Instant::now();
+            uut.record_success();
+
+            assert!(delay >= Duration::from_millis(50));
+            assert!(delay <= Duration::from_millis(150));
+        }
+    }
+}
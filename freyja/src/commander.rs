@@ -0,0 +1,657 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+// SPDX-License-Identifier: MIT
+
+use std::{sync::Arc, time::Duration};
+
+use futures::{future::BoxFuture, stream::FuturesUnordered, StreamExt};
+use log::{info, warn};
+use tokio::sync::Mutex;
+
+use freyja_common::signal_store::SignalStore;
+use freyja_contracts::{
+    cloud_adapter::{
+        CloudAdapter, CloudAdapterError, CloudCommandAction, CloudCommandOutcome,
+        CloudCommandRequest, CloudCommandResponse,
+    },
+    provider_proxy_selector::ProviderProxySelector,
+    value::Value,
+};
+
+const RECEIVE_RETRY_INTERVAL_MS: u64 = 1000;
+const MAX_CONCURRENT_COMMANDS: usize = 16;
+
+/// Handles commands from the cloud by routing them to the providers of the signals they target
+pub struct Commander<TCloudAdapter, TProviderProxySelector> {
+    /// The shared signal store
+    signals: Arc<SignalStore>,
+
+    /// The cloud adapter used to receive commands and send their responses
+    cloud_adapter: TCloudAdapter,
+
+    /// The provider proxy selector
+    provider_proxy_selector: Arc<Mutex<TProviderProxySelector>>,
+}
+
+impl<TCloudAdapter: CloudAdapter, TProviderProxySelector: ProviderProxySelector>
+    Commander<TCloudAdapter, TProviderProxySelector>
+{
+    /// Creates a new instance of the Commander
+    ///
+    /// # Arguments
+    /// - `signals`: the shared signal store
+    /// - `cloud_adapter`: the cloud adapter used to receive commands and send their responses
+    /// - `provider_proxy_selector`: the provider proxy selector
+    pub fn new(
+        signals: Arc<SignalStore>,
+        cloud_adapter: TCloudAdapter,
+        provider_proxy_selector: Arc<Mutex<TProviderProxySelector>>,
+    ) -> Self {
+        Self {
+            signals,
+            cloud_adapter,
+            provider_proxy_selector,
+        }
+    }
+
+    /// Runs the commander, which handles commands from the cloud until the cloud adapter stops receiving them.
+    /// Cloud adapters which don't support commands stop immediately.
+    /// Up to `MAX_CONCURRENT_COMMANDS` commands are handled at once, and the response to each command is sent as soon as it completes.
+    pub async fn run(&self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let mut in_flight = FuturesUnordered::new();
+        let mut receiving = true;
+        let mut receive: BoxFuture<'_, Result<Option<CloudCommandRequest>, CloudAdapterError>> =
+            self.cloud_adapter.receive_command();
+
+        loop {
+            if !receiving && in_flight.is_empty() {
+                return Ok(());
+            }
+
+            tokio::select! {
+                result = &mut receive, if receiving && in_flight.len() < MAX_CONCURRENT_COMMANDS => {
+                    match result {
+                        Ok(Some(command)) => {
+                            in_flight.push(self.handle_and_respond(command));
+                            receive = self.cloud_adapter.receive_command();
+                        }
+                        Ok(None) => {
+                            info!(
+                                "The cloud adapter is not receiving commands, so the commander is stopping"
+                            );
+                            receiving = false;
+                        }
+                        Err(e) => {
+                            warn!("Failed to receive a command from the cloud; will try again later. Error: {e:?}");
+                            let cloud_adapter = &self.cloud_adapter;
+                            receive = Box::pin(async move {
+                                tokio::time::sleep(Duration::from_millis(RECEIVE_RETRY_INTERVAL_MS)).await;
+                                cloud_adapter.receive_command().await
+                            });
+                        }
+                    }
+                }
+                Some(()) = in_flight.next(), if !in_flight.is_empty() => {}
+            }
+        }
+    }
+
+    /// Handles a command and sends its response to the cloud
+    ///
+    /// # Arguments
+    /// - `command`: the command to handle
+    async fn handle_and_respond(&self, command: CloudCommandRequest) {
+        let response = self.handle_command(command).await;
+        if let Err(e) = self.cloud_adapter.send_command_response(response).await {
+            warn!("Failed to send a command response to the cloud: {e:?}");
+        }
+    }
+
+    /// Handles a command by routing it to the provider of the signal that it targets
+    ///
+    /// # Arguments
+    /// - `command`: the command to handle
+    async fn handle_command(&self, command: CloudCommandRequest) -> CloudCommandResponse {
+        let CloudCommandRequest {
+            command_id,
+            cloud_signal,
+            action,
+        } = command;
+
+        info!("Received the command {command_id} from the cloud");
+
+        // Commands identify signals the same way that emitted data does, by the signal's target metadata
+        let outcome = match self
+            .signals
+            .get_all()
+            .into_iter()
+            .find(|signal| signal.target.metadata == cloud_signal)
+        {
+            Some(signal) => match action {
+                CloudCommandAction::SetValue { value } => match signal.reverse_conversion() {
+                    Some(conversion) => match conversion.apply(&value) {
+                        Ok(value) => self.set_value(&signal.source.id, value).await,
+                        Err(e) => CloudCommandOutcome::Failed {
+                            reason: format!(
+                                "Unable to convert {value} for signal {}: {e:?}",
+                                signal.id
+                            ),
+                        },
+                    },
+                    None => CloudCommandOutcome::Failed {
+                        reason: format!(
+                            "Unable to convert {value} for signal {}: its conversion has no exact inverse and no reverse conversion is mapped",
+                            signal.id
+                        ),
+                    },
+                },
+                CloudCommandAction::Invoke { payload } => {
+                    self.invoke(&signal.source.id, payload).await
+                }
+            },
+            None => CloudCommandOutcome::Failed {
+                reason: format!("No signal is mapped to {cloud_signal:?}"),
+            },
+        };
+
+        if let CloudCommandOutcome::Failed { reason } = &outcome {
+            warn!("The command {command_id} failed: {reason}");
+        }
+
+        CloudCommandResponse {
+            command_id,
+            outcome,
+        }
+    }
+
+    /// Sets the value of an entity.
+    /// The provider proxy selector is released while waiting for the provider to set the value.
+    ///
+    /// # Arguments
+    /// - `entity_id`: the entity to set the value of
+    /// - `value`: the value to set, in the provider's units
+    async fn set_value(&self, entity_id: &str, value: Value) -> CloudCommandOutcome {
+        let set_result = self
+            .provider_proxy_selector
+            .lock()
+            .await
+            .set_entity_value(entity_id, value)
+            .await;
+
+        let set_value_result = match set_result {
+            Ok(set_value_future) => set_value_future.await,
+            Err(e) => Err(e),
+        };
+
+        match set_value_result {
+            Ok(()) => CloudCommandOutcome::Succeeded { response: None },
+            Err(e) => CloudCommandOutcome::Failed {
+                reason: format!("Unable to set the value of {entity_id}: {e:?}"),
+            },
+        }
+    }
+
+    /// Invokes a command on an entity.
+    /// The provider proxy selector is released while waiting for the provider's response.
+    ///
+    /// # Arguments
+    /// - `entity_id`: the entity to invoke
+    /// - `payload`: the payload of the command
+    async fn invoke(&self, entity_id: &str, payload: Value) -> CloudCommandOutcome {
+        let invoke_result = self
+            .provider_proxy_selector
+            .lock()
+            .await
+            .invoke_entity(entity_id, payload)
+            .await;
+
+        let response_result = match invoke_result {
+            Ok(response_future) => response_future.await,
+            Err(e) => Err(e),
+        };
+
+        match response_result {
+            Ok(response) => CloudCommandOutcome::Succeeded {
+                response: Some(response),
+            },
+            Err(e) => CloudCommandOutcome::Failed {
+                reason: format!("Unable to invoke {entity_id}: {e:?}"),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod commander_tests {
+    use super::*;
+
+    use std::collections::HashMap;
+
+    use async_trait::async_trait;
+    use mockall::{
+        predicate::{eq, function},
+        *,
+    };
+
+    use freyja_contracts::{
+        cloud_adapter::{CloudAdapterError, CloudMessageRequest, CloudMessageResponse},
+        conversion::Conversion,
+        entity::Entity,
+        provider_proxy_selector::{
+            InvokeResponseFuture, ProviderProxySelectorError, SetValueFuture,
+        },
+        signal::{EmissionPolicy, SignalPatch, Target},
+    };
+
+    const SIGNAL_ID: &str = "dtmi:sdv:Vehicle:Cabin:HVAC:AmbientAirTemperature;1";
+
+    mock! {
+        pub CloudAdapter {}
+
+        #[async_trait]
+        impl CloudAdapter for CloudAdapter {
+            fn create_new() -> Result<Self, CloudAdapterError>;
+            async fn send_to_cloud(&self, cloud_message: CloudMessageRequest) -> Result<CloudMessageResponse, CloudAdapterError>;
+        }
+    }
+
+    mock! {
+        pub ProviderProxySelector {}
+
+        #[async_trait]
+        impl ProviderProxySelector for ProviderProxySelector {
+            async fn create_or_update_proxy(&mut self, entity: &Entity) -> Result<(), ProviderProxySelectorError>;
+            async fn remove_entity(&mut self, entity_id: &str) -> Result<(), ProviderProxySelectorError>;
+            async fn request_entity_value(&mut self, entity_id: &str) -> Result<(), ProviderProxySelectorError>;
+            async fn set_entity_value(&mut self, entity_id: &str, value: Value) -> Result<SetValueFuture, ProviderProxySelectorError>;
+            async fn invoke_entity(&mut self, entity_id: &str, payload: Value) -> Result<InvokeResponseFuture, ProviderProxySelectorError>;
+        }
+    }
+
+    /// Creates a signal store with a signal that is emitted in Fahrenheit from a provider which uses Celsius
+    fn create_signal_store() -> Arc<SignalStore> {
+        create_signal_store_with_conversion(Conversion::c_to_f())
+    }
+
+    /// Creates a signal store with a signal that is emitted with the specified conversion
+    ///
+    /// # Arguments
+    /// - `conversion`: the conversion applied to the signal's values before they are emitted
+    fn create_signal_store_with_conversion(conversion: Conversion) -> Arc<SignalStore> {
+        let signals = Arc::new(SignalStore::new());
+        signals.sync(
+            [SignalPatch {
+                id: String::from(SIGNAL_ID),
+                source: Entity {
+                    id: String::from(SIGNAL_ID),
+                    uri: String::from("in-memory"),
+                    name: None,
+                    description: None,
+                    operation: String::from("Get"),
+                    protocol: String::from("in-memory"),
+                },
+                target: Target {
+                    metadata: cloud_signal(),
+                    reverse_conversion: None,
+                },
+                emission_policy: EmissionPolicy {
+                    interval_ms: 1000,
+                    conversion,
+                    ..Default::default()
+                },
+            }]
+            .into_iter(),
+        );
+
+        signals
+    }
+
+    /// The metadata which identifies the test signal in the cloud
+    fn cloud_signal() -> HashMap<String, String> {
+        [(String::from("property"), String::from("temperature"))]
+            .into_iter()
+            .collect()
+    }
+
+    /// Checks whether a value is 100, allowing for floating-point errors from the reverse conversion
+    fn is_close_to_100(value: &Value) -> bool {
+        value
+            .as_f64()
+            .is_some_and(|value| (value - 100.0).abs() < 1e-9)
+    }
+
+    fn command(
+        cloud_signal: HashMap<String, String>,
+        action: CloudCommandAction,
+    ) -> CloudCommandRequest {
+        CloudCommandRequest {
+            command_id: String::from("command"),
+            cloud_signal,
+            action,
+        }
+    }
+
+    #[tokio::test]
+    async fn handle_command_sets_value_with_reverse_conversion() {
+        let mut mock_provider_proxy_selector = MockProviderProxySelector::new();
+        mock_provider_proxy_selector
+            .expect_set_entity_value()
+            .with(eq(SIGNAL_ID), function(is_close_to_100))
+            .once()
+            .returning(|_, _| Ok(Box::pin(async { Ok(()) })));
+
+        let uut = Commander {
+            signals: create_signal_store(),
+            cloud_adapter: MockCloudAdapter::new(),
+            provider_proxy_selector: Arc::new(Mutex::new(mock_provider_proxy_selector)),
+        };
+
+        let response = uut
+            .handle_command(command(
+                cloud_signal(),
+                CloudCommandAction::SetValue {
+                    value: Value::Float(212.0),
+                },
+            ))
+            .await;
+
+        assert_eq!(response.command_id, "command");
+        assert_eq!(
+            response.outcome,
+            CloudCommandOutcome::Succeeded { response: None }
+        );
+    }
+
+    #[tokio::test]
+    async fn handle_command_returns_invocation_response() {
+        let mut mock_provider_proxy_selector = MockProviderProxySelector::new();
+        mock_provider_proxy_selector
+            .expect_invoke_entity()
+            .with(eq(SIGNAL_ID), eq(Value::from("ping")))
+            .once()
+            .returning(|_, _| Ok(Box::pin(async { Ok(Value::from("pong")) })));
+
+        let uut = Commander {
+            signals: create_signal_store(),
+            cloud_adapter: MockCloudAdapter::new(),
+            provider_proxy_selector: Arc::new(Mutex::new(mock_provider_proxy_selector)),
+        };
+
+        let response = uut
+            .handle_command(command(
+                cloud_signal(),
+                CloudCommandAction::Invoke {
+                    payload: Value::from("ping"),
+                },
+            ))
+            .await;
+
+        assert_eq!(
+            response.outcome,
+            CloudCommandOutcome::Succeeded {
+                response: Some(Value::from("pong"))
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn handle_command_fails_for_unmapped_or_unconvertible_values() {
+        let mut mock_provider_proxy_selector = MockProviderProxySelector::new();
+        mock_provider_proxy_selector
+            .expect_set_entity_value()
+            .never();
+
+        let uut = Commander {
+            signals: create_signal_store(),
+            cloud_adapter: MockCloudAdapter::new(),
+            provider_proxy_selector: Arc::new(Mutex::new(mock_provider_proxy_selector)),
+        };
+
+        let response = uut
+            .handle_command(command(
+                HashMap::new(),
+                CloudCommandAction::SetValue {
+                    value: Value::Float(212.0),
+                },
+            ))
+            .await;
+        assert!(matches!(
+            response.outcome,
+            CloudCommandOutcome::Failed { .. }
+        ));
+
+        // The inverse of a linear conversion requires a numeric value
+        let response = uut
+            .handle_command(command(
+                cloud_signal(),
+                CloudCommandAction::SetValue {
+                    value: Value::from("hot"),
+                },
+            ))
+            .await;
+        assert!(matches!(
+            response.outcome,
+            CloudCommandOutcome::Failed { .. }
+        ));
+    }
+
+    #[tokio::test]
+    async fn handle_command_fails_to_set_value_without_exact_inverse() {
+        let mut mock_provider_proxy_selector = MockProviderProxySelector::new();
+        mock_provider_proxy_selector
+            .expect_set_entity_value()
+            .never();
+
+        let uut = Commander {
+            signals: create_signal_store_with_conversion(Conversion::Round { decimals: 1 }),
+            cloud_adapter: MockCloudAdapter::new(),
+            provider_proxy_selector: Arc::new(Mutex::new(mock_provider_proxy_selector)),
+        };
+
+        let response = uut
+            .handle_command(command(
+                cloud_signal(),
+                CloudCommandAction::SetValue {
+                    value: Value::Float(21.5),
+                },
+            ))
+            .await;
+
+        match response.outcome {
+            CloudCommandOutcome::Failed { reason } => assert!(reason.contains("no exact inverse")),
+            outcome => panic!("Expected the command to fail, but got {outcome:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn run_stops_when_cloud_adapter_does_not_receive_commands() {
+        let uut = Commander {
+            signals: create_signal_store(),
+            cloud_adapter: MockCloudAdapter::new(),
+            provider_proxy_selector: Arc::new(Mutex::new(MockProviderProxySelector::new())),
+        };
+
+        assert!(uut.run().await.is_ok());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn run_sends_responses_as_commands_complete() {
+        use in_memory_mock_cloud_adapter::in_memory_mock_cloud_adapter::InMemoryMockCloudAdapter;
+
+        // The first invocation takes much longer than the second
+        let mut mock_provider_proxy_selector = MockProviderProxySelector::new();
+        mock_provider_proxy_selector
+            .expect_invoke_entity()
+            .times(2)
+            .returning(|_, payload| {
+                Ok(Box::pin(async move {
+                    if payload == Value::from("slow") {
+                        tokio::time::sleep(Duration::from_secs(60)).await;
+                    }
+
+                    Ok(payload)
+                }))
+            });
+
+        let cloud_adapter = Arc::new(InMemoryMockCloudAdapter::create_new().unwrap());
+        let uut = Commander::new(
+            create_signal_store(),
+            cloud_adapter.clone(),
+            Arc::new(Mutex::new(mock_provider_proxy_selector)),
+        );
+        let commander = tokio::spawn(async move {
+            let _ = uut.run().await;
+        });
+
+        for payload in ["slow", "fast"] {
+            cloud_adapter.send_command(CloudCommandRequest {
+                command_id: String::from(payload),
+                cloud_signal: cloud_signal(),
+                action: CloudCommandAction::Invoke {
+                    payload: Value::from(payload),
+                },
+            });
+        }
+
+        // The fast command completes while the slow one is still running
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        let command_ids = |responses: Vec<CloudCommandResponse>| {
+            responses
+                .into_iter()
+                .map(|response| response.command_id)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(command_ids(cloud_adapter.command_responses()), ["fast"]);
+
+        tokio::time::sleep(Duration::from_secs(60)).await;
+        commander.abort();
+        assert_eq!(
+            command_ids(cloud_adapter.command_responses()),
+            ["fast", "slow"]
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn slow_set_value_does_not_block_invoke() {
+        use in_memory_mock_cloud_adapter::in_memory_mock_cloud_adapter::InMemoryMockCloudAdapter;
+
+        // Setting the value takes much longer than the invocation
+        let mut mock_provider_proxy_selector = MockProviderProxySelector::new();
+        mock_provider_proxy_selector
+            .expect_set_entity_value()
+            .once()
+            .returning(|_, _| {
+                Ok(Box::pin(async {
+                    tokio::time::sleep(Duration::from_secs(60)).await;
+                    Ok(())
+                }))
+            });
+        mock_provider_proxy_selector
+            .expect_invoke_entity()
+            .once()
+            .returning(|_, payload| Ok(Box::pin(async move { Ok(payload) })));
+
+        let cloud_adapter = Arc::new(InMemoryMockCloudAdapter::create_new().unwrap());
+        let uut = Commander::new(
+            create_signal_store(),
+            cloud_adapter.clone(),
+            Arc::new(Mutex::new(mock_provider_proxy_selector)),
+        );
+        let commander = tokio::spawn(async move {
+            let _ = uut.run().await;
+        });
+
+        cloud_adapter.send_command(CloudCommandRequest {
+            command_id: String::from("set"),
+            cloud_signal: cloud_signal(),
+            action: CloudCommandAction::SetValue {
+                value: Value::Float(212.0),
+            },
+        });
+        cloud_adapter.send_command(CloudCommandRequest {
+            command_id: String::from("invoke"),
+            cloud_signal: cloud_signal(),
+            action: CloudCommandAction::Invoke {
+                payload: Value::from("ping"),
+            },
+        });
+
+        // The invocation completes while the provider is still setting the value
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        let responses = cloud_adapter.command_responses();
+        assert_eq!(responses.len(), 1);
+        assert_eq!(responses[0].command_id, "invoke");
+
+        tokio::time::sleep(Duration::from_secs(60)).await;
+        commander.abort();
+        let responses = cloud_adapter.command_responses();
+        assert_eq!(responses.len(), 2);
+        assert_eq!(responses[1].command_id, "set");
+        assert_eq!(
+            responses[1].outcome,
+            CloudCommandOutcome::Succeeded { response: None }
+        );
+    }
+
+    #[cfg(feature = "in-memory-mock-provider-proxy")]
+    #[tokio::test]
+    async fn commands_from_in_memory_cloud_adapter_reach_in_memory_provider() {
+        use in_memory_mock_cloud_adapter::in_memory_mock_cloud_adapter::InMemoryMockCloudAdapter;
+        use provider_proxy_selector::provider_proxy_selector_impl::ProviderProxySelectorImpl;
+        use tokio::sync::mpsc::unbounded_channel;
+
+        let signals = create_signal_store();
+        let (signal_values_sender, mut signal_values_receiver) = unbounded_channel();
+        let provider_proxy_selector = Arc::new(Mutex::new(ProviderProxySelectorImpl::new(
+            signal_values_sender,
+        )));
+        let source = signals.get(&String::from(SIGNAL_ID)).unwrap().source;
+        assert!(provider_proxy_selector
+            .lock()
+            .await
+            .create_or_update_proxy(&source)
+            .await
+            .is_ok());
+
+        let cloud_adapter = Arc::new(InMemoryMockCloudAdapter::create_new().unwrap());
+        let uut = Commander::new(
+            signals,
+            cloud_adapter.clone(),
+            provider_proxy_selector.clone(),
+        );
+        let commander = tokio::spawn(async move {
+            let _ = uut.run().await;
+        });
+
+        cloud_adapter.send_command(command(
+            cloud_signal(),
+            CloudCommandAction::SetValue {
+                value: Value::Float(212.0),
+            },
+        ));
+        tokio::time::timeout(Duration::from_secs(10), async {
+            while cloud_adapter.command_responses().is_empty() {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+        commander.abort();
+
+        assert_eq!(
+            cloud_adapter.command_responses()[0].outcome,
+            CloudCommandOutcome::Succeeded { response: None }
+        );
+
+        // The provider now reports the value that was set, in its own units
+        assert!(provider_proxy_selector
+            .lock()
+            .await
+            .request_entity_value(SIGNAL_ID)
+            .await
+            .is_ok());
+        let signal_value = signal_values_receiver.recv().await.unwrap();
+        assert_eq!(signal_value.entity_id, SIGNAL_ID);
+        assert!(is_close_to_100(&signal_value.value));
+    }
+}
@@ -0,0 +1,184 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+// SPDX-License-Identifier: MIT
+
+use serde::{Deserialize, Serialize};
+
+/// Config for the Freyja core components
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Config {
+    /// Config for the cartographer
+    pub cartographer: CartographerConfig,
+
+    /// Config for the emitter's offline buffer
+    pub offline_buffer: OfflineBufferConfig,
+
+    /// Config for the cartographer's mapping cache
+    pub mapping_cache: MappingCacheConfig,
+
+    /// Config for the identity of the vehicle
+    pub vehicle: VehicleConfig,
+
+    /// Config for validating mappings before they are applied
+    pub mapping_validation: MappingValidationConfig,
+
+    /// Config for the status server
+    pub status_server: StatusServerConfig,
+}
+
+impl Config {
+    /// Checks that the values in the config are within their allowed ranges
+    pub fn validate(&self) -> Result<(), String> {
+        let backoff_jitter = self.cartographer.backoff_jitter;
+        if !(0.0..=1.0).contains(&backoff_jitter) {
+            return Err(format!(
+                "Invalid Freyja config: cartographer.backoff_jitter must be between 0 and 1, but was {backoff_jitter}"
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+/// Config for the cartographer
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CartographerConfig {
+    /// The interval at which the mapping service is polled, in milliseconds.
+    /// This is also the initial backoff after a failed call to the mapping service.
+    pub poll_interval_ms: u64,
+
+    /// The maximum backoff after failed calls to the mapping service, in milliseconds
+    pub max_backoff_ms: u64,
+
+    /// The amount of random jitter to apply to backoffs, as a fraction of the backoff between 0 and 1.
+    /// For example, `0.2` varies each backoff by up to 20% in either direction.
+    pub backoff_jitter: f64,
+
+    /// The number of consecutive failed calls after which the circuit breaker opens
+    pub circuit_breaker_failure_threshold: u32,
+
+    /// The time that the circuit breaker stays open before the mapping service is called again, in milliseconds
+    pub circuit_breaker_open_duration_ms: u64,
+}
+
+/// Config for the emitter's offline buffer
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct OfflineBufferConfig {
+    /// Whether messages which could not be sent to the cloud should be buffered
+    pub enabled: bool,
+
+    /// The directory to store buffered messages in.
+    /// If this is not set, the buffer is stored in `$FREYJA_HOME/state/offline_buffer`.
+    pub directory: Option<String>,
+
+    /// The maximum number of buffered messages
+    pub max_messages: usize,
+
+    /// The maximum total size of the buffered messages in bytes
+    pub max_size_bytes: u64,
+
+    /// The time in seconds after which buffered messages are discarded.
+    /// If this is not set, messages never expire.
+    pub ttl_s: Option<u64>,
+
+    /// Determines which messages are dropped when the buffer is full
+    pub drop_policy: DropPolicy,
+
+    /// The priorities of signals for the `lowest_priority` drop policy.
+    /// Signals which are not listed have a priority of 0.
+    pub signal_priorities: Vec<SignalPriority>,
+}
+
+/// Config for the cartographer's mapping cache
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MappingCacheConfig {
+    /// Whether the last applied mapping should be persisted and restored on startup
+    pub enabled: bool,
+
+    /// The directory to store the mapping in.
+    /// If this is not set, the mapping is stored in `$FREYJA_HOME/state/mapping_cache`.
+    pub directory: Option<String>,
+}
+
+/// Config for validating mappings before they are applied
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MappingValidationConfig {
+    /// The minimum emission interval of a mapping entry, in milliseconds
+    pub min_interval_ms: u64,
+
+    /// The maximum emission interval of a mapping entry, in milliseconds
+    pub max_interval_ms: u64,
+}
+
+/// Config for the status server, which serves the status of Freyja's components over HTTP
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct StatusServerConfig {
+    /// Whether the status server should be run
+    pub enabled: bool,
+
+    /// The address that the status server listens on
+    pub address: String,
+}
+
+/// Config for the identity of the vehicle, which is sent to the mapping service
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct VehicleConfig {
+    /// The identity of the vehicle, such as its VIN or a device id
+    pub vehicle_id: Option<String>,
+}
+
+/// Determines which messages are dropped when the offline buffer is full
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DropPolicy {
+    /// Drop the oldest message
+    OldestFirst,
+
+    /// Drop the oldest message with the lowest priority.
+    /// If the incoming message has a lower priority than every buffered message, it is dropped instead.
+    LowestPriority,
+}
+
+/// The priority of a signal in the offline buffer
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SignalPriority {
+    /// The signal id
+    pub signal_id: String,
+
+    /// The priority of the signal. Higher values are more important.
+    pub priority: u32,
+}
+
+#[cfg(test)]
+mod config_tests {
+    use super::*;
+
+    #[test]
+    fn default_config_is_valid() {
+        let config: Config =
+            serde_json::from_str(include_str!("../res/freyja_config.default.json")).unwrap();
+
+        assert!(config.offline_buffer.enabled);
+        assert_eq!(config.offline_buffer.drop_policy, DropPolicy::OldestFirst);
+        assert!(config.mapping_cache.enabled);
+        assert_eq!(config.cartographer.poll_interval_ms, 5000);
+        assert!(config.status_server.enabled);
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn validate_rejects_backoff_jitter_outside_of_range() {
+        let mut config: Config =
+            serde_json::from_str(include_str!("../res/freyja_config.default.json")).unwrap();
+
+        for backoff_jitter in [0.0, 0.5, 1.0] {
+            config.cartographer.backoff_jitter = backoff_jitter;
+            assert!(config.validate().is_ok());
+        }
+
+        for backoff_jitter in [-0.1, 1.1, f64::NAN, f64::INFINITY] {
+            config.cartographer.backoff_jitter = backoff_jitter;
+            assert!(config.validate().is_err());
+        }
+    }
+}
@@ -2,22 +2,31 @@
 // Licensed under the MIT license.
 // SPDX-License-Identifier: MIT
 
-use std::{cmp::min, sync::Arc, time::Duration};
+use std::{
+    cmp::min,
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
 
-use crossbeam::queue::SegQueue;
 use log::{info, warn};
 use time::OffsetDateTime;
-use tokio::{sync::Mutex, time::sleep};
+use tokio::{
+    sync::{mpsc::UnboundedReceiver, Mutex},
+    time::{sleep_until, Instant},
+};
 
+use crate::offline_buffer::OfflineBuffer;
 use freyja_common::signal_store::SignalStore;
 use freyja_contracts::{
-    cloud_adapter::{CloudAdapter, CloudMessageRequest, CloudMessageResponse},
+    cloud_adapter::{CloudAdapter, CloudAdapterError, CloudMessageRequest, CloudMessageResponse},
     provider_proxy::SignalValue,
     provider_proxy_selector::ProviderProxySelector,
-    signal::Signal,
+    signal::{EmissionMode, Signal},
+    value::Value,
 };
 
 const DEFAULT_SLEEP_INTERVAL_MS: u64 = 1000;
+const OFFLINE_BUFFER_FLUSH_BATCH_SIZE: usize = 100;
 
 /// Emits sensor data at regular intervals as configured in the store
 pub struct Emitter<TCloudAdapter, TProviderProxySelector> {
@@ -30,8 +39,21 @@ pub struct Emitter<TCloudAdapter, TProviderProxySelector> {
     /// The provider proxy selector
     provider_proxy_selector: Arc<Mutex<TProviderProxySelector>>,
 
-    /// Shared message queue for obtaining new signal values
-    signal_values_queue: Arc<SegQueue<SignalValue>>,
+    /// Receives new signal values from the provider proxies
+    signal_values_receiver: Mutex<UnboundedReceiver<SignalValue>>,
+
+    /// The buffer for messages which could not be sent to the cloud, if enabled
+    offline_buffer: Option<OfflineBuffer>,
+}
+
+/// The outcome of an emission
+#[derive(Debug)]
+enum EmissionResult {
+    /// The message was sent to the cloud
+    Sent(CloudMessageResponse),
+
+    /// The message was stored in the offline buffer to be sent later
+    Buffered,
 }
 
 impl<TCloudAdapter: CloudAdapter, TProviderProxySelector: ProviderProxySelector>
@@ -43,80 +65,160 @@ impl<TCloudAdapter: CloudAdapter, TProviderProxySelector: ProviderProxySelector>
     /// - `signals`: the shared signal store
     /// - `cloud_adapter`: the cloud adapter used to emit to the cloud
     /// - `provider_proxy_selector`: the provider proxy selector
-    /// - `signal_values_queue`: queue for receiving signal values
+    /// - `signal_values_receiver`: channel for receiving signal values
+    /// - `offline_buffer`: the buffer for messages which could not be sent to the cloud, or `None` to disable buffering
     pub fn new(
         signals: Arc<SignalStore>,
         cloud_adapter: TCloudAdapter,
         provider_proxy_selector: Arc<Mutex<TProviderProxySelector>>,
-        signal_values_queue: Arc<SegQueue<SignalValue>>,
+        signal_values_receiver: UnboundedReceiver<SignalValue>,
+        offline_buffer: Option<OfflineBuffer>,
     ) -> Self {
         Self {
             signals,
             cloud_adapter,
             provider_proxy_selector,
-            signal_values_queue,
+            signal_values_receiver: Mutex::new(signal_values_receiver),
+            offline_buffer,
         }
     }
 
     /// Execute this Emitter
     pub async fn run(&self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-        let mut sleep_interval = u64::MAX;
-        loop {
-            self.update_signal_values();
+        let mut signal_values_receiver = self.signal_values_receiver.lock().await;
 
-            // Update the emission times and get the list of all signals.
-            // This is performed as a single operation to minimize the impact of changes to the signal set during processing.
-            // Note that the first time the loop is executed sleep_interval will still be u64::MAX,
-            // which will have the effect of force-emitting every signal in the store (though typically there won't be anything).
-            // After that, the intervals will be no more than the max configured interval.
-            let signals = self
-                .signals
-                .update_emission_times_and_get_all(sleep_interval);
+        loop {
+            while let Ok(signal_value) = signal_values_receiver.try_recv() {
+                self.update_signal_value(signal_value);
+            }
 
-            sleep_interval = self.emit_data(signals).await?;
+            // Signals which have never been scheduled have no deadline and are emitted right away
+            let now = Instant::now();
+            let next_wakeup = self.emit_data(self.signals.get_all(), now).await?;
+
+            info!(
+                "Checking for next emission in {}ms\n",
+                next_wakeup.saturating_duration_since(now).as_millis()
+            );
+
+            // Wait for the next emission, waking up early if a changed value arrives for a signal which is emitted on change
+            let timer = sleep_until(next_wakeup);
+            tokio::pin!(timer);
+            loop {
+                tokio::select! {
+                    _ = &mut timer => break,
+                    Some(signal_value) = signal_values_receiver.recv() => {
+                        if let Some(deadline) = self.update_signal_value(signal_value) {
+                            if deadline < timer.deadline() {
+                                timer.as_mut().reset(deadline);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
 
-            info!("Checking for next emission in {sleep_interval}ms\n");
-            sleep(Duration::from_millis(sleep_interval)).await;
+    /// Updates the value of a signal in the store.
+    /// Returns the time at which the signal should be emitted because of the new value,
+    /// or `None` if the new value doesn't require an emission before the signal's next interval.
+    ///
+    /// # Arguments
+    /// - `signal_value`: the new value
+    fn update_signal_value(&self, signal_value: SignalValue) -> Option<Instant> {
+        let SignalValue { entity_id, value } = signal_value;
+        if self.signals.set_value(entity_id.clone(), value).is_none() {
+            warn!("Attempted to update signal {entity_id} but it wasn't found");
+            return None;
         }
+
+        let now = Instant::now();
+        let signal = self.signals.get(&entity_id)?;
+        Self::change_emission_delay(&signal, now).map(|delay| now + delay)
     }
 
-    /// Updates the signal values map.
-    /// This will eventually get removed and provider proxies will update the store directly,
-    /// but it remains temporarily to scope work down a bit.
-    fn update_signal_values(&self) {
-        while !self.signal_values_queue.is_empty() {
-            let SignalValue { entity_id, value } = self.signal_values_queue.pop().unwrap();
-            if self.signals.set_value(entity_id.clone(), value).is_none() {
-                warn!("Attempted to update signal {entity_id} but it wasn't found")
-            }
+    /// Gets the time until a signal which is emitted on change should be emitted because its value changed.
+    /// Returns `None` if the signal isn't emitted on change or its value hasn't changed since it was last emitted.
+    ///
+    /// # Arguments
+    /// - `signal`: the signal to check
+    /// - `now`: the current time
+    fn change_emission_delay(signal: &Signal, now: Instant) -> Option<Duration> {
+        let min_interval_ms = match signal.emission.policy.mode {
+            EmissionMode::OnChange { min_interval_ms } => min_interval_ms,
+            EmissionMode::Interval => return None,
+        };
+
+        if signal.value.is_none() || Self::is_unchanged(signal) {
+            return None;
         }
+
+        let time_since_emission_ms = signal.emission.time_since_emission_ms(now);
+        Some(Duration::from_millis(
+            min_interval_ms.saturating_sub(time_since_emission_ms),
+        ))
     }
 
-    /// Performs data emissions of the provided signals.
-    /// Returns the amount of time that the main emitter loop should sleep before the next iteration.
+    /// Performs data emissions of the provided signals and schedules the next emission of each signal that was due.
+    /// Returns the time at which the main emitter loop should wake up for the next iteration.
     ///
     /// # Arguments
     /// - `signals`: The set of signals to emit
-    async fn emit_data(&self, signals: Vec<Signal>) -> Result<u64, EmitterError> {
+    /// - `now`: The time to consider as the current time when determining which signals are due
+    async fn emit_data(&self, signals: Vec<Signal>, now: Instant) -> Result<Instant, EmitterError> {
+        // Messages from earlier emissions go first so that the cloud receives data in order
+        self.flush_offline_buffer().await;
+
         if signals.is_empty() {
-            Ok(DEFAULT_SLEEP_INTERVAL_MS)
+            Ok(now + Duration::from_millis(DEFAULT_SLEEP_INTERVAL_MS))
         } else {
             info!("********************BEGIN EMISSION********************");
-            let mut sleep_interval = u64::MAX;
+            let since_epoch = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default();
+            let mut next_wakeup: Option<Instant> = None;
+            let mut wake_at = |deadline: Instant| {
+                next_wakeup = Some(next_wakeup.map_or(deadline, |w| min(w, deadline)));
+            };
+            let mut batch = Vec::new();
 
             for signal in signals {
-                if signal.emission.next_emission_ms > 0 {
-                    // Don't emit this signal on this iteration, but use the value to update the sleep interval
-                    sleep_interval = min(sleep_interval, signal.emission.next_emission_ms);
+                if !signal.emission.is_due(now) {
+                    // Don't emit this signal on this iteration, but use its deadline to update the wakeup time.
+                    // The deadline is always present for signals which aren't due.
+                    if let Some(deadline) = signal.emission.next_emission {
+                        wake_at(deadline);
+                    }
+
+                    // Signals which are emitted on change are emitted before their interval elapses if their value changed,
+                    // as long as enough time has passed since their last emission
+                    match Self::change_emission_delay(&signal, now) {
+                        Some(delay) if delay.is_zero() => {
+                            info!(
+                                "Signal {} changed. Emitting before its next interval.",
+                                signal.id
+                            );
+                            batch.push(signal);
+                        }
+                        Some(delay) => wake_at(now + delay),
+                        None => {}
+                    }
 
                     // Go to next signal
                     continue;
-                } else {
-                    // We will emit this signal since the timer is expired,
-                    // but need to also check the new interval in case it's smaller than the remaining intervals
-                    sleep_interval = min(sleep_interval, signal.emission.policy.interval_ms);
                 }
 
+                // The signal is due, so schedule its next emission.
+                // The next deadline is based on the previous one so that the time spent emitting doesn't cause drift.
+                let next_emission = signal.emission.policy.next_emission(
+                    signal.emission.next_emission,
+                    now,
+                    since_epoch,
+                );
+                self.signals
+                    .set_next_emission(signal.id.clone(), next_emission);
+                wake_at(next_emission);
+
                 // Submit a request for a new value for the next iteration.
                 // This approach to requesting signal values introduces an inherent delay in uploading data
                 // of signal.emission.policy.interval_ms and needs to be revisited.
@@ -142,74 +244,271 @@ impl<TCloudAdapter: CloudAdapter, TProviderProxySelector: ProviderProxySelector>
                     continue;
                 }
 
-                if signal.emission.policy.emit_only_if_changed
-                    && signal.emission.last_emitted_value.is_some()
-                    && signal.value == signal.emission.last_emitted_value
-                {
-                    info!("Signal {} did not change and has already been emitted. Skipping emission for this signal.", signal.id);
-
-                    // Go to next signal
-                    continue;
+                if Self::is_unchanged(&signal) {
+                    let time_since_emission_ms = signal.emission.time_since_emission_ms(now);
+                    if signal
+                        .emission
+                        .policy
+                        .is_heartbeat_due(time_since_emission_ms)
+                    {
+                        info!("Signal {} did not change but has not been emitted for {time_since_emission_ms}ms. Emitting heartbeat.", signal.id);
+                    } else {
+                        info!("Signal {} did not change and has already been emitted. Skipping emission for this signal.", signal.id);
+
+                        // Go to next signal
+                        continue;
+                    }
                 }
 
-                let signal_id = signal.id.clone();
-                let send_to_cloud_result = self.send_to_cloud(signal).await;
+                batch.push(signal);
+            }
 
-                if send_to_cloud_result.is_err() {
-                    log::error!(
-                        "Error sending data to cloud while processing signal {}: {:?}",
-                        signal_id,
-                        send_to_cloud_result.err()
-                    );
+            // Due signals are handed to the cloud adapter together to avoid a round trip per signal
+            let signal_ids: Vec<String> = batch.iter().map(|signal| signal.id.clone()).collect();
+            let results = self.send_to_cloud(batch).await;
+
+            for (signal_id, result) in signal_ids.into_iter().zip(results) {
+                match result {
+                    Ok(EmissionResult::Sent(_)) => {}
+                    Ok(EmissionResult::Buffered) => {
+                        info!("Buffered data for signal {signal_id} to be sent later")
+                    }
+                    Err(e) => log::error!(
+                        "Error sending data to cloud while processing signal {signal_id}: {e:?}"
+                    ),
                 }
             }
 
             info!("*********************END EMISSION*********************");
 
-            Ok(sleep_interval)
+            Ok(next_wakeup.unwrap_or(now + Duration::from_millis(DEFAULT_SLEEP_INTERVAL_MS)))
+        }
+    }
+
+    /// Determines whether a signal should be skipped because its value has not changed since it was last emitted.
+    /// Always returns `false` for signals which aren't configured to be emitted only when they change.
+    ///
+    /// # Arguments
+    /// - `signal`: The signal to check
+    fn is_unchanged(signal: &Signal) -> bool {
+        let policy = &signal.emission.policy;
+
+        match (&signal.emission.last_emitted_value, &signal.value) {
+            (Some(last_emitted_value), Some(value)) if policy.is_change_driven() => {
+                !policy.is_changed(last_emitted_value, value)
+            }
+            _ => false,
         }
     }
 
-    /// Applies a conversion implicitly to a signal value and sends it to the cloud
+    /// Sends the messages in the offline buffer to the cloud in batches, oldest first.
+    /// Only the messages before the first failure in a batch are removed from the buffer,
+    /// and flushing stops there so that the remaining messages keep their order.
+    /// Messages after the failure which were accepted anyway are sent again on the next flush.
+    async fn flush_offline_buffer(&self) {
+        let offline_buffer = match &self.offline_buffer {
+            Some(offline_buffer) => offline_buffer,
+            None => return,
+        };
+
+        let mut sent = 0;
+        loop {
+            let (sequences, cloud_messages): (Vec<_>, Vec<_>) = offline_buffer
+                .peek(OFFLINE_BUFFER_FLUSH_BATCH_SIZE)
+                .into_iter()
+                .unzip();
+
+            if sequences.is_empty() {
+                break;
+            }
+
+            let results = self.cloud_adapter.send_batch_to_cloud(cloud_messages).await;
+            let mut failed = results.len() < sequences.len();
+
+            for (sequence, result) in sequences.into_iter().zip(results) {
+                if let Err(e) = result {
+                    warn!("Unable to send buffered data to the cloud, will try again later: {e:?}");
+                    failed = true;
+                    break;
+                }
+
+                match offline_buffer.remove(sequence) {
+                    Ok(_) => sent += 1,
+                    Err(e) => {
+                        // Leaving the message in the buffer would result in it being sent again on every iteration
+                        log::error!("Error removing sent message from the offline buffer: {e:?}");
+                        failed = true;
+                        break;
+                    }
+                }
+            }
+
+            if failed {
+                break;
+            }
+        }
+
+        if sent > 0 {
+            info!("Sent {sent} buffered message(s) to the cloud");
+        }
+    }
+
+    /// Applies a conversion implicitly to the values of a batch of signals and sends them to the cloud.
+    /// Returns one result per signal, in the same order as the signals.
+    /// The last emitted value is only updated for signals whose message was accepted by the cloud adapter
+    /// or stored in the offline buffer.
+    ///
+    /// # Arguments
+    /// - `signals`: The signals to emit
+    async fn send_to_cloud(
+        &self,
+        signals: Vec<Signal>,
+    ) -> Vec<Result<EmissionResult, EmitterError>> {
+        let prepared: Vec<_> = signals.iter().map(Self::create_cloud_message).collect();
+
+        let cloud_messages = prepared
+            .iter()
+            .filter_map(|message| message.as_ref().ok())
+            .map(|message| (message.signal_id.clone(), message.cloud_message.clone()))
+            .collect();
+
+        let mut outcomes = self.deliver(cloud_messages).await.into_iter();
+
+        prepared
+            .into_iter()
+            .map(|message| {
+                let PreparedMessage {
+                    signal_id, value, ..
+                } = message?;
+
+                // deliver returns exactly one outcome per message
+                let result = outcomes.next().unwrap()?;
+
+                // We don't set the last emitted value to the converted value so that we can meaningfully compare
+                // this value with the value coming directly from the signal.
+                // Buffered values count as emitted since they will be delivered once the cloud is reachable.
+                self.signals.set_last_emitted_value(signal_id, value);
+
+                Ok(result)
+            })
+            .collect()
+    }
+
+    /// Applies a conversion implicitly to a signal value and creates the message to send to the cloud
     ///
     /// # Arguments
     /// - `signal`: The signal to emit
-    async fn send_to_cloud(&self, signal: Signal) -> Result<CloudMessageResponse, EmitterError> {
+    fn create_cloud_message(signal: &Signal) -> Result<PreparedMessage, EmitterError> {
         let value = signal
             .value
             .clone()
             // This error case should actually be unreachable, but always good to check!
             .ok_or::<EmitterError>(EmitterErrorKind::SignalValueEmpty.into())?;
 
-        let converted = value.parse::<f32>().map_or(value.clone(), |v| {
-            signal.emission.policy.conversion.apply(v).to_string()
-        });
+        let converted = signal
+            .emission
+            .policy
+            .conversion
+            .apply(&value)
+            .unwrap_or_else(|e| {
+                warn!(
+                    "Unable to apply conversion for signal {}: {e}. Emitting the value without conversion.",
+                    signal.id
+                );
+                value.clone()
+            });
 
         info!(
             "Digital Twin Instance {:?}: {}",
             signal.target.metadata, converted
         );
 
-        info!("\t(from {}: {:?})", signal.source.id, signal.value);
+        info!("\t(from {}: {value})", signal.source.id);
 
-        let cloud_message = CloudMessageRequest {
-            cloud_signal: signal.target.metadata.clone(),
-            signal_value: converted,
-            signal_timestamp: OffsetDateTime::now_utc().to_string(),
-        };
+        Ok(PreparedMessage {
+            signal_id: signal.id.clone(),
+            value,
+            cloud_message: CloudMessageRequest {
+                cloud_signal: signal.target.metadata.clone(),
+                signal_value: converted,
+                signal_timestamp: OffsetDateTime::now_utc().to_string(),
+            },
+        })
+    }
 
-        let response = self
+    /// Sends a batch of messages to the cloud.
+    /// If the offline buffer is enabled, messages are buffered instead when they cannot be sent,
+    /// or when older messages are still waiting in the buffer.
+    /// Returns one result per message, in the same order as the messages.
+    ///
+    /// # Arguments
+    /// - `messages`: The messages to send along with the ids of the signals they were created for
+    async fn deliver(
+        &self,
+        messages: Vec<(String, CloudMessageRequest)>,
+    ) -> Vec<Result<EmissionResult, EmitterError>> {
+        if messages.is_empty() {
+            return Vec::new();
+        }
+
+        let offline_buffer = self.offline_buffer.as_ref();
+
+        // Sending directly would overtake the buffered messages
+        if let Some(offline_buffer) = offline_buffer.filter(|b| !b.is_empty()) {
+            return messages
+                .into_iter()
+                .map(|(signal_id, cloud_message)| {
+                    offline_buffer
+                        .push(&signal_id, cloud_message)
+                        .map(|_| EmissionResult::Buffered)
+                        .map_err(EmitterError::offline_buffer_error)
+                })
+                .collect();
+        }
+
+        let (signal_ids, cloud_messages): (Vec<_>, Vec<_>) = messages.into_iter().unzip();
+        let mut responses = self
             .cloud_adapter
-            .send_to_cloud(cloud_message)
+            .send_batch_to_cloud(cloud_messages.clone())
             .await
-            .map_err(EmitterError::cloud_error)?;
+            .into_iter();
+
+        signal_ids
+            .into_iter()
+            .zip(cloud_messages)
+            .map(|(signal_id, cloud_message)| {
+                let response = responses.next().unwrap_or_else(|| {
+                    Err(CloudAdapterError::unknown(
+                        "The cloud adapter did not return a result for this message",
+                    ))
+                });
+
+                match (response, offline_buffer) {
+                    (Ok(response), _) => Ok(EmissionResult::Sent(response)),
+                    (Err(e), Some(offline_buffer)) => {
+                        warn!("Error sending data for signal {signal_id} to the cloud: {e:?}");
+                        offline_buffer
+                            .push(&signal_id, cloud_message)
+                            .map(|_| EmissionResult::Buffered)
+                            .map_err(EmitterError::offline_buffer_error)
+                    }
+                    (Err(e), None) => Err(EmitterError::cloud_error(e)),
+                }
+            })
+            .collect()
+    }
+}
 
-        // We don't set the last emitted value to the converted value so that we can meaningfully compare
-        // this value with the value coming directly from the signal.
-        self.signals.set_last_emitted_value(signal.id, value);
+/// A message which is ready to be sent to the cloud
+struct PreparedMessage {
+    /// The id of the signal that the message was created for
+    signal_id: String,
 
-        Ok(response)
-    }
+    /// The unconverted signal value
+    value: Value,
+
+    /// The message to send
+    cloud_message: CloudMessageRequest,
 }
 
 proc_macros::error! {
@@ -217,6 +516,7 @@ proc_macros::error! {
         SignalValueEmpty,
         ProviderProxyError,
         CloudError,
+        OfflineBufferError,
     }
 }
 
@@ -225,15 +525,24 @@ mod emitter_tests {
     use super::*;
     use mockall::*;
 
+    use std::collections::HashMap;
+
     use async_trait::async_trait;
+    use tempfile::TempDir;
+    use tokio::{sync::mpsc::unbounded_channel, time::sleep};
 
     use freyja_contracts::{
-        cloud_adapter::{CloudAdapterError, CloudAdapterErrorKind},
+        cloud_adapter::CloudAdapterErrorKind,
+        conversion::Conversion,
         entity::Entity,
-        provider_proxy_selector::ProviderProxySelectorError,
+        provider_proxy_selector::{
+            InvokeResponseFuture, ProviderProxySelectorError, SetValueFuture,
+        },
         signal::{Emission, EmissionPolicy},
     };
 
+    use crate::config::{DropPolicy, OfflineBufferConfig};
+
     mock! {
         pub CloudAdapter {}
 
@@ -250,35 +559,114 @@ mod emitter_tests {
         }
     }
 
+    mock! {
+        pub BatchCloudAdapter {}
+
+        #[async_trait]
+        impl CloudAdapter for BatchCloudAdapter {
+            fn create_new() -> Result<Self, CloudAdapterError>
+            where
+                Self: Sized;
+
+            async fn send_to_cloud(
+                &self,
+                cloud_message: CloudMessageRequest,
+            ) -> Result<CloudMessageResponse, CloudAdapterError>;
+
+            async fn send_batch_to_cloud(
+                &self,
+                cloud_messages: Vec<CloudMessageRequest>,
+            ) -> Vec<Result<CloudMessageResponse, CloudAdapterError>>;
+        }
+    }
+
+    /// A cloud adapter which takes some time to send each message and records when it was sent
+    struct SlowCloudAdapter {
+        send_duration: Duration,
+        sent_at: Arc<std::sync::Mutex<Vec<Instant>>>,
+    }
+
+    #[async_trait]
+    impl CloudAdapter for SlowCloudAdapter {
+        fn create_new() -> Result<Self, CloudAdapterError> {
+            Ok(Self {
+                send_duration: Duration::ZERO,
+                sent_at: Arc::new(std::sync::Mutex::new(Vec::new())),
+            })
+        }
+
+        async fn send_to_cloud(
+            &self,
+            _cloud_message: CloudMessageRequest,
+        ) -> Result<CloudMessageResponse, CloudAdapterError> {
+            self.sent_at.lock().unwrap().push(Instant::now());
+            sleep(self.send_duration).await;
+            Ok(CloudMessageResponse {})
+        }
+    }
+
+    fn open_offline_buffer(dir: &TempDir) -> OfflineBuffer {
+        let config = OfflineBufferConfig {
+            enabled: true,
+            directory: None,
+            max_messages: 100,
+            max_size_bytes: 1024 * 1024,
+            ttl_s: None,
+            drop_policy: DropPolicy::OldestFirst,
+            signal_priorities: Vec::new(),
+        };
+
+        OfflineBuffer::open(&config, dir.path()).unwrap()
+    }
+
+    fn test_cloud_message(value: &str) -> CloudMessageRequest {
+        CloudMessageRequest {
+            cloud_signal: HashMap::new(),
+            signal_value: Value::from(value),
+            signal_timestamp: "timestamp".to_string(),
+        }
+    }
+
     mock! {
         pub ProviderProxySelector {}
 
         #[async_trait]
         impl ProviderProxySelector for ProviderProxySelector {
             async fn create_or_update_proxy(&mut self, entity: &Entity) -> Result<(), ProviderProxySelectorError>;
+            async fn remove_entity(&mut self, entity_id: &str) -> Result<(), ProviderProxySelectorError>;
             async fn request_entity_value(&mut self, entity_id: &str) -> Result<(), ProviderProxySelectorError>;
+            async fn set_entity_value(&mut self, entity_id: &str, value: Value) -> Result<SetValueFuture, ProviderProxySelectorError>;
+            async fn invoke_entity(&mut self, entity_id: &str, payload: Value) -> Result<InvokeResponseFuture, ProviderProxySelectorError>;
         }
     }
 
     #[tokio::test]
     async fn emit_data_returns_default_on_empty_input() {
+        let now = Instant::now();
+
         let uut = Emitter {
             signals: Arc::new(SignalStore::new()),
             cloud_adapter: MockCloudAdapter::new(),
             provider_proxy_selector: Arc::new(Mutex::new(MockProviderProxySelector::new())),
-            signal_values_queue: Arc::new(SegQueue::new()),
+            signal_values_receiver: Mutex::new(unbounded_channel().1),
+            offline_buffer: None,
         };
 
-        let result = uut.emit_data(vec![]).await;
+        let result = uut.emit_data(vec![], now).await;
 
         assert!(result.is_ok());
-        assert_eq!(result.unwrap(), DEFAULT_SLEEP_INTERVAL_MS);
+        assert_eq!(
+            result.unwrap(),
+            now + Duration::from_millis(DEFAULT_SLEEP_INTERVAL_MS)
+        );
     }
 
     #[tokio::test]
     async fn emit_data_handles_nonzero_next_emission_time() {
         const NEXT_EMISSION_MS: u64 = 42;
 
+        let now = Instant::now();
+
         let mut mock_provider_proxy_selector = MockProviderProxySelector::new();
         mock_provider_proxy_selector
             .expect_request_entity_value()
@@ -292,30 +680,36 @@ mod emitter_tests {
             signals: Arc::new(SignalStore::new()),
             cloud_adapter: mock_cloud_adapter,
             provider_proxy_selector,
-            signal_values_queue: Arc::new(SegQueue::new()),
+            signal_values_receiver: Mutex::new(unbounded_channel().1),
+            offline_buffer: None,
         };
 
         let test_signal = Signal {
             emission: Emission {
-                next_emission_ms: NEXT_EMISSION_MS,
+                next_emission: Some(now + Duration::from_millis(NEXT_EMISSION_MS)),
                 ..Default::default()
             },
             ..Default::default()
         };
 
-        let result = uut.emit_data(vec![test_signal]).await;
+        let result = uut.emit_data(vec![test_signal], now).await;
 
         uut.cloud_adapter.checkpoint();
         uut.provider_proxy_selector.lock().await.checkpoint();
 
         assert!(result.is_ok());
-        assert_eq!(result.unwrap(), NEXT_EMISSION_MS);
+        assert_eq!(
+            result.unwrap(),
+            now + Duration::from_millis(NEXT_EMISSION_MS)
+        );
     }
 
     #[tokio::test]
     async fn emit_data_handles_zero_next_emission_time() {
         const INTERVAL: u64 = 42;
 
+        let now = Instant::now();
+
         let mut mock_provider_proxy_selector = MockProviderProxySelector::new();
         mock_provider_proxy_selector
             .expect_request_entity_value()
@@ -333,13 +727,14 @@ mod emitter_tests {
             signals: Arc::new(SignalStore::new()),
             cloud_adapter: mock_cloud_adapter,
             provider_proxy_selector,
-            signal_values_queue: Arc::new(SegQueue::new()),
+            signal_values_receiver: Mutex::new(unbounded_channel().1),
+            offline_buffer: None,
         };
 
         let test_signal = Signal {
-            value: Some("foo".to_string()),
+            value: Some(Value::from("foo")),
             emission: Emission {
-                next_emission_ms: 0,
+                next_emission: None,
                 policy: EmissionPolicy {
                     interval_ms: INTERVAL,
                     ..Default::default()
@@ -349,19 +744,21 @@ mod emitter_tests {
             ..Default::default()
         };
 
-        let result = uut.emit_data(vec![test_signal]).await;
+        let result = uut.emit_data(vec![test_signal], now).await;
 
         uut.cloud_adapter.checkpoint();
         uut.provider_proxy_selector.lock().await.checkpoint();
 
         assert!(result.is_ok());
-        assert_eq!(result.unwrap(), INTERVAL);
+        assert_eq!(result.unwrap(), now + Duration::from_millis(INTERVAL));
     }
 
     #[tokio::test]
     async fn emit_data_doesnt_emit_when_value_empty() {
         const INTERVAL: u64 = 42;
 
+        let now = Instant::now();
+
         let mut mock_provider_proxy_selector = MockProviderProxySelector::new();
         mock_provider_proxy_selector
             .expect_request_entity_value()
@@ -376,13 +773,14 @@ mod emitter_tests {
             signals: Arc::new(SignalStore::new()),
             cloud_adapter: mock_cloud_adapter,
             provider_proxy_selector,
-            signal_values_queue: Arc::new(SegQueue::new()),
+            signal_values_receiver: Mutex::new(unbounded_channel().1),
+            offline_buffer: None,
         };
 
         let test_signal = Signal {
             value: None,
             emission: Emission {
-                next_emission_ms: 0,
+                next_emission: None,
                 policy: EmissionPolicy {
                     interval_ms: INTERVAL,
                     ..Default::default()
@@ -392,19 +790,21 @@ mod emitter_tests {
             ..Default::default()
         };
 
-        let result = uut.emit_data(vec![test_signal]).await;
+        let result = uut.emit_data(vec![test_signal], now).await;
 
         uut.cloud_adapter.checkpoint();
         uut.provider_proxy_selector.lock().await.checkpoint();
 
         assert!(result.is_ok());
-        assert_eq!(result.unwrap(), INTERVAL);
+        assert_eq!(result.unwrap(), now + Duration::from_millis(INTERVAL));
     }
 
     #[tokio::test]
     async fn emit_data_doesnt_emit_when_value_not_changed() {
         const INTERVAL: u64 = 42;
 
+        let now = Instant::now();
+
         let mut mock_provider_proxy_selector = MockProviderProxySelector::new();
         mock_provider_proxy_selector
             .expect_request_entity_value()
@@ -419,37 +819,41 @@ mod emitter_tests {
             signals: Arc::new(SignalStore::new()),
             cloud_adapter: mock_cloud_adapter,
             provider_proxy_selector,
-            signal_values_queue: Arc::new(SegQueue::new()),
+            signal_values_receiver: Mutex::new(unbounded_channel().1),
+            offline_buffer: None,
         };
 
-        let value = Some("foo".to_string());
+        let value = Some(Value::from("foo"));
         let test_signal = Signal {
             value: value.clone(),
             emission: Emission {
-                next_emission_ms: 0,
+                next_emission: None,
                 last_emitted_value: value,
                 policy: EmissionPolicy {
                     interval_ms: INTERVAL,
                     emit_only_if_changed: true,
                     ..Default::default()
                 },
+                ..Default::default()
             },
             ..Default::default()
         };
 
-        let result = uut.emit_data(vec![test_signal]).await;
+        let result = uut.emit_data(vec![test_signal], now).await;
 
         uut.cloud_adapter.checkpoint();
         uut.provider_proxy_selector.lock().await.checkpoint();
 
         assert!(result.is_ok());
-        assert_eq!(result.unwrap(), INTERVAL);
+        assert_eq!(result.unwrap(), now + Duration::from_millis(INTERVAL));
     }
 
     #[tokio::test]
     async fn emit_data_emits_when_value_changed() {
         const INTERVAL: u64 = 42;
 
+        let now = Instant::now();
+
         let mut mock_provider_proxy_selector = MockProviderProxySelector::new();
         mock_provider_proxy_selector
             .expect_request_entity_value()
@@ -467,36 +871,40 @@ mod emitter_tests {
             signals: Arc::new(SignalStore::new()),
             cloud_adapter: mock_cloud_adapter,
             provider_proxy_selector,
-            signal_values_queue: Arc::new(SegQueue::new()),
+            signal_values_receiver: Mutex::new(unbounded_channel().1),
+            offline_buffer: None,
         };
 
         let test_signal = Signal {
-            value: Some("foo".to_string()),
+            value: Some(Value::from("foo")),
             emission: Emission {
-                next_emission_ms: 0,
-                last_emitted_value: Some("bar".to_string()),
+                next_emission: None,
+                last_emitted_value: Some(Value::from("bar")),
                 policy: EmissionPolicy {
                     interval_ms: INTERVAL,
                     emit_only_if_changed: true,
                     ..Default::default()
                 },
+                ..Default::default()
             },
             ..Default::default()
         };
 
-        let result = uut.emit_data(vec![test_signal]).await;
+        let result = uut.emit_data(vec![test_signal], now).await;
 
         uut.cloud_adapter.checkpoint();
         uut.provider_proxy_selector.lock().await.checkpoint();
 
         assert!(result.is_ok());
-        assert_eq!(result.unwrap(), INTERVAL);
+        assert_eq!(result.unwrap(), now + Duration::from_millis(INTERVAL));
     }
 
     #[tokio::test]
     async fn emit_data_emits_when_last_value_empty() {
         const INTERVAL: u64 = 42;
 
+        let now = Instant::now();
+
         let mut mock_provider_proxy_selector = MockProviderProxySelector::new();
         mock_provider_proxy_selector
             .expect_request_entity_value()
@@ -514,34 +922,473 @@ mod emitter_tests {
             signals: Arc::new(SignalStore::new()),
             cloud_adapter: mock_cloud_adapter,
             provider_proxy_selector,
-            signal_values_queue: Arc::new(SegQueue::new()),
+            signal_values_receiver: Mutex::new(unbounded_channel().1),
+            offline_buffer: None,
         };
 
         let test_signal = Signal {
-            value: Some("foo".to_string()),
+            value: Some(Value::from("foo")),
             emission: Emission {
-                next_emission_ms: 0,
+                next_emission: None,
                 last_emitted_value: None,
                 policy: EmissionPolicy {
                     interval_ms: INTERVAL,
                     emit_only_if_changed: true,
                     ..Default::default()
                 },
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        let result = uut.emit_data(vec![test_signal], now).await;
+
+        uut.cloud_adapter.checkpoint();
+        uut.provider_proxy_selector.lock().await.checkpoint();
+
+        assert!(result.is_ok());
+        assert_eq!(result.unwrap(), now + Duration::from_millis(INTERVAL));
+    }
+
+    #[tokio::test]
+    async fn emit_data_doesnt_emit_when_change_within_deadband() {
+        const INTERVAL: u64 = 42;
+
+        let now = Instant::now();
+
+        let mut mock_provider_proxy_selector = MockProviderProxySelector::new();
+        mock_provider_proxy_selector
+            .expect_request_entity_value()
+            .once()
+            .returning(|_| Ok(()));
+        let provider_proxy_selector = Arc::new(Mutex::new(mock_provider_proxy_selector));
+
+        let mut mock_cloud_adapter = MockCloudAdapter::new();
+        mock_cloud_adapter.expect_send_to_cloud().never();
+
+        let mut uut = Emitter {
+            signals: Arc::new(SignalStore::new()),
+            cloud_adapter: mock_cloud_adapter,
+            provider_proxy_selector,
+            signal_values_receiver: Mutex::new(unbounded_channel().1),
+            offline_buffer: None,
+        };
+
+        let test_signal = Signal {
+            value: Some(Value::Float(77.70002)),
+            emission: Emission {
+                next_emission: None,
+                last_emitted_value: Some(Value::Float(77.70001)),
+                policy: EmissionPolicy {
+                    interval_ms: INTERVAL,
+                    emit_only_if_changed: true,
+                    deadband_abs: Some(0.01),
+                    ..Default::default()
+                },
+                ..Default::default()
             },
             ..Default::default()
         };
 
-        let result = uut.emit_data(vec![test_signal]).await;
+        let result = uut.emit_data(vec![test_signal], now).await;
 
         uut.cloud_adapter.checkpoint();
         uut.provider_proxy_selector.lock().await.checkpoint();
 
         assert!(result.is_ok());
-        assert_eq!(result.unwrap(), INTERVAL);
+        assert_eq!(result.unwrap(), now + Duration::from_millis(INTERVAL));
+    }
+
+    #[tokio::test]
+    async fn emit_data_emits_heartbeat_after_max_silence() {
+        const INTERVAL: u64 = 42;
+        const MAX_SILENCE: u64 = 1000;
+
+        let now = Instant::now();
+
+        let mut mock_provider_proxy_selector = MockProviderProxySelector::new();
+        mock_provider_proxy_selector
+            .expect_request_entity_value()
+            .times(2)
+            .returning(|_| Ok(()));
+        let provider_proxy_selector = Arc::new(Mutex::new(mock_provider_proxy_selector));
+
+        let mut mock_cloud_adapter = MockCloudAdapter::new();
+        mock_cloud_adapter
+            .expect_send_to_cloud()
+            .once()
+            .returning(|_| Ok(CloudMessageResponse {}));
+
+        let mut uut = Emitter {
+            signals: Arc::new(SignalStore::new()),
+            cloud_adapter: mock_cloud_adapter,
+            provider_proxy_selector,
+            signal_values_receiver: Mutex::new(unbounded_channel().1),
+            offline_buffer: None,
+        };
+
+        let value = Some(Value::from("foo"));
+        let silent_signal = Signal {
+            value: value.clone(),
+            emission: Emission {
+                next_emission: None,
+                last_emitted_value: value,
+                last_emission: Some(now - Duration::from_millis(MAX_SILENCE - 1)),
+                policy: EmissionPolicy {
+                    interval_ms: INTERVAL,
+                    emit_only_if_changed: true,
+                    max_silence_ms: Some(MAX_SILENCE),
+                    ..Default::default()
+                },
+            },
+            ..Default::default()
+        };
+
+        let mut heartbeat_signal = silent_signal.clone();
+        heartbeat_signal.emission.last_emission = Some(now - Duration::from_millis(MAX_SILENCE));
+
+        let result = uut
+            .emit_data(vec![silent_signal, heartbeat_signal], now)
+            .await;
+
+        uut.cloud_adapter.checkpoint();
+        uut.provider_proxy_selector.lock().await.checkpoint();
+
+        assert!(result.is_ok());
+        assert_eq!(result.unwrap(), now + Duration::from_millis(INTERVAL));
+    }
+
+    #[tokio::test]
+    async fn emit_data_emits_changed_on_change_signal_before_interval() {
+        const INTERVAL: u64 = 42;
+        const NEXT_EMISSION_MS: u64 = 20;
+
+        let now = Instant::now();
+
+        let mut mock_provider_proxy_selector = MockProviderProxySelector::new();
+        mock_provider_proxy_selector
+            .expect_request_entity_value()
+            .never();
+        let provider_proxy_selector = Arc::new(Mutex::new(mock_provider_proxy_selector));
+
+        let mut mock_cloud_adapter = MockCloudAdapter::new();
+        mock_cloud_adapter
+            .expect_send_to_cloud()
+            .once()
+            .returning(|_| Ok(CloudMessageResponse {}));
+
+        let mut uut = Emitter {
+            signals: Arc::new(SignalStore::new()),
+            cloud_adapter: mock_cloud_adapter,
+            provider_proxy_selector,
+            signal_values_receiver: Mutex::new(unbounded_channel().1),
+            offline_buffer: None,
+        };
+
+        let test_signal = Signal {
+            value: Some(Value::Int(2)),
+            emission: Emission {
+                next_emission: Some(now + Duration::from_millis(NEXT_EMISSION_MS)),
+                last_emitted_value: Some(Value::Int(1)),
+                last_emission: Some(now - Duration::from_millis(INTERVAL - NEXT_EMISSION_MS)),
+                policy: EmissionPolicy {
+                    interval_ms: INTERVAL,
+                    mode: EmissionMode::OnChange { min_interval_ms: 5 },
+                    ..Default::default()
+                },
+            },
+            ..Default::default()
+        };
+
+        let result = uut.emit_data(vec![test_signal], now).await;
+
+        uut.cloud_adapter.checkpoint();
+        uut.provider_proxy_selector.lock().await.checkpoint();
+
+        assert!(result.is_ok());
+        assert_eq!(
+            result.unwrap(),
+            now + Duration::from_millis(NEXT_EMISSION_MS)
+        );
+    }
+
+    #[tokio::test]
+    async fn emit_data_delays_on_change_emission_until_min_interval() {
+        const INTERVAL: u64 = 42;
+        const MIN_INTERVAL: u64 = 10;
+        const TIME_SINCE_EMISSION: u64 = 4;
+
+        let now = Instant::now();
+
+        let mut mock_provider_proxy_selector = MockProviderProxySelector::new();
+        mock_provider_proxy_selector
+            .expect_request_entity_value()
+            .never();
+        let provider_proxy_selector = Arc::new(Mutex::new(mock_provider_proxy_selector));
+
+        let mut mock_cloud_adapter = MockCloudAdapter::new();
+        mock_cloud_adapter.expect_send_to_cloud().never();
+
+        let mut uut = Emitter {
+            signals: Arc::new(SignalStore::new()),
+            cloud_adapter: mock_cloud_adapter,
+            provider_proxy_selector,
+            signal_values_receiver: Mutex::new(unbounded_channel().1),
+            offline_buffer: None,
+        };
+
+        let test_signal = Signal {
+            value: Some(Value::Int(2)),
+            emission: Emission {
+                next_emission: Some(now + Duration::from_millis(INTERVAL - TIME_SINCE_EMISSION)),
+                last_emitted_value: Some(Value::Int(1)),
+                last_emission: Some(now - Duration::from_millis(TIME_SINCE_EMISSION)),
+                policy: EmissionPolicy {
+                    interval_ms: INTERVAL,
+                    mode: EmissionMode::OnChange {
+                        min_interval_ms: MIN_INTERVAL,
+                    },
+                    ..Default::default()
+                },
+            },
+            ..Default::default()
+        };
+
+        let result = uut.emit_data(vec![test_signal], now).await;
+
+        uut.cloud_adapter.checkpoint();
+        uut.provider_proxy_selector.lock().await.checkpoint();
+
+        assert!(result.is_ok());
+        assert_eq!(
+            result.unwrap(),
+            now + Duration::from_millis(MIN_INTERVAL - TIME_SINCE_EMISSION)
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn update_signal_value_returns_deadline_for_changed_on_change_signal() {
+        const ON_CHANGE_ID: &str = "on_change";
+        const INTERVAL_ID: &str = "interval";
+        const MIN_INTERVAL: u64 = 100;
+
+        let signals = SignalStore::new();
+        signals.sync(
+            [
+                Signal {
+                    id: ON_CHANGE_ID.to_string(),
+                    emission: Emission {
+                        policy: EmissionPolicy {
+                            interval_ms: 1000,
+                            mode: EmissionMode::OnChange {
+                                min_interval_ms: MIN_INTERVAL,
+                            },
+                            ..Default::default()
+                        },
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+                Signal {
+                    id: INTERVAL_ID.to_string(),
+                    emission: Emission {
+                        policy: EmissionPolicy {
+                            interval_ms: 1000,
+                            emit_only_if_changed: true,
+                            ..Default::default()
+                        },
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+            ]
+            .into_iter(),
+        );
+        signals.set_last_emitted_value(ON_CHANGE_ID.to_string(), Value::Int(1));
+        signals.set_last_emitted_value(INTERVAL_ID.to_string(), Value::Int(1));
+
+        let uut = Emitter {
+            signals: Arc::new(signals),
+            cloud_adapter: MockCloudAdapter::new(),
+            provider_proxy_selector: Arc::new(Mutex::new(MockProviderProxySelector::new())),
+            signal_values_receiver: Mutex::new(unbounded_channel().1),
+            offline_buffer: None,
+        };
+
+        let on_change_value = |value| SignalValue {
+            entity_id: ON_CHANGE_ID.to_string(),
+            value,
+        };
+
+        let emitted_at = Instant::now();
+
+        tokio::time::advance(Duration::from_millis(30)).await;
+        assert_eq!(
+            uut.update_signal_value(on_change_value(Value::Int(2))),
+            Some(emitted_at + Duration::from_millis(MIN_INTERVAL))
+        );
+
+        tokio::time::advance(Duration::from_millis(MIN_INTERVAL)).await;
+        assert_eq!(
+            uut.update_signal_value(on_change_value(Value::Int(3))),
+            Some(Instant::now())
+        );
+        assert_eq!(
+            uut.update_signal_value(on_change_value(Value::Float(1.0))),
+            None
+        );
+        assert_eq!(
+            uut.update_signal_value(SignalValue {
+                entity_id: INTERVAL_ID.to_string(),
+                value: Value::Int(2),
+            },),
+            None
+        );
+        assert_eq!(
+            uut.update_signal_value(SignalValue {
+                entity_id: "unknown".to_string(),
+                value: Value::Int(2),
+            },),
+            None
+        );
+        assert_eq!(
+            uut.signals.get(&ON_CHANGE_ID.to_string()).unwrap().value,
+            Some(Value::Float(1.0))
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn run_emits_changed_value_without_waiting_for_interval() {
+        const ID: &str = "testid";
+
+        let mut mock_provider_proxy_selector = MockProviderProxySelector::new();
+        mock_provider_proxy_selector
+            .expect_request_entity_value()
+            .returning(|_| Ok(()));
+
+        let sent = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sent_clone = sent.clone();
+        let mut mock_cloud_adapter = MockCloudAdapter::new();
+        mock_cloud_adapter
+            .expect_send_to_cloud()
+            .returning(move |message| {
+                sent_clone.lock().unwrap().push(message.signal_value);
+                Ok(CloudMessageResponse {})
+            });
+
+        let signals = SignalStore::new();
+        signals.sync(
+            [Signal {
+                id: ID.to_string(),
+                emission: Emission {
+                    policy: EmissionPolicy {
+                        interval_ms: 60000,
+                        mode: EmissionMode::OnChange { min_interval_ms: 0 },
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+                ..Default::default()
+            }]
+            .into_iter(),
+        );
+        signals.set_value(ID.to_string(), Value::Int(1));
+
+        let (signal_values_sender, signal_values_receiver) = unbounded_channel();
+        let uut = Arc::new(Emitter::new(
+            Arc::new(signals),
+            mock_cloud_adapter,
+            Arc::new(Mutex::new(mock_provider_proxy_selector)),
+            signal_values_receiver,
+            None,
+        ));
+
+        let run_uut = uut.clone();
+        let handle = tokio::spawn(async move { run_uut.run().await });
+
+        // The first pass emits the initial value
+        sleep(Duration::from_millis(10)).await;
+        assert_eq!(*sent.lock().unwrap(), vec![Value::Int(1)]);
+
+        // A changed value is emitted right away rather than at the next interval
+        for value in [Value::Int(2), Value::Int(2), Value::Int(3)] {
+            signal_values_sender
+                .send(SignalValue {
+                    entity_id: ID.to_string(),
+                    value,
+                })
+                .unwrap();
+            sleep(Duration::from_millis(10)).await;
+        }
+
+        assert_eq!(
+            *sent.lock().unwrap(),
+            vec![Value::Int(1), Value::Int(2), Value::Int(3)]
+        );
+
+        handle.abort();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn run_doesnt_drift_when_emission_is_slow() {
+        const ID: &str = "testid";
+        const INTERVAL: u64 = 1000;
+        const SEND_DURATION: u64 = 300;
+
+        let mut mock_provider_proxy_selector = MockProviderProxySelector::new();
+        mock_provider_proxy_selector
+            .expect_request_entity_value()
+            .returning(|_| Ok(()));
+
+        let sent_at = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let cloud_adapter = SlowCloudAdapter {
+            send_duration: Duration::from_millis(SEND_DURATION),
+            sent_at: sent_at.clone(),
+        };
+
+        let signals = SignalStore::new();
+        signals.sync(
+            [Signal {
+                id: ID.to_string(),
+                emission: Emission {
+                    policy: EmissionPolicy {
+                        interval_ms: INTERVAL,
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+                ..Default::default()
+            }]
+            .into_iter(),
+        );
+        signals.set_value(ID.to_string(), Value::Int(1));
+
+        let uut = Arc::new(Emitter::new(
+            Arc::new(signals),
+            cloud_adapter,
+            Arc::new(Mutex::new(mock_provider_proxy_selector)),
+            unbounded_channel().1,
+            None,
+        ));
+
+        let start = Instant::now();
+        let run_uut = uut.clone();
+        let handle = tokio::spawn(async move { run_uut.run().await });
+
+        sleep(Duration::from_millis(5 * INTERVAL + 10)).await;
+        handle.abort();
+
+        // Each emission starts on the interval grid even though the previous one took part of the interval
+        let expected: Vec<Instant> = (0..=5)
+            .map(|k| start + Duration::from_millis(k * INTERVAL))
+            .collect();
+        assert_eq!(*sent_at.lock().unwrap(), expected);
     }
 
     #[tokio::test]
     async fn cloud_adapter_error_doesnt_prevent_further_emission_attempts() {
+        let now = Instant::now();
+
         let mut mock_provider_proxy_selector = MockProviderProxySelector::new();
         mock_provider_proxy_selector
             .expect_request_entity_value()
@@ -559,15 +1406,18 @@ mod emitter_tests {
             signals: Arc::new(SignalStore::new()),
             cloud_adapter: mock_cloud_adapter,
             provider_proxy_selector,
-            signal_values_queue: Arc::new(SegQueue::new()),
+            signal_values_receiver: Mutex::new(unbounded_channel().1),
+            offline_buffer: None,
         };
 
         let test_signal = Signal {
-            value: Some("foo".to_string()),
+            value: Some(Value::from("foo")),
             ..Default::default()
         };
 
-        let result = uut.emit_data(vec![test_signal.clone(), test_signal]).await;
+        let result = uut
+            .emit_data(vec![test_signal.clone(), test_signal], now)
+            .await;
 
         uut.cloud_adapter.checkpoint();
         uut.provider_proxy_selector.lock().await.checkpoint();
@@ -587,7 +1437,7 @@ mod emitter_tests {
 
         let test_signal = Signal {
             id: ID.to_string(),
-            value: Some("foo".to_string()),
+            value: Some(Value::from("foo")),
             emission: Emission {
                 policy: EmissionPolicy {
                     interval_ms: INTERVAL,
@@ -605,10 +1455,13 @@ mod emitter_tests {
             signals: Arc::new(signals),
             cloud_adapter: mock_cloud_adapter,
             provider_proxy_selector: Arc::new(Mutex::new(MockProviderProxySelector::new())),
-            signal_values_queue: Arc::new(SegQueue::new()),
+            signal_values_receiver: Mutex::new(unbounded_channel().1),
+            offline_buffer: None,
         };
 
-        let result = uut.send_to_cloud(test_signal).await;
+        let mut results = uut.send_to_cloud(vec![test_signal]).await;
+        assert_eq!(results.len(), 1);
+        let result = results.pop().unwrap();
 
         assert!(result.is_ok());
 
@@ -617,6 +1470,297 @@ mod emitter_tests {
         assert!(signal.is_some());
         let signal = signal.unwrap();
         assert!(signal.emission.last_emitted_value.is_some());
-        assert_eq!(signal.emission.next_emission_ms, INTERVAL);
+        assert!(signal.emission.last_emission.is_some());
+    }
+
+    #[tokio::test]
+    async fn send_to_cloud_buffers_message_when_cloud_unavailable() {
+        const ID: &str = "testid";
+
+        let mut mock_cloud_adapter = MockCloudAdapter::new();
+        mock_cloud_adapter
+            .expect_send_to_cloud()
+            .once()
+            .returning(|_| Err(CloudAdapterErrorKind::Communication.into()));
+
+        let test_signal = Signal {
+            id: ID.to_string(),
+            value: Some(Value::from("foo")),
+            ..Default::default()
+        };
+
+        let signals = SignalStore::new();
+        signals.sync([test_signal.clone()].into_iter());
+
+        let dir = TempDir::new().unwrap();
+        let uut = Emitter {
+            signals: Arc::new(signals),
+            cloud_adapter: mock_cloud_adapter,
+            provider_proxy_selector: Arc::new(Mutex::new(MockProviderProxySelector::new())),
+            signal_values_receiver: Mutex::new(unbounded_channel().1),
+            offline_buffer: Some(open_offline_buffer(&dir)),
+        };
+
+        let mut results = uut.send_to_cloud(vec![test_signal]).await;
+        assert_eq!(results.len(), 1);
+        let result = results.pop().unwrap();
+
+        assert!(matches!(result, Ok(EmissionResult::Buffered)));
+        assert_eq!(uut.offline_buffer.as_ref().unwrap().len(), 1);
+        let signal = uut.signals.get(&ID.to_string()).unwrap();
+        assert_eq!(signal.emission.last_emitted_value, Some(Value::from("foo")));
+    }
+
+    #[tokio::test]
+    async fn send_to_cloud_buffers_message_behind_existing_messages() {
+        let mut mock_cloud_adapter = MockCloudAdapter::new();
+        mock_cloud_adapter.expect_send_to_cloud().never();
+
+        let dir = TempDir::new().unwrap();
+        let offline_buffer = open_offline_buffer(&dir);
+        offline_buffer
+            .push("testid", test_cloud_message("1"))
+            .unwrap();
+
+        let mut uut = Emitter {
+            signals: Arc::new(SignalStore::new()),
+            cloud_adapter: mock_cloud_adapter,
+            provider_proxy_selector: Arc::new(Mutex::new(MockProviderProxySelector::new())),
+            signal_values_receiver: Mutex::new(unbounded_channel().1),
+            offline_buffer: Some(offline_buffer),
+        };
+
+        let test_signal = Signal {
+            id: "testid".to_string(),
+            value: Some(Value::from("2")),
+            ..Default::default()
+        };
+
+        let mut results = uut.send_to_cloud(vec![test_signal]).await;
+        assert_eq!(results.len(), 1);
+        let result = results.pop().unwrap();
+
+        uut.cloud_adapter.checkpoint();
+
+        assert!(matches!(result, Ok(EmissionResult::Buffered)));
+        assert_eq!(uut.offline_buffer.as_ref().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn emit_data_replays_buffered_messages_in_order() {
+        let now = Instant::now();
+
+        let sent_values = Arc::new(std::sync::Mutex::new(Vec::new()));
+
+        let mut mock_provider_proxy_selector = MockProviderProxySelector::new();
+        mock_provider_proxy_selector
+            .expect_request_entity_value()
+            .returning(|_| Ok(()));
+
+        let mut mock_cloud_adapter = MockCloudAdapter::new();
+        let sent_values_clone = sent_values.clone();
+        mock_cloud_adapter
+            .expect_send_to_cloud()
+            .times(3)
+            .returning(move |message| {
+                sent_values_clone.lock().unwrap().push(message.signal_value);
+                Ok(CloudMessageResponse {})
+            });
+
+        let dir = TempDir::new().unwrap();
+        let offline_buffer = open_offline_buffer(&dir);
+        for value in ["1", "2"] {
+            offline_buffer
+                .push("testid", test_cloud_message(value))
+                .unwrap();
+        }
+
+        let mut uut = Emitter {
+            signals: Arc::new(SignalStore::new()),
+            cloud_adapter: mock_cloud_adapter,
+            provider_proxy_selector: Arc::new(Mutex::new(mock_provider_proxy_selector)),
+            signal_values_receiver: Mutex::new(unbounded_channel().1),
+            offline_buffer: Some(offline_buffer),
+        };
+
+        let test_signal = Signal {
+            id: "testid".to_string(),
+            value: Some(Value::from("3")),
+            ..Default::default()
+        };
+
+        let result = uut.emit_data(vec![test_signal], now).await;
+
+        uut.cloud_adapter.checkpoint();
+
+        assert!(result.is_ok());
+        assert_eq!(
+            *sent_values.lock().unwrap(),
+            vec![Value::from("1"), Value::from("2"), Value::from("3")]
+        );
+        assert!(uut.offline_buffer.as_ref().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn flush_offline_buffer_keeps_messages_after_failure_in_order() {
+        let sent_batches = Arc::new(std::sync::Mutex::new(Vec::new()));
+
+        let mut mock_cloud_adapter = MockBatchCloudAdapter::new();
+        mock_cloud_adapter.expect_send_to_cloud().never();
+        let sent_batches_clone = sent_batches.clone();
+        mock_cloud_adapter
+            .expect_send_batch_to_cloud()
+            .times(2)
+            .returning(move |cloud_messages| {
+                let mut sent_batches = sent_batches_clone.lock().unwrap();
+                let is_first_batch = sent_batches.is_empty();
+                sent_batches.push(
+                    cloud_messages
+                        .iter()
+                        .map(|m| m.signal_value.clone())
+                        .collect::<Vec<_>>(),
+                );
+
+                cloud_messages
+                    .iter()
+                    .enumerate()
+                    .map(|(index, _)| {
+                        // The second message of the first batch is rejected
+                        if is_first_batch && index == 1 {
+                            Err(CloudAdapterErrorKind::Communication.into())
+                        } else {
+                            Ok(CloudMessageResponse {})
+                        }
+                    })
+                    .collect()
+            });
+
+        let dir = TempDir::new().unwrap();
+        let offline_buffer = open_offline_buffer(&dir);
+        for value in ["1", "2", "3"] {
+            offline_buffer
+                .push("testid", test_cloud_message(value))
+                .unwrap();
+        }
+
+        let mut uut = Emitter {
+            signals: Arc::new(SignalStore::new()),
+            cloud_adapter: mock_cloud_adapter,
+            provider_proxy_selector: Arc::new(Mutex::new(MockProviderProxySelector::new())),
+            signal_values_receiver: Mutex::new(unbounded_channel().1),
+            offline_buffer: Some(offline_buffer),
+        };
+
+        uut.flush_offline_buffer().await;
+        assert_eq!(uut.offline_buffer.as_ref().unwrap().len(), 2);
+
+        uut.flush_offline_buffer().await;
+        uut.cloud_adapter.checkpoint();
+
+        assert_eq!(
+            *sent_batches.lock().unwrap(),
+            vec![
+                vec![Value::from("1"), Value::from("2"), Value::from("3")],
+                vec![Value::from("2"), Value::from("3")],
+            ]
+        );
+        assert!(uut.offline_buffer.as_ref().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn emit_data_sends_due_signals_as_batch() {
+        const ACCEPTED_ID: &str = "accepted";
+        const REJECTED_ID: &str = "rejected";
+
+        let now = Instant::now();
+
+        let mut mock_provider_proxy_selector = MockProviderProxySelector::new();
+        mock_provider_proxy_selector
+            .expect_request_entity_value()
+            .times(2)
+            .returning(|_| Ok(()));
+
+        let mut mock_cloud_adapter = MockBatchCloudAdapter::new();
+        mock_cloud_adapter.expect_send_to_cloud().never();
+        mock_cloud_adapter
+            .expect_send_batch_to_cloud()
+            .withf(|cloud_messages| cloud_messages.len() == 2)
+            .once()
+            .returning(|_| {
+                vec![
+                    Ok(CloudMessageResponse {}),
+                    Err(CloudAdapterErrorKind::Communication.into()),
+                ]
+            });
+
+        let test_signals: Vec<Signal> = [ACCEPTED_ID, REJECTED_ID]
+            .into_iter()
+            .map(|id| Signal {
+                id: id.to_string(),
+                value: Some(Value::from("foo")),
+                ..Default::default()
+            })
+            .collect();
+
+        let signals = SignalStore::new();
+        signals.sync(test_signals.clone().into_iter());
+
+        let mut uut = Emitter {
+            signals: Arc::new(signals),
+            cloud_adapter: mock_cloud_adapter,
+            provider_proxy_selector: Arc::new(Mutex::new(mock_provider_proxy_selector)),
+            signal_values_receiver: Mutex::new(unbounded_channel().1),
+            offline_buffer: None,
+        };
+
+        let result = uut.emit_data(test_signals, now).await;
+
+        uut.cloud_adapter.checkpoint();
+
+        assert!(result.is_ok());
+        let accepted = uut.signals.get(&ACCEPTED_ID.to_string()).unwrap();
+        assert_eq!(
+            accepted.emission.last_emitted_value,
+            Some(Value::from("foo"))
+        );
+        let rejected = uut.signals.get(&REJECTED_ID.to_string()).unwrap();
+        assert!(rejected.emission.last_emitted_value.is_none());
+    }
+
+    #[tokio::test]
+    async fn send_to_cloud_applies_conversion() {
+        let mut mock_cloud_adapter = MockCloudAdapter::new();
+        mock_cloud_adapter
+            .expect_send_to_cloud()
+            .withf(|cloud_message| cloud_message.signal_value == Value::Float(212.0))
+            .once()
+            .returning(|_| Ok(CloudMessageResponse {}));
+
+        let test_signal = Signal {
+            id: "testid".to_string(),
+            value: Some(Value::Int(100)),
+            emission: Emission {
+                policy: EmissionPolicy {
+                    conversion: Conversion::c_to_f(),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        let mut uut = Emitter {
+            signals: Arc::new(SignalStore::new()),
+            cloud_adapter: mock_cloud_adapter,
+            provider_proxy_selector: Arc::new(Mutex::new(MockProviderProxySelector::new())),
+            signal_values_receiver: Mutex::new(unbounded_channel().1),
+            offline_buffer: None,
+        };
+
+        let results = uut.send_to_cloud(vec![test_signal]).await;
+
+        uut.cloud_adapter.checkpoint();
+
+        assert!(results[0].is_ok());
     }
 }
@@ -4,33 +4,84 @@
 
 // Re-export this macro for convenience so users don't need to manually import the proc_macros crate
 pub use proc_macros::freyja_main;
-use tokio::sync::Mutex;
+use tokio::sync::{mpsc::unbounded_channel, Mutex};
 
 mod cartographer;
+mod circuit_breaker;
+mod commander;
+mod config;
 mod emitter;
+mod mapping_cache;
+mod mapping_validator;
+mod offline_buffer;
+mod status_server;
 
-use std::{collections::HashMap, env, str::FromStr, sync::Arc, time::Duration};
+use std::{
+    collections::HashMap,
+    env, fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::Arc,
+};
 
-use crossbeam::queue::SegQueue;
 use env_logger::Target;
-use log::LevelFilter;
+use log::{warn, LevelFilter};
 
 use cartographer::Cartographer;
+use commander::Commander;
+use config::Config;
 use emitter::Emitter;
-use freyja_common::signal_store::SignalStore;
+use freyja_common::{
+    config_utils, mapping_service_health_store::MappingServiceHealthStore, out_dir,
+    resolution_status_store::ResolutionStatusStore, signal_store::SignalStore,
+};
 use freyja_contracts::{
-    cloud_adapter::CloudAdapter, digital_twin_adapter::DigitalTwinAdapter,
-    mapping_client::MappingClient, provider_proxy::SignalValue,
+    cloud_adapter::CloudAdapter,
+    conversion::Conversion,
+    digital_twin_adapter::DigitalTwinAdapter,
+    mapping_client::{GetMappingResponse, MappingClient, VehicleInfo},
+    provider_proxy::ProviderProxyFactory,
 };
+use mapping_cache::MappingCache;
+use mapping_validator::MappingValidator;
+use offline_buffer::{OfflineBuffer, OfflineBufferError};
 use provider_proxy_selector::provider_proxy_selector_impl::ProviderProxySelectorImpl;
+use status_server::StatusServer;
 
+const CONFIG_FILE_STEM: &str = "freyja_config";
+const VALIDATE_MAPPING_COMMAND: &str = "validate-mapping";
+const MAPPING_CACHE_DIR: &str = "mapping_cache";
+const OFFLINE_BUFFER_DIR: &str = "offline_buffer";
+
+/// Runs Freyja with the provided adapters.
+/// If the first argument is `validate-mapping <file>`, the mapping in the file is validated instead
+/// and the result is printed.
+///
+/// # Arguments
+/// - `provider_proxy_factories`: factories for additional provider proxies, keyed by the protocol that they handle.
+/// These are registered in addition to the built-in provider proxies, and replace a built-in proxy for the same protocol.
 pub async fn freyja_main<
     TDigitalTwinAdapter: DigitalTwinAdapter,
     TCloudAdapter: CloudAdapter,
     TMappingClient: MappingClient,
->() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    let args: HashMap<String, String> = env::args()
-        .skip(1)
+>(
+    provider_proxy_factories: Vec<(String, Box<dyn ProviderProxyFactory>)>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let mut args: Vec<String> = env::args().skip(1).collect();
+    let validate_mapping_path = match args.first().map(String::as_str) {
+        Some(VALIDATE_MAPPING_COMMAND) => {
+            let path = args
+                .get(1)
+                .map(PathBuf::from)
+                .ok_or(format!("Usage: {VALIDATE_MAPPING_COMMAND} <file>"))?;
+            args.drain(..2);
+            Some(path)
+        }
+        _ => None,
+    };
+
+    let args: HashMap<String, String> = args
+        .into_iter()
         .map(|arg| {
             let mut split = arg.split('=');
             let key = split
@@ -57,33 +108,168 @@ pub async fn freyja_main<
         .target(Target::Stdout)
         .init();
 
+    let config: Config = config_utils::read_from_files(
+        CONFIG_FILE_STEM,
+        config_utils::JSON_EXT,
+        out_dir!(),
+        |e| format!("Unable to read Freyja config: {e}"),
+        |e| format!("Unable to parse Freyja config: {e}"),
+    )?;
+    config.validate()?;
+
+    // The cloud adapter determines the target metadata which mapping entries require
+    let validator = MappingValidator::new(
+        &config.mapping_validation,
+        TCloudAdapter::required_target_metadata(),
+    );
+
+    if let Some(path) = validate_mapping_path {
+        return validate_mapping_file(&path, &validator);
+    }
+
+    // The cloud adapter is shared by the emitter and the commander
+    let cloud_adapter = Arc::new(TCloudAdapter::create_new()?);
+
     let signal_store = Arc::new(SignalStore::new());
-    let signal_values_queue: Arc<SegQueue<SignalValue>> = Arc::new(SegQueue::new());
-    let provider_proxy_selector = Arc::new(Mutex::new(ProviderProxySelectorImpl::new(
-        signal_values_queue.clone(),
-    )));
+    let resolution_status_store = Arc::new(ResolutionStatusStore::new());
+    let mapping_service_health_store = Arc::new(MappingServiceHealthStore::new());
+    let (signal_values_sender, signal_values_receiver) = unbounded_channel();
+    let mut provider_proxy_selector = ProviderProxySelectorImpl::new(signal_values_sender);
+    for (protocol, factory) in provider_proxy_factories {
+        provider_proxy_selector.register_factory(&protocol, factory);
+    }
+
+    // Describe the vehicle to the mapping service
+    let vehicle = VehicleInfo {
+        vehicle_id: config.vehicle.vehicle_id.clone(),
+        freyja_version: env!("CARGO_PKG_VERSION").to_string(),
+        supported_protocols: provider_proxy_selector.supported_protocols(),
+        supported_conversions: Conversion::SUPPORTED_KINDS
+            .iter()
+            .map(|kind| kind.to_string())
+            .collect(),
+    };
+
+    let provider_proxy_selector = Arc::new(Mutex::new(provider_proxy_selector));
 
     // Setup cartographer
-    let cartographer_poll_interval = Duration::from_secs(5);
+    let mapping_cache = if config.mapping_cache.enabled {
+        // Freyja can still start without the mapping cache, so failing to find its directory isn't fatal
+        let directory = match &config.mapping_cache.directory {
+            Some(directory) => Ok(PathBuf::from(directory)),
+            None => config_utils::freyja_state_dir().map(|dir| dir.join(MAPPING_CACHE_DIR)),
+        };
+
+        match directory {
+            Ok(directory) => Some(MappingCache::new(directory)),
+            Err(e) => {
+                warn!("Unable to find the mapping cache directory, so the mapping won't be cached: {e:?}");
+                None
+            }
+        }
+    } else {
+        None
+    };
+
     let cartographer = Cartographer::new(
         signal_store.clone(),
         TMappingClient::create_new().unwrap(),
         TDigitalTwinAdapter::create_new().unwrap(),
         provider_proxy_selector.clone(),
-        cartographer_poll_interval,
+        config.cartographer.clone(),
+        mapping_cache,
+        vehicle,
+        validator,
+        resolution_status_store.clone(),
+        mapping_service_health_store.clone(),
     );
 
     // Setup emitter
+    let offline_buffer = if config.offline_buffer.enabled {
+        // Freyja can still emit data without the offline buffer, so failing to open it isn't fatal
+        let directory = match &config.offline_buffer.directory {
+            Some(directory) => Ok(PathBuf::from(directory)),
+            None => config_utils::freyja_state_dir().map(|dir| dir.join(OFFLINE_BUFFER_DIR)),
+        };
+
+        match directory
+            .map_err(OfflineBufferError::io)
+            .and_then(|directory| OfflineBuffer::open(&config.offline_buffer, directory))
+        {
+            Ok(offline_buffer) => Some(offline_buffer),
+            Err(e) => {
+                warn!("Unable to open the offline buffer, so messages which can't be sent will be dropped: {e:?}");
+                None
+            }
+        }
+    } else {
+        None
+    };
+
     let emitter = Emitter::new(
         signal_store.clone(),
-        TCloudAdapter::create_new().unwrap(),
+        cloud_adapter.clone(),
+        provider_proxy_selector.clone(),
+        signal_values_receiver,
+        offline_buffer,
+    );
+
+    // Setup commander
+    let commander = Commander::new(
+        signal_store.clone(),
+        cloud_adapter,
         provider_proxy_selector.clone(),
-        signal_values_queue.clone(),
+    );
+
+    // Setup status server
+    let status_server = StatusServer::new(
+        &config.status_server,
+        resolution_status_store,
+        mapping_service_health_store,
     );
 
     tokio::select! {
         Err(e) = cartographer.run() => { println!("[main] cartographer terminated with error {e:?}"); Err(e) },
         Err(e) = emitter.run() => { println!("[main] emitter terminated with error {e:?}"); Err(e) },
+        Err(e) = commander.run() => { println!("[main] commander terminated with error {e:?}"); Err(e) },
+        Err(e) = status_server.run(), if config.status_server.enabled => { println!("[main] status server terminated with error {e:?}"); Err(e) },
         else => { println!("[main] all operations terminated successfully"); Ok(()) },
     }
 }
+
+/// Validates the mapping in a file with the same checks that the cartographer applies, and prints the result.
+/// The file contains a mapping in the JSON format returned by the mapping service's `get_mapping` API.
+/// Returns an error if the file can't be read or if any entries are rejected.
+///
+/// # Arguments
+/// - `path`: the path to the file
+/// - `validator`: the validator
+fn validate_mapping_file(
+    path: &Path,
+    validator: &MappingValidator,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let contents = fs::read_to_string(path)
+        .map_err(|e| format!("Unable to read mapping file {}: {e}", path.display()))?;
+    let mapping: GetMappingResponse = serde_json::from_str(&contents)
+        .map_err(|e| format!("Unable to parse mapping file {}: {e}", path.display()))?;
+
+    let rejected_entries = validator.validate(&mapping.map);
+    for rejected_entry in rejected_entries.iter() {
+        println!("Rejected {}:", rejected_entry.id);
+        for reason in rejected_entry.reasons.iter() {
+            println!("  - {reason}");
+        }
+    }
+
+    if rejected_entries.is_empty() {
+        println!("All {} mapping entries are valid", mapping.map.len());
+        Ok(())
+    } else {
+        Err(format!(
+            "{} of {} mapping entries were rejected",
+            rejected_entries.len(),
+            mapping.map.len()
+        )
+        .into())
+    }
+}
@@ -0,0 +1,159 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+// SPDX-License-Identifier: MIT
+
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use freyja_contracts::{entity::Entity, mapping_client::GetMappingResponse};
+
+const MAPPING_FILENAME: &str = "mapping.json";
+const TEMP_MAPPING_FILENAME: &str = "mapping.json.tmp";
+
+/// The last mapping which was successfully applied by the cartographer
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CachedMapping {
+    /// The mapping from the mapping service
+    pub mapping: GetMappingResponse,
+
+    /// The resolved sources of the mapped signals, keyed by signal id.
+    /// Signals whose entities could not be resolved are not included.
+    pub entities: HashMap<String, Entity>,
+}
+
+/// A persistent cache of the last applied mapping.
+/// This allows Freyja to start emitting data after a restart even if the mapping service is not reachable.
+#[derive(Debug)]
+pub struct MappingCache {
+    /// The directory where the mapping is stored
+    directory: PathBuf,
+}
+
+impl MappingCache {
+    /// Creates a mapping cache which stores the mapping in the specified directory.
+    /// The directory is created when a mapping is first stored.
+    ///
+    /// # Arguments
+    /// - `directory`: the directory to store the mapping in
+    pub fn new<P: AsRef<Path>>(directory: P) -> Self {
+        Self {
+            directory: directory.as_ref().to_path_buf(),
+        }
+    }
+
+    /// Loads the cached mapping. Returns `None` if no mapping has been stored.
+    pub fn load(&self) -> Result<Option<CachedMapping>, MappingCacheError> {
+        let bytes = match fs::read(self.directory.join(MAPPING_FILENAME)) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(MappingCacheError::io(e)),
+        };
+
+        serde_json::from_slice(&bytes)
+            .map(Some)
+            .map_err(MappingCacheError::deserialize)
+    }
+
+    /// Stores the mapping, replacing any previously cached mapping.
+    /// The mapping is written to a temporary file first so that an interrupted write doesn't corrupt the cache.
+    ///
+    /// # Arguments
+    /// - `mapping`: the mapping to store
+    pub fn store(&self, mapping: &CachedMapping) -> Result<(), MappingCacheError> {
+        let bytes = serde_json::to_vec(mapping).map_err(MappingCacheError::serialize)?;
+
+        fs::create_dir_all(&self.directory).map_err(MappingCacheError::io)?;
+
+        let temp_path = self.directory.join(TEMP_MAPPING_FILENAME);
+        fs::write(&temp_path, bytes).map_err(MappingCacheError::io)?;
+        fs::rename(&temp_path, self.directory.join(MAPPING_FILENAME)).map_err(MappingCacheError::io)
+    }
+}
+
+proc_macros::error! {
+    MappingCacheError {
+        Io,
+        Serialize,
+        Deserialize
+    }
+}
+
+#[cfg(test)]
+mod mapping_cache_tests {
+    use super::*;
+
+    use tempfile::TempDir;
+
+    use freyja_contracts::digital_twin_map_entry::DigitalTwinMapEntry;
+
+    #[test]
+    fn load_returns_none_when_nothing_is_cached() {
+        let dir = TempDir::new().unwrap();
+        let uut = MappingCache::new(dir.path().join("mapping_cache"));
+
+        assert!(uut.load().unwrap().is_none());
+    }
+
+    #[test]
+    fn stored_mapping_is_loaded() {
+        const ID: &str = "testid";
+
+        let dir = TempDir::new().unwrap();
+        let uut = MappingCache::new(dir.path().join("mapping_cache"));
+
+        let entity = Entity {
+            id: ID.to_string(),
+            uri: "uri".to_string(),
+            operation: "Subscribe".to_string(),
+            protocol: "grpc".to_string(),
+            ..Default::default()
+        };
+
+        uut.store(&CachedMapping {
+            mapping: GetMappingResponse {
+                map: [(
+                    ID.to_string(),
+                    DigitalTwinMapEntry {
+                        source: ID.to_string(),
+                        interval_ms: 42,
+                        ..Default::default()
+                    },
+                )]
+                .into_iter()
+                .collect(),
+                version: Some(3),
+            },
+            entities: [(ID.to_string(), entity.clone())].into_iter().collect(),
+        })
+        .unwrap();
+
+        let cached = MappingCache::new(dir.path().join("mapping_cache"))
+            .load()
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(cached.mapping.map.len(), 1);
+        assert_eq!(cached.mapping.map[ID].interval_ms, 42);
+        assert_eq!(cached.mapping.version, Some(3));
+        assert_eq!(cached.entities[ID], entity);
+    }
+
+    #[test]
+    fn load_fails_for_corrupt_mapping() {
+        let dir = TempDir::new().unwrap();
+        fs::write(dir.path().join(MAPPING_FILENAME), "not json").unwrap();
+
+        let result = MappingCache::new(dir.path()).load();
+
+        assert!(result.is_err());
+        assert_eq!(
+            result.err().unwrap().kind(),
+            MappingCacheErrorKind::Deserialize
+        );
+    }
+}
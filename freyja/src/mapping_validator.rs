@@ -0,0 +1,323 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+// SPDX-License-Identifier: MIT
+
+use std::collections::{hash_map::Entry, BTreeMap, HashMap};
+
+use log::warn;
+
+use crate::config::MappingValidationConfig;
+use freyja_contracts::{
+    conversion::Conversion, digital_twin_map_entry::DigitalTwinMapEntry,
+    mapping_client::RejectedMappingEntry,
+};
+
+/// Validates mapping entries before they are applied so that invalid entries can be rejected
+/// instead of causing errors while signals are emitted
+pub struct MappingValidator {
+    /// The minimum emission interval, in milliseconds
+    min_interval_ms: u64,
+
+    /// The maximum emission interval, in milliseconds
+    max_interval_ms: u64,
+
+    /// The keys which the cloud adapter requires in the target metadata of each entry
+    required_target_metadata: Vec<String>,
+}
+
+impl MappingValidator {
+    /// Creates a new MappingValidator
+    ///
+    /// # Arguments
+    /// - `config`: the validation config
+    /// - `required_target_metadata`: the keys which the cloud adapter requires in the target metadata of each entry
+    pub fn new(config: &MappingValidationConfig, required_target_metadata: Vec<String>) -> Self {
+        Self {
+            min_interval_ms: config.min_interval_ms,
+            max_interval_ms: config.max_interval_ms,
+            required_target_metadata,
+        }
+    }
+
+    /// Validates a mapping and returns the rejected entries, ordered by id.
+    /// If several entries have the same target, the valid entry with the lowest id is accepted and the others are rejected.
+    /// Entries which can't convert commands from the cloud are accepted with a warning, since they may only be used for emission.
+    ///
+    /// # Arguments
+    /// - `map`: the mapping entries, keyed by signal id
+    pub fn validate(
+        &self,
+        map: &HashMap<String, DigitalTwinMapEntry>,
+    ) -> Vec<RejectedMappingEntry> {
+        let mut ids: Vec<&String> = map.keys().collect();
+        ids.sort();
+
+        let mut targets = HashMap::new();
+        let mut rejected_entries = Vec::new();
+        for id in ids {
+            let entry = &map[id];
+            let mut reasons = self.validate_entry(entry);
+
+            // Without a reverse conversion, values from the cloud are converted with the inverse of the conversion
+            if entry.reverse_conversion.is_none() {
+                if let Err(e) = entry.conversion.validate_inverse() {
+                    warn!("The conversion of mapping entry {id} can't be inverted, so commands for it will fail unless it has a reverse conversion: {e:?}");
+                }
+            }
+
+            if reasons.is_empty() {
+                let target: BTreeMap<&String, &String> = entry.target.iter().collect();
+                match targets.entry(target) {
+                    Entry::Occupied(other) => reasons.push(format!(
+                        "The target is the same as the target of {}",
+                        other.get()
+                    )),
+                    Entry::Vacant(vacant) => {
+                        vacant.insert(id);
+                    }
+                }
+            }
+
+            if !reasons.is_empty() {
+                rejected_entries.push(RejectedMappingEntry {
+                    id: id.clone(),
+                    reasons,
+                });
+            }
+        }
+
+        rejected_entries
+    }
+
+    /// Validates a single mapping entry. Returns the reasons that the entry is invalid, if any.
+    ///
+    /// # Arguments
+    /// - `entry`: the entry to validate
+    fn validate_entry(&self, entry: &DigitalTwinMapEntry) -> Vec<String> {
+        let mut reasons = Vec::new();
+
+        if entry.interval_ms < self.min_interval_ms || entry.interval_ms > self.max_interval_ms {
+            reasons.push(format!(
+                "The interval of {}ms is outside of the allowed range of {}ms to {}ms",
+                entry.interval_ms, self.min_interval_ms, self.max_interval_ms
+            ));
+        }
+
+        if let Err(e) = entry.conversion.validate() {
+            reasons.push(format!("The conversion is invalid: {e:?}"));
+        }
+
+        if let Some(Err(e)) = entry.reverse_conversion.as_ref().map(Conversion::validate) {
+            reasons.push(format!("The reverse conversion is invalid: {e:?}"));
+        }
+
+        for key in self.required_target_metadata.iter() {
+            if !entry.target.contains_key(key) {
+                reasons.push(format!(
+                    "The target metadata is missing the required key {key}"
+                ));
+            }
+        }
+
+        reasons
+    }
+}
+
+#[cfg(test)]
+mod mapping_validator_tests {
+    use super::*;
+
+    use freyja_contracts::value::Value;
+
+    fn test_entry(target: &str) -> DigitalTwinMapEntry {
+        DigitalTwinMapEntry {
+            target: [("instance_id".to_string(), target.to_string())]
+                .into_iter()
+                .collect(),
+            interval_ms: 1000,
+            ..Default::default()
+        }
+    }
+
+    fn test_validator() -> MappingValidator {
+        MappingValidator::new(
+            &MappingValidationConfig {
+                min_interval_ms: 100,
+                max_interval_ms: 10000,
+            },
+            vec!["instance_id".to_string()],
+        )
+    }
+
+    /// Validates the entries and returns the ids of the rejected entries
+    fn rejected_ids(entries: Vec<(&str, DigitalTwinMapEntry)>) -> Vec<String> {
+        let map = entries
+            .into_iter()
+            .map(|(id, entry)| (id.to_string(), entry))
+            .collect();
+
+        test_validator()
+            .validate(&map)
+            .into_iter()
+            .map(|r| r.id)
+            .collect()
+    }
+
+    #[test]
+    fn validate_rejects_invalid_entries() {
+        let uut = test_validator();
+
+        let map = [
+            ("valid", test_entry("valid")),
+            (
+                "zero_interval",
+                DigitalTwinMapEntry {
+                    interval_ms: 0,
+                    ..test_entry("zero_interval")
+                },
+            ),
+            (
+                "bad_conversion",
+                DigitalTwinMapEntry {
+                    conversion: Conversion::Linear {
+                        mul: 0.0,
+                        offset: 1.0,
+                    },
+                    ..test_entry("bad_conversion")
+                },
+            ),
+            (
+                "missing_metadata",
+                DigitalTwinMapEntry {
+                    target: HashMap::new(),
+                    ..test_entry("")
+                },
+            ),
+            ("a_duplicate", test_entry("duplicate")),
+            ("b_duplicate", test_entry("duplicate")),
+        ]
+        .into_iter()
+        .map(|(id, entry)| (id.to_string(), entry))
+        .collect();
+
+        let rejected_entries = uut.validate(&map);
+
+        let rejected_ids: Vec<&str> = rejected_entries.iter().map(|r| r.id.as_str()).collect();
+        assert_eq!(
+            rejected_ids,
+            vec![
+                "b_duplicate",
+                "bad_conversion",
+                "missing_metadata",
+                "zero_interval"
+            ]
+        );
+        assert!(rejected_entries.iter().all(|r| r.reasons.len() == 1));
+        assert!(rejected_entries[0].reasons[0].contains("a_duplicate"));
+    }
+
+    #[test]
+    fn validate_accepts_intervals_at_the_boundaries() {
+        let with_interval = |target: &str, interval_ms: u64| DigitalTwinMapEntry {
+            interval_ms,
+            ..test_entry(target)
+        };
+
+        assert_eq!(
+            rejected_ids(vec![
+                ("below_min", with_interval("below_min", 99)),
+                ("min", with_interval("min", 100)),
+                ("max", with_interval("max", 10000)),
+                ("above_max", with_interval("above_max", 10001)),
+            ]),
+            vec!["above_max", "below_min"]
+        );
+    }
+
+    #[test]
+    fn validate_rejects_invalid_reverse_conversion() {
+        let rejected_entries = test_validator().validate(
+            &[(
+                "bad_reverse_conversion".to_string(),
+                DigitalTwinMapEntry {
+                    reverse_conversion: Some(Conversion::Clamp { min: 2.0, max: 1.0 }),
+                    ..test_entry("bad_reverse_conversion")
+                },
+            )]
+            .into_iter()
+            .collect(),
+        );
+
+        assert_eq!(rejected_entries.len(), 1);
+        assert_eq!(rejected_entries[0].reasons.len(), 1);
+        assert!(rejected_entries[0].reasons[0].contains("reverse conversion is invalid"));
+    }
+
+    #[test]
+    fn validate_accepts_tables_which_cannot_be_inverted() {
+        let non_monotonic = Conversion::PiecewiseLinear {
+            points: vec![(0.0, 0.0), (5.0, 10.0), (10.0, 0.0)],
+        };
+        let many_to_one = Conversion::Lookup {
+            map: [
+                ("1".to_string(), Value::from("On")),
+                ("2".to_string(), Value::from("On")),
+                ("3".to_string(), Value::from("Off")),
+            ]
+            .into_iter()
+            .collect(),
+            default: None,
+        };
+
+        // Entries which are only used for emission don't need their conversion to be inverted
+        assert!(rejected_ids(vec![
+            (
+                "non_monotonic",
+                DigitalTwinMapEntry {
+                    conversion: non_monotonic.clone(),
+                    ..test_entry("non_monotonic")
+                },
+            ),
+            (
+                "non_monotonic_with_reverse_conversion",
+                DigitalTwinMapEntry {
+                    conversion: non_monotonic,
+                    reverse_conversion: Some(Conversion::None),
+                    ..test_entry("non_monotonic_with_reverse_conversion")
+                },
+            ),
+            (
+                "many_to_one_lookup",
+                DigitalTwinMapEntry {
+                    conversion: many_to_one,
+                    ..test_entry("many_to_one_lookup")
+                },
+            ),
+        ])
+        .is_empty());
+    }
+
+    #[test]
+    fn validate_accepts_duplicate_target_when_lower_id_is_invalid() {
+        let rejected_entries = test_validator().validate(
+            &[
+                (
+                    "a_duplicate".to_string(),
+                    DigitalTwinMapEntry {
+                        interval_ms: 0,
+                        ..test_entry("duplicate")
+                    },
+                ),
+                ("b_duplicate".to_string(), test_entry("duplicate")),
+                ("c_duplicate".to_string(), test_entry("duplicate")),
+            ]
+            .into_iter()
+            .collect(),
+        );
+
+        let rejected_ids: Vec<&str> = rejected_entries.iter().map(|r| r.id.as_str()).collect();
+        assert_eq!(rejected_ids, vec!["a_duplicate", "c_duplicate"]);
+        assert!(rejected_entries[0].reasons[0].contains("interval"));
+        assert!(rejected_entries[1].reasons[0].contains("b_duplicate"));
+    }
+}
@@ -0,0 +1,689 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+// SPDX-License-Identifier: MIT
+
+use std::{
+    collections::{HashMap, VecDeque},
+    fs,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::config::{DropPolicy, OfflineBufferConfig};
+use freyja_contracts::cloud_adapter::CloudMessageRequest;
+
+const MESSAGE_FILE_EXT: &str = "json";
+const TEMP_FILE_EXT: &str = "tmp";
+
+/// A message stored in the offline buffer
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct BufferedMessage {
+    /// The id of the signal that the message was created for
+    signal_id: String,
+
+    /// The priority of the message
+    priority: u32,
+
+    /// The time that the message was buffered, in milliseconds since the unix epoch
+    buffered_at_ms: u64,
+
+    /// The message to send to the cloud
+    request: CloudMessageRequest,
+}
+
+/// An entry in the in-memory index of the offline buffer
+#[derive(Debug)]
+struct BufferEntry {
+    /// The sequence number of the message, which determines its position in the buffer and its filename
+    sequence: u64,
+
+    /// The size of the serialized message in bytes
+    size_bytes: u64,
+
+    /// The buffered message
+    message: BufferedMessage,
+}
+
+/// The mutable state of the offline buffer
+#[derive(Debug, Default)]
+struct BufferState {
+    /// The buffered messages, ordered from oldest to newest
+    entries: VecDeque<BufferEntry>,
+
+    /// The sequence number to use for the next message
+    next_sequence: u64,
+
+    /// The total size of the buffered messages in bytes
+    size_bytes: u64,
+}
+
+/// A persistent, disk-backed queue of messages which could not be sent to the cloud.
+/// Each message is stored as a separate file so that the buffer survives restarts,
+/// and an in-memory index is kept for ordering and enforcing size caps.
+/// Suitable for use as `Arc<OfflineBuffer>`.
+#[derive(Debug)]
+pub struct OfflineBuffer {
+    /// The directory where messages are stored
+    directory: PathBuf,
+
+    /// The maximum number of buffered messages
+    max_messages: usize,
+
+    /// The maximum total size of the buffered messages in bytes
+    max_size_bytes: u64,
+
+    /// The time after which messages expire, in milliseconds
+    ttl_ms: Option<u64>,
+
+    /// The policy for dropping messages when the buffer is full
+    drop_policy: DropPolicy,
+
+    /// The priority of each signal
+    signal_priorities: HashMap<String, u32>,
+
+    /// The buffer state
+    state: Mutex<BufferState>,
+}
+
+impl OfflineBuffer {
+    /// Opens the offline buffer in the specified directory, creating the directory if necessary.
+    /// Any messages left over from a previous run are loaded, and expired or unreadable messages are discarded.
+    ///
+    /// # Arguments
+    /// - `config`: the buffer config
+    /// - `directory`: the directory to store messages in
+    pub fn open<P: AsRef<Path>>(
+        config: &OfflineBufferConfig,
+        directory: P,
+    ) -> Result<Self, OfflineBufferError> {
+        let directory = directory.as_ref().to_path_buf();
+        fs::create_dir_all(&directory).map_err(OfflineBufferError::io)?;
+
+        let mut entries = Vec::new();
+        for dir_entry in fs::read_dir(&directory).map_err(OfflineBufferError::io)? {
+            let path = dir_entry.map_err(OfflineBufferError::io)?.path();
+
+            let sequence = match Self::sequence_from_path(&path) {
+                Some(sequence) => sequence,
+                None => {
+                    // Leftovers from interrupted writes are removed, anything else is ignored
+                    if path.extension().is_some_and(|ext| ext == TEMP_FILE_EXT) {
+                        let _ = fs::remove_file(&path);
+                    }
+
+                    continue;
+                }
+            };
+
+            let loaded = fs::read(&path)
+                .map_err(OfflineBufferError::io)
+                .and_then(|bytes| {
+                    serde_json::from_slice::<BufferedMessage>(&bytes)
+                        .map(|message| (bytes.len() as u64, message))
+                        .map_err(OfflineBufferError::deserialize)
+                });
+
+            match loaded {
+                Ok((size_bytes, message)) => entries.push(BufferEntry {
+                    sequence,
+                    size_bytes,
+                    message,
+                }),
+                Err(e) => {
+                    warn!("Discarding unreadable buffered message {path:?}: {e}");
+                    let _ = fs::remove_file(&path);
+                }
+            }
+        }
+
+        entries.sort_by_key(|entry| entry.sequence);
+
+        let state = BufferState {
+            next_sequence: entries.last().map_or(0, |entry| entry.sequence + 1),
+            size_bytes: entries.iter().map(|entry| entry.size_bytes).sum(),
+            entries: entries.into(),
+        };
+
+        let buffer = Self {
+            directory,
+            max_messages: config.max_messages,
+            max_size_bytes: config.max_size_bytes,
+            ttl_ms: config.ttl_s.map(|ttl_s| ttl_s.saturating_mul(1000)),
+            drop_policy: config.drop_policy,
+            signal_priorities: config
+                .signal_priorities
+                .iter()
+                .map(|p| (p.signal_id.clone(), p.priority))
+                .collect(),
+            state: Mutex::new(state),
+        };
+
+        {
+            let mut state = buffer.state.lock().unwrap();
+            buffer.discard_expired(&mut state, Self::now_ms());
+            debug!(
+                "Opened offline buffer at {:?} with {} message(s)",
+                buffer.directory,
+                state.entries.len()
+            );
+        }
+
+        Ok(buffer)
+    }
+
+    /// Gets the number of buffered messages
+    pub fn len(&self) -> usize {
+        self.state.lock().unwrap().entries.len()
+    }
+
+    /// Checks whether the buffer is empty
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Adds a message to the end of the buffer.
+    /// If the buffer is full, messages are dropped according to the drop policy.
+    ///
+    /// # Arguments
+    /// - `signal_id`: the id of the signal that the message was created for
+    /// - `request`: the message to buffer
+    pub fn push(
+        &self,
+        signal_id: &str,
+        request: CloudMessageRequest,
+    ) -> Result<(), OfflineBufferError> {
+        self.push_at(signal_id, request, Self::now_ms())
+    }
+
+    /// Gets up to `count` of the oldest messages in the buffer along with their sequence numbers,
+    /// discarding any expired messages. The messages remain in the buffer until they are removed with `remove`.
+    ///
+    /// # Arguments
+    /// - `count`: the maximum number of messages to get
+    pub fn peek(&self, count: usize) -> Vec<(u64, CloudMessageRequest)> {
+        self.peek_at(count, Self::now_ms())
+    }
+
+    /// Removes a message from the buffer.
+    /// Returns `true` if the message was in the buffer.
+    ///
+    /// # Arguments
+    /// - `sequence`: the sequence number of the message to remove
+    pub fn remove(&self, sequence: u64) -> Result<bool, OfflineBufferError> {
+        let mut state = self.state.lock().unwrap();
+
+        match state.entries.iter().position(|e| e.sequence == sequence) {
+            Some(index) => {
+                self.remove_at(&mut state, index)?;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    /// Adds a message to the end of the buffer as if it was buffered at the specified time
+    ///
+    /// # Arguments
+    /// - `signal_id`: the id of the signal that the message was created for
+    /// - `request`: the message to buffer
+    /// - `now_ms`: the current time in milliseconds since the unix epoch
+    fn push_at(
+        &self,
+        signal_id: &str,
+        request: CloudMessageRequest,
+        now_ms: u64,
+    ) -> Result<(), OfflineBufferError> {
+        let message = BufferedMessage {
+            signal_id: signal_id.to_string(),
+            priority: self.signal_priorities.get(signal_id).copied().unwrap_or(0),
+            buffered_at_ms: now_ms,
+            request,
+        };
+
+        let bytes = serde_json::to_vec(&message).map_err(OfflineBufferError::serialize)?;
+        let size_bytes = bytes.len() as u64;
+
+        let mut state = self.state.lock().unwrap();
+        self.discard_expired(&mut state, now_ms);
+
+        if size_bytes > self.max_size_bytes || self.max_messages == 0 {
+            warn!("Dropping message for signal {signal_id} since it can never fit in the offline buffer");
+            return Ok(());
+        }
+
+        // Make room for the new message. The victims are chosen up front so that nothing is dropped
+        // unless enough room can be made for the new message.
+        let victims = match self.select_victims(&state, message.priority, size_bytes) {
+            Some(victims) => victims,
+            None => {
+                warn!("Offline buffer is full, dropping new message for signal {signal_id}");
+                return Ok(());
+            }
+        };
+
+        for victim in victims {
+            // Entries can only be found here since the state has been locked since the victims were selected
+            let index = state
+                .entries
+                .iter()
+                .position(|e| e.sequence == victim)
+                .unwrap();
+
+            let dropped = self.remove_at(&mut state, index)?;
+            warn!(
+                "Offline buffer is full, dropped message for signal {}",
+                dropped.message.signal_id
+            );
+        }
+
+        let sequence = state.next_sequence;
+        let path = self.path_for(sequence);
+        let temp_path = path.with_extension(TEMP_FILE_EXT);
+
+        // Write to a temporary file first so that a crash never leaves a partial message behind
+        fs::write(&temp_path, &bytes).map_err(OfflineBufferError::io)?;
+        fs::rename(&temp_path, &path).map_err(OfflineBufferError::io)?;
+
+        state.next_sequence += 1;
+        state.size_bytes += size_bytes;
+        state.entries.push_back(BufferEntry {
+            sequence,
+            size_bytes,
+            message,
+        });
+
+        Ok(())
+    }
+
+    /// Gets up to `count` of the oldest messages in the buffer as of the specified time
+    ///
+    /// # Arguments
+    /// - `count`: the maximum number of messages to get
+    /// - `now_ms`: the current time in milliseconds since the unix epoch
+    fn peek_at(&self, count: usize, now_ms: u64) -> Vec<(u64, CloudMessageRequest)> {
+        let mut state = self.state.lock().unwrap();
+        self.discard_expired(&mut state, now_ms);
+
+        state
+            .entries
+            .iter()
+            .take(count)
+            .map(|entry| (entry.sequence, entry.message.request.clone()))
+            .collect()
+    }
+
+    /// Selects the messages to drop so that a new message fits in the buffer, according to the drop policy.
+    /// Returns the sequence numbers of the messages to drop,
+    /// or `None` if enough room can't be made without dropping messages with a higher priority than the new message.
+    ///
+    /// # Arguments
+    /// - `state`: the buffer state
+    /// - `priority`: the priority of the new message
+    /// - `size_bytes`: the size of the new message in bytes
+    fn select_victims(
+        &self,
+        state: &BufferState,
+        priority: u32,
+        size_bytes: u64,
+    ) -> Option<Vec<u64>> {
+        let mut candidates: Vec<&BufferEntry> = state.entries.iter().collect();
+        if self.drop_policy == DropPolicy::LowestPriority {
+            candidates.retain(|e| e.message.priority <= priority);
+            candidates.sort_by_key(|e| (e.message.priority, e.sequence));
+        }
+
+        let mut remaining_messages = state.entries.len();
+        let mut remaining_size_bytes = state.size_bytes;
+        let mut victims = Vec::new();
+        for candidate in candidates {
+            if remaining_messages < self.max_messages
+                && remaining_size_bytes + size_bytes <= self.max_size_bytes
+            {
+                break;
+            }
+
+            victims.push(candidate.sequence);
+            remaining_messages -= 1;
+            remaining_size_bytes -= candidate.size_bytes;
+        }
+
+        if remaining_messages < self.max_messages
+            && remaining_size_bytes + size_bytes <= self.max_size_bytes
+        {
+            Some(victims)
+        } else {
+            None
+        }
+    }
+
+    /// Discards all expired messages
+    ///
+    /// # Arguments
+    /// - `state`: the buffer state
+    /// - `now_ms`: the current time in milliseconds since the unix epoch
+    fn discard_expired(&self, state: &mut BufferState, now_ms: u64) {
+        let ttl_ms = match self.ttl_ms {
+            Some(ttl_ms) => ttl_ms,
+            None => return,
+        };
+
+        let mut index = 0;
+        while index < state.entries.len() {
+            let buffered_at_ms = state.entries[index].message.buffered_at_ms;
+            if now_ms.saturating_sub(buffered_at_ms) <= ttl_ms {
+                index += 1;
+                continue;
+            }
+
+            match self.remove_at(state, index) {
+                Ok(expired) => debug!(
+                    "Discarded expired message for signal {}",
+                    expired.message.signal_id
+                ),
+                Err(e) => {
+                    warn!("Unable to discard expired message: {e}");
+                    index += 1;
+                }
+            }
+        }
+    }
+
+    /// Removes the entry at the specified index from the buffer and deletes its file
+    ///
+    /// # Arguments
+    /// - `state`: the buffer state
+    /// - `index`: the index of the entry to remove
+    fn remove_at(
+        &self,
+        state: &mut BufferState,
+        index: usize,
+    ) -> Result<BufferEntry, OfflineBufferError> {
+        let sequence = state.entries[index].sequence;
+        match fs::remove_file(self.path_for(sequence)) {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(OfflineBufferError::io(e)),
+        }
+
+        let entry = state.entries.remove(index).unwrap();
+        state.size_bytes -= entry.size_bytes;
+
+        Ok(entry)
+    }
+
+    /// Gets the path of the file for a message
+    ///
+    /// # Arguments
+    /// - `sequence`: the sequence number of the message
+    fn path_for(&self, sequence: u64) -> PathBuf {
+        // Zero-padding keeps the files sorted when listed
+        self.directory
+            .join(format!("{sequence:020}.{MESSAGE_FILE_EXT}"))
+    }
+
+    /// Gets the sequence number of a message from its path, or `None` if the path isn't a message file
+    ///
+    /// # Arguments
+    /// - `path`: the path to parse
+    fn sequence_from_path(path: &Path) -> Option<u64> {
+        if path.extension()? != MESSAGE_FILE_EXT {
+            return None;
+        }
+
+        path.file_stem()?.to_str()?.parse().ok()
+    }
+
+    /// Gets the current time in milliseconds since the unix epoch
+    fn now_ms() -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_millis() as u64)
+    }
+}
+
+proc_macros::error! {
+    OfflineBufferError {
+        Io,
+        Serialize,
+        Deserialize
+    }
+}
+
+#[cfg(test)]
+mod offline_buffer_tests {
+    use super::*;
+
+    use std::collections::HashMap;
+
+    use tempfile::TempDir;
+
+    use crate::config::SignalPriority;
+    use freyja_contracts::value::Value;
+
+    fn test_config() -> OfflineBufferConfig {
+        OfflineBufferConfig {
+            enabled: true,
+            directory: None,
+            max_messages: 100,
+            max_size_bytes: 1024 * 1024,
+            ttl_s: None,
+            drop_policy: DropPolicy::OldestFirst,
+            signal_priorities: Vec::new(),
+        }
+    }
+
+    fn test_request(value: &str) -> CloudMessageRequest {
+        CloudMessageRequest {
+            cloud_signal: HashMap::new(),
+            signal_value: Value::from(value),
+            signal_timestamp: "timestamp".to_string(),
+        }
+    }
+
+    fn message_size(signal_id: &str, priority: u32, value: &str) -> u64 {
+        serde_json::to_vec(&BufferedMessage {
+            signal_id: signal_id.to_string(),
+            priority,
+            buffered_at_ms: 0,
+            request: test_request(value),
+        })
+        .unwrap()
+        .len() as u64
+    }
+
+    /// Removes every message from the buffer and returns their values in order
+    fn drain(uut: &OfflineBuffer) -> Vec<String> {
+        let mut result = Vec::new();
+        for (sequence, request) in uut.peek(usize::MAX) {
+            assert!(uut.remove(sequence).unwrap());
+            result.push(request.signal_value.to_string());
+        }
+
+        assert!(uut.is_empty());
+        result
+    }
+
+    #[test]
+    fn messages_are_returned_in_order() {
+        let dir = TempDir::new().unwrap();
+        let uut = OfflineBuffer::open(&test_config(), dir.path()).unwrap();
+
+        for value in ["1", "2", "3"] {
+            uut.push("signal", test_request(value)).unwrap();
+        }
+
+        assert_eq!(uut.len(), 3);
+        assert_eq!(drain(&uut), vec!["1", "2", "3"]);
+        assert!(uut.is_empty());
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn messages_survive_reopening() {
+        let dir = TempDir::new().unwrap();
+        {
+            let uut = OfflineBuffer::open(&test_config(), dir.path()).unwrap();
+            for value in ["1", "2"] {
+                uut.push("signal", test_request(value)).unwrap();
+            }
+        }
+
+        let uut = OfflineBuffer::open(&test_config(), dir.path()).unwrap();
+        uut.push("signal", test_request("3")).unwrap();
+
+        assert_eq!(drain(&uut), vec!["1", "2", "3"]);
+    }
+
+    #[test]
+    fn open_discards_unreadable_messages() {
+        let dir = TempDir::new().unwrap();
+        {
+            let uut = OfflineBuffer::open(&test_config(), dir.path()).unwrap();
+            uut.push("signal", test_request("1")).unwrap();
+        }
+
+        fs::write(dir.path().join("00000000000000000005.json"), "not json").unwrap();
+        fs::write(dir.path().join("00000000000000000006.tmp"), "partial").unwrap();
+
+        let uut = OfflineBuffer::open(&test_config(), dir.path()).unwrap();
+
+        assert_eq!(drain(&uut), vec!["1"]);
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn peek_returns_oldest_messages() {
+        let dir = TempDir::new().unwrap();
+        let uut = OfflineBuffer::open(&test_config(), dir.path()).unwrap();
+
+        for value in ["1", "2", "3"] {
+            uut.push("signal", test_request(value)).unwrap();
+        }
+
+        let messages: Vec<_> = uut
+            .peek(2)
+            .into_iter()
+            .map(|(_, request)| request.signal_value.to_string())
+            .collect();
+
+        assert_eq!(messages, vec!["1", "2"]);
+        assert_eq!(uut.len(), 3);
+    }
+
+    #[test]
+    fn oldest_first_drops_oldest_message() {
+        let dir = TempDir::new().unwrap();
+        let config = OfflineBufferConfig {
+            max_messages: 2,
+            ..test_config()
+        };
+        let uut = OfflineBuffer::open(&config, dir.path()).unwrap();
+
+        for value in ["1", "2", "3"] {
+            uut.push("signal", test_request(value)).unwrap();
+        }
+
+        assert_eq!(drain(&uut), vec!["2", "3"]);
+    }
+
+    #[test]
+    fn size_cap_drops_messages() {
+        let dir = TempDir::new().unwrap();
+        let config = OfflineBufferConfig {
+            max_size_bytes: message_size("signal", 0, "1") * 2,
+            ..test_config()
+        };
+        let uut = OfflineBuffer::open(&config, dir.path()).unwrap();
+
+        for value in ["1", "2", "3"] {
+            uut.push_at("signal", test_request(value), 0).unwrap();
+        }
+
+        assert_eq!(drain(&uut), vec!["2", "3"]);
+    }
+
+    #[test]
+    fn lowest_priority_drops_low_priority_messages() {
+        let dir = TempDir::new().unwrap();
+        let config = OfflineBufferConfig {
+            max_messages: 2,
+            drop_policy: DropPolicy::LowestPriority,
+            signal_priorities: vec![SignalPriority {
+                signal_id: "important".to_string(),
+                priority: 10,
+            }],
+            ..test_config()
+        };
+        let uut = OfflineBuffer::open(&config, dir.path()).unwrap();
+
+        uut.push("important", test_request("1")).unwrap();
+        uut.push("other", test_request("2")).unwrap();
+        uut.push("important", test_request("3")).unwrap();
+        // This message has a lower priority than everything in the buffer, so it gets dropped
+        uut.push("other", test_request("4")).unwrap();
+
+        assert_eq!(drain(&uut), vec!["1", "3"]);
+    }
+
+    #[test]
+    fn lowest_priority_drops_nothing_when_new_message_does_not_fit() {
+        let dir = TempDir::new().unwrap();
+        let low_size = message_size("low", 0, "1");
+        let high_size = message_size("high", 10, "2");
+        let medium_size = message_size("medium", 5, "3");
+
+        // The new message only fits if both buffered messages are dropped
+        assert!(medium_size <= low_size + high_size);
+        assert!(medium_size > high_size);
+
+        let config = OfflineBufferConfig {
+            max_size_bytes: low_size + high_size,
+            drop_policy: DropPolicy::LowestPriority,
+            signal_priorities: vec![
+                SignalPriority {
+                    signal_id: "high".to_string(),
+                    priority: 10,
+                },
+                SignalPriority {
+                    signal_id: "medium".to_string(),
+                    priority: 5,
+                },
+            ],
+            ..test_config()
+        };
+        let uut = OfflineBuffer::open(&config, dir.path()).unwrap();
+
+        uut.push_at("low", test_request("1"), 0).unwrap();
+        uut.push_at("high", test_request("2"), 0).unwrap();
+        // Dropping the low priority message doesn't make enough room and the high priority message must be kept,
+        // so the new message is dropped and the buffer is unchanged
+        uut.push_at("medium", test_request("3"), 0).unwrap();
+
+        assert_eq!(drain(&uut), vec!["1", "2"]);
+    }
+
+    #[test]
+    fn expired_messages_are_discarded() {
+        let dir = TempDir::new().unwrap();
+        let config = OfflineBufferConfig {
+            ttl_s: Some(10),
+            ..test_config()
+        };
+        let uut = OfflineBuffer::open(&config, dir.path()).unwrap();
+
+        uut.push_at("signal", test_request("1"), 1_000).unwrap();
+        uut.push_at("signal", test_request("2"), 5_000).unwrap();
+
+        let messages = uut.peek_at(usize::MAX, 12_000);
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].1.signal_value, Value::from("2"));
+        assert_eq!(uut.len(), 1);
+
+        assert!(uut.peek_at(usize::MAX, 20_000).is_empty());
+        assert!(uut.is_empty());
+    }
+}
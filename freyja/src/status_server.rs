@@ -0,0 +1,211 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+// SPDX-License-Identifier: MIT
+
+use std::{collections::HashMap, net::SocketAddr, str::FromStr, sync::Arc};
+
+use axum::{
+    extract::{FromRef, State},
+    routing::get,
+    Json, Router,
+};
+use log::{info, warn};
+
+use crate::config::StatusServerConfig;
+use freyja_common::{
+    mapping_service_health_store::{MappingServiceHealth, MappingServiceHealthStore},
+    resolution_status_store::{ResolutionStatus, ResolutionStatusStore},
+};
+
+const SIGNAL_STATUSES_PATH: &str = "/status/signals";
+const MAPPING_SERVICE_HEALTH_PATH: &str = "/status/mapping_service";
+
+/// Serves the status of Freyja's components over HTTP
+pub struct StatusServer {
+    /// The address to listen on
+    address: String,
+
+    /// The stores which are read by the status endpoints
+    stores: StatusStores,
+}
+
+/// The shared stores which are read by the status endpoints
+#[derive(Clone)]
+struct StatusStores {
+    /// The resolution status of each mapped signal
+    resolution_statuses: Arc<ResolutionStatusStore>,
+
+    /// The health of the connection to the mapping service
+    mapping_service_health: Arc<MappingServiceHealthStore>,
+}
+
+impl FromRef<StatusStores> for Arc<ResolutionStatusStore> {
+    fn from_ref(stores: &StatusStores) -> Self {
+        stores.resolution_statuses.clone()
+    }
+}
+
+impl FromRef<StatusStores> for Arc<MappingServiceHealthStore> {
+    fn from_ref(stores: &StatusStores) -> Self {
+        stores.mapping_service_health.clone()
+    }
+}
+
+impl StatusServer {
+    /// Create a new instance of a StatusServer
+    ///
+    /// # Arguments
+    /// - `config`: the config for the status server
+    /// - `resolution_statuses`: the shared resolution status store, which is updated by the cartographer
+    /// - `mapping_service_health`: the shared mapping service health store, which is updated by the cartographer
+    pub fn new(
+        config: &StatusServerConfig,
+        resolution_statuses: Arc<ResolutionStatusStore>,
+        mapping_service_health: Arc<MappingServiceHealthStore>,
+    ) -> Self {
+        Self {
+            address: config.address.clone(),
+            stores: StatusStores {
+                resolution_statuses,
+                mapping_service_health,
+            },
+        }
+    }
+
+    /// Creates the router which serves the status endpoints
+    fn router(&self) -> Router {
+        Router::new()
+            .route(SIGNAL_STATUSES_PATH, get(Self::get_signal_statuses))
+            .route(
+                MAPPING_SERVICE_HEALTH_PATH,
+                get(Self::get_mapping_service_health),
+            )
+            .with_state(self.stores.clone())
+    }
+
+    /// Handler which returns the resolution status of each mapped signal, keyed by signal id
+    ///
+    /// # Arguments
+    /// - `resolution_statuses`: the shared resolution status store
+    async fn get_signal_statuses(
+        State(resolution_statuses): State<Arc<ResolutionStatusStore>>,
+    ) -> Json<HashMap<String, ResolutionStatus>> {
+        Json(resolution_statuses.get_all())
+    }
+
+    /// Handler which returns the health of the connection to the mapping service
+    ///
+    /// # Arguments
+    /// - `mapping_service_health`: the shared mapping service health store
+    async fn get_mapping_service_health(
+        State(mapping_service_health): State<Arc<MappingServiceHealthStore>>,
+    ) -> Json<MappingServiceHealth> {
+        Json(mapping_service_health.get())
+    }
+
+    /// Runs the status server.
+    /// Freyja can still run without the status server, so failing to start it isn't fatal.
+    pub async fn run(&self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let builder = match SocketAddr::from_str(&self.address)
+            .map_err(|e| e.to_string())
+            .and_then(|address| axum::Server::try_bind(&address).map_err(|e| e.to_string()))
+        {
+            Ok(builder) => builder,
+            Err(e) => {
+                warn!("Unable to start the status server at {}: {e}", self.address);
+                return Ok(());
+            }
+        };
+
+        info!("Status server listening at http://{}", self.address); // Devskim: ignore DS137138
+
+        builder
+            .serve(self.router().into_make_service())
+            .await
+            .map_err(|e| e.into())
+    }
+}
+
+#[cfg(test)]
+mod status_server_tests {
+    use super::*;
+
+    use freyja_common::mapping_service_health_store::CircuitState;
+
+    fn create_test_server() -> StatusServer {
+        let config = StatusServerConfig {
+            enabled: true,
+            address: "127.0.0.1:0".to_string(),
+        };
+
+        StatusServer::new(
+            &config,
+            Arc::new(ResolutionStatusStore::new()),
+            Arc::new(MappingServiceHealthStore::new()),
+        )
+    }
+
+    #[tokio::test]
+    async fn get_signal_statuses_returns_statuses_set_by_other_components() {
+        let uut = create_test_server();
+        let resolution_statuses = uut.stores.resolution_statuses.clone();
+
+        // The cartographer updates the store through its own handle
+        resolution_statuses.set("resolved".to_string(), ResolutionStatus::Resolved);
+        resolution_statuses.set(
+            "pending".to_string(),
+            ResolutionStatus::Pending {
+                attempts: 2,
+                last_error: "provider not found".to_string(),
+            },
+        );
+
+        let Json(statuses) =
+            StatusServer::get_signal_statuses(State(Arc::from_ref(&uut.stores))).await;
+        assert_eq!(statuses.len(), 2);
+        assert_eq!(statuses["resolved"], ResolutionStatus::Resolved);
+
+        let json = serde_json::to_value(&statuses).unwrap();
+        assert_eq!(json["resolved"]["status"], "resolved");
+        assert_eq!(json["pending"]["status"], "pending");
+        assert_eq!(json["pending"]["attempts"], 2);
+        assert_eq!(json["pending"]["last_error"], "provider not found");
+    }
+
+    #[tokio::test]
+    async fn get_mapping_service_health_returns_health_set_by_other_components() {
+        let uut = create_test_server();
+
+        // The cartographer's circuit breaker updates the store through its own handle
+        uut.stores.mapping_service_health.set(MappingServiceHealth {
+            circuit_state: CircuitState::Open,
+            consecutive_failures: 5,
+            last_error: Some("connection refused".to_string()),
+        });
+
+        let Json(health) =
+            StatusServer::get_mapping_service_health(State(Arc::from_ref(&uut.stores))).await;
+        assert_eq!(health.circuit_state, CircuitState::Open);
+        assert_eq!(health.consecutive_failures, 5);
+
+        let json = serde_json::to_value(&health).unwrap();
+        assert_eq!(json["circuit_state"], "open");
+        assert_eq!(json["consecutive_failures"], 5);
+        assert_eq!(json["last_error"], "connection refused");
+    }
+
+    #[tokio::test]
+    async fn run_does_not_fail_with_invalid_address() {
+        let config = StatusServerConfig {
+            enabled: true,
+            address: "not an address".to_string(),
+        };
+        let uut = StatusServer::new(
+            &config,
+            Arc::new(ResolutionStatusStore::new()),
+            Arc::new(MappingServiceHealthStore::new()),
+        );
+
+        assert!(uut.run().await.is_ok());
+    }
+}
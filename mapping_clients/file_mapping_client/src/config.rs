@@ -0,0 +1,13 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+// SPDX-License-Identifier: MIT
+
+use serde::{Deserialize, Serialize};
+
+/// The file mapping client's config
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Config {
+    /// The path to the mapping file. The format of the file is determined by its extension.
+    /// If this is not set, defaults to `$FREYJA_HOME/config/mapping.json`.
+    pub mapping_file: Option<String>,
+}
@@ -0,0 +1,591 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+// SPDX-License-Identifier: MIT
+
+use std::{
+    collections::HashMap,
+    ffi::OsStr,
+    fs,
+    path::{Path, PathBuf},
+    sync::{
+        mpsc::{self, RecvTimeoutError},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use log::{info, warn};
+use notify::{
+    event::{AccessKind, AccessMode, ModifyKind, RenameMode},
+    Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher,
+};
+use sha2::{Digest, Sha256};
+use tokio::sync::watch;
+
+use crate::config::Config;
+use freyja_common::{config_utils, out_dir};
+use freyja_contracts::{digital_twin_map_entry::DigitalTwinMapEntry, mapping_client::*};
+
+const CONFIG_FILE_STEM: &str = "file_mapping_client_config";
+const CONFIG_DIR: &str = "config";
+const DEFAULT_MAPPING_FILENAME: &str = "mapping.json";
+const MODIFIED_FILE_DEBOUNCE_MS: u64 = 100;
+
+/// The formats that a mapping file can be written in
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MappingFileFormat {
+    Json,
+    Yaml,
+    Toml,
+}
+
+impl MappingFileFormat {
+    /// Determines the format of a mapping file from its extension
+    ///
+    /// # Arguments
+    ///
+    /// - `path`: the path to the mapping file
+    pub fn from_path(path: &Path) -> Result<Self, MappingClientError> {
+        match path.extension().and_then(OsStr::to_str) {
+            Some("json") => Ok(Self::Json),
+            Some("yaml" | "yml") => Ok(Self::Yaml),
+            Some("toml") => Ok(Self::Toml),
+            _ => Err(MappingClientError::deserialize(format!(
+                "Unsupported mapping file {path:?}. The file must have a .json, .yaml, .yml, or .toml extension"
+            ))),
+        }
+    }
+
+    /// Parses a map of mapping entries, keyed by id
+    ///
+    /// # Arguments
+    ///
+    /// - `contents`: the contents of the mapping file
+    pub fn parse(
+        &self,
+        contents: &[u8],
+    ) -> Result<HashMap<String, DigitalTwinMapEntry>, MappingClientError> {
+        match self {
+            Self::Json => serde_json::from_slice(contents).map_err(MappingClientError::deserialize),
+            Self::Yaml => serde_yaml::from_slice(contents).map_err(MappingClientError::deserialize),
+            Self::Toml => toml::from_slice(contents).map_err(MappingClientError::deserialize),
+        }
+    }
+}
+
+/// A change to a mapping file
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum MappingFileChange {
+    /// A new version of the file was completely written, so it can be loaded immediately
+    Written,
+
+    /// The file was modified, but the writer may not have finished writing it
+    Modified,
+}
+
+/// A mapping file and its format
+struct MappingFile {
+    /// The path to the file
+    path: PathBuf,
+
+    /// The format of the file
+    format: MappingFileFormat,
+}
+
+impl MappingFile {
+    /// Reads and parses the mapping file.
+    /// The version of the mapping is the first 8 bytes of the SHA-256 hash of the file's contents, read as a big-endian integer,
+    /// so it stays the same across restarts and Freyja versions as long as the file doesn't change.
+    fn load(&self) -> Result<GetMappingResponse, MappingClientError> {
+        let contents = fs::read(&self.path).map_err(MappingClientError::io)?;
+        let map = self.format.parse(&contents)?;
+
+        let hash = Sha256::digest(&contents);
+        let mut version = [0; 8];
+        version.copy_from_slice(&hash[..8]);
+
+        Ok(GetMappingResponse {
+            map,
+            version: Some(u64::from_be_bytes(version)),
+        })
+    }
+
+    /// Reloads the mapping file and publishes the new mapping if it differs from the current one.
+    /// If the file can't be read or parsed, the current mapping stays active.
+    ///
+    /// # Arguments
+    ///
+    /// - `mapping`: the sender for the current mapping
+    fn reload(&self, mapping: &watch::Sender<GetMappingResponse>) {
+        let new_mapping = match self.load() {
+            Ok(new_mapping) => new_mapping,
+            Err(e) => {
+                warn!(
+                    "Failed to load mapping file {:?}. The previous mapping remains active: {e:?}",
+                    self.path
+                );
+                return;
+            }
+        };
+
+        mapping.send_if_modified(|current_mapping| {
+            if current_mapping.map == new_mapping.map {
+                return false;
+            }
+
+            info!(
+                "Loaded mapping with {} entries from {:?}",
+                new_mapping.map.len(),
+                self.path
+            );
+            *current_mapping = new_mapping;
+            true
+        });
+    }
+
+    /// Gets the change to the mapping file that an event indicates, if any.
+    /// On Linux, the file is reported as written when a write to the file completes or another file is moved into its place.
+    /// Other platforms only report that the file was created, modified, or renamed, so those changes are reported as modifications.
+    ///
+    /// # Arguments
+    ///
+    /// - `event`: the event to check
+    fn change_in(&self, event: &Event) -> Option<MappingFileChange> {
+        if !event
+            .paths
+            .iter()
+            .any(|path| path.file_name() == self.path.file_name())
+        {
+            return None;
+        }
+
+        match event.kind {
+            EventKind::Access(AccessKind::Close(AccessMode::Write))
+            | EventKind::Modify(ModifyKind::Name(RenameMode::To | RenameMode::Both)) => {
+                Some(MappingFileChange::Written)
+            }
+            EventKind::Create(_)
+            | EventKind::Modify(
+                ModifyKind::Any | ModifyKind::Data(_) | ModifyKind::Name(RenameMode::Any),
+            ) => Some(MappingFileChange::Modified),
+            _ => None,
+        }
+    }
+
+    /// Reloads the mapping file whenever it changes, until the sender for the changes is dropped.
+    /// Files which were completely written are reloaded immediately, while modified files are only reloaded
+    /// once they haven't changed for `MODIFIED_FILE_DEBOUNCE_MS` so that partially written files are not loaded.
+    ///
+    /// # Arguments
+    ///
+    /// - `changes`: the receiver for changes to the mapping file
+    /// - `mapping`: the sender for the current mapping
+    fn reload_on_changes(
+        &self,
+        changes: mpsc::Receiver<MappingFileChange>,
+        mapping: &watch::Sender<GetMappingResponse>,
+    ) {
+        while let Ok(mut change) = changes.recv() {
+            while change == MappingFileChange::Modified {
+                match changes.recv_timeout(Duration::from_millis(MODIFIED_FILE_DEBOUNCE_MS)) {
+                    Ok(next_change) => change = next_change,
+                    Err(RecvTimeoutError::Timeout) => break,
+                    Err(RecvTimeoutError::Disconnected) => return,
+                }
+            }
+
+            self.reload(mapping);
+        }
+    }
+}
+
+/// Reads the mapping from a JSON, YAML, or TOML file and reloads it whenever the file changes.
+/// If the file can't be read or parsed, the previously loaded mapping stays active.
+pub struct FileMappingClient {
+    /// The mapping that was last loaded from the file. The mapping is unversioned until the file is first loaded.
+    mapping: Arc<watch::Sender<GetMappingResponse>>,
+
+    /// Watches the directory containing the mapping file. The file is no longer reloaded once this is dropped.
+    _watcher: RecommendedWatcher,
+}
+
+impl FileMappingClient {
+    /// Creates a new FileMappingClient with the specified config.
+    /// The mapping file is loaded immediately and then watched for changes.
+    ///
+    /// # Arguments
+    ///
+    /// - `config`: the config to use
+    pub fn from_config(config: Config) -> Result<Self, MappingClientError> {
+        let path = match config.mapping_file {
+            Some(path) => PathBuf::from(path),
+            None => config_utils::freyja_home_dir()
+                .map_err(MappingClientError::io)?
+                .join(CONFIG_DIR)
+                .join(DEFAULT_MAPPING_FILENAME),
+        };
+
+        let mapping_file = MappingFile {
+            format: MappingFileFormat::from_path(&path)?,
+            path,
+        };
+
+        let mapping = Arc::new(watch::Sender::new(GetMappingResponse::default()));
+        mapping_file.reload(&mapping);
+
+        // Watch the directory rather than the file itself so that the file is still watched after it's replaced
+        let directory = match mapping_file.path.parent() {
+            Some(directory) if !directory.as_os_str().is_empty() => directory.to_path_buf(),
+            _ => PathBuf::from("."),
+        };
+
+        let mapping_file = Arc::new(mapping_file);
+        let (changes_sender, changes_receiver) = mpsc::channel();
+
+        // The watcher owns the sender for changes, so the reloading thread stops once the watcher is dropped
+        let reloading_mapping_file = mapping_file.clone();
+        let reloading_mapping = mapping.clone();
+        thread::spawn(move || {
+            reloading_mapping_file.reload_on_changes(changes_receiver, &reloading_mapping)
+        });
+
+        let mut watcher =
+            notify::recommended_watcher(move |result: notify::Result<Event>| match result {
+                Ok(event) => {
+                    if let Some(change) = mapping_file.change_in(&event) {
+                        // The receiver is only dropped once the sender is dropped
+                        let _ = changes_sender.send(change);
+                    }
+                }
+                Err(e) => warn!("Error while watching the mapping file: {e:?}"),
+            })
+            .map_err(MappingClientError::io)?;
+
+        watcher
+            .watch(&directory, RecursiveMode::NonRecursive)
+            .map_err(MappingClientError::io)?;
+
+        Ok(Self {
+            mapping,
+            _watcher: watcher,
+        })
+    }
+
+    /// Checks whether the caller's mapping is out of date.
+    /// There is never any work before the mapping file has been loaded successfully.
+    ///
+    /// # Arguments
+    ///
+    /// - `mapping`: the current mapping
+    /// - `version`: the version of the caller's mapping
+    fn has_work(mapping: &GetMappingResponse, version: Option<u64>) -> bool {
+        mapping.version.is_some() && mapping.version != version
+    }
+}
+
+#[async_trait]
+impl MappingClient for FileMappingClient {
+    /// Creates a new instance of a FileMappingClient with default settings
+    fn create_new() -> Result<Self, MappingClientError> {
+        let config = config_utils::read_from_files(
+            CONFIG_FILE_STEM,
+            config_utils::JSON_EXT,
+            out_dir!(),
+            MappingClientError::io,
+            MappingClientError::deserialize,
+        )?;
+
+        Self::from_config(config)
+    }
+
+    /// Checks for any additional work that the mapping service requires.
+    /// Returns true if the mapping file has changed since the caller's version was loaded
+    async fn check_for_work(
+        &self,
+        request: CheckForWorkRequest,
+    ) -> Result<CheckForWorkResponse, MappingClientError> {
+        Ok(CheckForWorkResponse {
+            has_work: Self::has_work(&self.mapping.borrow(), request.version),
+        })
+    }
+
+    /// Waits until the mapping file has changed since the caller's version was loaded or the timeout elapses,
+    /// whichever comes first
+    async fn wait_for_work(
+        &self,
+        request: WaitForWorkRequest,
+    ) -> Result<CheckForWorkResponse, MappingClientError> {
+        let mut receiver = self.mapping.subscribe();
+        let result = tokio::time::timeout(
+            Duration::from_millis(request.timeout_ms),
+            receiver.wait_for(|mapping| Self::has_work(mapping, request.version)),
+        )
+        .await;
+
+        Ok(CheckForWorkResponse {
+            has_work: matches!(result, Ok(Ok(_))),
+        })
+    }
+
+    /// Gets the mapping that was last loaded from the mapping file
+    async fn get_mapping(
+        &self,
+        _request: GetMappingRequest,
+    ) -> Result<GetMappingResponse, MappingClientError> {
+        Ok(self.mapping.borrow().clone())
+    }
+}
+
+#[cfg(test)]
+mod file_mapping_client_tests {
+    use super::*;
+
+    use std::time::Instant;
+
+    use notify::event::{CreateKind, DataChange};
+    use tempfile::TempDir;
+
+    const ID: &str = "testid";
+    const WAIT_TIMEOUT_MS: u64 = 5000;
+
+    fn test_client(path: &Path) -> FileMappingClient {
+        FileMappingClient::from_config(Config {
+            mapping_file: Some(path.to_string_lossy().to_string()),
+        })
+        .unwrap()
+    }
+
+    fn json_mapping(interval_ms: u64) -> String {
+        format!(
+            r#"{{"{ID}": {{"source": "{ID}", "target": {{}}, "interval_ms": {interval_ms}, "conversion": null, "emit_on_change": false}}}}"#
+        )
+    }
+
+    #[test]
+    fn parse_supports_each_format() {
+        let json = json_mapping(1000);
+        let yaml = format!(
+            "{ID}:\n  source: {ID}\n  target:\n    instance_id: hvac\n  interval_ms: 1000\n  conversion:\n    mul: 1.8\n    offset: 32\n  emit_on_change: false\n"
+        );
+        let toml = format!(
+            "[{ID}]\nsource = \"{ID}\"\ninterval_ms = 1000\nemit_on_change = false\n\n[{ID}.target]\ninstance_id = \"hvac\"\n\n[{ID}.conversion]\nmul = 1.8\noffset = 32\n"
+        );
+
+        let json_map = MappingFileFormat::Json.parse(json.as_bytes()).unwrap();
+        let yaml_map = MappingFileFormat::Yaml.parse(yaml.as_bytes()).unwrap();
+        let toml_map = MappingFileFormat::Toml.parse(toml.as_bytes()).unwrap();
+
+        assert_eq!(json_map[ID].interval_ms, 1000);
+        assert_eq!(yaml_map, toml_map);
+        assert_eq!(yaml_map[ID].target["instance_id"], "hvac");
+        assert_eq!(
+            MappingFileFormat::from_path(Path::new("mapping.yml")).unwrap(),
+            MappingFileFormat::Yaml
+        );
+        assert!(MappingFileFormat::from_path(Path::new("mapping.xml")).is_err());
+    }
+
+    #[tokio::test]
+    async fn file_changes_are_reported_as_work() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("mapping.json");
+        fs::write(&path, json_mapping(1000)).unwrap();
+
+        let uut = test_client(&path);
+
+        assert!(
+            uut.check_for_work(CheckForWorkRequest::default())
+                .await
+                .unwrap()
+                .has_work
+        );
+        let mapping = uut.get_mapping(GetMappingRequest::default()).await.unwrap();
+        assert_eq!(mapping.map[ID].interval_ms, 1000);
+
+        let request = CheckForWorkRequest {
+            version: mapping.version,
+            ..Default::default()
+        };
+        assert!(!uut.check_for_work(request).await.unwrap().has_work);
+
+        fs::write(&path, json_mapping(2000)).unwrap();
+
+        let response = uut
+            .wait_for_work(WaitForWorkRequest {
+                timeout_ms: WAIT_TIMEOUT_MS,
+                version: mapping.version,
+                vehicle: VehicleInfo::default(),
+            })
+            .await
+            .unwrap();
+        assert!(response.has_work);
+
+        let new_mapping = uut.get_mapping(GetMappingRequest::default()).await.unwrap();
+        assert_eq!(new_mapping.map[ID].interval_ms, 2000);
+        assert_ne!(new_mapping.version, mapping.version);
+    }
+
+    #[tokio::test]
+    async fn parse_errors_keep_previous_mapping() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("mapping.json");
+        fs::write(&path, json_mapping(1000)).unwrap();
+
+        let uut = test_client(&path);
+        let mapping = uut.get_mapping(GetMappingRequest::default()).await.unwrap();
+
+        fs::write(&path, "not json").unwrap();
+        let replacement_path = dir.path().join("mapping.json.tmp");
+        fs::write(&replacement_path, json_mapping(3000)).unwrap();
+
+        let response = uut
+            .wait_for_work(WaitForWorkRequest {
+                timeout_ms: 200,
+                version: mapping.version,
+                vehicle: VehicleInfo::default(),
+            })
+            .await
+            .unwrap();
+        assert!(!response.has_work);
+        assert_eq!(
+            uut.get_mapping(GetMappingRequest::default())
+                .await
+                .unwrap()
+                .map[ID]
+                .interval_ms,
+            1000
+        );
+
+        fs::rename(&replacement_path, &path).unwrap();
+
+        let response = uut
+            .wait_for_work(WaitForWorkRequest {
+                timeout_ms: WAIT_TIMEOUT_MS,
+                version: mapping.version,
+                vehicle: VehicleInfo::default(),
+            })
+            .await
+            .unwrap();
+        assert!(response.has_work);
+        assert_eq!(
+            uut.get_mapping(GetMappingRequest::default())
+                .await
+                .unwrap()
+                .map[ID]
+                .interval_ms,
+            3000
+        );
+    }
+
+    #[test]
+    fn change_in_distinguishes_written_and_modified_files() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("mapping.json");
+        let mapping_file = MappingFile {
+            format: MappingFileFormat::Json,
+            path: path.clone(),
+        };
+
+        let event = |kind| Event::new(kind).add_path(path.clone());
+        assert_eq!(
+            mapping_file.change_in(&event(EventKind::Access(AccessKind::Close(
+                AccessMode::Write
+            )))),
+            Some(MappingFileChange::Written)
+        );
+        assert_eq!(
+            mapping_file.change_in(&event(EventKind::Modify(ModifyKind::Name(RenameMode::To)))),
+            Some(MappingFileChange::Written)
+        );
+        assert_eq!(
+            mapping_file.change_in(&event(EventKind::Modify(ModifyKind::Data(
+                DataChange::Content
+            )))),
+            Some(MappingFileChange::Modified)
+        );
+        assert_eq!(
+            mapping_file.change_in(&event(EventKind::Create(CreateKind::File))),
+            Some(MappingFileChange::Modified)
+        );
+        assert_eq!(
+            mapping_file.change_in(&event(EventKind::Access(AccessKind::Read))),
+            None
+        );
+        assert_eq!(
+            mapping_file.change_in(
+                &Event::new(EventKind::Access(AccessKind::Close(AccessMode::Write)))
+                    .add_path(dir.path().join("other.json"))
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn modified_files_are_reloaded_once_unchanged() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("mapping.json");
+        fs::write(&path, json_mapping(1000)).unwrap();
+
+        let mapping_file = Arc::new(MappingFile {
+            format: MappingFileFormat::Json,
+            path,
+        });
+        let mapping = Arc::new(watch::Sender::new(GetMappingResponse::default()));
+        let (changes_sender, changes_receiver) = mpsc::channel();
+
+        let reloading_mapping_file = mapping_file.clone();
+        let reloading_mapping = mapping.clone();
+        let reloading = thread::spawn(move || {
+            reloading_mapping_file.reload_on_changes(changes_receiver, &reloading_mapping)
+        });
+
+        changes_sender.send(MappingFileChange::Modified).unwrap();
+        changes_sender.send(MappingFileChange::Modified).unwrap();
+
+        let deadline = Instant::now() + Duration::from_millis(WAIT_TIMEOUT_MS);
+        while mapping.borrow().version.is_none() {
+            assert!(Instant::now() < deadline, "The mapping was not reloaded");
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(mapping.borrow().map[ID].interval_ms, 1000);
+
+        // The reloading thread stops once the sender is dropped
+        drop(changes_sender);
+        reloading.join().unwrap();
+    }
+
+    #[test]
+    fn version_is_a_stable_hash_of_the_contents() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("mapping.json");
+        fs::write(&path, "{}").unwrap();
+
+        let mapping_file = MappingFile {
+            format: MappingFileFormat::Json,
+            path,
+        };
+
+        // The first 8 bytes of the SHA-256 hash of "{}"
+        assert_eq!(
+            mapping_file.load().unwrap().version,
+            Some(0x44136fa355b3678a)
+        );
+    }
+
+    #[tokio::test]
+    async fn missing_file_has_no_work() {
+        let dir = TempDir::new().unwrap();
+        let uut = test_client(&dir.path().join("mapping.toml"));
+
+        let response = uut
+            .check_for_work(CheckForWorkRequest {
+                version: Some(1),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        assert!(!response.has_work);
+    }
+}
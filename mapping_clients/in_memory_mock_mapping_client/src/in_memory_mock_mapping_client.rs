@@ -88,6 +88,7 @@ impl MappingClient for InMemoryMockMappingClient {
                     _ => None,
                 })
                 .collect(),
+            version: None,
         })
     }
 }
@@ -121,6 +122,7 @@ mod in_memory_mock_mapping_client_tests {
                         interval_ms: 0,
                         conversion: Conversion::None,
                         emit_on_change: false,
+                        ..Default::default()
                     },
                 },
                 ConfigItem {
@@ -132,6 +134,7 @@ mod in_memory_mock_mapping_client_tests {
                         interval_ms: 0,
                         conversion: Conversion::None,
                         emit_on_change: false,
+                        ..Default::default()
                     },
                 },
                 ConfigItem {
@@ -143,6 +146,7 @@ mod in_memory_mock_mapping_client_tests {
                         interval_ms: 0,
                         conversion: Conversion::None,
                         emit_on_change: false,
+                        ..Default::default()
                     },
                 },
             ],
@@ -152,7 +156,7 @@ mod in_memory_mock_mapping_client_tests {
 
         for i in 0..30 {
             let result = uut
-                .check_for_work(CheckForWorkRequest {})
+                .check_for_work(CheckForWorkRequest::default())
                 .await
                 .unwrap()
                 .has_work;
@@ -176,6 +180,7 @@ mod in_memory_mock_mapping_client_tests {
                         interval_ms: 0,
                         conversion: Conversion::None,
                         emit_on_change: false,
+                        ..Default::default()
                     },
                 },
                 ConfigItem {
@@ -187,6 +192,7 @@ mod in_memory_mock_mapping_client_tests {
                         interval_ms: 0,
                         conversion: Conversion::None,
                         emit_on_change: false,
+                        ..Default::default()
                     },
                 },
                 ConfigItem {
@@ -198,6 +204,7 @@ mod in_memory_mock_mapping_client_tests {
                         interval_ms: 0,
                         conversion: Conversion::None,
                         emit_on_change: false,
+                        ..Default::default()
                     },
                 },
             ],
@@ -206,10 +213,14 @@ mod in_memory_mock_mapping_client_tests {
         let uut = InMemoryMockMappingClient::from_config(config).unwrap();
 
         for _ in 0..9 {
-            uut.check_for_work(CheckForWorkRequest {})
+            uut.check_for_work(CheckForWorkRequest::default())
                 .await
                 .expect("check_for_work failed");
-            let mapping = uut.get_mapping(GetMappingRequest {}).await.unwrap().map;
+            let mapping = uut
+                .get_mapping(GetMappingRequest::default())
+                .await
+                .unwrap()
+                .map;
             assert_eq!(2, mapping.len());
             assert!(mapping.iter().any(|p| *p.0 == "always-active"));
             assert!(!mapping.iter().any(|p| *p.0 == "delayed-activation"));
@@ -217,10 +228,14 @@ mod in_memory_mock_mapping_client_tests {
         }
 
         for _ in 10..20 {
-            uut.check_for_work(CheckForWorkRequest {})
+            uut.check_for_work(CheckForWorkRequest::default())
                 .await
                 .expect("check_for_work failed");
-            let mapping = uut.get_mapping(GetMappingRequest {}).await.unwrap().map;
+            let mapping = uut
+                .get_mapping(GetMappingRequest::default())
+                .await
+                .unwrap()
+                .map;
             assert_eq!(3, mapping.len());
             assert!(mapping.iter().any(|p| *p.0 == "always-active"));
             assert!(mapping.iter().any(|p| *p.0 == "delayed-activation"));
@@ -228,10 +243,14 @@ mod in_memory_mock_mapping_client_tests {
         }
 
         for _ in 21..30 {
-            uut.check_for_work(CheckForWorkRequest {})
+            uut.check_for_work(CheckForWorkRequest::default())
                 .await
                 .expect("check_for_work failed");
-            let mapping = uut.get_mapping(GetMappingRequest {}).await.unwrap().map;
+            let mapping = uut
+                .get_mapping(GetMappingRequest::default())
+                .await
+                .unwrap()
+                .map;
             assert_eq!(2, mapping.len());
             assert!(mapping.iter().any(|p| *p.0 == "always-active"));
             assert!(mapping.iter().any(|p| *p.0 == "delayed-activation"));
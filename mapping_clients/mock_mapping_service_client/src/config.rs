@@ -2,9 +2,10 @@
 // Licensed under the MIT license.
 // SPDX-License-Identifier: MIT
 
+use freyja_common::service_discovery::ServiceUri;
 use serde::{Deserialize, Serialize};
 
-/// Configuration metadata for discovering Ibeji using Chariott
+/// Config for the mock mapping service client
 #[derive(Clone, Serialize, Deserialize)]
 pub struct Config {
     /// Max retries
@@ -14,5 +15,5 @@ pub struct Config {
     pub retry_interval_ms: u64,
 
     /// The url for the mock mapping service
-    pub mock_mapping_service_url: String,
+    pub mock_mapping_service_url: ServiceUri,
 }
@@ -6,9 +6,12 @@ use std::time::Duration;
 
 use async_trait::async_trait;
 use reqwest::Client;
+use tokio::sync::OnceCell;
 
 use crate::config::Config;
-use freyja_common::{config_utils, out_dir, retry_utils::execute_with_retry};
+use freyja_common::{
+    config_utils, out_dir, retry_utils::execute_with_retry, service_discovery::ServiceUri,
+};
 use freyja_contracts::mapping_client::*;
 
 const CONFIG_FILE_STEM: &str = "mock_mapping_client_config";
@@ -16,7 +19,10 @@ const CONFIG_FILE_STEM: &str = "mock_mapping_client_config";
 /// Mocks a mapping provider in memory
 pub struct MockMappingServiceClient {
     /// The base URL for requests
-    base_url: String,
+    mock_mapping_service_url: ServiceUri,
+
+    /// The resolved base URL for requests
+    base_url: OnceCell<String>,
 
     /// An internal HTTP client
     client: Client,
@@ -35,12 +41,22 @@ impl MockMappingServiceClient {
     /// - `config`: the config
     pub fn from_config(config: Config) -> Self {
         Self {
-            base_url: config.mock_mapping_service_url,
+            mock_mapping_service_url: config.mock_mapping_service_url,
+            base_url: OnceCell::new(),
             client: reqwest::Client::new(),
             max_retries: config.max_retries,
             retry_interval_ms: config.retry_interval_ms,
         }
     }
+
+    /// Gets the base URL for requests, discovering it on first use if necessary
+    async fn base_url(&self) -> Result<&str, MappingClientError> {
+        self.base_url
+            .get_or_try_init(|| self.mock_mapping_service_url.resolve())
+            .await
+            .map(String::as_str)
+            .map_err(MappingClientError::communication)
+    }
 }
 
 #[async_trait]
@@ -63,14 +79,14 @@ impl MappingClient for MockMappingServiceClient {
     /// Increments the internal counter and returns true if this would affect the result of get_mapping compared to the previous call
     async fn check_for_work(
         &self,
-        _request: CheckForWorkRequest,
+        request: CheckForWorkRequest,
     ) -> Result<CheckForWorkResponse, MappingClientError> {
-        let target = format!("{}/work", self.base_url);
+        let target = format!("{}/work", self.base_url().await?);
 
         execute_with_retry(
             self.max_retries,
             Duration::from_millis(self.retry_interval_ms),
-            || self.client.get(&target).send(),
+            || self.client.post(&target).json(&request).send(),
             Some(String::from("Checking for work from the mapping service")),
         )
         .await
@@ -82,6 +98,32 @@ impl MappingClient for MockMappingServiceClient {
         .map_err(MappingClientError::deserialize)
     }
 
+    /// Waits until the mapping service has work or the timeout elapses, using the mock mapping service's long polling API
+    ///
+    /// # Arguments
+    ///
+    /// - `request`: the request to send
+    async fn wait_for_work(
+        &self,
+        request: WaitForWorkRequest,
+    ) -> Result<CheckForWorkResponse, MappingClientError> {
+        let target = format!("{}/work/wait", self.base_url().await?);
+
+        execute_with_retry(
+            self.max_retries,
+            Duration::from_millis(self.retry_interval_ms),
+            || self.client.post(&target).json(&request).send(),
+            Some(String::from("Waiting for work from the mapping service")),
+        )
+        .await
+        .map_err(MappingClientError::communication)?
+        .error_for_status()
+        .map_err(MappingClientError::communication)?
+        .json::<CheckForWorkResponse>()
+        .await
+        .map_err(MappingClientError::deserialize)
+    }
+
     /// Sends the provider inventory to the mapping service
     ///
     /// # Arguments
@@ -91,7 +133,7 @@ impl MappingClient for MockMappingServiceClient {
         &self,
         inventory: SendInventoryRequest,
     ) -> Result<SendInventoryResponse, MappingClientError> {
-        let target = format!("{}/inventory", self.base_url);
+        let target = format!("{}/inventory", self.base_url().await?);
         self.client
             .post(&target)
             .json(&inventory)
@@ -109,14 +151,14 @@ impl MappingClient for MockMappingServiceClient {
     /// Returns the values that are configured to exist for the current internal count
     async fn get_mapping(
         &self,
-        _request: GetMappingRequest,
+        request: GetMappingRequest,
     ) -> Result<GetMappingResponse, MappingClientError> {
-        let target = format!("{}/mapping", self.base_url);
+        let target = format!("{}/mapping", self.base_url().await?);
 
         execute_with_retry(
             self.max_retries,
             Duration::from_millis(self.retry_interval_ms),
-            || self.client.get(&target).send(),
+            || self.client.post(&target).json(&request).send(),
             Some(String::from(
                 "Getting mapping info from the mapping service",
             )),
@@ -129,4 +171,55 @@ impl MappingClient for MockMappingServiceClient {
         .await
         .map_err(MappingClientError::deserialize)
     }
+
+    /// Reports the mapping entries which were rejected by the cartographer to the mapping service
+    ///
+    /// # Arguments
+    ///
+    /// - `request`: the request to send
+    async fn report_rejected_entries(
+        &self,
+        request: ReportRejectedEntriesRequest,
+    ) -> Result<ReportRejectedEntriesResponse, MappingClientError> {
+        let target = format!("{}/mapping/rejections", self.base_url().await?);
+        self.client
+            .post(&target)
+            .json(&request)
+            .send()
+            .await
+            .map_err(MappingClientError::communication)?
+            .error_for_status()
+            .map_err(MappingClientError::communication)?
+            .json::<ReportRejectedEntriesResponse>()
+            .await
+            .map_err(MappingClientError::deserialize)
+    }
+
+    /// Gets the changes to the mapping since the caller's version from the mapping service
+    ///
+    /// # Arguments
+    ///
+    /// - `request`: the request to send
+    async fn get_mapping_update(
+        &self,
+        request: GetMappingRequest,
+    ) -> Result<MappingUpdate, MappingClientError> {
+        let target = format!("{}/mapping/update", self.base_url().await?);
+
+        execute_with_retry(
+            self.max_retries,
+            Duration::from_millis(self.retry_interval_ms),
+            || self.client.post(&target).json(&request).send(),
+            Some(String::from(
+                "Getting mapping changes from the mapping service",
+            )),
+        )
+        .await
+        .map_err(MappingClientError::communication)?
+        .error_for_status()
+        .map_err(MappingClientError::communication)?
+        .json::<MappingUpdate>()
+        .await
+        .map_err(MappingClientError::deserialize)
+    }
 }
@@ -4,5 +4,6 @@
 
 pub const ENTITY_PATH: &str = "/entity";
 pub const ENTITY_QUERY_PATH: &str = "/entity?id=";
+pub const ENTITY_LIST_PATH: &str = "/entities";
 pub const ENTITY_SUBSCRIBE_PATH: &str = "/entity/subscribe";
 pub const ENTITY_GET_VALUE_PATH: &str = "/entity/request=value";
@@ -9,7 +9,7 @@ use std::sync::{Arc, Mutex};
 use std::{io, net::SocketAddr, thread, time::Duration};
 
 use axum::response::{IntoResponse, Response};
-use axum::routing::{get, post};
+use axum::routing::{delete, get, post};
 use axum::{extract, extract::State, Json, Router, Server};
 use env_logger::Target;
 use log::{debug, error, info, warn, LevelFilter};
@@ -19,9 +19,14 @@ use tokio::sync::{mpsc, mpsc::UnboundedSender};
 
 use crate::config::{Config, EntityConfig};
 use freyja_common::{config_utils, out_dir};
-use freyja_contracts::digital_twin_adapter::GetDigitalTwinProviderResponse;
+use freyja_contracts::{
+    digital_twin_adapter::{GetDigitalTwinProviderResponse, ListEntitiesResponse},
+    entity::Entity,
+};
 use http_mock_provider_proxy::http_mock_provider_proxy::{EntityValueRequest, EntityValueResponse};
-use mock_digital_twin::{ENTITY_GET_VALUE_PATH, ENTITY_PATH, ENTITY_SUBSCRIBE_PATH};
+use mock_digital_twin::{
+    ENTITY_GET_VALUE_PATH, ENTITY_LIST_PATH, ENTITY_PATH, ENTITY_SUBSCRIBE_PATH,
+};
 
 const CONFIG_FILE_STEM: &str = "mock_digital_twin_config";
 const GET_OPERATION: &str = "Get";
@@ -226,7 +231,9 @@ async fn main() {
 
     let app = Router::new()
         .route(ENTITY_PATH, get(get_entity))
+        .route(ENTITY_LIST_PATH, get(list_entities))
         .route(ENTITY_SUBSCRIBE_PATH, post(subscribe))
+        .route(ENTITY_SUBSCRIBE_PATH, delete(unsubscribe))
         .route(ENTITY_GET_VALUE_PATH, post(request_value))
         .with_state(state);
 
@@ -253,23 +260,51 @@ async fn get_entity(
     info!("Received request to get entity: {}", query.id);
     let state = state.lock().unwrap();
     find_entity(&state, &query.id)
-        .map(|(config_item, _)| {
-            let operation_path = if config_item.entity.operation == SUBSCRIBE_OPERATION {
-                ENTITY_SUBSCRIBE_PATH
-            } else if config_item.entity.operation == GET_OPERATION {
-                ENTITY_GET_VALUE_PATH
-            } else {
-                return server_error!("Entity didn't have a valid operation");
-            };
-
-            let mut entity = config_item.entity.clone();
-            entity.uri = format!("{}{operation_path}", config_item.entity.uri);
-
-            ok!(GetDigitalTwinProviderResponse { entity })
+        .map(|(config_item, _)| match provider_entity(config_item) {
+            Some(entity) => ok!(GetDigitalTwinProviderResponse { entity }),
+            None => server_error!("Entity didn't have a valid operation"),
         })
         .unwrap_or(not_found!())
 }
 
+/// Handles listing the access info of all active entities.
+/// Entities without a valid operation are skipped.
+///
+/// # Arguments
+/// - `state`: the state of the DigitalTwinAdapter which consists of active entities and their subscriptions
+async fn list_entities(State(state): State<Arc<Mutex<DigitalTwinAdapterState>>>) -> Response {
+    info!("Received request to list entities");
+    let state = state.lock().unwrap();
+    let entities = state
+        .entities
+        .iter()
+        .filter(|(config_item, _)| within_bounds(state.count, config_item.begin, config_item.end))
+        .filter_map(|(config_item, _)| provider_entity(config_item))
+        .collect();
+
+    ok!(ListEntitiesResponse { entities })
+}
+
+/// Gets the entity with the uri of the endpoint for its operation.
+/// Returns `None` if the entity doesn't have a valid operation.
+///
+/// # Arguments
+/// - `config_item`: the config of the entity
+fn provider_entity(config_item: &EntityConfig) -> Option<Entity> {
+    let operation_path = if config_item.entity.operation == SUBSCRIBE_OPERATION {
+        ENTITY_SUBSCRIBE_PATH
+    } else if config_item.entity.operation == GET_OPERATION {
+        ENTITY_GET_VALUE_PATH
+    } else {
+        return None;
+    };
+
+    let mut entity = config_item.entity.clone();
+    entity.uri = format!("{}{operation_path}", config_item.entity.uri);
+
+    Some(entity)
+}
+
 /// Handles subscribe requests to an entity
 ///
 /// # Arguments
@@ -296,6 +331,27 @@ async fn subscribe(
     }
 }
 
+/// Handles unsubscribe requests to an entity
+///
+/// # Arguments
+/// - `state`: the state of the DigitalTwinAdapter which consists of active providers and their subscriptions
+/// - `request`: the unsubscribe request to an entity
+async fn unsubscribe(
+    State(state): State<Arc<Mutex<DigitalTwinAdapterState>>>,
+    Json(request): Json<EntityValueRequest>,
+) -> Response {
+    info!("Received unsubscribe request: {request:?}");
+    let mut state = state.lock().unwrap();
+
+    match state.subscriptions.get_mut(&request.entity_id) {
+        Some(subscribers) => {
+            subscribers.remove(&request.callback_uri);
+            ok!()
+        }
+        None => not_found!(),
+    }
+}
+
 /// Handles async get requests
 ///
 /// # Arguments
@@ -5,25 +5,33 @@
 mod config;
 
 use std::{
+    collections::{HashMap, HashSet},
     io,
     net::SocketAddr,
     sync::{Arc, Mutex},
     thread,
+    time::Duration,
 };
 
 use axum::{
     extract::State,
     response::{IntoResponse, Response},
-    routing::{get, post},
+    routing::post,
     Json, Router, Server,
 };
 use env_logger::Target;
 use freyja_common::{config_utils, out_dir};
-use log::{info, LevelFilter};
+use log::{info, warn, LevelFilter};
+use tokio::{sync::Notify, time::Instant};
 
 use config::Config;
-use freyja_contracts::mapping_client::{
-    CheckForWorkResponse, GetMappingResponse, SendInventoryRequest, SendInventoryResponse,
+use freyja_contracts::{
+    digital_twin_map_entry::DigitalTwinMapEntry,
+    mapping_client::{
+        CheckForWorkRequest, CheckForWorkResponse, GetMappingRequest, GetMappingResponse,
+        MappingDelta, MappingUpdate, ReportRejectedEntriesRequest, ReportRejectedEntriesResponse,
+        SendInventoryRequest, SendInventoryResponse, WaitForWorkRequest,
+    },
 };
 
 const CONFIG_FILE_STEM: &str = "mock_mapping_config";
@@ -32,6 +40,34 @@ struct MappingState {
     count: u8,
     pending_work: bool,
     config: Config,
+    work_available: Arc<Notify>,
+    inventory: Option<HashSet<String>>,
+    inventory_changes: u64,
+}
+
+impl MappingState {
+    /// Gets the version of the mapping.
+    /// The lowest byte is the count and the remaining bits are the number of times the inventory changed,
+    /// so the mapping at an earlier version can be reconstructed as long as the inventory hasn't changed since then.
+    fn version(&self) -> u64 {
+        (self.inventory_changes << 8) | self.count as u64
+    }
+
+    /// Gets the mapping entries that are configured to exist for a count.
+    /// If an inventory was received, only entries whose source is in the inventory are included.
+    fn mapping_at(&self, n: u8) -> HashMap<String, DigitalTwinMapEntry> {
+        self.config
+            .values
+            .iter()
+            .filter(|c| within_bounds(n, c.begin, c.end))
+            .filter(|c| {
+                self.inventory
+                    .as_ref()
+                    .map_or(true, |inventory| inventory.contains(&c.value.source))
+            })
+            .map(|c| (c.value.source.clone(), c.value.clone()))
+            .collect()
+    }
 }
 
 macro_rules! ok {
@@ -65,6 +101,9 @@ async fn main() {
         count: 0,
         pending_work: check_for_work(&config, 0),
         config: config.clone(),
+        work_available: Arc::new(Notify::new()),
+        inventory: None,
+        inventory_changes: 0,
     }));
 
     let state_clone = state.clone();
@@ -100,6 +139,7 @@ async fn main() {
                     .collect();
 
                 info!("Work available for {work_available_state:?}");
+                state.work_available.notify_waiters();
             }
         }
     });
@@ -108,9 +148,12 @@ async fn main() {
 
     // HTTP server setup
     let app = Router::new()
-        .route("/work", get(get_work))
+        .route("/work", post(get_work))
+        .route("/work/wait", post(wait_for_work))
         .route("/inventory", post(send_inventory))
-        .route("/mapping", get(get_mapping))
+        .route("/mapping", post(get_mapping))
+        .route("/mapping/update", post(get_mapping_update))
+        .route("/mapping/rejections", post(report_rejected_entries))
         .with_state(state);
 
     Server::bind(
@@ -123,45 +166,165 @@ async fn main() {
     .unwrap();
 }
 
-async fn get_work(State(state): State<Arc<Mutex<MappingState>>>) -> Response {
+async fn get_work(
+    State(state): State<Arc<Mutex<MappingState>>>,
+    Json(request): Json<CheckForWorkRequest>,
+) -> Response {
+    ok!(CheckForWorkResponse {
+        has_work: consume_work(&state, request.version)
+    })
+}
+
+/// Long-polls for work: responds as soon as there is work, or with no work once the timeout elapses
+async fn wait_for_work(
+    State(state): State<Arc<Mutex<MappingState>>>,
+    Json(request): Json<WaitForWorkRequest>,
+) -> Response {
+    let deadline = Instant::now() + Duration::from_millis(request.timeout_ms);
+    let work_available = state.lock().unwrap().work_available.clone();
+
+    loop {
+        // Register for notifications before checking for work so that no notification is missed
+        let notified = work_available.notified();
+        tokio::pin!(notified);
+        notified.as_mut().enable();
+
+        if consume_work(&state, request.version) {
+            return ok!(CheckForWorkResponse { has_work: true });
+        }
+
+        if tokio::time::timeout_at(deadline, notified).await.is_err() {
+            return ok!(CheckForWorkResponse { has_work: false });
+        }
+    }
+}
+
+/// Consumes the pending work, if there is any. Returns whether there was work.
+/// A caller whose mapping version is out of date always has work.
+fn consume_work(state: &Mutex<MappingState>, version: Option<u64>) -> bool {
     let mut state = state.lock().unwrap();
     if state.pending_work {
         info!("Work consumed");
         state.pending_work = false;
-        ok!(CheckForWorkResponse { has_work: true })
+        true
     } else {
-        ok!(CheckForWorkResponse { has_work: false })
+        version.is_some_and(|v| v != state.version())
     }
 }
 
+/// Stores the inventory so that the mapping only includes entities which are in the inventory.
+/// If the inventory changed, the caller has work.
 async fn send_inventory(
-    State(_state): State<Arc<Mutex<MappingState>>>,
+    State(state): State<Arc<Mutex<MappingState>>>,
     Json(body): Json<SendInventoryRequest>,
 ) -> Response {
     info!("Got {} items in body", body.inventory.len());
+
+    let mut state = state.lock().unwrap();
+    if state.inventory.as_ref() != Some(&body.inventory) {
+        info!("Inventory changed: {:?}", body.inventory);
+        state.inventory = Some(body.inventory);
+        state.inventory_changes += 1;
+        state.pending_work = true;
+        state.work_available.notify_waiters();
+    }
+
     ok!(SendInventoryResponse {})
 }
 
-async fn get_mapping(State(state): State<Arc<Mutex<MappingState>>>) -> Response {
+async fn get_mapping(
+    State(state): State<Arc<Mutex<MappingState>>>,
+    Json(request): Json<GetMappingRequest>,
+) -> Response {
+    info!("Mapping requested by vehicle {:?}", request.vehicle);
+
     let state = state.lock().unwrap();
     let response = GetMappingResponse {
-        map: state
-            .config
-            .values
-            .iter()
-            .filter_map(|c| match c.end {
-                Some(end) if state.count >= c.begin && state.count < end => {
-                    Some((c.value.source.clone(), c.value.clone()))
-                }
-                None if state.count >= c.begin => Some((c.value.source.clone(), c.value.clone())),
-                _ => None,
-            })
-            .collect(),
+        map: state.mapping_at(state.count),
+        version: Some(state.version()),
     };
 
     ok!(response)
 }
 
+/// Gets the changes since the caller's version of the mapping.
+/// The full mapping is returned if the caller has no version or the inventory changed since the caller's version.
+async fn get_mapping_update(
+    State(state): State<Arc<Mutex<MappingState>>>,
+    Json(request): Json<GetMappingRequest>,
+) -> Response {
+    info!("Mapping update requested by vehicle {:?}", request.vehicle);
+
+    let state = state.lock().unwrap();
+    let map = state.mapping_at(state.count);
+    let version = state.version();
+
+    let base_version = match request.version {
+        Some(base_version) if base_version >> 8 == version >> 8 && base_version <= version => {
+            base_version
+        }
+        _ => {
+            return ok!(MappingUpdate::Full(GetMappingResponse {
+                map,
+                version: Some(version)
+            }))
+        }
+    };
+
+    let mut base_map = state.mapping_at(base_version as u8);
+    let mut delta = MappingDelta {
+        base_version,
+        version,
+        ..Default::default()
+    };
+
+    for (id, entry) in map {
+        match base_map.remove(&id) {
+            None => {
+                delta.added.insert(id, entry);
+            }
+            Some(base_entry) if base_entry != entry => {
+                delta.changed.insert(id, entry);
+            }
+            Some(_) => {}
+        }
+    }
+
+    delta.removed = base_map.into_keys().collect();
+
+    info!(
+        "Sending mapping changes from version {base_version} to {version}: {} added, {} changed, {} removed",
+        delta.added.len(),
+        delta.changed.len(),
+        delta.removed.len()
+    );
+
+    ok!(MappingUpdate::Delta(delta))
+}
+
+/// Logs the mapping entries which were rejected by the caller
+async fn report_rejected_entries(Json(request): Json<ReportRejectedEntriesRequest>) -> Response {
+    for rejected_entry in request.rejected_entries {
+        warn!(
+            "Vehicle {:?} rejected entry {} of mapping version {:?}: {}",
+            request.vehicle.vehicle_id,
+            rejected_entry.id,
+            request.version,
+            rejected_entry.reasons.join("; ")
+        );
+    }
+
+    ok!(ReportRejectedEntriesResponse {})
+}
+
+/// Checks whether a count is within the bounds of a config entry
+fn within_bounds(n: u8, begin: u8, end: Option<u8>) -> bool {
+    match end {
+        Some(end) => n >= begin && n < end,
+        None => n >= begin,
+    }
+}
+
 fn check_for_work(config: &Config, n: u8) -> bool {
     config.values.iter().any(|c| match c.end {
         Some(end) => {
@@ -26,7 +26,7 @@ pub(crate) fn generate(ir: FreyjaMainOutput) -> TokenStream {
     quote! {
         #[tokio::main]
         async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-            freyja::freyja_main::<#dt_adapter_type, #cloud_adapter_type, #mapping_client_type>().await
+            freyja::freyja_main::<#dt_adapter_type, #cloud_adapter_type, #mapping_client_type>(Vec::new()).await
         }
     }
 }
@@ -10,4 +10,17 @@ pub struct Config {
     /// The set of config values
     pub consumer_address: String,
     pub consumer_address_advertised: String,
+
+    /// The time to wait before reopening a stream that failed or ended, in milliseconds.
+    /// This doubles after each consecutive failure.
+    pub stream_reconnect_initial_backoff_ms: u64,
+
+    /// The maximum time to wait before reopening a stream, in milliseconds
+    pub stream_reconnect_max_backoff_ms: u64,
+
+    /// The interval between checks of whether the provider is reachable, in milliseconds
+    pub health_check_interval_ms: u64,
+
+    /// The time to wait for a provider to respond to an invocation, in milliseconds
+    pub invoke_timeout_ms: u64,
 }
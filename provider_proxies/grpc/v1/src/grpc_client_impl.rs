@@ -2,21 +2,79 @@
 // Licensed under the MIT license.
 // SPDX-License-Identifier: MIT
 
-use std::sync::Arc;
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
 
-use crossbeam::queue::SegQueue;
 use log::{debug, warn};
+use tokio::sync::{mpsc::UnboundedSender, oneshot};
 use tonic::{Request, Response, Status};
 
-use freyja_contracts::provider_proxy::SignalValue;
+use freyja_contracts::{provider_proxy::SignalValue, value::Value};
 use samples_protobuf_data_access::sample_grpc::v1::digital_twin_consumer::{
     digital_twin_consumer_server::DigitalTwinConsumer, PublishRequest, PublishResponse,
     RespondRequest, RespondResponse,
 };
 
+/// Keeps track of the invocations which are waiting for a response from a provider
 #[derive(Debug, Default)]
+pub struct PendingResponses {
+    /// The id to use for the next invocation
+    next_response_id: AtomicU64,
+
+    /// The senders for the responses to invocations, keyed by response id
+    senders: Mutex<HashMap<String, oneshot::Sender<String>>>,
+}
+
+impl PendingResponses {
+    /// Registers an invocation which is waiting for a response.
+    /// Returns the response id to send with the invocation, and a receiver for the response's payload.
+    pub fn register(&self) -> (String, oneshot::Receiver<String>) {
+        let response_id = self
+            .next_response_id
+            .fetch_add(1, Ordering::SeqCst)
+            .to_string();
+        let (sender, receiver) = oneshot::channel();
+        self.senders
+            .lock()
+            .unwrap()
+            .insert(response_id.clone(), sender);
+
+        (response_id, receiver)
+    }
+
+    /// Stops waiting for the response to an invocation
+    ///
+    /// # Arguments
+    /// - `response_id`: the response id of the invocation
+    pub fn cancel(&self, response_id: &str) {
+        self.senders.lock().unwrap().remove(response_id);
+    }
+
+    /// Completes an invocation with the payload of its response.
+    /// Returns `false` if no invocation is waiting for this response.
+    ///
+    /// # Arguments
+    /// - `response_id`: the response id of the invocation
+    /// - `payload`: the payload of the response
+    pub fn complete(&self, response_id: &str, payload: String) -> bool {
+        match self.senders.lock().unwrap().remove(response_id) {
+            Some(sender) => sender.send(payload).is_ok(),
+            None => false,
+        }
+    }
+}
+
+#[derive(Debug)]
 pub struct GRPCClientImpl {
-    pub signal_values_queue: Arc<SegQueue<SignalValue>>,
+    pub signal_values_sender: UnboundedSender<SignalValue>,
+
+    /// The invocations which are waiting for a response
+    pub pending_responses: Arc<PendingResponses>,
 }
 
 #[tonic::async_trait]
@@ -33,8 +91,14 @@ impl DigitalTwinConsumer for GRPCClientImpl {
 
         debug!("Received a publish for entity id {entity_id} with the value {value}");
 
-        let new_signal_value = SignalValue { entity_id, value };
-        self.signal_values_queue.push(new_signal_value);
+        // Providers send values as text, so the type of the value is inferred
+        let new_signal_value = SignalValue {
+            entity_id,
+            value: Value::infer(&value),
+        };
+        self.signal_values_sender
+            .send(new_signal_value)
+            .map_err(|_| Status::unavailable("Signal values are no longer being received"))?;
         let response = PublishResponse {};
         Ok(Response::new(response))
     }
@@ -47,9 +111,23 @@ impl DigitalTwinConsumer for GRPCClientImpl {
         &self,
         request: Request<RespondRequest>,
     ) -> Result<Response<RespondResponse>, Status> {
-        warn!("Got a response request: {request:?}");
+        let RespondRequest {
+            entity_id,
+            response_id,
+            payload,
+        } = request.into_inner();
+
+        debug!("Received the response {response_id} for entity id {entity_id}");
 
-        Err(Status::unimplemented("respond has not been implemented"))
+        if !self.pending_responses.complete(&response_id, payload) {
+            warn!("Received the response {response_id} for entity id {entity_id}, but no invocation is waiting for it");
+            return Err(Status::not_found(format!(
+                "No invocation is waiting for the response {response_id}"
+            )));
+        }
+
+        let response = RespondResponse {};
+        Ok(Response::new(response))
     }
 }
 
@@ -57,10 +135,14 @@ impl DigitalTwinConsumer for GRPCClientImpl {
 mod consumer_impl_tests {
     use super::*;
 
+    use tokio::sync::mpsc::unbounded_channel;
+
     #[tokio::test]
     async fn publish_test() {
+        let (signal_values_sender, mut signal_values_receiver) = unbounded_channel();
         let consumer_impl = GRPCClientImpl {
-            signal_values_queue: Arc::new(SegQueue::new()),
+            signal_values_sender,
+            pending_responses: Arc::default(),
         };
 
         let entity_id = String::from("some-id");
@@ -69,5 +151,35 @@ mod consumer_impl_tests {
         let request = tonic::Request::new(PublishRequest { entity_id, value });
         let result = consumer_impl.publish(request).await;
         assert!(result.is_ok());
+
+        let signal_value = signal_values_receiver.try_recv().unwrap();
+        assert_eq!(signal_value.entity_id, "some-id");
+        assert_eq!(signal_value.value, Value::from("some-value"));
+    }
+
+    #[tokio::test]
+    async fn respond_completes_pending_invocation() {
+        let consumer_impl = GRPCClientImpl {
+            signal_values_sender: unbounded_channel().0,
+            pending_responses: Arc::default(),
+        };
+
+        let (response_id, response_receiver) = consumer_impl.pending_responses.register();
+        let request = tonic::Request::new(RespondRequest {
+            entity_id: String::from("some-id"),
+            response_id: response_id.clone(),
+            payload: String::from("some-payload"),
+        });
+        assert!(consumer_impl.respond(request).await.is_ok());
+        assert_eq!(response_receiver.await.unwrap(), "some-payload");
+
+        // Each response is only delivered once
+        let request = tonic::Request::new(RespondRequest {
+            entity_id: String::from("some-id"),
+            response_id,
+            payload: String::from("some-payload"),
+        });
+        let result = consumer_impl.respond(request).await;
+        assert_eq!(result.unwrap_err().code(), tonic::Code::NotFound);
     }
 }
@@ -3,29 +3,71 @@
 // SPDX-License-Identifier: MIT
 
 use std::{
+    cmp::min,
     collections::HashMap,
     net::SocketAddr,
-    sync::{Arc, Mutex},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
 };
 
 use async_trait::async_trait;
-use crossbeam::queue::SegQueue;
 use freyja_common::{config_utils, out_dir};
-use log::info;
+use log::{info, warn};
 use samples_protobuf_data_access::sample_grpc::v1::{
     digital_twin_consumer::digital_twin_consumer_server::DigitalTwinConsumerServer,
     digital_twin_provider::digital_twin_provider_client::DigitalTwinProviderClient,
-    digital_twin_provider::{GetRequest, SubscribeRequest},
+    digital_twin_provider::{
+        GetRequest, InvokeRequest, Media, SetRequest, StreamRequest, SubscribeRequest,
+        UnsubscribeRequest,
+    },
 };
-use tonic::transport::{Channel, Server};
+use tokio::{net::TcpStream, sync::mpsc::UnboundedSender, task::JoinHandle};
+use tonic::{
+    codec::ProstCodec,
+    codegen::http::uri::PathAndQuery,
+    transport::{Channel, Endpoint, Server, Uri},
+    Code,
+};
+use tower::service_fn;
 
-use crate::{config::Config, grpc_client_impl::GRPCClientImpl};
-use freyja_contracts::provider_proxy::{ProviderProxy, ProviderProxyError, SignalValue};
+use crate::{
+    config::Config,
+    grpc_client_impl::{GRPCClientImpl, PendingResponses},
+};
+use freyja_contracts::{
+    provider_proxy::{
+        ConsumerEndpointFuture, ProviderProxy, ProviderProxyError, ProviderProxyFactory,
+        SignalValue,
+    },
+    value::Value,
+};
 
 const CONFIG_FILE_STEM: &str = "grpc_proxy_config";
 const GET_OPERATION: &str = "Get";
 const SUBSCRIBE_OPERATION: &str = "Subscribe";
-const SUPPORTED_OPERATIONS: &[&str] = &[GET_OPERATION, SUBSCRIBE_OPERATION];
+const STREAM_OPERATION: &str = "Stream";
+const SUPPORTED_OPERATIONS: &[&str] = &[GET_OPERATION, SUBSCRIBE_OPERATION, STREAM_OPERATION];
+const DEFAULT_HTTP_PORT: u16 = 80;
+
+/// The method called to check whether the provider is reachable.
+/// Providers don't need to implement it: any response from the provider, including `Unimplemented`, shows that it's reachable.
+const PROBE_PATH: &str = "/grpc.health.v1.Health/Check";
+
+/// The health of the connection to a provider
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProviderHealth {
+    /// The provider has not been checked yet
+    Unknown,
+
+    /// The provider is reachable
+    Healthy,
+
+    /// The provider is unreachable, or subscribing to it failed
+    Unhealthy,
+}
 
 /// Interfaces with providers which support GRPC. Based on the Ibeji mixed sample.
 #[derive(Debug)]
@@ -33,76 +75,378 @@ pub struct GRPCProviderProxy {
     /// The proxy config
     config: Config,
 
-    /// Client for connecting to a provider
+    /// The uri of the provider
+    provider_uri: String,
+
+    /// Client for connecting to a provider.
+    /// The client connects lazily and reconnects automatically if the connection is lost.
     provider_client: DigitalTwinProviderClient<Channel>,
 
+    /// The channel used by the provider client, which is also used for checking whether the provider is reachable
+    provider_channel: Channel,
+
+    /// The number of times that the client has connected to the provider. This is updated by the client's connector.
+    connections: Arc<AtomicU64>,
+
+    /// The number of connections as of the last health check
+    checked_connections: AtomicU64,
+
+    /// The last known health of the connection to the provider
+    health: Mutex<ProviderHealth>,
+
     /// Local cache for keeping track of which entities this provider proxy contains
     entity_operation_map: Mutex<HashMap<String, String>>,
 
-    /// Shared queue for all proxies to push new signal values of entities
-    signal_values_queue: Arc<SegQueue<SignalValue>>,
+    /// The tasks receiving values for entities with the Stream operation, keyed by entity id
+    stream_tasks: Mutex<HashMap<String, JoinHandle<()>>>,
+
+    /// Shared channel for all proxies to send new signal values of entities
+    signal_values_sender: UnboundedSender<SignalValue>,
+
+    /// The invocations which are waiting for a response.
+    /// Responses are received by the consumer endpoint, so this is shared with the endpoint by `GRPCProviderProxyFactory`.
+    pending_responses: Arc<PendingResponses>,
 }
 
-#[async_trait]
-impl ProviderProxy for GRPCProviderProxy {
-    /// Creates a provider proxy
+impl GRPCProviderProxy {
+    /// Creates a new GRPCProviderProxy with the specified config.
+    /// This doesn't connect to the provider: the connection is established when the provider is first used,
+    /// so the provider doesn't need to be running yet.
     ///
     /// # Arguments
+    /// - `config`: the config to use
     /// - `provider_uri`: the provider uri for accessing an entity's information
-    /// - `signal_values_queue`: shared queue for all proxies to push new signal values of entities
-    fn create_new(
+    /// - `signal_values_sender`: shared channel for all proxies to send new signal values of entities
+    pub fn from_config(
+        config: Config,
         provider_uri: &str,
-        signal_values_queue: Arc<SegQueue<SignalValue>>,
-    ) -> Result<Box<dyn ProviderProxy + Send + Sync>, ProviderProxyError>
-    where
-        Self: Sized,
-    {
-        let config = config_utils::read_from_files(
+        signal_values_sender: UnboundedSender<SignalValue>,
+    ) -> Result<Self, ProviderProxyError> {
+        let provider_endpoint =
+            Endpoint::from_shared(String::from(provider_uri)).map_err(ProviderProxyError::parse)?;
+
+        // Count the connections so that reconnecting, for example after the provider restarts, can be detected
+        let connections = Arc::new(AtomicU64::new(0));
+        let connector_connections = connections.clone();
+        let channel = provider_endpoint.connect_with_connector_lazy(service_fn(move |uri: Uri| {
+            let connections = connector_connections.clone();
+            async move {
+                let host = uri.host().unwrap_or_default();
+                let port = uri.port_u16().unwrap_or(DEFAULT_HTTP_PORT);
+                let stream = TcpStream::connect(format!("{host}:{port}")).await?;
+                stream.set_nodelay(true)?;
+
+                connections.fetch_add(1, Ordering::SeqCst);
+                Ok::<_, std::io::Error>(stream)
+            }
+        }));
+
+        Ok(Self {
+            config,
+            provider_uri: String::from(provider_uri),
+            provider_client: DigitalTwinProviderClient::new(channel.clone()),
+            provider_channel: channel,
+            connections,
+            checked_connections: AtomicU64::new(0),
+            health: Mutex::new(ProviderHealth::Unknown),
+            entity_operation_map: Mutex::new(HashMap::new()),
+            stream_tasks: Mutex::new(HashMap::new()),
+            signal_values_sender,
+            pending_responses: Arc::default(),
+        })
+    }
+
+    /// Reads the proxy config
+    fn read_config() -> Result<Config, ProviderProxyError> {
+        config_utils::read_from_files(
             CONFIG_FILE_STEM,
             config_utils::JSON_EXT,
             out_dir!(),
             ProviderProxyError::io,
             ProviderProxyError::deserialize,
-        )?;
+        )
+    }
+
+    /// Checks that an entity is registered with this provider proxy
+    ///
+    /// # Arguments
+    /// - `entity_id`: the entity id to check
+    fn ensure_registered(&self, entity_id: &str) -> Result<(), ProviderProxyError> {
+        if self
+            .entity_operation_map
+            .lock()
+            .unwrap()
+            .contains_key(entity_id)
+        {
+            Ok(())
+        } else {
+            Err(ProviderProxyError::entity_not_found(format!(
+                "Entity {entity_id} is not registered"
+            )))
+        }
+    }
+
+    /// Gets the last known health of the connection to the provider
+    pub fn health(&self) -> ProviderHealth {
+        *self.health.lock().unwrap()
+    }
+
+    /// Updates the health of the connection to the provider and returns the previous health
+    ///
+    /// # Arguments
+    /// - `health`: the new health
+    fn set_health(&self, health: ProviderHealth) -> ProviderHealth {
+        std::mem::replace(&mut *self.health.lock().unwrap(), health)
+    }
 
-        let provider_client = futures::executor::block_on(async {
-            DigitalTwinProviderClient::connect(String::from(provider_uri))
+    /// Checks whether the provider is reachable with a request over the provider client's channel
+    async fn probe(&self) -> bool {
+        let mut client = tonic::client::Grpc::new(self.provider_channel.clone());
+        let probe = async {
+            client
+                .ready()
                 .await
-                .map_err(ProviderProxyError::communication)
-        })?;
+                .map_err(|e| tonic::Status::unavailable(e.to_string()))?;
+            client
+                .unary(
+                    tonic::Request::new(()),
+                    PathAndQuery::from_static(PROBE_PATH),
+                    ProstCodec::<(), ()>::default(),
+                )
+                .await
+        };
 
-        Ok(GRPCProviderProxy {
-            config,
-            provider_client,
-            entity_operation_map: Mutex::new(HashMap::new()),
-            signal_values_queue,
-        })
-        .map(|r| Box::new(r) as _)
+        let probe_timeout = Duration::from_millis(self.config.health_check_interval_ms);
+        match tokio::time::timeout(probe_timeout, probe).await {
+            Ok(Ok(_)) => true,
+            // Failures to connect or send the request are reported with these codes,
+            // while any other status was sent by the provider
+            Ok(Err(status)) => !matches!(
+                status.code(),
+                Code::Unknown | Code::Unavailable | Code::Cancelled | Code::DeadlineExceeded
+            ),
+            Err(_) => false,
+        }
     }
 
-    /// Runs a provider proxy
-    async fn run(&self) -> Result<(), ProviderProxyError> {
-        info!("Started a GRPCProviderProxy!");
+    /// Checks whether the provider is reachable and updates the health accordingly.
+    /// When an unhealthy provider becomes reachable again, or the client had to reconnect to the provider since the last check,
+    /// all of the entities with the Subscribe operation are subscribed to again.
+    /// Reconnecting means that the provider may have restarted and lost its subscriptions,
+    /// even if it was never seen as unreachable.
+    async fn check_health(&self) {
+        let is_reachable = self.probe().await;
+
+        // The first connection isn't a reconnection, even if it happened before the first check
+        let connections = self.connections.load(Ordering::SeqCst);
+        let checked_connections = self.checked_connections.swap(connections, Ordering::SeqCst);
+        let reconnected = connections > checked_connections.max(1);
+
+        let provider_uri = &self.provider_uri;
+        if !is_reachable {
+            if self.set_health(ProviderHealth::Unhealthy) != ProviderHealth::Unhealthy {
+                warn!("The provider at {provider_uri} is unreachable");
+            }
+
+            return;
+        }
+
+        match self.set_health(ProviderHealth::Healthy) {
+            ProviderHealth::Unhealthy => {
+                info!("The provider at {provider_uri} is reachable again, restoring subscriptions");
+                self.resubscribe().await;
+            }
+            _ if reconnected => {
+                info!("Reconnected to the provider at {provider_uri}, which may have restarted, restoring subscriptions");
+                self.resubscribe().await;
+            }
+            ProviderHealth::Unknown => info!("The provider at {provider_uri} is reachable"),
+            ProviderHealth::Healthy => {}
+        }
+    }
+
+    /// Subscribes to all of the entities with the Subscribe operation.
+    /// If any of them fails, the provider is marked as unhealthy so that this is retried during the next health check.
+    async fn resubscribe(&self) {
+        let entity_ids: Vec<String> = self
+            .entity_operation_map
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(_, operation)| *operation == SUBSCRIBE_OPERATION)
+            .map(|(entity_id, _)| entity_id.clone())
+            .collect();
+
+        for entity_id in entity_ids {
+            if let Err(e) = self.subscribe(&entity_id).await {
+                warn!("Unable to subscribe to entity {entity_id}: {e:?}");
+                self.set_health(ProviderHealth::Unhealthy);
+            }
+        }
+    }
+
+    /// Subscribes to an entity so that the provider publishes its values to the consumer endpoint
+    ///
+    /// # Arguments
+    /// - `entity_id`: the entity id to subscribe to
+    async fn subscribe(&self, entity_id: &str) -> Result<(), ProviderProxyError> {
+        let consumer_uri = format!("http://{}", self.config.consumer_address_advertised); // Devskim: ignore DS137138
+        let mut client = self.provider_client.clone();
+        let request = tonic::Request::new(SubscribeRequest {
+            entity_id: String::from(entity_id),
+            consumer_uri,
+        });
+
+        client
+            .subscribe(request)
+            .await
+            .map_err(ProviderProxyError::communication)?;
+
+        Ok(())
+    }
+
+    /// Opens a stream of values for an entity and sends the values to the signal values channel.
+    /// The stream is reopened with exponential backoff whenever it fails or ends.
+    ///
+    /// # Arguments
+    /// - `provider_client`: the client for the provider
+    /// - `entity_id`: the entity to stream values for
+    /// - `signal_values_sender`: shared channel for all proxies to send new signal values of entities
+    /// - `initial_backoff`: the time to wait before reopening the stream after the first failure
+    /// - `max_backoff`: the maximum time to wait before reopening the stream
+    async fn run_stream(
+        mut provider_client: DigitalTwinProviderClient<Channel>,
+        entity_id: String,
+        signal_values_sender: UnboundedSender<SignalValue>,
+        initial_backoff: Duration,
+        max_backoff: Duration,
+    ) {
+        let mut backoff = initial_backoff;
+
+        loop {
+            let request = tonic::Request::new(StreamRequest {
+                entity_id: entity_id.clone(),
+            });
+
+            match provider_client.stream(request).await {
+                Ok(response) => {
+                    info!("Opened a stream for entity {entity_id}");
+                    let mut stream = response.into_inner();
+
+                    loop {
+                        match stream.message().await {
+                            Ok(Some(response)) => {
+                                // The stream is healthy again, so the next failure starts over with the initial backoff
+                                backoff = initial_backoff;
 
-        let addr: SocketAddr = self
-            .config
+                                let media = match response.media {
+                                    Some(media) => media,
+                                    None => continue,
+                                };
+
+                                let new_signal_value = SignalValue {
+                                    entity_id: entity_id.clone(),
+                                    value: decode_media(media),
+                                };
+                                if signal_values_sender.send(new_signal_value).is_err() {
+                                    warn!("Unable to send value because signal values are no longer being received");
+                                    return;
+                                }
+                            }
+                            Ok(None) => {
+                                info!("The stream for entity {entity_id} ended");
+                                break;
+                            }
+                            Err(e) => {
+                                warn!("The stream for entity {entity_id} failed: {e}");
+                                break;
+                            }
+                        }
+                    }
+                }
+                Err(e) => warn!("Unable to open a stream for entity {entity_id}: {e}"),
+            }
+
+            info!(
+                "Reopening the stream for entity {entity_id} in {}ms",
+                backoff.as_millis()
+            );
+            tokio::time::sleep(backoff).await;
+            backoff = min(backoff * 2, max_backoff);
+        }
+    }
+
+    /// Stops the stream of values for an entity, if there is one
+    ///
+    /// # Arguments
+    /// - `entity_id`: the entity id
+    fn stop_stream(&self, entity_id: &str) {
+        if let Some(stream_task) = self.stream_tasks.lock().unwrap().remove(entity_id) {
+            stream_task.abort();
+        }
+    }
+
+    /// Runs the consumer server which receives the values that providers publish and the responses to invocations.
+    /// The server doesn't depend on a specific provider, so a single instance is shared by all GRPCProviderProxy instances.
+    ///
+    /// # Arguments
+    /// - `signal_values_sender`: shared channel for all proxies to send new signal values of entities
+    /// - `pending_responses`: the invocations which are waiting for a response
+    async fn run_consumer_endpoint(
+        signal_values_sender: UnboundedSender<SignalValue>,
+        pending_responses: Arc<PendingResponses>,
+    ) -> Result<(), ProviderProxyError> {
+        let config = Self::read_config()?;
+
+        let addr: SocketAddr = config
             .consumer_address
             .parse()
-            .map_err(ProviderProxyError::parse)
-            .unwrap();
+            .map_err(ProviderProxyError::parse)?;
+
+        info!("Starting the gRPC consumer endpoint at {addr}");
 
-        let signal_values_queue = self.signal_values_queue.clone();
         let consumer_impl = GRPCClientImpl {
-            signal_values_queue,
+            signal_values_sender,
+            pending_responses,
         };
-        let server_future = Server::builder()
+        Server::builder()
             .add_service(DigitalTwinConsumerServer::new(consumer_impl))
-            .serve(addr);
-        let _ = server_future
+            .serve(addr)
             .await
-            .map_err(ProviderProxyError::communication);
+            .map_err(ProviderProxyError::communication)
+    }
+}
 
-        Ok(())
+#[async_trait]
+impl ProviderProxy for GRPCProviderProxy {
+    /// Creates a provider proxy
+    ///
+    /// # Arguments
+    /// - `provider_uri`: the provider uri for accessing an entity's information
+    /// - `signal_values_sender`: shared channel for all proxies to send new signal values of entities
+    fn create_new(
+        provider_uri: &str,
+        signal_values_sender: UnboundedSender<SignalValue>,
+    ) -> Result<Box<dyn ProviderProxy + Send + Sync>, ProviderProxyError>
+    where
+        Self: Sized,
+    {
+        let config = Self::read_config()?;
+
+        Self::from_config(config, provider_uri, signal_values_sender).map(|r| Box::new(r) as _)
+    }
+
+    /// Runs a provider proxy, which periodically checks the health of the provider.
+    /// Values that providers publish are received by the shared consumer endpoint, see `run_consumer_endpoint`,
+    /// and values for entities with the Stream operation are received by tasks started when the entities are registered.
+    async fn run(&self) -> Result<(), ProviderProxyError> {
+        info!("Started a GRPCProviderProxy!");
+
+        let health_check_interval = Duration::from_millis(self.config.health_check_interval_ms);
+        loop {
+            self.check_health().await;
+            tokio::time::sleep(health_check_interval).await;
+        }
     }
 
     /// Sends a request to a provider for obtaining the value of an entity
@@ -124,7 +468,7 @@ impl ProviderProxy for GRPCProviderProxy {
             return Err(ProviderProxyError::unknown(message));
         }
 
-        // Only need to handle Get operations since subscribe has already happened
+        // Only need to handle Get operations since subscribe or stream has already happened
         let operation = operation_result.unwrap();
         if operation == GET_OPERATION {
             let mut client = self.provider_client.clone();
@@ -143,6 +487,8 @@ impl ProviderProxy for GRPCProviderProxy {
 
     /// Registers an entity id to a local cache inside a provider proxy to keep track of which entities a provider proxy contains.
     /// If the operation is Subscribe for an entity, the expectation is subscribe will happen in this function after registering an entity.
+    /// If subscribing fails, the entity is kept and is subscribed to again once the provider is healthy.
+    /// If the operation is Stream, this starts receiving values from the provider's stream for the entity.
     ///
     /// # Arguments
     /// - `entity_id`: the entity id to add
@@ -157,28 +503,120 @@ impl ProviderProxy for GRPCProviderProxy {
             .unwrap()
             .insert(String::from(entity_id), String::from(operation));
 
+        // Replace any existing stream, since the entity's registration may have changed
+        self.stop_stream(entity_id);
+
+        if operation == STREAM_OPERATION {
+            let stream_task = tokio::spawn(Self::run_stream(
+                self.provider_client.clone(),
+                String::from(entity_id),
+                self.signal_values_sender.clone(),
+                Duration::from_millis(self.config.stream_reconnect_initial_backoff_ms),
+                Duration::from_millis(self.config.stream_reconnect_max_backoff_ms),
+            ));
+
+            self.stream_tasks
+                .lock()
+                .unwrap()
+                .insert(String::from(entity_id), stream_task);
+        }
+
         if operation == SUBSCRIBE_OPERATION {
+            if let Err(e) = self.subscribe(entity_id).await {
+                warn!("Unable to subscribe to entity {entity_id}, retrying once the provider is healthy: {e:?}");
+                self.set_health(ProviderHealth::Unhealthy);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Unregisters an entity id from the local cache inside a provider proxy.
+    /// If the entity was subscribed to, this also unsubscribes from the provider,
+    /// and if the entity was streamed, this closes the stream.
+    ///
+    /// # Arguments
+    /// - `entity_id`: the entity id to remove
+    async fn unregister_entity(&self, entity_id: &str) -> Result<(), ProviderProxyError> {
+        let operation = self.entity_operation_map.lock().unwrap().remove(entity_id);
+        self.stop_stream(entity_id);
+
+        if operation.as_deref() == Some(SUBSCRIBE_OPERATION) {
             let consumer_uri = format!("http://{}", self.config.consumer_address_advertised); // Devskim: ignore DS137138
             let mut client = self.provider_client.clone();
-            let request = tonic::Request::new(SubscribeRequest {
+            let request = tonic::Request::new(UnsubscribeRequest {
                 entity_id: String::from(entity_id),
                 consumer_uri,
             });
 
-            let result = client
-                .subscribe(request)
+            client
+                .unsubscribe(request)
                 .await
-                .map_err(ProviderProxyError::communication);
-
-            // Remove from map if subscribing to the provider fails
-            if result.is_err() {
-                self.entity_operation_map.lock().unwrap().remove(entity_id);
-            }
+                .map_err(ProviderProxyError::communication)?;
         }
 
         Ok(())
     }
 
+    /// Sets the value of an entity with the provider's Set operation
+    ///
+    /// # Arguments
+    /// - `entity_id`: the entity id to set the value of
+    /// - `value`: the value to set
+    async fn set_value(&self, entity_id: &str, value: Value) -> Result<(), ProviderProxyError> {
+        self.ensure_registered(entity_id)?;
+
+        let mut client = self.provider_client.clone();
+        let request = tonic::Request::new(SetRequest {
+            entity_id: String::from(entity_id),
+            value: value.to_string(),
+        });
+
+        client
+            .set(request)
+            .await
+            .map_err(ProviderProxyError::communication)?;
+
+        Ok(())
+    }
+
+    /// Invokes a command on an entity with the provider's Invoke operation.
+    /// The provider sends its response to the consumer endpoint, and this waits for the response until the configured timeout.
+    ///
+    /// # Arguments
+    /// - `entity_id`: the entity id to invoke
+    /// - `payload`: the payload of the command
+    async fn invoke(&self, entity_id: &str, payload: Value) -> Result<Value, ProviderProxyError> {
+        self.ensure_registered(entity_id)?;
+
+        let (response_id, response_receiver) = self.pending_responses.register();
+        let consumer_uri = format!("http://{}", self.config.consumer_address_advertised); // Devskim: ignore DS137138
+        let mut client = self.provider_client.clone();
+        let request = tonic::Request::new(InvokeRequest {
+            entity_id: String::from(entity_id),
+            consumer_uri,
+            response_id: response_id.clone(),
+            payload: payload.to_string(),
+        });
+
+        if let Err(e) = client.invoke(request).await {
+            self.pending_responses.cancel(&response_id);
+            return Err(ProviderProxyError::communication(e));
+        }
+
+        let invoke_timeout = Duration::from_millis(self.config.invoke_timeout_ms);
+        match tokio::time::timeout(invoke_timeout, response_receiver).await {
+            Ok(Ok(response)) => Ok(Value::infer(&response)),
+            Ok(Err(e)) => Err(ProviderProxyError::communication(e)),
+            Err(_) => {
+                self.pending_responses.cancel(&response_id);
+                Err(ProviderProxyError::communication(format!(
+                    "Timed out waiting for the response to invoking {entity_id}"
+                )))
+            }
+        }
+    }
+
     /// Checks if the operation is supported
     ///
     /// # Arguments
@@ -188,22 +626,114 @@ impl ProviderProxy for GRPCProviderProxy {
     }
 }
 
+impl Drop for GRPCProviderProxy {
+    fn drop(&mut self) {
+        for (_, stream_task) in self.stream_tasks.lock().unwrap().drain() {
+            stream_task.abort();
+        }
+    }
+}
+
+/// Decodes the media received from a stream into a signal value.
+/// Text and JSON media are decoded like the values that providers publish, and other media is used as bytes.
+///
+/// # Arguments
+/// - `media`: the media to decode
+fn decode_media(media: Media) -> Value {
+    let Media {
+        media_type,
+        media_content,
+    } = media;
+
+    if media_type.starts_with("text/") || media_type == "application/json" {
+        match String::from_utf8(media_content) {
+            Ok(text) => Value::infer(&text),
+            Err(e) => Value::Bytes(e.into_bytes()),
+        }
+    } else {
+        Value::Bytes(media_content)
+    }
+}
+
+/// Creates GRPCProviderProxy instances
+#[derive(Debug, Default)]
+pub struct GRPCProviderProxyFactory {
+    /// The invocations which are waiting for a response, shared by the proxies and the consumer endpoint
+    pending_responses: Arc<PendingResponses>,
+}
+
+impl ProviderProxyFactory for GRPCProviderProxyFactory {
+    /// Checks if the operation is supported
+    ///
+    /// # Arguments
+    /// - `operation`: check to see if this operation is supported by the provider proxy
+    fn is_operation_supported(&self, operation: &str) -> bool {
+        GRPCProviderProxy::is_operation_supported(operation)
+    }
+
+    /// Creates a provider proxy
+    ///
+    /// # Arguments
+    /// - `provider_uri`: the provider uri for accessing an entity's information
+    /// - `signal_values_sender`: shared channel for all provider proxies to send new signal values of entities
+    fn create_proxy(
+        &self,
+        provider_uri: &str,
+        signal_values_sender: UnboundedSender<SignalValue>,
+    ) -> Result<Box<dyn ProviderProxy + Send + Sync>, ProviderProxyError> {
+        let config = GRPCProviderProxy::read_config()?;
+        let mut provider_proxy =
+            GRPCProviderProxy::from_config(config, provider_uri, signal_values_sender)?;
+        provider_proxy.pending_responses = self.pending_responses.clone();
+
+        Ok(Box::new(provider_proxy))
+    }
+
+    /// Creates the consumer endpoint shared by all GRPCProviderProxy instances
+    ///
+    /// # Arguments
+    /// - `signal_values_sender`: shared channel for all provider proxies to send new signal values of entities
+    fn create_consumer_endpoint(
+        &self,
+        signal_values_sender: UnboundedSender<SignalValue>,
+    ) -> Option<ConsumerEndpointFuture> {
+        Some(Box::pin(GRPCProviderProxy::run_consumer_endpoint(
+            signal_values_sender,
+            self.pending_responses.clone(),
+        )))
+    }
+}
+
 #[cfg(test)]
 mod grpc_provider_proxy_v1_tests {
-    use std::pin::Pin;
+    use std::{pin::Pin, sync::Arc};
 
     use super::*;
 
-    use tokio_stream::Stream;
+    use tokio::{net::TcpListener, sync::oneshot};
+    use tokio_stream::{wrappers::TcpListenerStream, Stream};
     use tonic::{Request, Response, Status};
 
+    use samples_protobuf_data_access::sample_grpc::v1::digital_twin_consumer::{
+        digital_twin_consumer_server::DigitalTwinConsumer, RespondRequest,
+    };
     use samples_protobuf_data_access::sample_grpc::v1::digital_twin_provider::{
         digital_twin_provider_server::{DigitalTwinProvider, DigitalTwinProviderServer},
         GetResponse, InvokeRequest, InvokeResponse, SetRequest, SetResponse, StreamRequest,
-        StreamResponse, SubscribeResponse, UnsubscribeRequest, UnsubscribeResponse,
+        StreamResponse, SubscribeResponse, UnsubscribeResponse,
     };
 
-    pub struct MockProvider {}
+    #[derive(Default)]
+    pub struct MockProvider {
+        /// The entities that have been subscribed to
+        subscriptions: Arc<Mutex<Vec<String>>>,
+
+        /// The values that have been set, keyed by entity id
+        set_values: Arc<Mutex<HashMap<String, String>>>,
+
+        /// The invocations of the consumer which are waiting for a response
+        pending_responses: Arc<PendingResponses>,
+    }
 
     #[tonic::async_trait]
     impl DigitalTwinProvider for MockProvider {
@@ -211,8 +741,13 @@ mod grpc_provider_proxy_v1_tests {
         type StreamStream = Pin<Box<dyn Stream<Item = Result<StreamResponse, Status>> + Send>>;
         async fn subscribe(
             &self,
-            _request: Request<SubscribeRequest>,
+            request: Request<SubscribeRequest>,
         ) -> Result<Response<SubscribeResponse>, Status> {
+            self.subscriptions
+                .lock()
+                .unwrap()
+                .push(request.into_inner().entity_id);
+
             let response = SubscribeResponse {};
             Ok(Response::new(response))
         }
@@ -221,9 +756,8 @@ mod grpc_provider_proxy_v1_tests {
             &self,
             _request: Request<UnsubscribeRequest>,
         ) -> Result<Response<UnsubscribeResponse>, Status> {
-            Err(Status::unimplemented(
-                "unsubscribe has not been implemented",
-            ))
+            let response = UnsubscribeResponse {};
+            Ok(Response::new(response))
         }
 
         async fn get(
@@ -234,28 +768,223 @@ mod grpc_provider_proxy_v1_tests {
             Ok(Response::new(response))
         }
 
-        async fn set(
-            &self,
-            _request: Request<SetRequest>,
-        ) -> Result<Response<SetResponse>, Status> {
-            Err(Status::unimplemented("set has not been implemented"))
+        async fn set(&self, request: Request<SetRequest>) -> Result<Response<SetResponse>, Status> {
+            let SetRequest { entity_id, value } = request.into_inner();
+            self.set_values.lock().unwrap().insert(entity_id, value);
+
+            let response = SetResponse {};
+            Ok(Response::new(response))
         }
 
         async fn invoke(
             &self,
-            _request: Request<InvokeRequest>,
+            request: Request<InvokeRequest>,
         ) -> Result<Response<InvokeResponse>, Status> {
-            Err(Status::unimplemented("invoke has not been implemented"))
+            let InvokeRequest {
+                entity_id,
+                response_id,
+                payload,
+                ..
+            } = request.into_inner();
+
+            // Respond to the consumer as a provider would, echoing the payload
+            let consumer = GRPCClientImpl {
+                signal_values_sender: tokio::sync::mpsc::unbounded_channel().0,
+                pending_responses: self.pending_responses.clone(),
+            };
+            let respond_request = Request::new(RespondRequest {
+                entity_id,
+                response_id,
+                payload,
+            });
+            consumer.respond(respond_request).await?;
+
+            let response = InvokeResponse {};
+            Ok(Response::new(response))
         }
 
         async fn stream(
             &self,
             _request: Request<StreamRequest>,
         ) -> Result<Response<Self::StreamStream>, Status> {
-            Err(Status::unimplemented("stream has not been implemented"))
+            let responses = ["42", "43"].map(|value| {
+                Ok(StreamResponse {
+                    media: Some(Media {
+                        media_type: "text/plain".to_string(),
+                        media_content: value.as_bytes().to_vec(),
+                    }),
+                })
+            });
+
+            Ok(Response::new(Box::pin(tokio_stream::iter(responses))))
         }
     }
 
+    #[test]
+    fn decode_media_decodes_text_and_json_values() {
+        let decode = |media_type: &str, media_content: &[u8]| {
+            decode_media(Media {
+                media_type: media_type.to_string(),
+                media_content: media_content.to_vec(),
+            })
+        };
+
+        assert_eq!(decode("text/plain", b"42"), Value::infer("42"));
+        assert_eq!(
+            decode("application/json", br#"{"speed":42}"#),
+            Value::infer(r#"{"speed":42}"#)
+        );
+        assert_eq!(decode("text/plain", &[0xff]), Value::Bytes(vec![0xff]));
+        assert_eq!(
+            decode("application/octet-stream", b"42"),
+            Value::Bytes(b"42".to_vec())
+        );
+    }
+
+    /// Starts a mock provider listening on an address, which runs until the returned sender is used or dropped
+    ///
+    /// # Arguments
+    /// - `addr`: the address to listen on
+    /// - `subscriptions`: the list to record the entities that are subscribed to in
+    async fn start_mock_provider(
+        addr: SocketAddr,
+        subscriptions: Arc<Mutex<Vec<String>>>,
+    ) -> (oneshot::Sender<()>, JoinHandle<()>) {
+        let listener = TcpListener::bind(addr).await.unwrap();
+        let (shutdown_sender, shutdown_receiver) = oneshot::channel::<()>();
+        let server = tokio::spawn(async move {
+            Server::builder()
+                .add_service(DigitalTwinProviderServer::new(MockProvider {
+                    subscriptions,
+                    ..Default::default()
+                }))
+                .serve_with_incoming_shutdown(TcpListenerStream::new(listener), async {
+                    let _ = shutdown_receiver.await;
+                })
+                .await
+                .unwrap();
+        });
+
+        (shutdown_sender, server)
+    }
+
+    /// Waits until a condition holds, panicking if it takes too long
+    ///
+    /// # Arguments
+    /// - `condition`: the condition to wait for
+    async fn wait_until(condition: impl Fn() -> bool) {
+        tokio::time::timeout(Duration::from_secs(10), async {
+            while !condition() {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("timed out waiting for condition");
+    }
+
+    #[tokio::test]
+    async fn subscriptions_are_restored_after_provider_restarts() {
+        // Reserve a port for the provider, which isn't started until after the proxy subscribes
+        let addr = TcpListener::bind("127.0.0.1:0")
+            .await
+            .unwrap()
+            .local_addr()
+            .unwrap();
+
+        let config = Config {
+            consumer_address: "[::1]:60010".to_string(),
+            consumer_address_advertised: "[::1]:60010".to_string(),
+            stream_reconnect_initial_backoff_ms: 1000,
+            stream_reconnect_max_backoff_ms: 30000,
+            health_check_interval_ms: 10,
+            invoke_timeout_ms: 5000,
+        };
+        let (signal_values_sender, _signal_values_receiver) =
+            tokio::sync::mpsc::unbounded_channel();
+        let grpc_provider_proxy = Arc::new(
+            GRPCProviderProxy::from_config(
+                config,
+                &format!("http://{addr}"), // Devskim: ignore DS137138
+                signal_values_sender,
+            )
+            .unwrap(),
+        );
+
+        // Registering succeeds while the provider is down, and the subscription happens once it starts
+        let entity_id = "operation_subscribe_entity_id";
+        let result = grpc_provider_proxy
+            .register_entity(entity_id, SUBSCRIBE_OPERATION)
+            .await;
+        assert!(result.is_ok());
+        assert_eq!(grpc_provider_proxy.health(), ProviderHealth::Unhealthy);
+
+        let proxy = grpc_provider_proxy.clone();
+        let proxy_task = tokio::spawn(async move {
+            let _ = proxy.run().await;
+        });
+
+        let subscriptions = Arc::new(Mutex::new(Vec::new()));
+        let (shutdown_sender, server) = start_mock_provider(addr, subscriptions.clone()).await;
+        wait_until(|| {
+            subscriptions
+                .lock()
+                .unwrap()
+                .contains(&entity_id.to_string())
+        })
+        .await;
+        wait_until(|| grpc_provider_proxy.health() == ProviderHealth::Healthy).await;
+
+        // Kill the provider
+        shutdown_sender.send(()).unwrap();
+        server.await.unwrap();
+        wait_until(|| grpc_provider_proxy.health() == ProviderHealth::Unhealthy).await;
+
+        // The restarted provider has no subscriptions until the proxy subscribes again
+        let subscriptions = Arc::new(Mutex::new(Vec::new()));
+        let (shutdown_sender, server) = start_mock_provider(addr, subscriptions.clone()).await;
+        wait_until(|| {
+            subscriptions
+                .lock()
+                .unwrap()
+                .contains(&entity_id.to_string())
+        })
+        .await;
+        wait_until(|| grpc_provider_proxy.health() == ProviderHealth::Healthy).await;
+
+        // Restart the provider between two health checks, so that it's never seen as unreachable.
+        // Stopping the proxy's health check loop makes sure that no check happens while the provider is down.
+        proxy_task.abort();
+        let _ = proxy_task.await;
+        grpc_provider_proxy.check_health().await;
+        assert_eq!(grpc_provider_proxy.health(), ProviderHealth::Healthy);
+
+        shutdown_sender.send(()).unwrap();
+        server.await.unwrap();
+        let subscriptions = Arc::new(Mutex::new(Vec::new()));
+        let (shutdown_sender, server) = start_mock_provider(addr, subscriptions.clone()).await;
+
+        // The client reconnects to the restarted provider, which shows that it may have lost its subscriptions
+        tokio::time::timeout(Duration::from_secs(10), async {
+            while !subscriptions
+                .lock()
+                .unwrap()
+                .contains(&entity_id.to_string())
+            {
+                grpc_provider_proxy.check_health().await;
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("timed out waiting for the subscription to be restored");
+
+        // The restored subscription works with the original client
+        let result = grpc_provider_proxy.unregister_entity(entity_id).await;
+        assert!(result.is_ok());
+
+        shutdown_sender.send(()).unwrap();
+        server.await.unwrap();
+    }
+
     /// The tests below uses Unix sockets to create a channel between a gRPC client and a gRPC server.
     /// Unix sockets are more ideal than using TCP/IP sockets since Rust tests will run in parallel
     /// so you would need to set an arbitrary port per test for TCP/IP sockets.
@@ -263,31 +992,24 @@ mod grpc_provider_proxy_v1_tests {
     mod unix_tests {
         use super::*;
 
-        use std::sync::Arc;
-
         use tempfile::TempPath;
         use tokio::net::{UnixListener, UnixStream};
         use tokio_stream::wrappers::UnixListenerStream;
-        use tonic::transport::{Channel, Endpoint, Server, Uri};
+        use tonic::transport::Uri;
         use tower::service_fn;
 
-        async fn create_test_grpc_client(
-            bind_path: Arc<TempPath>,
-        ) -> DigitalTwinProviderClient<Channel> {
-            let channel = Endpoint::try_from("http://URI_IGNORED") // Devskim: ignore DS137138
+        async fn create_test_grpc_channel(bind_path: Arc<TempPath>) -> Channel {
+            Endpoint::try_from("http://URI_IGNORED") // Devskim: ignore DS137138
                 .unwrap()
                 .connect_with_connector(service_fn(move |_: Uri| {
                     let bind_path = bind_path.clone();
                     async move { UnixStream::connect(bind_path.as_ref()).await }
                 }))
                 .await
-                .unwrap();
-
-            DigitalTwinProviderClient::new(channel)
+                .unwrap()
         }
 
-        async fn run_test_grpc_server(uds_stream: UnixListenerStream) {
-            let mock_provider = MockProvider {};
+        async fn run_test_grpc_server(uds_stream: UnixListenerStream, mock_provider: MockProvider) {
             Server::builder()
                 .add_service(DigitalTwinProviderServer::new(mock_provider))
                 .serve_with_incoming(uds_stream)
@@ -309,15 +1031,26 @@ mod grpc_provider_proxy_v1_tests {
             let uds_stream = UnixListenerStream::new(uds);
 
             let request_future = async {
-                let client = create_test_grpc_client(bind_path.clone()).await;
+                let channel = create_test_grpc_channel(bind_path.clone()).await;
                 let grpc_provider_proxy = GRPCProviderProxy {
                     config: Config {
                         consumer_address: "[::1]:60010".to_string(),
                         consumer_address_advertised: "[::1]:60010".to_string(),
+                        stream_reconnect_initial_backoff_ms: 1000,
+                        stream_reconnect_max_backoff_ms: 30000,
+                        health_check_interval_ms: 5000,
+                        invoke_timeout_ms: 5000,
                     },
-                    provider_client: client,
+                    provider_uri: "http://URI_IGNORED".to_string(), // Devskim: ignore DS137138
+                    provider_client: DigitalTwinProviderClient::new(channel.clone()),
+                    provider_channel: channel,
+                    connections: Arc::default(),
+                    checked_connections: AtomicU64::new(0),
+                    health: Mutex::new(ProviderHealth::Unknown),
                     entity_operation_map: Mutex::new(HashMap::new()),
-                    signal_values_queue: Arc::new(SegQueue::new()),
+                    stream_tasks: Mutex::new(HashMap::new()),
+                    signal_values_sender: tokio::sync::mpsc::unbounded_channel().0,
+                    pending_responses: Arc::default(),
                 };
                 assert!(grpc_provider_proxy
                     .send_request_to_provider("unknown_entity_id")
@@ -344,10 +1077,161 @@ mod grpc_provider_proxy_v1_tests {
                     .send_request_to_provider(entity_id)
                     .await
                     .is_ok());
+
+                let result = grpc_provider_proxy.unregister_entity(entity_id).await;
+                assert!(result.is_ok());
+                assert!(grpc_provider_proxy
+                    .send_request_to_provider(entity_id)
+                    .await
+                    .is_err());
+            };
+
+            tokio::select! {
+                _ = run_test_grpc_server(uds_stream, MockProvider::default()) => (),
+                _ = request_future => ()
+            }
+
+            std::fs::remove_file(bind_path.as_ref()).unwrap();
+        }
+
+        #[tokio::test]
+        async fn stream_values_are_sent_to_channel() {
+            // Create the Unix Socket
+            let bind_path = Arc::new(tempfile::NamedTempFile::new().unwrap().into_temp_path());
+            let uds = match UnixListener::bind(bind_path.as_ref()) {
+                Ok(unix_listener) => unix_listener,
+                Err(_) => {
+                    std::fs::remove_file(bind_path.as_ref()).unwrap();
+                    UnixListener::bind(bind_path.as_ref()).unwrap()
+                }
+            };
+            let uds_stream = UnixListenerStream::new(uds);
+
+            let request_future = async {
+                let channel = create_test_grpc_channel(bind_path.clone()).await;
+                let (signal_values_sender, mut signal_values_receiver) =
+                    tokio::sync::mpsc::unbounded_channel();
+                let grpc_provider_proxy = GRPCProviderProxy {
+                    config: Config {
+                        consumer_address: "[::1]:60010".to_string(),
+                        consumer_address_advertised: "[::1]:60010".to_string(),
+                        stream_reconnect_initial_backoff_ms: 1,
+                        stream_reconnect_max_backoff_ms: 10,
+                        health_check_interval_ms: 5000,
+                        invoke_timeout_ms: 5000,
+                    },
+                    provider_uri: "http://URI_IGNORED".to_string(), // Devskim: ignore DS137138
+                    provider_client: DigitalTwinProviderClient::new(channel.clone()),
+                    provider_channel: channel,
+                    connections: Arc::default(),
+                    checked_connections: AtomicU64::new(0),
+                    health: Mutex::new(ProviderHealth::Unknown),
+                    entity_operation_map: Mutex::new(HashMap::new()),
+                    stream_tasks: Mutex::new(HashMap::new()),
+                    signal_values_sender,
+                    pending_responses: Arc::default(),
+                };
+
+                let entity_id = "operation_stream_entity_id";
+                let result = grpc_provider_proxy
+                    .register_entity(entity_id, STREAM_OPERATION)
+                    .await;
+                assert!(result.is_ok());
+                assert!(grpc_provider_proxy
+                    .send_request_to_provider(entity_id)
+                    .await
+                    .is_ok());
+
+                // The mock provider ends each stream after two values, so a third value means the stream was reopened
+                for expected_value in ["42", "43", "42"] {
+                    let signal_value = signal_values_receiver.recv().await.unwrap();
+                    assert_eq!(signal_value.entity_id, entity_id);
+                    assert_eq!(signal_value.value, Value::infer(expected_value));
+                }
+
+                let result = grpc_provider_proxy.unregister_entity(entity_id).await;
+                assert!(result.is_ok());
+                assert!(grpc_provider_proxy.stream_tasks.lock().unwrap().is_empty());
+            };
+
+            tokio::select! {
+                _ = run_test_grpc_server(uds_stream, MockProvider::default()) => (),
+                _ = request_future => ()
+            }
+
+            std::fs::remove_file(bind_path.as_ref()).unwrap();
+        }
+
+        #[tokio::test]
+        async fn set_and_invoke_are_sent_to_provider() {
+            // Create the Unix Socket
+            let bind_path = Arc::new(tempfile::NamedTempFile::new().unwrap().into_temp_path());
+            let uds = match UnixListener::bind(bind_path.as_ref()) {
+                Ok(unix_listener) => unix_listener,
+                Err(_) => {
+                    std::fs::remove_file(bind_path.as_ref()).unwrap();
+                    UnixListener::bind(bind_path.as_ref()).unwrap()
+                }
+            };
+            let uds_stream = UnixListenerStream::new(uds);
+
+            // The mock provider responds to invocations through the proxy's pending responses,
+            // as the shared consumer endpoint would
+            let pending_responses = Arc::new(PendingResponses::default());
+            let mock_provider = MockProvider {
+                pending_responses: pending_responses.clone(),
+                ..Default::default()
+            };
+            let set_values = mock_provider.set_values.clone();
+
+            let request_future = async {
+                let channel = create_test_grpc_channel(bind_path.clone()).await;
+                let grpc_provider_proxy = GRPCProviderProxy {
+                    config: Config {
+                        consumer_address: "[::1]:60010".to_string(),
+                        consumer_address_advertised: "[::1]:60010".to_string(),
+                        stream_reconnect_initial_backoff_ms: 1000,
+                        stream_reconnect_max_backoff_ms: 30000,
+                        health_check_interval_ms: 5000,
+                        invoke_timeout_ms: 5000,
+                    },
+                    provider_uri: "http://URI_IGNORED".to_string(), // Devskim: ignore DS137138
+                    provider_client: DigitalTwinProviderClient::new(channel.clone()),
+                    provider_channel: channel,
+                    connections: Arc::default(),
+                    checked_connections: AtomicU64::new(0),
+                    health: Mutex::new(ProviderHealth::Unknown),
+                    entity_operation_map: Mutex::new(HashMap::new()),
+                    stream_tasks: Mutex::new(HashMap::new()),
+                    signal_values_sender: tokio::sync::mpsc::unbounded_channel().0,
+                    pending_responses,
+                };
+
+                let entity_id = "operation_get_entity_id";
+                assert!(grpc_provider_proxy
+                    .set_value(entity_id, Value::Int(42))
+                    .await
+                    .is_err());
+
+                let result = grpc_provider_proxy
+                    .register_entity(entity_id, GET_OPERATION)
+                    .await;
+                assert!(result.is_ok());
+
+                let result = grpc_provider_proxy
+                    .set_value(entity_id, Value::Int(42))
+                    .await;
+                assert!(result.is_ok());
+                assert_eq!(set_values.lock().unwrap()[entity_id], "42");
+
+                let result = grpc_provider_proxy
+                    .invoke(entity_id, Value::from("ping"))
+                    .await;
+                assert_eq!(result.unwrap(), Value::from("ping"));
             };
 
             tokio::select! {
-                _ = run_test_grpc_server(uds_stream) => (),
+                _ = run_test_grpc_server(uds_stream, mock_provider) => (),
                 _ = request_future => ()
             }
 
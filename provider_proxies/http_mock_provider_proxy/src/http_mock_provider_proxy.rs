@@ -2,7 +2,7 @@
 // Licensed under the MIT license.
 // SPDX-License-Identifier: MIT
 
-use std::sync::{Arc, Mutex};
+use std::sync::Mutex;
 use std::{collections::HashMap, net::SocketAddr, str::FromStr};
 
 use async_trait::async_trait;
@@ -10,16 +10,22 @@ use axum::extract::{Json, State};
 use axum::response::{IntoResponse, Response};
 use axum::routing::post;
 use axum::Router;
-use crossbeam::queue::SegQueue;
 use freyja_common::{config_utils, out_dir};
 use log::{debug, error, info};
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc::UnboundedSender;
 
 use crate::config::Config;
-use freyja_contracts::provider_proxy::{ProviderProxy, ProviderProxyError, SignalValue};
-
-const CONFIG_FILE_STEM: &str = "http_mock_provider_proxy";
+use freyja_contracts::{
+    provider_proxy::{
+        ConsumerEndpointFuture, ProviderProxy, ProviderProxyError, ProviderProxyFactory,
+        SignalValue,
+    },
+    value::Value,
+};
+
+const CONFIG_FILE_STEM: &str = "http_mock_proxy_config";
 const GET_OPERATION: &str = "Get";
 const SUBSCRIBE_OPERATION: &str = "Subscribe";
 const SUPPORTED_OPERATIONS: &[&str] = &[GET_OPERATION, SUBSCRIBE_OPERATION];
@@ -63,9 +69,6 @@ pub struct HttpMockProviderProxy {
     /// Local cache for keeping track of which entities this provider proxy contains
     entity_operation_map: Mutex<HashMap<String, String>>,
 
-    /// Shared queue for all proxies to push new signal values
-    signal_values_queue: Arc<SegQueue<SignalValue>>,
-
     /// The proxy configuration
     config: Config,
 
@@ -91,28 +94,43 @@ impl HttpMockProviderProxy {
     /// - `signal_values`: shared map of provider IDs to provider values
     /// - `value`: the value received from a provider
     async fn receive_value_handler(
-        State(signal_values_queue): State<Arc<SegQueue<SignalValue>>>,
+        State(signal_values_sender): State<UnboundedSender<SignalValue>>,
         Json(value): Json<EntityValueResponse>,
     ) -> Response {
         let EntityValueResponse { entity_id, value } = value;
 
         debug!("Received a response for entity id {entity_id} with the value {value}");
 
-        let new_signal_value = SignalValue { entity_id, value };
-        signal_values_queue.push(new_signal_value);
+        // Providers send values as text, so the type of the value is inferred
+        let new_signal_value = SignalValue {
+            entity_id,
+            value: Value::infer(&value),
+        };
+        if signal_values_sender.send(new_signal_value).is_err() {
+            error!("Unable to send value because signal values are no longer being received");
+            return axum::http::StatusCode::SERVICE_UNAVAILABLE.into_response();
+        }
 
         ok!()
     }
 
-    /// Run a listener, so the providers' server can publish data back
+    /// Runs the listener which receives the values that providers publish.
+    /// The listener doesn't depend on a specific provider, so a single instance is shared by all HttpMockProviderProxy instances.
     ///
     /// # Arguments
-    /// - `signal_values_queue`: shared queue for all proxies to push new signal values of entities
-    async fn run_signal_values_listener(
-        &self,
-        signal_values_queue: Arc<SegQueue<SignalValue>>,
+    /// - `signal_values_sender`: shared channel for all proxies to send new signal values of entities
+    pub async fn run_consumer_endpoint(
+        signal_values_sender: UnboundedSender<SignalValue>,
     ) -> Result<(), ProviderProxyError> {
-        let server_endpoint_addr = SocketAddr::from_str(&self.config.proxy_callback_address)
+        let config: Config = config_utils::read_from_files(
+            CONFIG_FILE_STEM,
+            config_utils::JSON_EXT,
+            out_dir!(),
+            ProviderProxyError::io,
+            ProviderProxyError::deserialize,
+        )?;
+
+        let server_endpoint_addr = SocketAddr::from_str(&config.proxy_callback_address)
             .map_err(ProviderProxyError::parse)?;
         // Start a listener server to have a digital twin provider push data
         // http://{provider_callback_authority}/value
@@ -120,21 +138,21 @@ impl HttpMockProviderProxy {
         // Set up router path
         let router = Router::new()
             .route(CALLBACK_FOR_VALUES_PATH, post(Self::receive_value_handler))
-            .with_state(signal_values_queue);
+            .with_state(signal_values_sender);
 
         // Run the listener
         let builder = axum::Server::try_bind(&server_endpoint_addr)
             .map_err(ProviderProxyError::communication)?;
-        builder
-            .serve(router.into_make_service())
-            .await
-            .map_err(ProviderProxyError::communication)?;
 
         info!(
             "Http Provider Proxy listening at http://{}", // Devskim: ignore DS137138
-            self.config.proxy_callback_address
+            config.proxy_callback_address
         );
-        Ok(())
+
+        builder
+            .serve(router.into_make_service())
+            .await
+            .map_err(ProviderProxyError::communication)
     }
 }
 
@@ -144,10 +162,10 @@ impl ProviderProxy for HttpMockProviderProxy {
     ///
     /// # Arguments
     /// - `provider_uri`: the provider uri for accessing an entity's information
-    /// - `signal_values_queue`: shared queue for all proxies to push new signal values of entities
+    /// - `_signal_values_sender`: unused, since values are received by the shared consumer endpoint
     fn create_new(
         provider_uri: &str,
-        signal_values_queue: Arc<SegQueue<SignalValue>>,
+        _signal_values_sender: UnboundedSender<SignalValue>,
     ) -> Result<Box<dyn ProviderProxy + Send + Sync>, ProviderProxyError>
     where
         Self: Sized,
@@ -161,7 +179,6 @@ impl ProviderProxy for HttpMockProviderProxy {
         )?;
 
         Ok(Self {
-            signal_values_queue,
             config,
             provider_uri: provider_uri.to_string(),
             client: reqwest::Client::new(),
@@ -170,11 +187,10 @@ impl ProviderProxy for HttpMockProviderProxy {
         .map(|r| Box::new(r) as _)
     }
 
-    /// Runs a provider proxy
+    /// Runs a provider proxy.
+    /// Values that providers publish are received by the shared consumer endpoint, see `run_consumer_endpoint`.
     async fn run(&self) -> Result<(), ProviderProxyError> {
         info!("Started an HttpProviderProxy!");
-        self.run_signal_values_listener(self.signal_values_queue.clone())
-            .await?;
         Ok(())
     }
 
@@ -265,6 +281,37 @@ impl ProviderProxy for HttpMockProviderProxy {
         Ok(())
     }
 
+    /// Unregisters an entity id from the local cache inside a provider proxy.
+    /// If the entity was subscribed to, this also unsubscribes from the provider.
+    ///
+    /// # Arguments
+    /// - `entity_id`: the entity id to remove
+    async fn unregister_entity(&self, entity_id: &str) -> Result<(), ProviderProxyError> {
+        let operation = self.entity_operation_map.lock().unwrap().remove(entity_id);
+
+        if operation.as_deref() != Some(SUBSCRIBE_OPERATION) {
+            return Ok(());
+        }
+
+        // Unsubscribe
+        let request = EntityValueRequest {
+            entity_id: String::from(entity_id),
+            callback_uri: Self::construct_callback_uri(&self.config.proxy_callback_address),
+        };
+
+        let subscribe_endpoint_for_entity = self.provider_uri.clone();
+        self.client
+            .delete(&subscribe_endpoint_for_entity)
+            .json(&request)
+            .send()
+            .await
+            .map_err(ProviderProxyError::communication)?
+            .error_for_status()
+            .map_err(ProviderProxyError::unknown)?;
+
+        Ok(())
+    }
+
     /// Checks if the operation is supported
     ///
     /// # Arguments
@@ -273,3 +320,43 @@ impl ProviderProxy for HttpMockProviderProxy {
         SUPPORTED_OPERATIONS.contains(&operation)
     }
 }
+
+/// Creates HttpMockProviderProxy instances
+#[derive(Debug, Default)]
+pub struct HttpMockProviderProxyFactory;
+
+impl ProviderProxyFactory for HttpMockProviderProxyFactory {
+    /// Checks if the operation is supported
+    ///
+    /// # Arguments
+    /// - `operation`: check to see if this operation is supported by the provider proxy
+    fn is_operation_supported(&self, operation: &str) -> bool {
+        HttpMockProviderProxy::is_operation_supported(operation)
+    }
+
+    /// Creates a provider proxy
+    ///
+    /// # Arguments
+    /// - `provider_uri`: the provider uri for accessing an entity's information
+    /// - `signal_values_sender`: shared channel for all provider proxies to send new signal values of entities
+    fn create_proxy(
+        &self,
+        provider_uri: &str,
+        signal_values_sender: UnboundedSender<SignalValue>,
+    ) -> Result<Box<dyn ProviderProxy + Send + Sync>, ProviderProxyError> {
+        HttpMockProviderProxy::create_new(provider_uri, signal_values_sender)
+    }
+
+    /// Creates the consumer endpoint shared by all HttpMockProviderProxy instances
+    ///
+    /// # Arguments
+    /// - `signal_values_sender`: shared channel for all provider proxies to send new signal values of entities
+    fn create_consumer_endpoint(
+        &self,
+        signal_values_sender: UnboundedSender<SignalValue>,
+    ) -> Option<ConsumerEndpointFuture> {
+        Some(Box::pin(HttpMockProviderProxy::run_consumer_endpoint(
+            signal_values_sender,
+        )))
+    }
+}
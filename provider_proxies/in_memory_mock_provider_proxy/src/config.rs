@@ -28,10 +28,10 @@ pub struct EntityConfig {
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum SensorValueConfig {
     /// A static value which does not change
-    Static(f32),
+    Static(f64),
 
     /// A value which changes according to a fixed interval with set start and end values
-    Stepwise { start: f32, end: f32, delta: f32 },
+    Stepwise { start: f64, end: f64, delta: f64 },
 }
 
 impl SensorValueConfig {
@@ -40,10 +40,10 @@ impl SensorValueConfig {
     /// # Arguments
     ///
     /// - `n`: the seed for the value
-    pub fn get_nth(&self, n: u8) -> f32 {
+    pub fn get_nth(&self, n: u8) -> f64 {
         match self {
             Self::Static(val) => *val,
-            Self::Stepwise { start, end, delta } => match start + delta * n as f32 {
+            Self::Stepwise { start, end, delta } => match start + delta * n as f64 {
                 val if val > *end && *delta > 0.0 => *end,
                 val if val < *end && *delta < 0.0 => *end,
                 val => val,
@@ -56,9 +56,9 @@ impl SensorValueConfig {
 mod config_item_tests {
     use super::*;
 
-    /// Valdiates that abs(lhs - rhs) < epsilon, or that lhs and rhs are both f32::NAN or infinite with the same sign
-    fn f32_close_enough(lhs: f32, rhs: f32, epsilon: f32) -> bool {
-        f32::abs(lhs - rhs) < epsilon
+    /// Valdiates that abs(lhs - rhs) < epsilon, or that lhs and rhs are both f64::NAN or infinite with the same sign
+    fn f64_close_enough(lhs: f64, rhs: f64, epsilon: f64) -> bool {
+        f64::abs(lhs - rhs) < epsilon
             || lhs.is_nan() && rhs.is_nan()
             || lhs.is_infinite()
                 && rhs.is_infinite()
@@ -72,7 +72,7 @@ mod config_item_tests {
         let c = SensorValueConfig::Static(val);
 
         for i in 0..10 {
-            assert!(f32_close_enough(val, c.get_nth(i), 0.001));
+            assert!(f64_close_enough(val, c.get_nth(i), 0.001));
         }
     }
 
@@ -81,12 +81,12 @@ mod config_item_tests {
         let (start, end, delta) = (42.0, 44.4, 0.1);
         let c = SensorValueConfig::Stepwise { start, end, delta };
 
-        let iters_to_end = ((end - start) / delta) as u8;
+        let iters_to_end = ((end - start) / delta).round() as u8;
 
         // First check for values less than end
         for i in 0..iters_to_end {
-            assert!(f32_close_enough(
-                start + delta * i as f32,
+            assert!(f64_close_enough(
+                start + delta * i as f64,
                 c.get_nth(i),
                 0.001
             ));
@@ -94,7 +94,7 @@ mod config_item_tests {
 
         // Now validate behavior past end
         for i in iters_to_end..(iters_to_end + 10) {
-            assert!(f32_close_enough(end, c.get_nth(i), 0.001));
+            assert!(f64_close_enough(end, c.get_nth(i), 0.001));
         }
     }
 }
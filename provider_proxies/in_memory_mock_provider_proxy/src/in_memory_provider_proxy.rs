@@ -5,18 +5,20 @@
 use std::{
     collections::HashMap,
     sync::atomic::{AtomicU8, Ordering},
-    sync::Arc,
     sync::Mutex,
     time::Duration,
 };
 
 use async_trait::async_trait;
-use crossbeam::queue::SegQueue;
 use freyja_common::{config_utils, out_dir};
 use log::info;
+use tokio::sync::mpsc::UnboundedSender;
 
 use crate::config::{Config, EntityConfig};
-use freyja_contracts::provider_proxy::{ProviderProxy, ProviderProxyError, SignalValue};
+use freyja_contracts::{
+    provider_proxy::{ProviderProxy, ProviderProxyError, ProviderProxyFactory, SignalValue},
+    value::Value,
+};
 
 const CONFIG_FILE_STEM: &str = "in_memory_mock_proxy_config";
 const GET_OPERATION: &str = "Get";
@@ -31,8 +33,11 @@ pub struct InMemoryMockProviderProxy {
     /// Local cache for keeping track of which entities this provider proxy contains
     entity_operation_map: Mutex<HashMap<String, String>>,
 
-    /// Shared queue for all proxies to push new signal values of entities
-    signal_values_queue: Arc<SegQueue<SignalValue>>,
+    /// Values which have been set for entities, which are reported instead of the configured values
+    set_values: Mutex<HashMap<String, Value>>,
+
+    /// Shared channel for all proxies to send new signal values of entities
+    signal_values_sender: UnboundedSender<SignalValue>,
 
     /// The frequency between updates to signal values
     signal_update_frequency: Duration,
@@ -43,34 +48,38 @@ impl InMemoryMockProviderProxy {
     ///
     /// # Arguments
     /// - `config`: the config to use
-    /// - `signal_values_queue`: shared queue for all proxies to push new signal values of entities
+    /// - `signal_values_sender`: shared channel for all proxies to send new signal values of entities
     /// - `interval_between_signal_generation_ms`: the interval in milliseconds between signal value generation
     pub fn from_config(
         config: Config,
-        signal_values_queue: Arc<SegQueue<SignalValue>>,
+        signal_values_sender: UnboundedSender<SignalValue>,
     ) -> Result<Self, ProviderProxyError> {
         Ok(Self {
             entity_operation_map: Mutex::new(HashMap::new()),
+            set_values: Mutex::new(HashMap::new()),
             data: config
                 .entities
                 .into_iter()
                 .map(|c| (c.entity_id.clone(), (c, AtomicU8::new(0))))
                 .collect(),
-            signal_values_queue,
+            signal_values_sender,
             signal_update_frequency: Duration::from_millis(config.signal_update_frequency_ms),
         })
     }
 
-    /// Generates signal value for an entity id
+    /// Generates signal value for an entity id.
+    /// If a value has been set for the entity, that value is used instead of the configured values.
     ///
     /// # Arguments
     /// - `entity_id`: the entity id that needs a signal value
-    /// - `signal_values_queue`: shared queue for all proxies to push new signal values of entities
+    /// - `signal_values_sender`: shared channel for all proxies to send new signal values of entities
     /// - `data`: the current data of a provider
+    /// - `set_values`: the values which have been set for entities
     fn generate_signal_value(
         entity_id: &str,
-        signal_values_queue: Arc<SegQueue<SignalValue>>,
+        signal_values_sender: &UnboundedSender<SignalValue>,
         data: &HashMap<String, (EntityConfig, AtomicU8)>,
+        set_values: &Mutex<HashMap<String, Value>>,
     ) -> Result<(), ProviderProxyError> {
         let (entity_config, counter) = data
             .get(entity_id)
@@ -78,12 +87,36 @@ impl InMemoryMockProviderProxy {
             .map_err(ProviderProxyError::entity_not_found)?;
         let n = counter.fetch_add(1, Ordering::SeqCst);
 
-        let value = entity_config.values.get_nth(n).to_string();
+        let value = match set_values.lock().unwrap().get(entity_id) {
+            Some(value) => value.clone(),
+            None => Value::Float(entity_config.values.get_nth(n)),
+        };
         let entity_id = String::from(entity_id);
 
         let new_signal_value = SignalValue { entity_id, value };
-        signal_values_queue.push(new_signal_value);
-        Ok(())
+        signal_values_sender
+            .send(new_signal_value)
+            .map_err(ProviderProxyError::communication)
+    }
+
+    /// Checks that an entity is registered with this provider proxy and has a mocked provider
+    ///
+    /// # Arguments
+    /// - `entity_id`: the entity id to check
+    fn ensure_registered(&self, entity_id: &str) -> Result<(), ProviderProxyError> {
+        if self
+            .entity_operation_map
+            .lock()
+            .unwrap()
+            .contains_key(entity_id)
+            && self.data.contains_key(entity_id)
+        {
+            Ok(())
+        } else {
+            Err(ProviderProxyError::entity_not_found(format!(
+                "Entity {entity_id} is not registered"
+            )))
+        }
     }
 }
 
@@ -93,10 +126,10 @@ impl ProviderProxy for InMemoryMockProviderProxy {
     ///
     /// # Arguments
     /// - `provider_uri`: the provider uri for accessing an entity's information
-    /// - `signal_values_queue`: shared queue for all proxies to push new signal values of entities
+    /// - `signal_values_sender`: shared channel for all proxies to send new signal values of entities
     fn create_new(
         _provider_uri: &str,
-        signal_values_queue: Arc<SegQueue<SignalValue>>,
+        signal_values_sender: UnboundedSender<SignalValue>,
     ) -> Result<Box<dyn ProviderProxy + Send + Sync>, ProviderProxyError>
     where
         Self: Sized,
@@ -109,7 +142,7 @@ impl ProviderProxy for InMemoryMockProviderProxy {
             ProviderProxyError::deserialize,
         )?;
 
-        Self::from_config(config, signal_values_queue).map(|r| Box::new(r) as _)
+        Self::from_config(config, signal_values_sender).map(|r| Box::new(r) as _)
     }
 
     /// Runs a provider proxy
@@ -134,8 +167,9 @@ impl ProviderProxy for InMemoryMockProviderProxy {
             for entity_id in entities_with_subscribe {
                 let _ = Self::generate_signal_value(
                     &entity_id,
-                    self.signal_values_queue.clone(),
+                    &self.signal_values_sender,
                     &self.data,
+                    &self.set_values,
                 );
             }
 
@@ -165,8 +199,9 @@ impl ProviderProxy for InMemoryMockProviderProxy {
         if operation == GET_OPERATION {
             let _ = Self::generate_signal_value(
                 entity_id,
-                self.signal_values_queue.clone(),
+                &self.signal_values_sender,
                 &self.data,
+                &self.set_values,
             );
         }
 
@@ -191,6 +226,46 @@ impl ProviderProxy for InMemoryMockProviderProxy {
         Ok(())
     }
 
+    /// Unregisters an entity id from the local cache inside a provider proxy.
+    /// Values are no longer generated for subscribed entities once they are removed.
+    ///
+    /// # Arguments
+    /// - `entity_id`: the entity id to remove
+    async fn unregister_entity(&self, entity_id: &str) -> Result<(), ProviderProxyError> {
+        self.entity_operation_map.lock().unwrap().remove(entity_id);
+        Ok(())
+    }
+
+    /// Sets the value of an entity.
+    /// The value is reported for the entity from then on instead of the configured values.
+    ///
+    /// # Arguments
+    /// - `entity_id`: the entity id to set the value of
+    /// - `value`: the value to set
+    async fn set_value(&self, entity_id: &str, value: Value) -> Result<(), ProviderProxyError> {
+        self.ensure_registered(entity_id)?;
+
+        info!("Setting the value of {entity_id} to {value}");
+        self.set_values
+            .lock()
+            .unwrap()
+            .insert(String::from(entity_id), value);
+
+        Ok(())
+    }
+
+    /// Invokes a command on an entity, which responds with the payload of the command
+    ///
+    /// # Arguments
+    /// - `entity_id`: the entity id to invoke
+    /// - `payload`: the payload of the command
+    async fn invoke(&self, entity_id: &str, payload: Value) -> Result<Value, ProviderProxyError> {
+        self.ensure_registered(entity_id)?;
+
+        info!("Invoking {entity_id} with the payload {payload}");
+        Ok(payload)
+    }
+
     /// Checks if the operation is supported
     ///
     /// # Arguments
@@ -200,16 +275,45 @@ impl ProviderProxy for InMemoryMockProviderProxy {
     }
 }
 
+/// Creates InMemoryMockProviderProxy instances
+#[derive(Debug, Default)]
+pub struct InMemoryMockProviderProxyFactory;
+
+impl ProviderProxyFactory for InMemoryMockProviderProxyFactory {
+    /// Checks if the operation is supported
+    ///
+    /// # Arguments
+    /// - `operation`: check to see if this operation is supported by the provider proxy
+    fn is_operation_supported(&self, operation: &str) -> bool {
+        InMemoryMockProviderProxy::is_operation_supported(operation)
+    }
+
+    /// Creates a provider proxy
+    ///
+    /// # Arguments
+    /// - `provider_uri`: the provider uri for accessing an entity's information
+    /// - `signal_values_sender`: shared channel for all provider proxies to send new signal values of entities
+    fn create_proxy(
+        &self,
+        provider_uri: &str,
+        signal_values_sender: UnboundedSender<SignalValue>,
+    ) -> Result<Box<dyn ProviderProxy + Send + Sync>, ProviderProxyError> {
+        InMemoryMockProviderProxy::create_new(provider_uri, signal_values_sender)
+    }
+}
+
 #[cfg(test)]
 mod in_memory_mock_digital_twin_adapter_tests {
     use super::*;
 
+    use tokio::sync::mpsc::unbounded_channel;
+
     use crate::config::SensorValueConfig;
 
     #[test]
     fn can_create_new() {
-        let signal_values_queue = Arc::new(SegQueue::new());
-        let result = InMemoryMockProviderProxy::create_new("FAKE_URI", signal_values_queue);
+        let (signal_values_sender, _) = unbounded_channel();
+        let result = InMemoryMockProviderProxy::create_new("FAKE_URI", signal_values_sender);
         assert!(result.is_ok());
     }
 
@@ -242,9 +346,9 @@ mod in_memory_mock_digital_twin_adapter_tests {
             ],
         };
 
-        let signal_values_queue = Arc::new(SegQueue::new());
+        let (signal_values_sender, mut signal_values_receiver) = unbounded_channel();
         let in_memory_mock_provider_proxy =
-            InMemoryMockProviderProxy::from_config(config, signal_values_queue.clone()).unwrap();
+            InMemoryMockProviderProxy::from_config(config, signal_values_sender.clone()).unwrap();
 
         const END_OF_SENSOR_VALUE_CONFIG_ITERATION: i32 = 5;
 
@@ -253,41 +357,44 @@ mod in_memory_mock_digital_twin_adapter_tests {
         for i in 0..END_OF_SENSOR_VALUE_CONFIG_ITERATION {
             let result = InMemoryMockProviderProxy::generate_signal_value(
                 STATIC_ID,
-                signal_values_queue.clone(),
+                &signal_values_sender,
                 &in_memory_mock_provider_proxy.data,
+                &in_memory_mock_provider_proxy.set_values,
             );
             assert!(result.is_ok());
 
-            let static_value = signal_values_queue.pop().unwrap();
+            let static_value = signal_values_receiver.try_recv().unwrap();
             assert_eq!(static_value.entity_id, STATIC_ID);
-            assert_eq!(static_value.value.parse::<f32>().unwrap(), 42.0);
+            assert_eq!(static_value.value.as_f64().unwrap(), 42.0);
 
             let result = InMemoryMockProviderProxy::generate_signal_value(
                 INCREASING_ID,
-                signal_values_queue.clone(),
+                &signal_values_sender,
                 &in_memory_mock_provider_proxy.data,
+                &in_memory_mock_provider_proxy.set_values,
             );
             assert!(result.is_ok());
 
-            let increasing_value = signal_values_queue.pop().unwrap();
+            let increasing_value = signal_values_receiver.try_recv().unwrap();
             assert_eq!(increasing_value.entity_id, INCREASING_ID);
             assert_eq!(
-                increasing_value.value.parse::<f32>().unwrap(),
-                start + delta * i as f32
+                increasing_value.value.as_f64().unwrap(),
+                start + delta * i as f64
             );
 
             let result = InMemoryMockProviderProxy::generate_signal_value(
                 DECREASING_ID,
-                signal_values_queue.clone(),
+                &signal_values_sender,
                 &in_memory_mock_provider_proxy.data,
+                &in_memory_mock_provider_proxy.set_values,
             );
             assert!(result.is_ok());
 
-            let decreasing_value = signal_values_queue.pop().unwrap();
+            let decreasing_value = signal_values_receiver.try_recv().unwrap();
             assert_eq!(decreasing_value.entity_id, DECREASING_ID);
             assert_eq!(
-                decreasing_value.value.parse::<f32>().unwrap(),
-                start - delta * i as f32
+                decreasing_value.value.as_f64().unwrap(),
+                start - delta * i as f64
             );
         }
 
@@ -295,36 +402,93 @@ mod in_memory_mock_digital_twin_adapter_tests {
         for _ in 0..END_OF_SENSOR_VALUE_CONFIG_ITERATION {
             let result = InMemoryMockProviderProxy::generate_signal_value(
                 STATIC_ID,
-                signal_values_queue.clone(),
+                &signal_values_sender,
                 &in_memory_mock_provider_proxy.data,
+                &in_memory_mock_provider_proxy.set_values,
             );
             assert!(result.is_ok());
 
-            let static_value = signal_values_queue.pop().unwrap();
+            let static_value = signal_values_receiver.try_recv().unwrap();
             assert_eq!(static_value.entity_id, STATIC_ID);
-            assert_eq!(static_value.value.parse::<f32>().unwrap(), 42.0);
+            assert_eq!(static_value.value.as_f64().unwrap(), 42.0);
 
             let result = InMemoryMockProviderProxy::generate_signal_value(
                 INCREASING_ID,
-                signal_values_queue.clone(),
+                &signal_values_sender,
                 &in_memory_mock_provider_proxy.data,
+                &in_memory_mock_provider_proxy.set_values,
             );
             assert!(result.is_ok());
 
-            let increasing_value = signal_values_queue.pop().unwrap();
+            let increasing_value = signal_values_receiver.try_recv().unwrap();
             assert_eq!(increasing_value.entity_id, INCREASING_ID);
-            assert_eq!(increasing_value.value.parse::<f32>().unwrap(), end);
+            assert_eq!(increasing_value.value.as_f64().unwrap(), end);
 
             let result = InMemoryMockProviderProxy::generate_signal_value(
                 DECREASING_ID,
-                signal_values_queue.clone(),
+                &signal_values_sender,
                 &in_memory_mock_provider_proxy.data,
+                &in_memory_mock_provider_proxy.set_values,
             );
             assert!(result.is_ok());
 
-            let decreasing_value = signal_values_queue.pop().unwrap();
+            let decreasing_value = signal_values_receiver.try_recv().unwrap();
             assert_eq!(decreasing_value.entity_id, DECREASING_ID);
-            assert_eq!(decreasing_value.value.parse::<f32>().unwrap(), -end);
+            assert_eq!(decreasing_value.value.as_f64().unwrap(), -end);
         }
     }
+
+    #[tokio::test]
+    async fn unregistered_entities_are_not_handled() {
+        const ENTITY_ID: &str = "entity";
+
+        let config = Config {
+            signal_update_frequency_ms: 1000,
+            entities: vec![EntityConfig {
+                entity_id: String::from(ENTITY_ID),
+                values: SensorValueConfig::Static(42.0),
+            }],
+        };
+
+        let (signal_values_sender, _signal_values_receiver) = unbounded_channel();
+        let uut = InMemoryMockProviderProxy::from_config(config, signal_values_sender).unwrap();
+
+        assert!(uut.register_entity(ENTITY_ID, GET_OPERATION).await.is_ok());
+        assert!(uut.send_request_to_provider(ENTITY_ID).await.is_ok());
+
+        assert!(uut.unregister_entity(ENTITY_ID).await.is_ok());
+        assert!(uut.send_request_to_provider(ENTITY_ID).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn set_values_are_reported_instead_of_configured_values() {
+        const ENTITY_ID: &str = "entity";
+
+        let config = Config {
+            signal_update_frequency_ms: 1000,
+            entities: vec![EntityConfig {
+                entity_id: String::from(ENTITY_ID),
+                values: SensorValueConfig::Static(42.0),
+            }],
+        };
+
+        let (signal_values_sender, mut signal_values_receiver) = unbounded_channel();
+        let uut = InMemoryMockProviderProxy::from_config(config, signal_values_sender).unwrap();
+
+        assert!(uut.set_value(ENTITY_ID, Value::Float(21.0)).await.is_err());
+        assert!(uut.invoke(ENTITY_ID, Value::from("ping")).await.is_err());
+
+        assert!(uut.register_entity(ENTITY_ID, GET_OPERATION).await.is_ok());
+        assert!(uut.set_value(ENTITY_ID, Value::Float(21.0)).await.is_ok());
+        assert!(uut.send_request_to_provider(ENTITY_ID).await.is_ok());
+        assert_eq!(
+            signal_values_receiver.try_recv().unwrap().value,
+            Value::Float(21.0)
+        );
+
+        assert_eq!(
+            uut.invoke(ENTITY_ID, Value::from("ping")).await.unwrap(),
+            Value::from("ping")
+        );
+    }
 }
@@ -0,0 +1,39 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+// SPDX-License-Identifier: MIT
+
+use serde::{Deserialize, Serialize};
+
+/// The MQTT provider proxy config
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Config {
+    /// The keep alive interval for the broker connection in seconds
+    pub keep_alive_interval_s: u64,
+
+    /// The quality of service to use when subscribing to topics
+    pub qos: i32,
+
+    /// The interval between attempts to (re)connect to the broker in milliseconds
+    pub reconnect_interval_ms: u64,
+
+    /// The default JSON pointer used to extract values from payloads.
+    /// If this is not set, the whole payload is used as the value.
+    pub payload_pointer: Option<String>,
+
+    /// Settings for specific entities
+    pub entities: Vec<EntityConfig>,
+}
+
+/// Settings for a specific entity
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct EntityConfig {
+    /// The entity id
+    pub entity_id: String,
+
+    /// The topic to subscribe to for this entity. If this is not set, the entity id is used as the topic.
+    pub topic: Option<String>,
+
+    /// The JSON pointer used to extract values from this entity's payloads.
+    /// If this is not set, the default payload pointer is used.
+    pub payload_pointer: Option<String>,
+}
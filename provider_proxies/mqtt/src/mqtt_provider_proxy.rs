@@ -0,0 +1,622 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+// SPDX-License-Identifier: MIT
+
+use std::{
+    collections::HashMap,
+    fmt::Debug,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use futures::StreamExt;
+use log::{debug, info, warn};
+use paho_mqtt::{AsyncClient, ConnectOptionsBuilder, CreateOptionsBuilder, Message};
+use tokio::sync::mpsc::UnboundedSender;
+
+use crate::config::{Config, EntityConfig};
+use freyja_common::{config_utils, out_dir};
+use freyja_contracts::{
+    provider_proxy::{ProviderProxy, ProviderProxyError, ProviderProxyFactory, SignalValue},
+    value::Value,
+};
+
+const CONFIG_FILE_STEM: &str = "mqtt_proxy_config";
+const SUBSCRIBE_OPERATION: &str = "Subscribe";
+const SUPPORTED_OPERATIONS: &[&str] = &[SUBSCRIBE_OPERATION];
+const MESSAGE_BUFFER_SIZE: usize = 100;
+
+/// Used to give each client a unique id within this process
+static NEXT_CLIENT_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// Interfaces with providers which publish their values to an MQTT broker
+pub struct MqttProviderProxy {
+    /// The proxy config
+    config: Config,
+
+    /// Per-entity settings, keyed by entity id
+    entity_configs: HashMap<String, EntityConfig>,
+
+    /// The uri of the broker
+    provider_uri: String,
+
+    /// Client for the broker
+    client: AsyncClient,
+
+    /// The topics that this proxy is subscribed to, mapped to the corresponding entity id
+    topic_entity_map: Mutex<HashMap<String, String>>,
+
+    /// Shared channel for all proxies to send new signal values of entities
+    signal_values_sender: UnboundedSender<SignalValue>,
+}
+
+impl MqttProviderProxy {
+    /// Gets the topic for an entity
+    ///
+    /// # Arguments
+    /// - `entity_id`: the entity id
+    fn topic_for(&self, entity_id: &str) -> String {
+        self.entity_configs
+            .get(entity_id)
+            .and_then(|c| c.topic.clone())
+            .unwrap_or_else(|| entity_id.to_string())
+    }
+
+    /// Gets the JSON pointer used to extract values for an entity, if any
+    ///
+    /// # Arguments
+    /// - `entity_id`: the entity id
+    fn payload_pointer_for(&self, entity_id: &str) -> Option<&str> {
+        self.entity_configs
+            .get(entity_id)
+            .and_then(|c| c.payload_pointer.as_deref())
+            .or(self.config.payload_pointer.as_deref())
+    }
+
+    /// Connects to the broker, retrying until the connection succeeds,
+    /// then subscribes to the topics for all registered entities.
+    async fn connect(&self) {
+        let connect_options = ConnectOptionsBuilder::new()
+            .keep_alive_interval(Duration::from_secs(self.config.keep_alive_interval_s))
+            .clean_session(true)
+            .finalize();
+
+        while let Err(e) = self.client.connect(connect_options.clone()).await {
+            warn!(
+                "Unable to connect to the broker at {}; will try again later. Error: {e}",
+                self.provider_uri
+            );
+            tokio::time::sleep(Duration::from_millis(self.config.reconnect_interval_ms)).await;
+        }
+
+        info!("Connected to the broker at {}", self.provider_uri);
+
+        let topics: Vec<String> = self
+            .topic_entity_map
+            .lock()
+            .unwrap()
+            .keys()
+            .cloned()
+            .collect();
+
+        for topic in topics {
+            if let Err(e) = self.client.subscribe(topic.as_str(), self.config.qos).await {
+                warn!("Unable to subscribe to topic {topic}: {e}");
+            }
+        }
+    }
+
+    /// Decodes an incoming message and sends its value to the signal values channel
+    ///
+    /// # Arguments
+    /// - `message`: the message received from the broker
+    fn handle_message(&self, message: &Message) {
+        let entity_id = match self
+            .topic_entity_map
+            .lock()
+            .unwrap()
+            .get(message.topic())
+            .cloned()
+        {
+            Some(entity_id) => entity_id,
+            None => {
+                debug!("Ignoring message on unknown topic {}", message.topic());
+                return;
+            }
+        };
+
+        match decode_payload(message.payload(), self.payload_pointer_for(&entity_id)) {
+            Ok(value) => {
+                debug!("Received a value for entity id {entity_id}: {value}");
+                if self
+                    .signal_values_sender
+                    .send(SignalValue { entity_id, value })
+                    .is_err()
+                {
+                    warn!(
+                        "Unable to send value because signal values are no longer being received"
+                    );
+                }
+            }
+            Err(e) => warn!("Unable to decode payload for entity id {entity_id}: {e}"),
+        }
+    }
+}
+
+impl Debug for MqttProviderProxy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MqttProviderProxy")
+            .field("config", &self.config)
+            .field("provider_uri", &self.provider_uri)
+            .field("topic_entity_map", &self.topic_entity_map)
+            .finish_non_exhaustive()
+    }
+}
+
+#[async_trait]
+impl ProviderProxy for MqttProviderProxy {
+    /// Creates a provider proxy
+    ///
+    /// # Arguments
+    /// - `provider_uri`: the uri of the broker that the provider publishes to
+    /// - `signal_values_sender`: shared channel for all proxies to send new signal values of entities
+    fn create_new(
+        provider_uri: &str,
+        signal_values_sender: UnboundedSender<SignalValue>,
+    ) -> Result<Box<dyn ProviderProxy + Send + Sync>, ProviderProxyError>
+    where
+        Self: Sized,
+    {
+        let config: Config = config_utils::read_from_files(
+            CONFIG_FILE_STEM,
+            config_utils::JSON_EXT,
+            out_dir!(),
+            ProviderProxyError::io,
+            ProviderProxyError::deserialize,
+        )?;
+
+        let client_id = format!(
+            "freyja-{}-{}",
+            std::process::id(),
+            NEXT_CLIENT_ID.fetch_add(1, Ordering::SeqCst)
+        );
+
+        let client = AsyncClient::new(
+            CreateOptionsBuilder::new()
+                .server_uri(provider_uri)
+                .client_id(client_id)
+                .finalize(),
+        )
+        .map_err(ProviderProxyError::communication)?;
+
+        let entity_configs = config
+            .entities
+            .iter()
+            .map(|c| (c.entity_id.clone(), c.clone()))
+            .collect();
+
+        Ok(MqttProviderProxy {
+            config,
+            entity_configs,
+            provider_uri: provider_uri.to_string(),
+            client,
+            topic_entity_map: Mutex::new(HashMap::new()),
+            signal_values_sender,
+        })
+        .map(|r| Box::new(r) as _)
+    }
+
+    /// Runs a provider proxy. This connects to the broker and handles incoming messages,
+    /// reconnecting and resubscribing if the connection is lost.
+    async fn run(&self) -> Result<(), ProviderProxyError> {
+        info!("Started an MqttProviderProxy!");
+
+        // The stream must be created before connecting so that no messages are missed
+        let mut client = self.client.clone();
+        let mut message_stream = client.get_stream(MESSAGE_BUFFER_SIZE);
+
+        self.connect().await;
+
+        while let Some(message) = message_stream.next().await {
+            match message {
+                Some(message) => self.handle_message(&message),
+                None => {
+                    // A None message indicates that the connection was lost
+                    warn!("Lost connection to the broker at {}", self.provider_uri);
+                    self.connect().await;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Sends a request to a provider for obtaining the value of an entity.
+    /// Providers publish values whenever they change, so there is nothing to request.
+    ///
+    /// # Arguments
+    /// - `entity_id`: the entity id that needs a value
+    async fn send_request_to_provider(&self, entity_id: &str) -> Result<(), ProviderProxyError> {
+        let topic = self.topic_for(entity_id);
+        if !self.topic_entity_map.lock().unwrap().contains_key(&topic) {
+            let message = format!("Entity {entity_id} is not registered with this proxy");
+            info!("{message}");
+            return Err(ProviderProxyError::entity_not_found(message));
+        }
+
+        Ok(())
+    }
+
+    /// Registers an entity id to a local cache inside a provider proxy to keep track of which entities a provider proxy contains.
+    /// This subscribes to the entity's topic if the proxy is connected to the broker.
+    /// Otherwise, the subscription is made once the connection is established.
+    ///
+    /// # Arguments
+    /// - `entity_id`: the entity id to add
+    /// - `operation`: the operation that this entity supports
+    async fn register_entity(
+        &self,
+        entity_id: &str,
+        operation: &str,
+    ) -> Result<(), ProviderProxyError> {
+        if operation != SUBSCRIBE_OPERATION {
+            return Err(ProviderProxyError::unknown(format!(
+                "Operation {operation} is not supported"
+            )));
+        }
+
+        let topic = self.topic_for(entity_id);
+        self.topic_entity_map
+            .lock()
+            .unwrap()
+            .insert(topic.clone(), entity_id.to_string());
+
+        if self.client.is_connected() {
+            let result = self
+                .client
+                .subscribe(topic.as_str(), self.config.qos)
+                .await
+                .map_err(ProviderProxyError::communication);
+
+            // Remove from map if subscribing to the topic fails
+            if result.is_err() {
+                self.topic_entity_map.lock().unwrap().remove(&topic);
+            }
+
+            result?;
+        }
+
+        Ok(())
+    }
+
+    /// Unregisters an entity id from the local cache inside a provider proxy.
+    /// This unsubscribes from the entity's topic if the proxy is connected to the broker.
+    ///
+    /// # Arguments
+    /// - `entity_id`: the entity id to remove
+    async fn unregister_entity(&self, entity_id: &str) -> Result<(), ProviderProxyError> {
+        let topic = self.topic_for(entity_id);
+        let removed = {
+            let mut topic_entity_map = self.topic_entity_map.lock().unwrap();
+            match topic_entity_map.get(&topic) {
+                Some(id) if id == entity_id => topic_entity_map.remove(&topic).is_some(),
+                _ => false,
+            }
+        };
+
+        if removed && self.client.is_connected() {
+            self.client
+                .unsubscribe(topic.as_str())
+                .await
+                .map_err(ProviderProxyError::communication)?;
+        }
+
+        Ok(())
+    }
+
+    /// Checks if the operation is supported
+    ///
+    /// # Arguments
+    /// - `operation`: check to see if this operation is supported by this provider proxy
+    fn is_operation_supported(operation: &str) -> bool {
+        SUPPORTED_OPERATIONS.contains(&operation)
+    }
+}
+
+/// Creates MqttProviderProxy instances
+#[derive(Debug, Default)]
+pub struct MqttProviderProxyFactory;
+
+impl ProviderProxyFactory for MqttProviderProxyFactory {
+    /// Checks if the operation is supported
+    ///
+    /// # Arguments
+    /// - `operation`: check to see if this operation is supported by the provider proxy
+    fn is_operation_supported(&self, operation: &str) -> bool {
+        MqttProviderProxy::is_operation_supported(operation)
+    }
+
+    /// Creates a provider proxy
+    ///
+    /// # Arguments
+    /// - `provider_uri`: the provider uri for accessing an entity's information
+    /// - `signal_values_sender`: shared channel for all provider proxies to send new signal values of entities
+    fn create_proxy(
+        &self,
+        provider_uri: &str,
+        signal_values_sender: UnboundedSender<SignalValue>,
+    ) -> Result<Box<dyn ProviderProxy + Send + Sync>, ProviderProxyError> {
+        MqttProviderProxy::create_new(provider_uri, signal_values_sender)
+    }
+}
+
+/// Decodes the payload of a message into a signal value.
+/// If a JSON pointer is provided, the payload is parsed as JSON and the value at the pointer is used with its JSON type.
+/// Otherwise the type of the value is inferred from the payload text, and payloads which aren't valid UTF-8 are used as bytes.
+///
+/// # Arguments
+/// - `payload`: the raw payload of the message
+/// - `payload_pointer`: the JSON pointer for extracting the value, if any
+fn decode_payload(
+    payload: &[u8],
+    payload_pointer: Option<&str>,
+) -> Result<Value, ProviderProxyError> {
+    let pointer = match payload_pointer {
+        Some(pointer) => pointer,
+        None => {
+            return Ok(match std::str::from_utf8(payload) {
+                Ok(text) => Value::infer(text.trim()),
+                Err(_) => Value::Bytes(payload.to_vec()),
+            })
+        }
+    };
+
+    let payload = std::str::from_utf8(payload).map_err(ProviderProxyError::parse)?;
+
+    let json: serde_json::Value =
+        serde_json::from_str(payload).map_err(ProviderProxyError::deserialize)?;
+
+    match json.pointer(pointer) {
+        Some(value) => Ok(value.clone().into()),
+        None => Err(ProviderProxyError::parse(format!(
+            "The payload does not contain a value at {pointer}"
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod mqtt_provider_proxy_tests {
+    use super::*;
+
+    use freyja_contracts::provider_proxy::ProviderProxyErrorKind;
+    use serde_json::json;
+    use tokio::sync::mpsc::unbounded_channel;
+
+    #[test]
+    fn decode_payload_without_pointer_infers_value() {
+        assert_eq!(
+            decode_payload(b" 42.5\n", None).unwrap(),
+            Value::Float(42.5)
+        );
+        assert_eq!(decode_payload(b"on", None).unwrap(), Value::from("on"));
+    }
+
+    #[test]
+    fn decode_payload_without_pointer_returns_binary_payload_as_bytes() {
+        let payload = [0xff, 0xfe, 0x00];
+        assert_eq!(
+            decode_payload(&payload, None).unwrap(),
+            Value::Bytes(payload.to_vec())
+        );
+    }
+
+    #[test]
+    fn decode_payload_with_pointer_extracts_value() {
+        let payload = br#"{ "value": 42.5, "metadata": { "unit": "celsius" } }"#;
+
+        assert_eq!(
+            decode_payload(payload, Some("/value")).unwrap(),
+            Value::Float(42.5)
+        );
+        assert_eq!(
+            decode_payload(payload, Some("/metadata/unit")).unwrap(),
+            Value::from("celsius")
+        );
+        assert_eq!(
+            decode_payload(payload, Some("/metadata")).unwrap(),
+            Value::Json(json!({"unit": "celsius"}))
+        );
+    }
+
+    #[test]
+    fn decode_payload_with_missing_value_fails() {
+        let result = decode_payload(br#"{ "value": 42.5 }"#, Some("/foo"));
+
+        assert!(result.is_err());
+        assert_eq!(result.err().unwrap().kind(), ProviderProxyErrorKind::Parse);
+    }
+
+    #[test]
+    fn decode_payload_with_invalid_json_fails() {
+        let result = decode_payload(b"42.5 celsius", Some("/value"));
+
+        assert!(result.is_err());
+        assert_eq!(
+            result.err().unwrap().kind(),
+            ProviderProxyErrorKind::Deserialize
+        );
+    }
+
+    const BROKER_URI: &str = "tcp://localhost:1883";
+    const ENTITY_ID: &str = "dtmi:sdv:Vehicle:Cabin:HVAC:AmbientAirTemperature;1";
+    const TOPIC: &str = "freyja/test/ambient_air_temperature";
+    const OTHER_ENTITY_ID: &str = "dtmi:sdv:Vehicle:Cabin:HVAC:IsAirConditioningActive;1";
+
+    /// Creates a proxy for the test broker. The first entity uses the default payload pointer and a custom topic,
+    /// and the other entity uses its own payload pointer and its id as the topic.
+    ///
+    /// # Arguments
+    /// - `payload_pointer`: the default payload pointer
+    /// - `signal_values_sender`: the channel for the values received by the proxy
+    fn create_test_proxy(
+        payload_pointer: Option<&str>,
+        signal_values_sender: UnboundedSender<SignalValue>,
+    ) -> MqttProviderProxy {
+        let config = Config {
+            keep_alive_interval_s: 20,
+            qos: 1,
+            reconnect_interval_ms: 100,
+            payload_pointer: payload_pointer.map(str::to_string),
+            entities: vec![
+                EntityConfig {
+                    entity_id: ENTITY_ID.to_string(),
+                    topic: Some(TOPIC.to_string()),
+                    payload_pointer: None,
+                },
+                EntityConfig {
+                    entity_id: OTHER_ENTITY_ID.to_string(),
+                    topic: None,
+                    payload_pointer: Some("/data/active".to_string()),
+                },
+            ],
+        };
+
+        let entity_configs = config
+            .entities
+            .iter()
+            .map(|c| (c.entity_id.clone(), c.clone()))
+            .collect();
+
+        MqttProviderProxy {
+            config,
+            entity_configs,
+            provider_uri: BROKER_URI.to_string(),
+            client: AsyncClient::new(
+                CreateOptionsBuilder::new()
+                    .server_uri(BROKER_URI)
+                    .client_id("freyja-test-subscriber")
+                    .finalize(),
+            )
+            .unwrap(),
+            topic_entity_map: Mutex::new(HashMap::new()),
+            signal_values_sender,
+        }
+    }
+
+    #[tokio::test]
+    async fn handle_message_sends_values_of_registered_entities() {
+        let (signal_values_sender, mut signal_values_receiver) = unbounded_channel();
+        let uut = create_test_proxy(Some("/value"), signal_values_sender);
+
+        // The proxy isn't connected, so registering only records the topics
+        for entity_id in [ENTITY_ID, OTHER_ENTITY_ID] {
+            assert!(uut
+                .register_entity(entity_id, SUBSCRIBE_OPERATION)
+                .await
+                .is_ok());
+        }
+
+        uut.handle_message(&Message::new(TOPIC, r#"{ "value": 42 }"#, 1));
+        let signal_value = signal_values_receiver.try_recv().unwrap();
+        assert_eq!(signal_value.entity_id, ENTITY_ID);
+        assert_eq!(signal_value.value, Value::Int(42));
+
+        // The entity's payload pointer overrides the default one
+        uut.handle_message(&Message::new(
+            OTHER_ENTITY_ID,
+            r#"{ "value": 1, "data": { "active": true } }"#,
+            1,
+        ));
+        let signal_value = signal_values_receiver.try_recv().unwrap();
+        assert_eq!(signal_value.entity_id, OTHER_ENTITY_ID);
+        assert_eq!(signal_value.value, Value::Bool(true));
+
+        // Messages on unknown topics and payloads which can't be decoded are dropped
+        uut.handle_message(&Message::new("freyja/test/unknown", r#"{ "value": 1 }"#, 1));
+        uut.handle_message(&Message::new(TOPIC, "42", 1));
+        uut.handle_message(&Message::new(TOPIC, r#"{ "other": 42 }"#, 1));
+        assert!(signal_values_receiver.try_recv().is_err());
+
+        // Messages are no longer handled once the entity is unregistered
+        assert!(uut.unregister_entity(ENTITY_ID).await.is_ok());
+        uut.handle_message(&Message::new(TOPIC, r#"{ "value": 42 }"#, 1));
+        assert!(signal_values_receiver.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn handle_message_without_payload_pointer_infers_value() {
+        let (signal_values_sender, mut signal_values_receiver) = unbounded_channel();
+        let uut = create_test_proxy(None, signal_values_sender);
+        assert!(uut
+            .register_entity(ENTITY_ID, SUBSCRIBE_OPERATION)
+            .await
+            .is_ok());
+
+        uut.handle_message(&Message::new(TOPIC, "21.5", 1));
+        let signal_value = signal_values_receiver.try_recv().unwrap();
+        assert_eq!(signal_value.entity_id, ENTITY_ID);
+        assert_eq!(signal_value.value, Value::Float(21.5));
+
+        uut.handle_message(&Message::new(TOPIC, vec![0xff, 0xfe], 1));
+        assert_eq!(
+            signal_values_receiver.try_recv().unwrap().value,
+            Value::Bytes(vec![0xff, 0xfe])
+        );
+    }
+
+    /// The tests below require an MQTT broker listening on localhost:1883, for example Mosquitto.
+    /// Run them with `cargo test -p mqtt-provider-proxy -- --ignored`.
+    mod broker_tests {
+        use super::*;
+
+        use std::sync::Arc;
+
+        #[tokio::test]
+        #[ignore = "requires an MQTT broker on localhost:1883"]
+        async fn subscribed_values_are_sent_to_channel() {
+            let (signal_values_sender, mut signal_values_receiver) = unbounded_channel();
+            let uut = Arc::new(create_test_proxy(Some("/value"), signal_values_sender));
+
+            assert!(uut
+                .register_entity(ENTITY_ID, SUBSCRIBE_OPERATION)
+                .await
+                .is_ok());
+
+            let run_uut = uut.clone();
+            tokio::spawn(async move { run_uut.run().await });
+
+            let publisher = AsyncClient::new(
+                CreateOptionsBuilder::new()
+                    .server_uri(BROKER_URI)
+                    .client_id("freyja-test-publisher")
+                    .finalize(),
+            )
+            .unwrap();
+            publisher.connect(None).await.unwrap();
+
+            // Keep publishing until the proxy has subscribed and received a value
+            let mut signal_value = None;
+            for _ in 0..50 {
+                publisher
+                    .publish(Message::new(TOPIC, r#"{ "value": 42 }"#, 1))
+                    .await
+                    .unwrap();
+
+                tokio::time::sleep(Duration::from_millis(100)).await;
+                signal_value = signal_values_receiver.try_recv().ok();
+                if signal_value.is_some() {
+                    break;
+                }
+            }
+
+            let signal_value = signal_value.expect("No value was received from the broker");
+            assert_eq!(signal_value.entity_id, ENTITY_ID);
+            assert_eq!(signal_value.value, Value::Int(42));
+            assert!(uut.send_request_to_provider(ENTITY_ID).await.is_ok());
+        }
+    }
+}
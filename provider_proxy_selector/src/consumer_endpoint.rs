@@ -0,0 +1,85 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+// SPDX-License-Identifier: MIT
+
+use std::future::Future;
+
+use log::{info, warn};
+use tokio::task::JoinHandle;
+
+use freyja_contracts::provider_proxy::ProviderProxyError;
+
+/// An endpoint which receives the values that providers publish for a protocol.
+/// A single endpoint is shared by all of the provider proxies for a protocol, so that each endpoint only binds its address once.
+/// Share it with `Arc<ConsumerEndpoint>`: the endpoint is stopped when the last reference is dropped.
+#[derive(Debug)]
+pub struct ConsumerEndpoint {
+    /// The protocol that this endpoint handles
+    protocol: String,
+
+    /// The task running the endpoint
+    task: JoinHandle<()>,
+}
+
+impl ConsumerEndpoint {
+    /// Starts a consumer endpoint
+    ///
+    /// # Arguments
+    /// - `protocol`: the protocol that this endpoint handles
+    /// - `endpoint`: the future which runs the endpoint
+    pub fn start<TEndpoint>(protocol: &str, endpoint: TEndpoint) -> Self
+    where
+        TEndpoint: Future<Output = Result<(), ProviderProxyError>> + Send + 'static,
+    {
+        info!("Starting the shared {protocol} consumer endpoint");
+
+        let endpoint_protocol = protocol.to_string();
+        let task = tokio::spawn(async move {
+            if let Err(e) = endpoint.await {
+                warn!("The shared {endpoint_protocol} consumer endpoint stopped: {e:?}");
+            }
+        });
+
+        Self {
+            protocol: protocol.to_string(),
+            task,
+        }
+    }
+}
+
+impl Drop for ConsumerEndpoint {
+    fn drop(&mut self) {
+        info!(
+            "Stopping the shared {} consumer endpoint since no provider proxies use it",
+            self.protocol
+        );
+
+        self.task.abort();
+    }
+}
+
+#[cfg(test)]
+mod consumer_endpoint_tests {
+    use super::*;
+
+    use std::sync::Arc;
+
+    use tokio::sync::oneshot;
+
+    #[tokio::test]
+    async fn endpoint_stops_when_last_reference_is_dropped() {
+        let (stopped_sender, stopped_receiver) = oneshot::channel::<()>();
+        let endpoint = Arc::new(ConsumerEndpoint::start("test", async move {
+            let _stopped_sender = stopped_sender;
+            std::future::pending().await
+        }));
+
+        let endpoint_clone = endpoint.clone();
+        drop(endpoint);
+        tokio::task::yield_now().await;
+        assert!(!endpoint_clone.task.is_finished());
+
+        drop(endpoint_clone);
+        assert!(stopped_receiver.await.is_err());
+    }
+}
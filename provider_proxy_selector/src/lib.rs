@@ -2,4 +2,5 @@
 // Licensed under the MIT license.
 // SPDX-License-Identifier: MIT
 
+pub mod consumer_endpoint;
 pub mod provider_proxy_selector_impl;
@@ -4,153 +4,233 @@
 
 use std::{
     collections::{hash_map::Entry, HashMap},
-    str::FromStr,
-    sync::Arc,
+    sync::{Arc, Weak},
 };
 
 use async_trait::async_trait;
-use crossbeam::queue::SegQueue;
 use log::{debug, info, warn};
-use strum_macros::{Display, EnumString};
+use tokio::{sync::mpsc::UnboundedSender, task::JoinHandle};
 
 use freyja_contracts::{
     entity::Entity,
-    provider_proxy::{ProviderProxy, ProviderProxyError, SignalValue},
-    provider_proxy_selector::{ProviderProxySelector, ProviderProxySelectorError},
+    provider_proxy::{ProviderProxy, ProviderProxyFactory, SignalValue},
+    provider_proxy_selector::{
+        InvokeResponseFuture, ProviderProxySelector, ProviderProxySelectorError, SetValueFuture,
+    },
+    value::Value,
 };
-use grpc_provider_proxy_v1::grpc_provider_proxy::GRPCProviderProxy;
-use http_mock_provider_proxy::http_mock_provider_proxy::HttpMockProviderProxy;
-use in_memory_mock_provider_proxy::in_memory_provider_proxy::InMemoryMockProviderProxy;
+
+use crate::consumer_endpoint::ConsumerEndpoint;
 
 type ProviderProxyImpl = Arc<Box<dyn ProviderProxy + Send + Sync>>;
+type ProviderProxyFactoryImpl = Arc<dyn ProviderProxyFactory>;
+
+/// The protocol handled by the GRPCProviderProxy
+pub const GRPC_PROTOCOL: &str = "grpc";
+
+/// The protocol handled by the MqttProviderProxy
+pub const MQTT_PROTOCOL: &str = "mqtt";
+
+/// The protocol handled by the InMemoryMockProviderProxy
+pub const IN_MEMORY_PROTOCOL: &str = "in-memory";
+
+/// The protocol handled by the HttpMockProviderProxy
+pub const HTTP_PROTOCOL: &str = "http";
+
+/// The provider proxy selector selects which provider proxy to create based on protocol and operation.
+/// Provider proxies are created by the factory registered for the entity's protocol.
+/// This struct is **not** thread-safe and should be shared with `Arc<Mutex<ProviderProxySelectorImpl>>`.
+pub struct ProviderProxySelectorImpl {
+    /// A map of entity uri to provider proxy
+    pub provider_proxies: HashMap<String, ProviderProxyImpl>,
+
+    /// A map of entity id to provider uri
+    pub entity_map: HashMap<String, String>,
+
+    /// A map of provider uri to the task running its provider proxy
+    pub proxy_tasks: HashMap<String, JoinHandle<()>>,
 
-/// Provider Proxy for matching the message delivery model
-#[derive(Debug, Clone, EnumString, Display, PartialEq)]
-#[strum(ascii_case_insensitive)]
-pub enum ProviderProxyKind {
-    #[strum(serialize = "grpc")]
-    GRPCProviderProxy,
+    /// A map of protocol to the factory which creates provider proxies for that protocol.
+    /// Protocols are stored in lowercase since they are matched case-insensitively.
+    pub factories: HashMap<String, ProviderProxyFactoryImpl>,
 
-    #[strum(serialize = "mqtt")]
-    MqttProviderProxy,
+    /// A map of protocol to the consumer endpoint shared by all proxies of that protocol
+    pub consumer_endpoints: HashMap<String, Weak<ConsumerEndpoint>>,
 
-    #[strum(serialize = "in-memory")]
-    InMemoryMockProviderProxy,
+    /// A map of provider uri to the consumer endpoint used by its provider proxy
+    pub proxy_consumer_endpoints: HashMap<String, Arc<ConsumerEndpoint>>,
 
-    #[strum(serialize = "http")]
-    HttpProviderProxy,
+    /// The signal values sender used for creating the proxies
+    pub signal_values_sender: UnboundedSender<SignalValue>,
 }
 
-impl ProviderProxyKind {
-    /// Handles the result from a provider proxy's instantiation
+impl ProviderProxySelectorImpl {
+    /// Instantiates the provider proxy selector with factories for the built-in provider proxies which are enabled
     ///
     /// # Arguments
-    /// - `provider_proxy_result`: the result of creating a provider proxy
-    /// - `provider_proxy_kind`: the provider proxy kind
-    fn handle_provider_proxy_result(
-        provider_proxy_result: Result<Box<dyn ProviderProxy + Send + Sync>, ProviderProxyError>,
-        provider_proxy_kind: ProviderProxyKind,
-    ) -> Result<ProviderProxyImpl, ProviderProxySelectorError> {
-        if let Err(error) = provider_proxy_result {
-            warn!("Cannot create a {provider_proxy_kind} due to {error:?}");
-            return Err(ProviderProxySelectorError::communication(error));
+    /// - `signal_values_sender`: The sender that is passed to proxies and used to update the emitter
+    pub fn new(signal_values_sender: UnboundedSender<SignalValue>) -> Self {
+        #[allow(unused_mut)]
+        let mut provider_proxy_selector = ProviderProxySelectorImpl {
+            provider_proxies: HashMap::new(),
+            entity_map: HashMap::new(),
+            proxy_tasks: HashMap::new(),
+            factories: HashMap::new(),
+            consumer_endpoints: HashMap::new(),
+            proxy_consumer_endpoints: HashMap::new(),
+            signal_values_sender,
+        };
+
+        #[cfg(feature = "grpc")]
+        provider_proxy_selector.register_factory(
+            GRPC_PROTOCOL,
+            Box::<grpc_provider_proxy_v1::grpc_provider_proxy::GRPCProviderProxyFactory>::default(),
+        );
+
+        #[cfg(feature = "mqtt")]
+        provider_proxy_selector.register_factory(
+            MQTT_PROTOCOL,
+            Box::new(mqtt_provider_proxy::mqtt_provider_proxy::MqttProviderProxyFactory),
+        );
+
+        #[cfg(feature = "in-memory-mock")]
+        provider_proxy_selector.register_factory(
+            IN_MEMORY_PROTOCOL,
+            Box::new(
+                in_memory_mock_provider_proxy::in_memory_provider_proxy::InMemoryMockProviderProxyFactory,
+            ),
+        );
+
+        #[cfg(feature = "http-mock")]
+        provider_proxy_selector.register_factory(
+            HTTP_PROTOCOL,
+            Box::new(
+                http_mock_provider_proxy::http_mock_provider_proxy::HttpMockProviderProxyFactory,
+            ),
+        );
+
+        provider_proxy_selector
+    }
+
+    /// Registers a factory which creates provider proxies for a protocol.
+    /// Protocols are matched case-insensitively, and a factory which was already registered for the protocol is replaced.
+    /// Proxies which were already created are not affected.
+    ///
+    /// # Arguments
+    /// - `protocol`: the protocol that the factory handles
+    /// - `factory`: the factory
+    pub fn register_factory(&mut self, protocol: &str, factory: Box<dyn ProviderProxyFactory>) {
+        info!("Registering a provider proxy factory for {protocol}");
+
+        if self
+            .factories
+            .insert(protocol.to_lowercase(), Arc::from(factory))
+            .is_some()
+        {
+            info!("Replaced the existing provider proxy factory for {protocol}");
         }
+    }
+
+    /// Gets the protocols which have a registered factory, in alphabetical order
+    pub fn supported_protocols(&self) -> Vec<String> {
+        let mut protocols: Vec<String> = self.factories.keys().cloned().collect();
+        protocols.sort();
+        protocols
+    }
 
-        Ok(Arc::new(provider_proxy_result.unwrap()))
+    /// Gets the factory registered for a protocol
+    ///
+    /// # Arguments
+    /// - `protocol`: the protocol
+    fn get_factory(
+        &self,
+        protocol: &str,
+    ) -> Result<ProviderProxyFactoryImpl, ProviderProxySelectorError> {
+        self.factories.get(&protocol.to_lowercase()).cloned().ok_or(
+            ProviderProxySelectorError::protocol_not_supported(format!(
+                "No provider proxy factory is registered for {protocol}"
+            )),
+        )
     }
 
-    /// Instantiates the respective provider proxy using protocol and operation
+    /// Instantiates a provider proxy using the factory registered for the protocol
     ///
     /// # Arguments
     /// - `protocol`: the protocol for identifying the provider proxy
     /// - `operation`: the operation for identifying the provider proxy
     /// - `provider_uri`: the provider uri to contact
-    /// - `signal_values_queue`: shared queue for all proxies to push new signal values of entities
-    async fn create_provider_proxy(
+    fn create_provider_proxy(
+        &self,
         protocol: &str,
         operation: &str,
         provider_uri: &str,
-        signal_values_queue: Arc<SegQueue<SignalValue>>,
     ) -> Result<ProviderProxyImpl, ProviderProxySelectorError> {
-        // Take the protocol match it to the ProviderProxyKind concrete implementation
-        // With the concrete implementation, check if our operation matches with anything that the provider has
-        let protocol_kind = ProviderProxyKind::from_str(protocol)
-            .map_err(ProviderProxySelectorError::protocol_not_supported)?;
-
-        let provider_proxy: ProviderProxyImpl;
-        match protocol_kind {
-            ProviderProxyKind::GRPCProviderProxy
-                if GRPCProviderProxy::is_operation_supported(operation) =>
-            {
-                info!("Creating a GRPCProviderProxy");
-
-                let grpc_provider_proxy_result =
-                    GRPCProviderProxy::create_new(provider_uri, signal_values_queue);
-
-                provider_proxy = ProviderProxyKind::handle_provider_proxy_result(
-                    grpc_provider_proxy_result,
-                    ProviderProxyKind::GRPCProviderProxy,
-                )?;
-            }
-            ProviderProxyKind::InMemoryMockProviderProxy
-                if InMemoryMockProviderProxy::is_operation_supported(operation) =>
-            {
-                info!("Creating an InMemoryProviderProxy");
-
-                let in_memory_mock_provider_proxy_result =
-                    InMemoryMockProviderProxy::create_new(provider_uri, signal_values_queue);
-
-                provider_proxy = ProviderProxyKind::handle_provider_proxy_result(
-                    in_memory_mock_provider_proxy_result,
-                    ProviderProxyKind::InMemoryMockProviderProxy,
-                )?;
-            }
-            ProviderProxyKind::HttpProviderProxy
-                if HttpMockProviderProxy::is_operation_supported(operation) =>
-            {
-                info!("Creating an HttpProviderProxy");
-                let http_provider_proxy_result =
-                    HttpMockProviderProxy::create_new(provider_uri, signal_values_queue);
-
-                provider_proxy = ProviderProxyKind::handle_provider_proxy_result(
-                    http_provider_proxy_result,
-                    ProviderProxyKind::HttpProviderProxy,
-                )?;
-            }
-            _ => {
-                return Err(ProviderProxySelectorError::operation_not_supported(
-                    "operation not supported",
-                ))
-            }
+        let factory = self.get_factory(protocol)?;
+
+        if !factory.is_operation_supported(operation) {
+            return Err(ProviderProxySelectorError::operation_not_supported(
+                format!("operation {operation} is not supported for {protocol}"),
+            ));
         }
-        Ok(provider_proxy)
+
+        info!("Creating a provider proxy for {protocol}");
+
+        factory
+            .create_proxy(provider_uri, self.signal_values_sender.clone())
+            .map(Arc::new)
+            .map_err(|error| {
+                warn!("Cannot create a provider proxy for {protocol} due to {error:?}");
+                ProviderProxySelectorError::communication(error)
+            })
     }
-}
 
-/// The provider proxy selector selects which provider proxy to create based on protocol and operation.
-/// This struct is **not** thread-safe and should be shared with `Arc<Mutex<ProviderProxySelectorImpl>>`.
-pub struct ProviderProxySelectorImpl {
-    /// A map of entity uri to provider proxy
-    pub provider_proxies: HashMap<String, ProviderProxyImpl>,
+    /// Gets the consumer endpoint shared by all provider proxies of a protocol, starting it if it isn't running.
+    /// Returns `None` if provider proxies of this protocol don't need a consumer endpoint.
+    ///
+    /// # Arguments
+    /// - `protocol`: the protocol
+    fn acquire_consumer_endpoint(&mut self, protocol: &str) -> Option<Arc<ConsumerEndpoint>> {
+        let protocol = protocol.to_lowercase();
+        if let Some(consumer_endpoint) = self
+            .consumer_endpoints
+            .get(&protocol)
+            .and_then(Weak::upgrade)
+        {
+            return Some(consumer_endpoint);
+        }
 
-    /// A map of entity id to provider uri
-    pub entity_map: HashMap<String, String>,
+        let endpoint = self
+            .get_factory(&protocol)
+            .ok()?
+            .create_consumer_endpoint(self.signal_values_sender.clone())?;
+        let consumer_endpoint = Arc::new(ConsumerEndpoint::start(&protocol, endpoint));
 
-    /// The signal values queue used for creating the proxies
-    pub signal_values_queue: Arc<SegQueue<SignalValue>>,
-}
+        self.consumer_endpoints
+            .insert(protocol, Arc::downgrade(&consumer_endpoint));
 
-impl ProviderProxySelectorImpl {
-    /// Instantiates the provider proxy selector
+        Some(consumer_endpoint)
+    }
+
+    /// Gets the provider proxy which handles an entity
     ///
     /// # Arguments
-    /// - `signal_values_queue`: The queue that is passed to proxies andused to update the emitter
-    pub fn new(signal_values_queue: Arc<SegQueue<SignalValue>>) -> Self {
-        ProviderProxySelectorImpl {
-            provider_proxies: HashMap::new(),
-            entity_map: HashMap::new(),
-            signal_values_queue,
-        }
+    /// - `entity_id`: the entity id
+    fn get_entity_proxy(
+        &self,
+        entity_id: &str,
+    ) -> Result<ProviderProxyImpl, ProviderProxySelectorError> {
+        let provider_uri =
+            self.entity_map
+                .get(entity_id)
+                .ok_or(ProviderProxySelectorError::entity_not_found(format!(
+                    "Unable to retrieve entity uri for {entity_id}"
+                )))?;
+
+        self.provider_proxies.get(provider_uri).cloned().ok_or(
+            ProviderProxySelectorError::entity_not_found(format!(
+                "Provider proxy for {entity_id} is not available"
+            )),
+        )
     }
 }
 
@@ -168,6 +248,17 @@ impl ProviderProxySelector for ProviderProxySelectorImpl {
         let (entity_id, provider_uri, operation, protocol) =
             (&entity.id, &entity.uri, &entity.operation, &entity.protocol);
 
+        // If the entity was previously handled by a different provider, remove it from that provider first
+        if self
+            .entity_map
+            .get(entity_id)
+            .is_some_and(|uri| uri != provider_uri)
+        {
+            if let Err(e) = self.remove_entity(entity_id).await {
+                warn!("Unable to remove {entity_id} from its previous provider proxy: {e:?}");
+            }
+        }
+
         // If a provider proxy already exists for this uri,
         // then we notify that proxy to include this new entity_id
         if let Some(provider_proxy) = self.provider_proxies.get(provider_uri).cloned() {
@@ -180,13 +271,7 @@ impl ProviderProxySelector for ProviderProxySelectorImpl {
                 .map_err(ProviderProxySelectorError::communication);
         }
 
-        let provider_proxy = ProviderProxyKind::create_provider_proxy(
-            protocol,
-            operation,
-            provider_uri,
-            self.signal_values_queue.clone(),
-        )
-        .await?;
+        let provider_proxy = self.create_provider_proxy(protocol, operation, provider_uri)?;
 
         // If we're able to create a provider_proxy then map the
         // provider uri to that created proxy
@@ -197,9 +282,16 @@ impl ProviderProxySelector for ProviderProxySelectorImpl {
             .insert(String::from(entity_id), String::from(provider_uri));
 
         let proxy = provider_proxy.clone();
-        tokio::spawn(async move {
+        let proxy_task = tokio::spawn(async move {
             let _ = proxy.run().await;
         });
+        self.proxy_tasks.insert(provider_uri.clone(), proxy_task);
+
+        // Values published to proxies of this protocol are received by a consumer endpoint shared with the other proxies of the same protocol
+        if let Some(consumer_endpoint) = self.acquire_consumer_endpoint(protocol) {
+            self.proxy_consumer_endpoints
+                .insert(provider_uri.clone(), consumer_endpoint);
+        }
 
         provider_proxy
             .register_entity(entity_id, operation)
@@ -207,6 +299,43 @@ impl ProviderProxySelector for ProviderProxySelectorImpl {
             .map_err(ProviderProxySelectorError::provider_proxy_error)
     }
 
+    /// Removes an entity from the proxy that handles it.
+    /// The proxy is shut down if it no longer handles any entities.
+    ///
+    /// # Arguments
+    /// - `entity_id`: the entity to remove
+    async fn remove_entity(&mut self, entity_id: &str) -> Result<(), ProviderProxySelectorError> {
+        let provider_uri = self.entity_map.remove(entity_id).ok_or(
+            ProviderProxySelectorError::entity_not_found(format!(
+                "Unable to retrieve entity uri for {entity_id}"
+            )),
+        )?;
+
+        let result = match self.provider_proxies.get(&provider_uri) {
+            Some(provider_proxy) => provider_proxy
+                .unregister_entity(entity_id)
+                .await
+                .map_err(ProviderProxySelectorError::provider_proxy_error),
+            None => Ok(()),
+        };
+
+        // Shut down the proxy once none of the remaining entities use it
+        if !self.entity_map.values().any(|uri| *uri == provider_uri) {
+            info!(
+                "Shutting down the provider proxy for {provider_uri} since it has no entities left"
+            );
+            self.provider_proxies.remove(&provider_uri);
+            if let Some(proxy_task) = self.proxy_tasks.remove(&provider_uri) {
+                proxy_task.abort();
+            }
+
+            // The shared consumer endpoint is stopped if this was the last proxy using it
+            self.proxy_consumer_endpoints.remove(&provider_uri);
+        }
+
+        result
+    }
+
     /// Requests that the value of an entity be published as soon as possible
     ///
     /// # Arguments
@@ -235,21 +364,144 @@ impl ProviderProxySelector for ProviderProxySelectorImpl {
             ))),
         }
     }
+
+    /// Sets the value of an entity using the proxy that handles it.
+    /// The returned future resolves once the provider has set the value. It doesn't borrow the selector,
+    /// so callers can release the selector while waiting for the provider.
+    ///
+    /// # Arguments
+    /// - `entity_id`: the entity to set the value of
+    /// - `value`: the value to set, in the provider's units
+    async fn set_entity_value(
+        &mut self,
+        entity_id: &str,
+        value: Value,
+    ) -> Result<SetValueFuture, ProviderProxySelectorError> {
+        let provider_proxy = self.get_entity_proxy(entity_id)?;
+        let entity_id = String::from(entity_id);
+
+        Ok(Box::pin(async move {
+            provider_proxy
+                .set_value(&entity_id, value)
+                .await
+                .map_err(ProviderProxySelectorError::provider_proxy_error)
+        }))
+    }
+
+    /// Invokes a command on an entity using the proxy that handles it.
+    /// The returned future resolves to the provider's response. It doesn't borrow the selector,
+    /// so callers can release the selector while waiting for a response.
+    ///
+    /// # Arguments
+    /// - `entity_id`: the entity to invoke
+    /// - `payload`: the payload of the command
+    async fn invoke_entity(
+        &mut self,
+        entity_id: &str,
+        payload: Value,
+    ) -> Result<InvokeResponseFuture, ProviderProxySelectorError> {
+        let provider_proxy = self.get_entity_proxy(entity_id)?;
+        let entity_id = String::from(entity_id);
+
+        Ok(Box::pin(async move {
+            provider_proxy
+                .invoke(&entity_id, payload)
+                .await
+                .map_err(ProviderProxySelectorError::provider_proxy_error)
+        }))
+    }
 }
 
 #[cfg(test)]
 mod provider_proxy_selector_tests {
     use super::*;
 
-    use freyja_contracts::provider_proxy_selector::ProviderProxySelectorErrorKind;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use freyja_contracts::{
+        provider_proxy::ProviderProxyError, provider_proxy_selector::ProviderProxySelectorErrorKind,
+    };
+    use tokio::sync::mpsc::unbounded_channel;
 
     const AMBIENT_AIR_TEMPERATURE_ID: &str = "dtmi:sdv:Vehicle:Cabin:HVAC:AmbientAirTemperature;1";
-    const OPERATION: &str = "Subscribe";
+    const TEST_OPERATION: &str = "Test";
+
+    /// A provider proxy which doesn't communicate with any providers
+    #[derive(Debug)]
+    struct TestProviderProxy;
+
+    #[async_trait]
+    impl ProviderProxy for TestProviderProxy {
+        fn create_new(
+            _provider_uri: &str,
+            _signal_values_sender: UnboundedSender<SignalValue>,
+        ) -> Result<Box<dyn ProviderProxy + Send + Sync>, ProviderProxyError> {
+            Ok(Box::new(Self))
+        }
+
+        async fn run(&self) -> Result<(), ProviderProxyError> {
+            Ok(())
+        }
+
+        async fn send_request_to_provider(
+            &self,
+            _entity_id: &str,
+        ) -> Result<(), ProviderProxyError> {
+            Ok(())
+        }
+
+        async fn register_entity(
+            &self,
+            _entity_id: &str,
+            _operation: &str,
+        ) -> Result<(), ProviderProxyError> {
+            Ok(())
+        }
+
+        async fn unregister_entity(&self, _entity_id: &str) -> Result<(), ProviderProxyError> {
+            Ok(())
+        }
+
+        async fn invoke(
+            &self,
+            _entity_id: &str,
+            payload: Value,
+        ) -> Result<Value, ProviderProxyError> {
+            Ok(payload)
+        }
+
+        fn is_operation_supported(operation: &str) -> bool {
+            operation == TEST_OPERATION
+        }
+    }
 
+    /// Creates TestProviderProxy instances and counts how many were created
+    struct TestProviderProxyFactory {
+        created: Arc<AtomicUsize>,
+    }
+
+    impl ProviderProxyFactory for TestProviderProxyFactory {
+        fn is_operation_supported(&self, operation: &str) -> bool {
+            TestProviderProxy::is_operation_supported(operation)
+        }
+
+        fn create_proxy(
+            &self,
+            provider_uri: &str,
+            signal_values_sender: UnboundedSender<SignalValue>,
+        ) -> Result<Box<dyn ProviderProxy + Send + Sync>, ProviderProxyError> {
+            self.created.fetch_add(1, Ordering::SeqCst);
+            TestProviderProxy::create_new(provider_uri, signal_values_sender)
+        }
+    }
+
+    #[cfg(feature = "grpc")]
     #[tokio::test]
     async fn handle_start_provider_proxy_request_return_err_test() {
-        let signal_values_queue: Arc<SegQueue<SignalValue>> = Arc::new(SegQueue::new());
-        let mut uut = ProviderProxySelectorImpl::new(signal_values_queue);
+        const OPERATION: &str = "Subscribe";
+
+        let (signal_values_sender, _) = unbounded_channel();
+        let mut uut = ProviderProxySelectorImpl::new(signal_values_sender);
 
         let entity = Entity {
             id: String::from(AMBIENT_AIR_TEMPERATURE_ID),
@@ -257,7 +509,7 @@ mod provider_proxy_selector_tests {
             name: None,
             description: None,
             operation: OPERATION.to_string(),
-            protocol: String::from("grpc"),
+            protocol: String::from(GRPC_PROTOCOL),
         };
 
         let result = uut.create_or_update_proxy(&entity).await;
@@ -269,22 +521,215 @@ mod provider_proxy_selector_tests {
         );
     }
 
+    #[cfg(feature = "in-memory-mock")]
+    #[tokio::test]
+    async fn remove_entity_shuts_down_unused_proxies() {
+        const PROVIDER_URI: &str = "in_memory_provider";
+        const OTHER_ENTITY_ID: &str = "other_entity";
+
+        let (signal_values_sender, _signal_values_receiver) = unbounded_channel();
+        let mut uut = ProviderProxySelectorImpl::new(signal_values_sender);
+
+        for entity_id in [AMBIENT_AIR_TEMPERATURE_ID, OTHER_ENTITY_ID] {
+            let entity = Entity {
+                id: String::from(entity_id),
+                uri: String::from(PROVIDER_URI),
+                name: None,
+                description: None,
+                operation: String::from("Get"),
+                protocol: String::from(IN_MEMORY_PROTOCOL),
+            };
+
+            assert!(uut.create_or_update_proxy(&entity).await.is_ok());
+        }
+
+        assert_eq!(uut.provider_proxies.len(), 1);
+
+        // The proxy is kept while it still handles an entity
+        assert!(uut.remove_entity(OTHER_ENTITY_ID).await.is_ok());
+        assert!(!uut.entity_map.contains_key(OTHER_ENTITY_ID));
+        assert!(uut.provider_proxies.contains_key(PROVIDER_URI));
+        assert!(uut.proxy_tasks.contains_key(PROVIDER_URI));
+
+        // Replace the proxy task with one that reports when it is stopped
+        let (stopped_sender, stopped_receiver) = tokio::sync::oneshot::channel::<()>();
+        let proxy_task = tokio::spawn(async move {
+            let _stopped_sender = stopped_sender;
+            std::future::pending::<()>().await
+        });
+        uut.proxy_tasks
+            .insert(String::from(PROVIDER_URI), proxy_task)
+            .unwrap()
+            .abort();
+
+        assert!(uut.remove_entity(AMBIENT_AIR_TEMPERATURE_ID).await.is_ok());
+        assert!(uut.entity_map.is_empty());
+        assert!(uut.provider_proxies.is_empty());
+        assert!(uut.proxy_tasks.is_empty());
+        assert!(stopped_receiver.await.is_err());
+
+        let result = uut.remove_entity(AMBIENT_AIR_TEMPERATURE_ID).await;
+        assert!(result.is_err());
+        assert_eq!(
+            result.unwrap_err().kind(),
+            ProviderProxySelectorErrorKind::EntityNotFound
+        );
+    }
+
+    #[cfg(feature = "http-mock")]
+    #[tokio::test]
+    async fn proxies_of_the_same_protocol_share_a_consumer_endpoint() {
+        const PROVIDER_URIS: &[&str] = &["http://provider1", "http://provider2"];
+
+        let (signal_values_sender, _signal_values_receiver) = unbounded_channel();
+        let mut uut = ProviderProxySelectorImpl::new(signal_values_sender);
+
+        for (i, provider_uri) in PROVIDER_URIS.iter().enumerate() {
+            let entity = Entity {
+                id: format!("entity{i}"),
+                uri: String::from(*provider_uri),
+                name: None,
+                description: None,
+                operation: String::from("Get"),
+                protocol: String::from(HTTP_PROTOCOL),
+            };
+
+            assert!(uut.create_or_update_proxy(&entity).await.is_ok());
+        }
+
+        assert_eq!(uut.provider_proxies.len(), PROVIDER_URIS.len());
+        assert_eq!(uut.consumer_endpoints.len(), 1);
+        assert!(Arc::ptr_eq(
+            &uut.proxy_consumer_endpoints[PROVIDER_URIS[0]],
+            &uut.proxy_consumer_endpoints[PROVIDER_URIS[1]]
+        ));
+
+        // The endpoint is stopped once the last proxy using it is removed
+        let consumer_endpoint = Arc::downgrade(&uut.proxy_consumer_endpoints[PROVIDER_URIS[0]]);
+        assert!(uut.remove_entity("entity0").await.is_ok());
+        assert!(consumer_endpoint.upgrade().is_some());
+        assert!(uut.remove_entity("entity1").await.is_ok());
+        assert!(consumer_endpoint.upgrade().is_none());
+    }
+
     #[test]
-    fn protocol_kind_match_test() {
-        let mut grpc = String::from("grpc");
-        let mut protocol_kind = ProviderProxyKind::from_str(&grpc).unwrap();
-        assert_eq!(protocol_kind, ProviderProxyKind::GRPCProviderProxy);
-
-        grpc = String::from("gRPC");
-        protocol_kind = ProviderProxyKind::from_str(&grpc).unwrap();
-        assert_eq!(protocol_kind, ProviderProxyKind::GRPCProviderProxy);
-
-        let mut mqtt = String::from("mqtt");
-        protocol_kind = ProviderProxyKind::from_str(&mqtt).unwrap();
-        assert_eq!(protocol_kind, ProviderProxyKind::MqttProviderProxy);
-
-        mqtt = String::from("mQTT");
-        protocol_kind = ProviderProxyKind::from_str(&mqtt).unwrap();
-        assert_eq!(protocol_kind, ProviderProxyKind::MqttProviderProxy);
+    fn new_registers_enabled_built_in_factories() {
+        let (signal_values_sender, _) = unbounded_channel();
+        let uut = ProviderProxySelectorImpl::new(signal_values_sender);
+
+        assert_eq!(
+            uut.factories.contains_key(GRPC_PROTOCOL),
+            cfg!(feature = "grpc")
+        );
+        assert_eq!(
+            uut.factories.contains_key(MQTT_PROTOCOL),
+            cfg!(feature = "mqtt")
+        );
+        assert_eq!(
+            uut.factories.contains_key(IN_MEMORY_PROTOCOL),
+            cfg!(feature = "in-memory-mock")
+        );
+        assert_eq!(
+            uut.factories.contains_key(HTTP_PROTOCOL),
+            cfg!(feature = "http-mock")
+        );
+    }
+
+    #[tokio::test]
+    async fn registered_factories_create_proxies_for_their_protocol() {
+        let (signal_values_sender, _) = unbounded_channel();
+        let mut uut = ProviderProxySelectorImpl::new(signal_values_sender);
+
+        let created = Arc::new(AtomicUsize::new(0));
+        uut.register_factory(
+            "Custom",
+            Box::new(TestProviderProxyFactory {
+                created: created.clone(),
+            }),
+        );
+
+        let entity = |protocol: &str, operation: &str| Entity {
+            id: String::from(AMBIENT_AIR_TEMPERATURE_ID),
+            uri: format!("{protocol}://{operation}"),
+            name: None,
+            description: None,
+            operation: String::from(operation),
+            protocol: String::from(protocol),
+        };
+
+        // Protocols are matched case-insensitively
+        let result = uut
+            .create_or_update_proxy(&entity("cUSTOM", TEST_OPERATION))
+            .await;
+        assert!(result.is_ok());
+        assert_eq!(created.load(Ordering::SeqCst), 1);
+        assert_eq!(uut.provider_proxies.len(), 1);
+        assert!(uut.proxy_consumer_endpoints.is_empty());
+        assert!(uut.supported_protocols().contains(&String::from("custom")));
+
+        let result = uut
+            .create_or_update_proxy(&entity("custom", "Unsupported"))
+            .await;
+        assert_eq!(
+            result.unwrap_err().kind(),
+            ProviderProxySelectorErrorKind::OperationNotSupported
+        );
+
+        let result = uut
+            .create_or_update_proxy(&entity("unknown", TEST_OPERATION))
+            .await;
+        assert_eq!(
+            result.unwrap_err().kind(),
+            ProviderProxySelectorErrorKind::ProtocolNotSupported
+        );
+
+        assert_eq!(created.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn commands_are_routed_to_the_entitys_proxy() {
+        let (signal_values_sender, _) = unbounded_channel();
+        let mut uut = ProviderProxySelectorImpl::new(signal_values_sender);
+        uut.register_factory(
+            "custom",
+            Box::new(TestProviderProxyFactory {
+                created: Arc::new(AtomicUsize::new(0)),
+            }),
+        );
+
+        let entity = Entity {
+            id: String::from(AMBIENT_AIR_TEMPERATURE_ID),
+            uri: String::from("custom://provider"),
+            name: None,
+            description: None,
+            operation: String::from(TEST_OPERATION),
+            protocol: String::from("custom"),
+        };
+        assert!(uut.create_or_update_proxy(&entity).await.is_ok());
+
+        let response = uut
+            .invoke_entity(AMBIENT_AIR_TEMPERATURE_ID, Value::from("payload"))
+            .await
+            .unwrap()
+            .await;
+        assert_eq!(response.unwrap(), Value::from("payload"));
+
+        // The test proxy doesn't override set_value, so setting values is not supported
+        let result = uut
+            .set_entity_value(AMBIENT_AIR_TEMPERATURE_ID, Value::Int(42))
+            .await
+            .unwrap()
+            .await;
+        assert_eq!(
+            result.unwrap_err().kind(),
+            ProviderProxySelectorErrorKind::ProviderProxyError
+        );
+
+        let result = uut.set_entity_value("unknown", Value::Int(42)).await;
+        assert_eq!(
+            result.err().unwrap().kind(),
+            ProviderProxySelectorErrorKind::EntityNotFound
+        );
+        assert!(uut.invoke_entity("unknown", Value::Int(42)).await.is_err());
     }
 }